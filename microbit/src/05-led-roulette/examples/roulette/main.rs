@@ -0,0 +1,260 @@
+//! The roulette with everything on top: a banner and a heartbeat at boot,
+//! a random start, a snake with a fading tail on the perimeter or the
+//! spiral, and the buttons to pause it, change its speed, its pattern and
+//! its direction. The display is refreshed from the TIMER1 interrupt and
+//! the core sleeps in between.
+//!
+//! ``` console
+//! $ cargo embed --features v2 --target thumbv7em-none-eabihf --example roulette
+//! ```
+
+#![deny(unsafe_code)]
+#![no_main]
+#![no_std]
+
+use core::cell::{Cell, RefCell};
+use cortex_m::asm::wfi;
+use cortex_m::interrupt::{free, Mutex};
+use cortex_m_rt::entry;
+use microbit::board::Board;
+use microbit::display::nonblocking::{Display, GreyscaleImage};
+use microbit::hal::prelude::*;
+use microbit::hal::rng::Rng;
+use microbit::hal::timer::{Periodic, Timer};
+use microbit::pac::{self, interrupt, TIMER0, TIMER1};
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+
+use led_roulette::debounce::{Buttons, DebouncedButton, Press};
+use led_roulette::font;
+use led_roulette::frame::Frame;
+use led_roulette::roulette::{Direction, Image, Roulette, Speed, PERIMETER};
+
+// Only the pulse itself, the display here isn't the blocking one
+#[allow(dead_code)]
+#[path = "../../src/pulse.rs"]
+mod pulse;
+
+/// How often the main loop wakes up to look at the buttons and, when it is
+/// time, step the roulette. Every speed is a whole number of ticks.
+const TICK_MS: u32 = Speed::STEP_MS;
+/// Shown once, scrolling, before the roulette starts
+const BANNER: &str = "ROULETTE";
+/// How long the banner takes per column
+const BANNER_SPEED: Speed = Speed::from_ms(120);
+/// How many ticks go by between two reports of how often the core woke up,
+/// and how many ticks it slept through
+const REPORT_TICKS: u32 = 1000 / TICK_MS;
+
+/// Refreshed from the TIMER1 interrupt, a row at a time
+static DISPLAY: Mutex<RefCell<Option<Display<TIMER1>>>> = Mutex::new(RefCell::new(None));
+static TICKER: Mutex<RefCell<Option<Timer<TIMER0, Periodic>>>> = Mutex::new(RefCell::new(None));
+/// Counted up by the TIMER0 interrupt, wrapping
+static TICKS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// Let `interrupt` in, now that everything its handler uses is in place.
+#[allow(unsafe_code)]
+fn unmask(interrupt: pac::Interrupt) {
+    unsafe { pac::NVIC::unmask(interrupt) };
+}
+
+fn show(image_buffer: &Image) {
+    free(|cs| {
+        if let Some(display) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            display.show(&GreyscaleImage::new(image_buffer));
+        }
+    });
+}
+
+fn ticks() -> u32 {
+    free(|cs| TICKS.borrow(cs).get())
+}
+
+/// Sleep until a tick after `last`. Returns the tick it is now, and how
+/// many times the core woke up in the meantime, most of them for the
+/// display.
+///
+/// If the work since `last` took longer than a tick, the ticks it missed
+/// are gone: this returns at once with the latest, so that whatever runs
+/// once a tick drops frames rather than falling behind.
+fn wait_tick(last: u32) -> (u32, u32) {
+    let mut wakeups = 0;
+    loop {
+        let now = ticks();
+        if now != last {
+            return (now, wakeups);
+        }
+        wfi();
+        wakeups += 1;
+    }
+}
+
+/// Sleep for at least `ms`, in whole ticks.
+fn sleep_ms(ms: u32) {
+    let start = ticks();
+    let mut now = start;
+    while now.wrapping_sub(start) < ms / TICK_MS {
+        now = wait_tick(now).0;
+    }
+}
+
+/// Scroll `text` across the display from right to left, a column every
+/// `speed`, and return once it is gone.
+fn scroll_text(text: &str, speed: Speed) {
+    let start = ticks();
+    let mut now = start;
+    let mut shown = None;
+    loop {
+        // Where the text should be by now, even if that skips a column
+        let step = (now.wrapping_sub(start) / (speed.ms() / TICK_MS)) as usize;
+        if shown != Some(step) {
+            match font::scroll_frame(text, step) {
+                Some(image) => show(&image),
+                None => return,
+            }
+            shown = Some(step);
+        }
+        now = wait_tick(now).0;
+    }
+}
+
+/// Where on the perimeter the roulette starts, and which way, picked by
+/// the RNG so that it isn't the same every boot. The roulette itself knows
+/// nothing of it.
+struct RandomStart {
+    index: u8,
+    direction: Direction,
+}
+
+impl RandomStart {
+    fn new(rng: &mut Rng) -> RandomStart {
+        // The perimeter is 16 LEDs, so the low bits are an even pick
+        let bits = rng.random_u8();
+        RandomStart {
+            index: bits % PERIMETER,
+            direction: match bits & 0x80 {
+                0 => Direction::Clockwise,
+                _ => Direction::CounterClockwise,
+            },
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+
+    let board = Board::take().unwrap();
+    let display = Display::new(board.TIMER1, board.display_pins);
+    let mut ticker = Timer::periodic(board.TIMER0);
+    ticker.enable_interrupt();
+    ticker.start(TICK_MS * 1000u32);
+    free(|cs| {
+        DISPLAY.borrow(cs).replace(Some(display));
+        TICKER.borrow(cs).replace(Some(ticker));
+    });
+    unmask(pac::Interrupt::TIMER1);
+    unmask(pac::Interrupt::TIMER0);
+
+    let mut frame = Frame::new();
+    let mut roulette: Roulette = Roulette::new();
+    let start = RandomStart::new(&mut Rng::new(board.RNG));
+    if roulette.start_at(start.index, start.direction).is_err() {
+        rprintln!("unexpected start {}, starting at the top left", start.index);
+    }
+    let mut speed = Speed::new();
+    let mut button_a = DebouncedButton::new(board.buttons.button_a);
+    let mut button_b = DebouncedButton::new(board.buttons.button_b);
+    let mut buttons = Buttons::default();
+    let mut paused = false;
+    let mut wakeups = 0;
+    let mut dropped = 0;
+
+    pulse::play(|image, ms| {
+        show(image);
+        sleep_ms(ms);
+    });
+    scroll_text(BANNER, BANNER_SPEED);
+    roulette.render(&mut frame);
+    frame.swap();
+    show(frame.front());
+    let mut last = ticks();
+    let mut reported = last;
+    let mut next_step = last.wrapping_add(speed.ms() / TICK_MS);
+
+    // infinite loop; just so we don't leave this stack frame
+    loop {
+        let (now, woke) = wait_tick(last);
+        wakeups += woke;
+        dropped += now.wrapping_sub(last) - 1;
+        last = now;
+        if now.wrapping_sub(reported) >= REPORT_TICKS {
+            rprintln!(
+                "{} wakeups, {} ticks dropped in the last second",
+                wakeups,
+                dropped
+            );
+            reported = now;
+            wakeups = 0;
+            dropped = 0;
+        }
+
+        // Sampled every tick, so that no press is too short even at the
+        // slowest speed
+        button_a.update();
+        button_b.update();
+        match buttons.update(button_a.is_pressed(), button_b.is_pressed()) {
+            Some(Press::A) => speed = speed.slower(),
+            Some(Press::LongA) => {
+                roulette.toggle_pattern();
+                rprintln!("pattern: {:?}", roulette.pattern());
+            }
+            Some(Press::B) => {
+                paused = !paused;
+                rprintln!("{}", if paused { "paused" } else { "resumed" });
+            }
+            Some(Press::LongB) => speed = speed.faster(),
+            Some(Press::Both) => roulette.reverse(),
+            None => {}
+        }
+        // Paused, it stays a whole step from moving on
+        let period = speed.ms() / TICK_MS;
+        if paused {
+            next_step = now.wrapping_add(period);
+        }
+        if (now.wrapping_sub(next_step) as i32) < 0 {
+            continue;
+        }
+        // Once, however late: the steps missed are dropped
+        next_step = now.wrapping_add(period);
+
+        if roulette.step().is_err() {
+            rprintln!("unexpected state encountered, resetting");
+            roulette.restart();
+        }
+        roulette.render(&mut frame);
+        frame.swap();
+        show(frame.front());
+    }
+}
+
+#[interrupt]
+fn TIMER0() {
+    free(|cs| {
+        if let Some(ticker) = TICKER.borrow(cs).borrow_mut().as_mut() {
+            // Clears the compare event, or the interrupt would come right back
+            ticker.wait().ok();
+        }
+        let ticks = TICKS.borrow(cs);
+        ticks.set(ticks.get().wrapping_add(1));
+    });
+}
+
+#[interrupt]
+fn TIMER1() {
+    free(|cs| {
+        if let Some(display) = DISPLAY.borrow(cs).borrow_mut().as_mut() {
+            display.handle_display_event();
+        }
+    });
+}
@@ -0,0 +1,284 @@
+//! Buttons that bounce, read as the presses they mean.
+//!
+//! A contact closing or opening chatters for a few milliseconds, so a raw
+//! read can see a press come and go several times. [`DebouncedButton`] is
+//! sampled once a tick and only believes a new level once it has read the
+//! same one [`SAMPLES`] times in a row. Holding a button down is one press,
+//! however long: only the edges between the levels it believes count.
+//! [`Buttons`] goes on from there to tell the presses of A and B apart,
+//! short, long or together.
+
+use embedded_hal::digital::v2::InputPin;
+
+use crate::roulette::Speed;
+
+/// How many reads in a row it takes to believe a new level
+pub const SAMPLES: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    Pressed,
+    Released,
+}
+
+/// The levels read so far, apart from the pin, so that it can be fed any
+/// sequence of reads.
+#[derive(Default)]
+pub struct Debouncer {
+    pressed: bool,
+    /// Reads in a row that disagreed with `pressed`
+    disagreeing: u8,
+}
+
+impl Debouncer {
+    /// Take one read, and return the edge if it made a new level stick.
+    pub fn update(&mut self, pressed: bool) -> Option<Edge> {
+        if pressed == self.pressed {
+            self.disagreeing = 0;
+            return None;
+        }
+        self.disagreeing += 1;
+        if self.disagreeing < SAMPLES {
+            return None;
+        }
+        self.disagreeing = 0;
+        self.pressed = pressed;
+        Some(match pressed {
+            true => Edge::Pressed,
+            false => Edge::Released,
+        })
+    }
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+/// A button that reads low while pressed, as the micro:bit's do.
+pub struct DebouncedButton<P> {
+    pin: P,
+    debouncer: Debouncer,
+}
+
+impl<P: InputPin> DebouncedButton<P> {
+    pub fn new(pin: P) -> DebouncedButton<P> {
+        DebouncedButton {
+            pin,
+            debouncer: Debouncer::default(),
+        }
+    }
+    /// Read the pin, once a tick. A read that fails counts as released.
+    pub fn update(&mut self) -> Option<Edge> {
+        let pressed = self.pin.is_low().unwrap_or(false);
+        self.debouncer.update(pressed)
+    }
+    pub fn is_pressed(&self) -> bool {
+        self.debouncer.is_pressed()
+    }
+}
+
+/// How long a button has to be held for its second job, in ticks of
+/// [`Speed::STEP_MS`]: A toggles the pattern rather than slowing down, B
+/// speeds up rather than pausing
+pub const LONG_PRESS_TICKS: u32 = 500 / Speed::STEP_MS;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Press {
+    A,
+    /// A, held for at least [`LONG_PRESS_TICKS`]
+    LongA,
+    B,
+    LongB,
+    Both,
+}
+
+/// Tells presses apart once every button is let go, so that pressing both
+/// doesn't also count as pressing the one that went down first. Updated
+/// once a tick.
+#[derive(Default)]
+pub struct Buttons {
+    a: bool,
+    b: bool,
+    /// Both were down at some point since they were last both up
+    both: bool,
+    /// How many ticks each has been down since they were last both up
+    a_ticks: u32,
+    b_ticks: u32,
+}
+
+impl Buttons {
+    pub fn update(&mut self, a: bool, b: bool) -> Option<Press> {
+        self.both |= a && b;
+        self.a_ticks += a as u32;
+        self.b_ticks += b as u32;
+        let press = match (self.a || self.b, a || b) {
+            (true, false) if self.both => Some(Press::Both),
+            (true, false) if self.a && self.a_ticks >= LONG_PRESS_TICKS => Some(Press::LongA),
+            (true, false) if self.a => Some(Press::A),
+            (true, false) if self.b_ticks >= LONG_PRESS_TICKS => Some(Press::LongB),
+            (true, false) => Some(Press::B),
+            _ => None,
+        };
+        if !(a || b) {
+            self.both = false;
+            self.a_ticks = 0;
+            self.b_ticks = 0;
+        }
+        self.a = a;
+        self.b = b;
+        press
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use std::vec::Vec;
+
+    /// A contact closing with a bounce, a held press, and a bouncing release
+    const BOUNCY_PRESS: [bool; 18] = [
+        true, false, true, true, false, true, true, true, true, true, true, true, true, false,
+        true, false, false, false,
+    ];
+
+    /// The edges `reads` give, with the read each one came at.
+    fn edges(debouncer: &mut Debouncer, reads: &[bool]) -> Vec<(usize, Edge)> {
+        reads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &pressed)| debouncer.update(pressed).map(|edge| (i, edge)))
+            .collect()
+    }
+
+    #[test]
+    fn a_bouncing_press_is_one_press_and_one_release() {
+        let mut debouncer = Debouncer::default();
+        assert_eq!(
+            edges(&mut debouncer, &BOUNCY_PRESS),
+            [(7, Edge::Pressed), (17, Edge::Released)]
+        );
+        assert!(!debouncer.is_pressed());
+    }
+
+    #[test]
+    fn holding_the_button_gives_no_more_edges() {
+        let mut debouncer = Debouncer::default();
+        let reads = [true; 100];
+        assert_eq!(edges(&mut debouncer, &reads), [(2, Edge::Pressed)]);
+        assert!(debouncer.is_pressed());
+    }
+
+    #[test]
+    fn fewer_reads_in_a_row_than_it_takes_are_ignored() {
+        let mut debouncer = Debouncer::default();
+        let mut reads = Vec::new();
+        for _ in 0..10 {
+            reads.extend_from_slice(&[true; SAMPLES as usize - 1]);
+            reads.push(false);
+        }
+        assert_eq!(edges(&mut debouncer, &reads), []);
+        assert!(!debouncer.is_pressed());
+    }
+
+    #[test]
+    fn every_press_counts_however_soon_after_the_last() {
+        let mut debouncer = Debouncer::default();
+        let mut reads = Vec::new();
+        for _ in 0..3 {
+            reads.extend_from_slice(&[true; SAMPLES as usize]);
+            reads.extend_from_slice(&[false; SAMPLES as usize]);
+        }
+        let found: Vec<Edge> = edges(&mut debouncer, &reads)
+            .into_iter()
+            .map(|(_, edge)| edge)
+            .collect();
+        assert_eq!(found, [Edge::Pressed, Edge::Released].repeat(3));
+    }
+
+    /// A pin that reads whatever the test sets: low, high, or an error
+    struct Pin<'a>(&'a Cell<Option<bool>>);
+
+    impl InputPin for Pin<'_> {
+        type Error = ();
+
+        fn is_high(&self) -> Result<bool, ()> {
+            self.0.get().map(|low| !low).ok_or(())
+        }
+
+        fn is_low(&self) -> Result<bool, ()> {
+            self.0.get().ok_or(())
+        }
+    }
+
+    #[test]
+    fn the_button_is_pressed_while_its_pin_reads_low() {
+        let low = Cell::new(Some(false));
+        let mut button = DebouncedButton::new(Pin(&low));
+        let mut found = Vec::new();
+        for &pressed in &BOUNCY_PRESS {
+            low.set(Some(pressed));
+            found.extend(button.update());
+        }
+        assert_eq!(found, [Edge::Pressed, Edge::Released]);
+    }
+
+    #[test]
+    fn a_failing_read_counts_as_released() {
+        let low = Cell::new(Some(true));
+        let mut button = DebouncedButton::new(Pin(&low));
+        for _ in 0..SAMPLES {
+            button.update();
+        }
+        assert!(button.is_pressed());
+        low.set(None);
+        let found: Vec<Edge> = (0..SAMPLES).filter_map(|_| button.update()).collect();
+        assert_eq!(found, [Edge::Released]);
+    }
+
+    /// Hold A and B down for as many ticks as given, with both let go at
+    /// the end, and return the presses they made.
+    fn press(buttons: &mut Buttons, a_ticks: u32, b_ticks: u32) -> Vec<Press> {
+        let mut presses = Vec::new();
+        for tick in 0..a_ticks.max(b_ticks) {
+            presses.extend(buttons.update(tick < a_ticks, tick < b_ticks));
+        }
+        presses.extend(buttons.update(false, false));
+        presses
+    }
+
+    #[test]
+    fn a_press_counts_once_it_is_let_go() {
+        let mut buttons = Buttons::default();
+        for _ in 0..LONG_PRESS_TICKS * 3 {
+            assert_eq!(buttons.update(true, false), None);
+        }
+        assert_eq!(buttons.update(false, false), Some(Press::LongA));
+        assert_eq!(buttons.update(false, false), None);
+    }
+
+    #[test]
+    fn a_press_is_long_from_the_threshold_on() {
+        let mut buttons = Buttons::default();
+        assert_eq!(press(&mut buttons, 1, 0), [Press::A]);
+        assert_eq!(press(&mut buttons, LONG_PRESS_TICKS - 1, 0), [Press::A]);
+        assert_eq!(press(&mut buttons, LONG_PRESS_TICKS, 0), [Press::LongA]);
+        assert_eq!(press(&mut buttons, 0, LONG_PRESS_TICKS - 1), [Press::B]);
+        assert_eq!(press(&mut buttons, 0, LONG_PRESS_TICKS), [Press::LongB]);
+    }
+
+    #[test]
+    fn both_together_is_neither_alone() {
+        let mut buttons = Buttons::default();
+        assert_eq!(press(&mut buttons, 5, 5), [Press::Both]);
+        // Long or short, and whichever goes first
+        assert_eq!(press(&mut buttons, LONG_PRESS_TICKS * 2, 3), [Press::Both]);
+        let mut presses = Vec::new();
+        presses.extend(buttons.update(false, true));
+        presses.extend(buttons.update(true, true));
+        presses.extend(buttons.update(true, false));
+        presses.extend(buttons.update(false, false));
+        assert_eq!(presses, [Press::Both]);
+        // And it doesn't stick to the next press
+        assert_eq!(press(&mut buttons, 1, 0), [Press::A]);
+    }
+}
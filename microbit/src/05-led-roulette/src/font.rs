@@ -0,0 +1,196 @@
+//! A 3x5 font for printable ASCII, and text scrolled across the display
+//! with it, one column at a time.
+//!
+//! Each glyph is three columns, the top row in bit 0. Lowercase letters are
+//! shown as capitals, five rows leave no room for anything else.
+
+use crate::roulette::Image;
+
+pub const WIDTH: usize = 3;
+
+/// From the space to the backtick, then the braces, the bar and the tilde.
+const GLYPHS: [[u8; WIDTH]; 69] = [
+    [0x00, 0x00, 0x00], // space
+    [0x00, 0x17, 0x00], // !
+    [0x03, 0x00, 0x03], // "
+    [0x1f, 0x0a, 0x1f], // #
+    [0x12, 0x1f, 0x09], // $
+    [0x19, 0x04, 0x13], // %
+    [0x0a, 0x15, 0x1a], // &
+    [0x00, 0x03, 0x00], // '
+    [0x00, 0x0e, 0x11], // (
+    [0x11, 0x0e, 0x00], // )
+    [0x0a, 0x04, 0x0a], // *
+    [0x04, 0x0e, 0x04], // +
+    [0x10, 0x08, 0x00], // ,
+    [0x04, 0x04, 0x04], // -
+    [0x00, 0x10, 0x00], // .
+    [0x18, 0x04, 0x03], // /
+    [0x1f, 0x11, 0x1f], // 0
+    [0x12, 0x1f, 0x10], // 1
+    [0x19, 0x15, 0x12], // 2
+    [0x11, 0x15, 0x0a], // 3
+    [0x07, 0x04, 0x1f], // 4
+    [0x17, 0x15, 0x09], // 5
+    [0x1e, 0x15, 0x1d], // 6
+    [0x01, 0x1d, 0x03], // 7
+    [0x1f, 0x15, 0x1f], // 8
+    [0x17, 0x15, 0x0f], // 9
+    [0x00, 0x0a, 0x00], // :
+    [0x10, 0x0a, 0x00], // ;
+    [0x04, 0x0a, 0x11], // <
+    [0x0a, 0x0a, 0x0a], // =
+    [0x11, 0x0a, 0x04], // >
+    [0x01, 0x15, 0x02], // ?
+    [0x0e, 0x15, 0x16], // @
+    [0x1e, 0x05, 0x1e], // A
+    [0x1f, 0x15, 0x0a], // B
+    [0x0e, 0x11, 0x11], // C
+    [0x1f, 0x11, 0x0e], // D
+    [0x1f, 0x15, 0x11], // E
+    [0x1f, 0x05, 0x01], // F
+    [0x0e, 0x11, 0x1d], // G
+    [0x1f, 0x04, 0x1f], // H
+    [0x11, 0x1f, 0x11], // I
+    [0x08, 0x10, 0x0f], // J
+    [0x1f, 0x04, 0x1b], // K
+    [0x1f, 0x10, 0x10], // L
+    [0x1f, 0x06, 0x1f], // M
+    [0x1f, 0x01, 0x1e], // N
+    [0x0e, 0x11, 0x0e], // O
+    [0x1f, 0x05, 0x02], // P
+    [0x0e, 0x19, 0x16], // Q
+    [0x1f, 0x05, 0x1a], // R
+    [0x12, 0x15, 0x09], // S
+    [0x01, 0x1f, 0x01], // T
+    [0x1f, 0x10, 0x1f], // U
+    [0x0f, 0x10, 0x0f], // V
+    [0x1f, 0x0c, 0x1f], // W
+    [0x1b, 0x04, 0x1b], // X
+    [0x03, 0x1c, 0x03], // Y
+    [0x19, 0x15, 0x13], // Z
+    [0x00, 0x1f, 0x11], // [
+    [0x03, 0x04, 0x18], // \
+    [0x11, 0x1f, 0x00], // ]
+    [0x02, 0x01, 0x02], // ^
+    [0x10, 0x10, 0x10], // _
+    [0x01, 0x02, 0x00], // `
+    [0x04, 0x0e, 0x11], // {
+    [0x00, 0x1f, 0x00], // |
+    [0x11, 0x0e, 0x04], // }
+    [0x04, 0x06, 0x02], // ~
+];
+
+/// The columns of `c`, or of `'?'` for anything that isn't printable.
+pub fn glyph(c: char) -> [u8; WIDTH] {
+    let c = c.to_ascii_uppercase();
+    let index = match c {
+        ' '..='`' => c as usize - ' ' as usize,
+        '{'..='~' => c as usize - '{' as usize + ('`' as usize - ' ' as usize + 1),
+        _ => '?' as usize - ' ' as usize,
+    };
+    GLYPHS[index]
+}
+
+/// How bright scrolled text is
+const LIT: u8 = 9;
+
+/// The display `step` columns into scrolling `text` in from the right,
+/// `None` once it is gone off to the left. Every glyph is followed by a
+/// blank column, the last one too.
+pub fn scroll_frame(text: &str, step: usize) -> Option<Image> {
+    let columns = text.len() * (WIDTH + 1);
+    if step >= columns + 5 {
+        return None;
+    }
+    let mut image = [[0; 5]; 5];
+    for col in 0..5 {
+        let column = match (step + col).checked_sub(5) {
+            Some(i) if i < columns && i % (WIDTH + 1) < WIDTH => {
+                let c = text.as_bytes()[i / (WIDTH + 1)] as char;
+                glyph(c)[i % (WIDTH + 1)]
+            }
+            _ => 0,
+        };
+        for (row, line) in image.iter_mut().enumerate() {
+            if column & (1 << row) != 0 {
+                line[col] = LIT;
+            }
+        }
+    }
+    Some(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The five columns of `image`, as glyph columns are: top row in bit 0,
+    /// checking every lit LED is as bright as text is.
+    fn columns(image: &Image) -> [u8; 5] {
+        let mut columns = [0; 5];
+        for (row, line) in image.iter().enumerate() {
+            for (col, &brightness) in line.iter().enumerate() {
+                match brightness {
+                    0 => {}
+                    LIT => columns[col] |= 1 << row,
+                    other => panic!("brightness {} at {}, {}", other, row, col),
+                }
+            }
+        }
+        columns
+    }
+
+    fn frame(text: &str, step: usize) -> [u8; 5] {
+        columns(&scroll_frame(text, step).unwrap())
+    }
+
+    #[test]
+    fn a_character_scrolls_in_from_the_right() {
+        let t = glyph('T');
+        assert_eq!(frame("T", 0), [0; 5]);
+        assert_eq!(frame("T", 1), [0, 0, 0, 0, t[0]]);
+        assert_eq!(frame("T", 3), [0, 0, t[0], t[1], t[2]]);
+        assert_eq!(frame("T", 5), [t[0], t[1], t[2], 0, 0]);
+    }
+
+    #[test]
+    fn glyphs_are_a_blank_column_apart() {
+        let (h, i) = (glyph('H'), glyph('I'));
+        assert_eq!(frame("HI", 6), [h[1], h[2], 0, i[0], i[1]]);
+        assert_eq!(frame("HI", 8), [0, i[0], i[1], i[2], 0]);
+    }
+
+    #[test]
+    fn the_text_scrolls_off_to_the_left_and_ends() {
+        // One glyph and its gap, then the width of the display
+        let last = WIDTH + 1 + 5 - 1;
+        assert_eq!(frame("T", last - 1), [glyph('T')[2], 0, 0, 0, 0]);
+        assert_eq!(frame("T", last), [0; 5]);
+        assert_eq!(scroll_frame("T", last + 1), None);
+        assert_eq!(scroll_frame("T", 1000), None);
+        assert_eq!(scroll_frame("", 5), None);
+    }
+
+    #[test]
+    fn lowercase_is_shown_as_capitals() {
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(frame("hi", 5), frame("HI", 5));
+    }
+
+    #[test]
+    fn anything_unprintable_is_a_question_mark() {
+        let q = glyph('?');
+        assert_eq!(glyph('\t'), q);
+        assert_eq!(glyph('\u{7f}'), q);
+        assert_eq!(glyph('é'), q);
+        assert_eq!(frame("\t", 5), [q[0], q[1], q[2], 0, 0]);
+    }
+
+    #[test]
+    fn the_braces_bar_and_tilde_come_after_the_backtick() {
+        assert_eq!(glyph('~'), GLYPHS[GLYPHS.len() - 1]);
+        assert_eq!(glyph('{'), GLYPHS[GLYPHS.len() - 4]);
+        assert_eq!(glyph(' '), [0; WIDTH]);
+    }
+}
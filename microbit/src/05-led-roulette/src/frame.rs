@@ -0,0 +1,50 @@
+//! An image drawn on one buffer while the display is handed the other, so
+//! that it never gets a frame that is only partly drawn.
+//!
+//! Everything that draws goes to the back buffer, and nothing of it shows
+//! until [`Frame::swap`]. [`Frame::get`] and [`Frame::front`] only ever see
+//! the front, the last frame swapped in.
+
+use crate::roulette::{Coordinate, Image};
+
+pub struct Frame {
+    front: Image,
+    back: Image,
+}
+
+impl Frame {
+    /// Both buffers blank.
+    pub const fn new() -> Frame {
+        Frame {
+            front: [[0; 5]; 5],
+            back: [[0; 5]; 5],
+        }
+    }
+    /// Draw one LED on the back buffer.
+    pub fn set(&mut self, coordinate: Coordinate, brightness: u8) {
+        self.back[coordinate] = brightness;
+    }
+    /// Blank the back buffer.
+    pub fn clear(&mut self) {
+        self.back = [[0; 5]; 5];
+    }
+    /// One LED as the front buffer has it.
+    pub fn get(&self, coordinate: Coordinate) -> u8 {
+        self.front[coordinate]
+    }
+    /// The front buffer, for the display.
+    pub fn front(&self) -> &Image {
+        &self.front
+    }
+    /// Show what was drawn. The back buffer goes on from a copy of it, so
+    /// that the next frame only has to draw what changes.
+    pub fn swap(&mut self) {
+        self.front = self.back;
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Frame {
+        Frame::new()
+    }
+}
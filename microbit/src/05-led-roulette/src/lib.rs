@@ -0,0 +1,15 @@
+//! The parts of the roulette that never touch the board, as a library of
+//! their own so that their tests run on the host:
+//!
+//! ``` console
+//! $ cargo test --lib
+//! ```
+//!
+//! The `roulette` example takes them from here.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod debounce;
+pub mod font;
+pub mod frame;
+pub mod roulette;
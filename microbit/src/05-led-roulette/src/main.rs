@@ -5,6 +5,8 @@
 use cortex_m_rt::entry;
 use microbit::board::Board;
 use microbit::display::blocking::Display;
+// Unused here, but the `delay_ms` calls later in this chapter need it
+#[allow(unused_imports)]
 use microbit::hal::prelude::*;
 use microbit::hal::timer::Timer;
 use panic_rtt_target as _;
@@ -0,0 +1,62 @@
+//! A heart beating twice on the LED matrix, to show the firmware is alive
+//! before anything else runs. Shared by the chapters that play it at boot,
+//! this file is included by path from the `roulette` example, 07-uart and
+//! 08-i2c.
+//!
+//! [`heartbeat`] plays it on the blocking display. Firmware that drives
+//! the display some other way hands [`play`] a function that shows one
+//! image for a while. Either way it takes under a second and leaves the
+//! display blank.
+
+use microbit::display::blocking::Display;
+use microbit::hal::timer::{Instance, Timer};
+
+/// One greyscale value from 0 to 9 per LED, by row and column, as in the
+/// non-blocking display. The blocking one lights every LED that isn't 0.
+pub type Image = [[u8; 5]; 5];
+
+/// Between beats, dim
+const SMALL: Image = [
+    [0, 0, 0, 0, 0],
+    [0, 3, 0, 3, 0],
+    [0, 3, 3, 3, 0],
+    [0, 0, 3, 0, 0],
+    [0, 0, 0, 0, 0],
+];
+
+/// On a beat, bright
+const BIG: Image = [
+    [0, 9, 0, 9, 0],
+    [9, 9, 9, 9, 9],
+    [9, 9, 9, 9, 9],
+    [0, 9, 9, 9, 0],
+    [0, 0, 9, 0, 0],
+];
+
+const BLANK: Image = [[0; 5]; 5];
+
+/// The pulse, as the image and how long it shows in ms: 800 ms in all.
+pub const BEATS: [(Image, u32); 8] = [
+    (SMALL, 100),
+    (BIG, 150),
+    (SMALL, 100),
+    (BLANK, 100),
+    (SMALL, 100),
+    (BIG, 150),
+    (SMALL, 100),
+    (BLANK, 0),
+];
+
+/// Play the pulse, blocking until it is done.
+pub fn heartbeat<T: Instance>(display: &mut Display, timer: &mut Timer<T>) {
+    play(|image, ms| display.show(timer, *image, ms));
+    display.clear();
+}
+
+/// Play the pulse through `show`, which shows an image for so many ms and
+/// only then returns. Ends by showing a blank image.
+pub fn play(mut show: impl FnMut(&Image, u32)) {
+    for (image, ms) in &BEATS {
+        show(image, *ms);
+    }
+}
@@ -0,0 +1,858 @@
+//! The roulette itself: which LED is lit, and which one comes next, on the
+//! perimeter or on the spiral. Nothing in here touches the board, the
+//! `roulette` example does that.
+
+use core::ops::{Index, IndexMut};
+use heapless::{Deque, Vec};
+
+use crate::frame::Frame;
+
+/// The image the roulette draws into, one brightness per LED.
+pub type Image = [[u8; 5]; 5];
+
+/// An LED, as row and column from the top left, both always below 5.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coordinate {
+    row: u8,
+    col: u8,
+}
+
+impl Coordinate {
+    pub fn new(row: u8, col: u8) -> Option<Coordinate> {
+        match row < 5 && col < 5 {
+            true => Some(Coordinate { row, col }),
+            false => None,
+        }
+    }
+    /// From the 1 to 5 the states count in.
+    fn from_one_based(row: u16, col: u16) -> Option<Coordinate> {
+        let row = row.checked_sub(1)?;
+        let col = col.checked_sub(1)?;
+        Coordinate::new(row.min(5) as u8, col.min(5) as u8)
+    }
+    pub fn row(self) -> u8 {
+        self.row
+    }
+    pub fn col(self) -> u8 {
+        self.col
+    }
+}
+
+impl Index<Coordinate> for Image {
+    type Output = u8;
+
+    fn index(&self, coordinate: Coordinate) -> &u8 {
+        &self[coordinate.row as usize][coordinate.col as usize]
+    }
+}
+
+impl IndexMut<Coordinate> for Image {
+    fn index_mut(&mut self, coordinate: Coordinate) -> &mut u8 {
+        &mut self[coordinate.row as usize][coordinate.col as usize]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Direction {
+    pub fn reversed(self) -> Direction {
+        match self {
+            Direction::Clockwise => Direction::CounterClockwise,
+            Direction::CounterClockwise => Direction::Clockwise,
+        }
+    }
+}
+
+/// How many LEDs there are on the perimeter, and states to start from.
+pub const PERIMETER: u8 = 16;
+
+/// A state off the perimeter, which only a bug can get into, or an index
+/// past it.
+#[derive(Debug, PartialEq)]
+pub struct Invalid;
+
+/// Every pixel on the perimeter belongs to exactly one side, the corners to
+/// the side that reaches them last going clockwise, which is also how the
+/// sides are named. Only the start has two names for the top left corner.
+pub enum State {
+    Row1GoingRight { col: u16 },
+    Col5GoingDown { row: u16 },
+    Row5GoingLeft { col: u16 },
+    Col1GoingUp { row: u16 },
+}
+
+impl State {
+    /// The LED `index` steps clockwise from the top left, going clockwise.
+    /// Index 0 is the start.
+    pub fn from_index(index: u8) -> Result<State, Invalid> {
+        Ok(match index as u16 {
+            index @ 0..=4 => State::Row1GoingRight { col: index + 1 },
+            index @ 5..=8 => State::Col5GoingDown { row: index - 3 },
+            index @ 9..=12 => State::Row5GoingLeft { col: 13 - index },
+            index @ 13..=15 => State::Col1GoingUp { row: 17 - index },
+            _ => return Err(Invalid),
+        })
+    }
+    pub fn next(&self, direction: Direction) -> Result<State, Invalid> {
+        match direction {
+            Direction::Clockwise => self.clockwise(),
+            Direction::CounterClockwise => self.counter_clockwise(),
+        }
+    }
+    fn clockwise(&self) -> Result<State, Invalid> {
+        Ok(match self {
+            State::Row1GoingRight { col } if *col == 5 => State::Col5GoingDown {
+                row: 2, /* row1 col5 did light up in the previous state */
+            },
+            State::Row1GoingRight { col } if 1 <= *col && *col <= 4 => {
+                State::Row1GoingRight { col: col + 1 }
+            }
+            State::Col5GoingDown { row } if *row == 5 => State::Row5GoingLeft {
+                col: 4, /* row5 col5 did light up in the previous state */
+            },
+            State::Col5GoingDown { row } if 2 <= *row && *row <= 4 => {
+                State::Col5GoingDown { row: row + 1 }
+            }
+            State::Row5GoingLeft { col } if *col == 1 => State::Col1GoingUp { row: 4 },
+            State::Row5GoingLeft { col } if 2 <= *col && *col <= 4 => {
+                State::Row5GoingLeft { col: col - 1 }
+            }
+            State::Col1GoingUp { row } if *row == 1 => State::Row1GoingRight { col: 2 },
+            State::Col1GoingUp { row } if 2 <= *row && *row <= 4 => {
+                State::Col1GoingUp { row: row - 1 }
+            }
+            _ => return Err(Invalid),
+        })
+    }
+    /// The same pixels backwards, so that turning round on a corner neither
+    /// skips it nor lights it twice.
+    fn counter_clockwise(&self) -> Result<State, Invalid> {
+        Ok(match self {
+            State::Row1GoingRight { col } if *col == 1 => State::Col1GoingUp { row: 2 },
+            State::Row1GoingRight { col } if *col == 2 => State::Col1GoingUp { row: 1 },
+            State::Row1GoingRight { col } if 3 <= *col && *col <= 5 => {
+                State::Row1GoingRight { col: col - 1 }
+            }
+            State::Col1GoingUp { row } if *row == 4 => State::Row5GoingLeft { col: 1 },
+            State::Col1GoingUp { row } if 1 <= *row && *row <= 3 => {
+                State::Col1GoingUp { row: row + 1 }
+            }
+            State::Row5GoingLeft { col } if *col == 4 => State::Col5GoingDown { row: 5 },
+            State::Row5GoingLeft { col } if 1 <= *col && *col <= 3 => {
+                State::Row5GoingLeft { col: col + 1 }
+            }
+            State::Col5GoingDown { row } if *row == 2 => State::Row1GoingRight { col: 5 },
+            State::Col5GoingDown { row } if 3 <= *row && *row <= 5 => {
+                State::Col5GoingDown { row: row - 1 }
+            }
+            _ => return Err(Invalid),
+        })
+    }
+    /// The LED this state lights.
+    pub fn position(&self) -> Result<Coordinate, Invalid> {
+        let (row, col) = match *self {
+            State::Row1GoingRight { col } => (1, col),
+            State::Col5GoingDown { row } => (row, 5),
+            State::Row5GoingLeft { col } => (5, col),
+            State::Col1GoingUp { row } => (row, 1),
+        };
+        Coordinate::from_one_based(row, col).ok_or(Invalid)
+    }
+}
+
+/// The outer ring clockwise from the top left, like the perimeter, then one
+/// step in to the inner ring, the same way round, and the centre last. Every
+/// step is to a neighbour, the ring to ring one included.
+const SPIRAL: [(u8, u8); 25] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 4),
+    (2, 4),
+    (3, 4),
+    (4, 4),
+    (4, 3),
+    (4, 2),
+    (4, 1),
+    (4, 0),
+    (3, 0),
+    (2, 0),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (1, 3),
+    (2, 3),
+    (3, 3),
+    (3, 2),
+    (3, 1),
+    (2, 1),
+    (2, 2),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    Perimeter,
+    Spiral,
+}
+
+impl Pattern {
+    pub fn toggled(self) -> Pattern {
+        match self {
+            Pattern::Perimeter => Pattern::Spiral,
+            Pattern::Spiral => Pattern::Perimeter,
+        }
+    }
+}
+
+/// Where the roulette is on its pattern.
+enum Path {
+    Perimeter(State),
+    /// An index into [`SPIRAL`], which wraps round from the centre to the
+    /// top left, or back
+    Spiral(usize),
+}
+
+impl Path {
+    fn start(pattern: Pattern) -> Path {
+        match pattern {
+            Pattern::Perimeter => Path::Perimeter(State::Row1GoingRight { col: 1 }),
+            Pattern::Spiral => Path::Spiral(0),
+        }
+    }
+    fn next(&self, direction: Direction) -> Result<Path, Invalid> {
+        Ok(match (self, direction) {
+            (Path::Perimeter(state), _) => Path::Perimeter(state.next(direction)?),
+            (Path::Spiral(index), Direction::Clockwise) => Path::Spiral((index + 1) % SPIRAL.len()),
+            (Path::Spiral(index), Direction::CounterClockwise) => {
+                Path::Spiral((index + SPIRAL.len() - 1) % SPIRAL.len())
+            }
+        })
+    }
+    fn position(&self) -> Result<Coordinate, Invalid> {
+        match self {
+            Path::Perimeter(state) => state.position(),
+            Path::Spiral(index) => SPIRAL
+                .get(*index)
+                .and_then(|&(row, col)| Coordinate::new(row, col))
+                .ok_or(Invalid),
+        }
+    }
+}
+
+/// The brightness of the snake's head. Every pixel behind it is a bit
+/// dimmer, down to the tail.
+pub const HEAD: u8 = 9;
+
+/// The pattern and where on it, which way it goes, and the snake of `N`
+/// pixels chasing round it, at least one.
+///
+/// Each step the head moves on and only the pixel that falls off the tail
+/// is erased, see [`Roulette::render`]; nothing else outside the snake is
+/// ever written.
+///
+/// As an [`Iterator`] it yields the head's position, then steps, forever:
+/// clockwise from the top left, the 16 LEDs on the perimeter or the 25 of
+/// the spiral over and over. It only ends on an invalid state, which is a
+/// bug.
+pub struct Roulette<const N: usize = 3> {
+    pattern: Pattern,
+    path: Path,
+    direction: Direction,
+    /// Oldest first, the head last
+    snake: Deque<Coordinate, N>,
+    /// Fallen off the tail since the last render
+    erase: Vec<Coordinate, N>,
+    /// Blank the whole image at the next render, rather than only what is
+    /// in `erase`
+    clear: bool,
+}
+
+impl<const N: usize> Roulette<N> {
+    const NOT_EMPTY: () = assert!(N > 0, "a snake of no pixels");
+
+    pub fn new() -> Roulette<N> {
+        let () = Self::NOT_EMPTY;
+        let mut roulette = Roulette {
+            pattern: Pattern::Perimeter,
+            path: Path::start(Pattern::Perimeter),
+            direction: Direction::Clockwise,
+            snake: Deque::new(),
+            erase: Vec::new(),
+            clear: true,
+        };
+        roulette.restart();
+        roulette
+    }
+    /// Over to the perimeter, with the snake only its head on the LED
+    /// `index` steps clockwise from the top left, see [`State::from_index`].
+    /// Leaves everything as it was if `index` is past the perimeter.
+    pub fn start_at(&mut self, index: u8, direction: Direction) -> Result<(), Invalid> {
+        let state = State::from_index(index)?;
+        let position = state.position()?;
+        self.pattern = Pattern::Perimeter;
+        self.path = Path::Perimeter(state);
+        self.direction = direction;
+        self.restart_at(position);
+        Ok(())
+    }
+    /// Turn round, from the next step on.
+    pub fn reverse(&mut self) {
+        self.direction = self.direction.reversed();
+    }
+    pub fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+    /// Over to the other pattern, from its start. The snake's head jumps
+    /// there and the rest follows step by step, rather than the display
+    /// going blank at once.
+    pub fn toggle_pattern(&mut self) {
+        self.pattern = self.pattern.toggled();
+        self.path = Path::start(self.pattern);
+        if let Ok(position) = self.path.position() {
+            self.visit(position);
+        }
+    }
+    /// Back to the start, the snake only its head. Keeps the pattern and
+    /// the direction.
+    pub fn restart(&mut self) {
+        self.path = Path::start(self.pattern);
+        if let Ok(position) = self.path.position() {
+            self.restart_at(position);
+        }
+    }
+    fn restart_at(&mut self, head: Coordinate) {
+        self.snake.clear();
+        self.erase.clear();
+        self.clear = true;
+        self.snake.push_back(head).ok();
+    }
+    /// One position on. Leaves everything as it was if the state is
+    /// invalid.
+    pub fn step(&mut self) -> Result<(), Invalid> {
+        let path = self.path.next(self.direction)?;
+        self.visit(path.position()?);
+        self.path = path;
+        Ok(())
+    }
+    /// Move the head to `position`, the tail dropping off once the snake is
+    /// full grown.
+    fn visit(&mut self, position: Coordinate) {
+        if self.snake.is_full() {
+            if let Some(tail) = self.snake.pop_front() {
+                // More steps than pixels since the last render, which
+                // blanks everything instead
+                self.clear |= self.erase.push(tail).is_err();
+            }
+        }
+        self.snake.push_back(position).ok();
+    }
+    /// How bright the pixel `age` steps behind the head is.
+    fn brightness(age: usize) -> u8 {
+        HEAD - (HEAD as usize * age / N) as u8
+    }
+    /// Bring the back of `frame` up to date from the last render: erase
+    /// what has fallen off the tail and draw the snake. The first render
+    /// after [`Roulette::new`] or [`Roulette::restart`] starts from blank.
+    /// Where the snake crosses itself after turning round, the newer pixel
+    /// shows.
+    pub fn render(&mut self, frame: &mut Frame) {
+        if self.clear {
+            frame.clear();
+        }
+        for &coordinate in &self.erase {
+            frame.set(coordinate, 0);
+        }
+        self.erase.clear();
+        self.clear = false;
+        let len = self.snake.len();
+        for (index, &coordinate) in self.snake.iter().enumerate() {
+            frame.set(coordinate, Self::brightness(len - 1 - index));
+        }
+    }
+}
+
+impl<const N: usize> Default for Roulette<N> {
+    fn default() -> Roulette<N> {
+        Roulette::new()
+    }
+}
+
+impl<const N: usize> Iterator for Roulette<N> {
+    type Item = Coordinate;
+
+    fn next(&mut self) -> Option<Coordinate> {
+        let head = *self.snake.back()?;
+        self.step().ok()?;
+        Some(head)
+    }
+}
+
+/// How long the roulette stays on each position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Speed(u32);
+
+impl Speed {
+    pub const STEP_MS: u32 = 10;
+    /// Fast enough to blur, and never a step without a frame to show it
+    pub const MIN_MS: u32 = 10;
+    pub const MAX_MS: u32 = 1000;
+
+    pub const fn new() -> Speed {
+        Speed(50)
+    }
+    /// As close to `ms` as the steps go, between the slowest and the
+    /// fastest.
+    pub const fn from_ms(ms: u32) -> Speed {
+        let ms = ms / Speed::STEP_MS * Speed::STEP_MS;
+        if ms < Speed::MIN_MS {
+            Speed(Speed::MIN_MS)
+        } else if ms > Speed::MAX_MS {
+            Speed(Speed::MAX_MS)
+        } else {
+            Speed(ms)
+        }
+    }
+    pub fn ms(self) -> u32 {
+        self.0
+    }
+    /// One step less per position, down to [`Speed::MIN_MS`].
+    pub fn faster(self) -> Speed {
+        Speed(self.0.saturating_sub(Speed::STEP_MS).max(Speed::MIN_MS))
+    }
+    /// One step more per position, up to [`Speed::MAX_MS`].
+    pub fn slower(self) -> Speed {
+        Speed((self.0 + Speed::STEP_MS).min(Speed::MAX_MS))
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Speed {
+        Speed::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The perimeter clockwise from the top left
+    const CLOCKWISE: [(u8, u8); 16] = [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (0, 3),
+        (0, 4),
+        (1, 4),
+        (2, 4),
+        (3, 4),
+        (4, 4),
+        (4, 3),
+        (4, 2),
+        (4, 1),
+        (4, 0),
+        (3, 0),
+        (2, 0),
+        (1, 0),
+    ];
+
+    fn at((row, col): (u8, u8)) -> Coordinate {
+        Coordinate::new(row, col).unwrap()
+    }
+
+    /// Every LED that is on, as row, column and brightness
+    fn lit(image: &Image) -> std::vec::Vec<(u8, u8, u8)> {
+        let mut lit = std::vec::Vec::new();
+        for (row, line) in image.iter().enumerate() {
+            for (col, &brightness) in line.iter().enumerate() {
+                if brightness != 0 {
+                    lit.push((row as u8, col as u8, brightness));
+                }
+            }
+        }
+        lit
+    }
+
+    #[test]
+    fn from_index_walks_the_perimeter_clockwise() {
+        for (index, &position) in CLOCKWISE.iter().enumerate() {
+            let state = State::from_index(index as u8).unwrap();
+            assert_eq!(state.position(), Ok(at(position)), "index {}", index);
+        }
+        assert!(State::from_index(PERIMETER).is_err());
+    }
+
+    #[test]
+    fn from_index_is_where_stepping_from_the_start_gets_to() {
+        let mut state = State::from_index(0).unwrap();
+        for index in 1..PERIMETER {
+            state = state.next(Direction::Clockwise).unwrap();
+            let expected = State::from_index(index).unwrap();
+            assert_eq!(state.position(), expected.position(), "index {}", index);
+        }
+    }
+
+    #[test]
+    fn a_lap_each_way_gets_back_to_the_start() {
+        for &direction in &[Direction::Clockwise, Direction::CounterClockwise] {
+            let start = State::from_index(0).unwrap();
+            let mut state = start.next(direction).unwrap();
+            for _ in 1..PERIMETER {
+                assert_ne!(state.position(), start.position());
+                state = state.next(direction).unwrap();
+            }
+            assert_eq!(state.position(), start.position());
+        }
+    }
+
+    #[test]
+    fn corners_go_round_to_the_next_side() {
+        // Each corner, with the LED after it either way
+        let corners = [
+            (4, (1, 4), (0, 3)),
+            (8, (4, 3), (3, 4)),
+            (12, (3, 0), (4, 1)),
+            (0, (0, 1), (1, 0)),
+        ];
+        for &(index, clockwise, counter_clockwise) in &corners {
+            let corner = State::from_index(index).unwrap();
+            assert_eq!(
+                corner.next(Direction::Clockwise).unwrap().position(),
+                Ok(at(clockwise)),
+                "index {}",
+                index
+            );
+            assert_eq!(
+                corner.next(Direction::CounterClockwise).unwrap().position(),
+                Ok(at(counter_clockwise)),
+                "index {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn states_off_the_perimeter_are_invalid() {
+        for state in [
+            State::Row1GoingRight { col: 0 },
+            State::Row1GoingRight { col: 6 },
+            State::Col5GoingDown { row: 6 },
+            State::Row5GoingLeft { col: 0 },
+            State::Col1GoingUp { row: 0 },
+        ] {
+            assert!(state.position().is_err());
+        }
+        assert!(State::Row1GoingRight { col: 6 }
+            .next(Direction::Clockwise)
+            .is_err());
+        assert!(State::Col5GoingDown { row: 1 }
+            .next(Direction::CounterClockwise)
+            .is_err());
+    }
+
+    #[test]
+    fn turning_round_goes_back_over_the_same_led() {
+        for index in 0..PERIMETER {
+            for &direction in &[Direction::Clockwise, Direction::CounterClockwise] {
+                let state = State::from_index(index).unwrap();
+                let back = state
+                    .next(direction)
+                    .and_then(|next| next.next(direction.reversed()))
+                    .unwrap();
+                assert_eq!(back.position(), state.position(), "index {}", index);
+            }
+        }
+    }
+
+    #[test]
+    fn reversing_on_every_led_neither_skips_nor_lights_one_twice() {
+        let len = CLOCKWISE.len();
+        for index in 0..len {
+            for &(direction, ahead) in &[
+                (Direction::Clockwise, 1),
+                (Direction::CounterClockwise, len - 1),
+            ] {
+                let mut roulette: Roulette<1> = Roulette::new();
+                roulette.start_at(index as u8, direction).unwrap();
+                let first = roulette.next().unwrap();
+                let second = roulette.next().unwrap();
+                roulette.reverse();
+                let third = roulette.next().unwrap();
+                let fourth = roulette.next().unwrap();
+                assert_eq!(
+                    [first, second, third, fourth],
+                    [
+                        at(CLOCKWISE[index]),
+                        at(CLOCKWISE[(index + ahead) % len]),
+                        at(CLOCKWISE[(index + 2 * ahead) % len]),
+                        at(CLOCKWISE[(index + ahead) % len]),
+                    ],
+                    "index {}",
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn one_pixel_lights_one_led_at_a_time_round_the_perimeter() {
+        let mut roulette: Roulette<1> = Roulette::new();
+        let mut frame = Frame::new();
+        for step in 0..2 * CLOCKWISE.len() {
+            roulette.render(&mut frame);
+            frame.swap();
+            let (row, col) = CLOCKWISE[step % CLOCKWISE.len()];
+            assert_eq!(lit(frame.front()), [(row, col, HEAD)], "step {}", step);
+            roulette.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn speed_from_ms_rounds_down_to_a_step_within_the_bounds() {
+        assert_eq!(Speed::from_ms(0).ms(), Speed::MIN_MS);
+        assert_eq!(Speed::from_ms(Speed::MIN_MS - 1).ms(), Speed::MIN_MS);
+        assert_eq!(Speed::from_ms(55).ms(), 50);
+        assert_eq!(Speed::from_ms(Speed::MAX_MS).ms(), Speed::MAX_MS);
+        assert_eq!(Speed::from_ms(Speed::MAX_MS + 1).ms(), Speed::MAX_MS);
+        assert_eq!(Speed::from_ms(u32::MAX).ms(), Speed::MAX_MS);
+    }
+
+    #[test]
+    fn speed_goes_a_step_at_a_time() {
+        assert_eq!(
+            Speed::new().faster().ms(),
+            Speed::new().ms() - Speed::STEP_MS
+        );
+        assert_eq!(
+            Speed::new().slower().ms(),
+            Speed::new().ms() + Speed::STEP_MS
+        );
+        assert_eq!(Speed::new().faster().slower(), Speed::new());
+    }
+
+    #[test]
+    fn speed_stops_at_the_fastest_and_the_slowest() {
+        let mut speed = Speed::new();
+        for _ in 0..1000 {
+            speed = speed.faster();
+            assert!(speed.ms() >= Speed::MIN_MS);
+        }
+        assert_eq!(speed.ms(), Speed::MIN_MS);
+        for _ in 0..1000 {
+            speed = speed.slower();
+            assert!(speed.ms() <= Speed::MAX_MS);
+        }
+        assert_eq!(speed.ms(), Speed::MAX_MS);
+        assert_eq!(Speed::from_ms(Speed::MIN_MS).faster().ms(), Speed::MIN_MS);
+        assert_eq!(Speed::from_ms(Speed::MAX_MS).slower().ms(), Speed::MAX_MS);
+    }
+
+    #[test]
+    fn coordinates_stay_on_the_display() {
+        assert_eq!(
+            Coordinate::new(4, 4).map(|c| (c.row(), c.col())),
+            Some((4, 4))
+        );
+        assert_eq!(Coordinate::new(5, 0), None);
+        assert_eq!(Coordinate::new(0, 5), None);
+    }
+
+    #[test]
+    fn the_iterator_yields_the_perimeter_in_order_and_repeats() {
+        let roulette: Roulette = Roulette::new();
+        let walked: std::vec::Vec<_> = roulette.take(3 * CLOCKWISE.len()).collect();
+        let expected: std::vec::Vec<_> = CLOCKWISE
+            .iter()
+            .cycle()
+            .take(walked.len())
+            .map(|&p| at(p))
+            .collect();
+        assert_eq!(walked, expected);
+    }
+
+    #[test]
+    fn the_spiral_visits_every_led_once_before_starting_over() {
+        let mut roulette: Roulette = Roulette::new();
+        roulette.toggle_pattern();
+        assert_eq!(roulette.pattern(), Pattern::Spiral);
+        let walked: std::vec::Vec<_> = roulette.take(2 * 25).collect();
+        let (first, second) = walked.split_at(25);
+        assert_eq!(first, second);
+        let mut seen = [[0; 5]; 5];
+        for &coordinate in first {
+            seen[coordinate] += 1;
+        }
+        assert_eq!(seen, [[1; 5]; 5]);
+        assert_eq!(first[0], at((0, 0)));
+        assert_eq!(first[24], at((2, 2)));
+    }
+
+    #[test]
+    fn the_spiral_only_ever_steps_to_a_neighbour() {
+        let mut roulette: Roulette = Roulette::new();
+        roulette.toggle_pattern();
+        let walked: std::vec::Vec<_> = roulette.take(25).collect();
+        for pair in walked.windows(2) {
+            let rows = (pair[0].row() as i8 - pair[1].row() as i8).abs();
+            let cols = (pair[0].col() as i8 - pair[1].col() as i8).abs();
+            assert_eq!(rows + cols, 1, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn starting_the_spiral_over_leaves_the_snake_lit() {
+        let mut roulette: Roulette = Roulette::new();
+        roulette.toggle_pattern();
+        let mut frame = Frame::new();
+        roulette.render(&mut frame);
+        for _ in 0..25 {
+            roulette.step().unwrap();
+            roulette.render(&mut frame);
+        }
+        frame.swap();
+        // Back at the top left, the centre and the LED before it behind
+        assert_eq!(lit(frame.front()), [(0, 0, HEAD), (2, 1, 3), (2, 2, 6)]);
+    }
+
+    /// The display after `steps` steps clockwise from the LED `start`,
+    /// rendered after every one of them
+    fn snapshot<const N: usize>(start: u8, steps: usize) -> Image {
+        let mut roulette: Roulette<N> = Roulette::new();
+        roulette.start_at(start, Direction::Clockwise).unwrap();
+        let mut frame = Frame::new();
+        roulette.render(&mut frame);
+        for _ in 0..steps {
+            roulette.step().unwrap();
+            roulette.render(&mut frame);
+        }
+        frame.swap();
+        *frame.front()
+    }
+
+    #[test]
+    fn one_pixel_round_a_corner() {
+        assert_eq!(
+            snapshot::<1>(3, 2),
+            [
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 9],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn three_pixels_with_the_tail_on_a_corner() {
+        assert_eq!(
+            snapshot::<3>(3, 3),
+            [
+                [0, 0, 0, 0, 3],
+                [0, 0, 0, 0, 6],
+                [0, 0, 0, 0, 9],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+            ]
+        );
+        assert_eq!(
+            snapshot::<3>(10, 3),
+            [
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [9, 0, 0, 0, 0],
+                [6, 3, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn three_pixels_across_the_seam() {
+        // From the left column onto the top row, where the states start
+        // over
+        assert_eq!(
+            snapshot::<3>(14, 3),
+            [
+                [6, 9, 0, 0, 0],
+                [3, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+            ]
+        );
+        assert_eq!(
+            snapshot::<3>(14, 4),
+            [
+                [3, 6, 9, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+                [0, 0, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn sixteen_pixels_fill_the_perimeter_and_go_round_it() {
+        assert_eq!(
+            snapshot::<16>(0, 15),
+            [
+                [1, 2, 2, 3, 3],
+                [9, 0, 0, 0, 4],
+                [9, 0, 0, 0, 4],
+                [8, 0, 0, 0, 5],
+                [8, 7, 7, 6, 6],
+            ]
+        );
+        // The tail falls off where the head comes in
+        assert_eq!(
+            snapshot::<16>(0, 16),
+            [
+                [9, 1, 2, 2, 3],
+                [9, 0, 0, 0, 3],
+                [8, 0, 0, 0, 4],
+                [8, 0, 0, 0, 4],
+                [7, 7, 6, 6, 5],
+            ]
+        );
+    }
+
+    #[test]
+    fn render_erases_only_what_fell_off_the_tail() {
+        let mut roulette: Roulette = Roulette::new();
+        let mut frame = Frame::new();
+        roulette.render(&mut frame);
+        for _ in 0..2 {
+            roulette.step().unwrap();
+            roulette.render(&mut frame);
+        }
+        // Off the path, so only a full clear would take it out
+        frame.set(at((2, 2)), 1);
+        roulette.step().unwrap();
+        roulette.render(&mut frame);
+        frame.swap();
+        assert_eq!(
+            lit(frame.front()),
+            [(0, 1, 3), (0, 2, 6), (0, 3, HEAD), (2, 2, 1)]
+        );
+    }
+
+    #[test]
+    fn render_starts_from_blank_after_more_steps_than_pixels() {
+        let mut roulette: Roulette<1> = Roulette::new();
+        let mut frame = Frame::new();
+        roulette.render(&mut frame);
+        frame.set(at((2, 2)), 1);
+        roulette.step().unwrap();
+        roulette.step().unwrap();
+        roulette.render(&mut frame);
+        frame.swap();
+        assert_eq!(lit(frame.front()), [(0, 2, HEAD)]);
+    }
+}
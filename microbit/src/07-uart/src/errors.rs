@@ -0,0 +1,101 @@
+//! How reading a line off the serial port and answering it goes wrong.
+//! Shared by the chapters that read lines off the serial port, like
+//! [`line`], this file is included by path from 08-i2c.
+
+use core::fmt;
+
+use crate::line;
+
+/// `E` is how the port itself goes wrong, the UARTE's error wherever the
+/// port is one.
+#[derive(Debug)]
+pub enum SerialError<E> {
+    Uarte(E),
+    /// The line is full, with the byte that didn't fit
+    Push(u8),
+    Utf8(core::str::Utf8Error),
+    Write(fmt::Error),
+}
+
+impl<E> From<u8> for SerialError<E> {
+    fn from(value: u8) -> Self {
+        SerialError::Push(value)
+    }
+}
+
+impl<E> From<core::str::Utf8Error> for SerialError<E> {
+    fn from(value: core::str::Utf8Error) -> Self {
+        SerialError::Utf8(value)
+    }
+}
+
+impl<E> From<fmt::Error> for SerialError<E> {
+    fn from(value: fmt::Error) -> Self {
+        SerialError::Write(value)
+    }
+}
+
+impl<E> From<line::Error<E>> for SerialError<E> {
+    fn from(value: line::Error<E>) -> Self {
+        match value {
+            line::Error::Serial(err) => SerialError::Uarte(err),
+            line::Error::Full(byte) => SerialError::Push(byte),
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for SerialError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerialError::Uarte(err) => write!(f, "serial communication: {:?}", err),
+            SerialError::Push(byte) => write!(f, "line too long, no room for {:#04x}", byte),
+            SerialError::Utf8(err) => write!(f, "utf8 conversion: {}", err),
+            SerialError::Write(err) => write!(f, "formatted write: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[derive(Debug)]
+    struct Overrun;
+
+    type Error = SerialError<Overrun>;
+
+    #[test]
+    fn a_full_line_shows_the_byte_that_did_not_fit() {
+        assert_eq!(
+            Error::Push(b'x').to_string(),
+            "line too long, no room for 0x78"
+        );
+        assert_eq!(
+            Error::Push(0).to_string(),
+            "line too long, no room for 0x00"
+        );
+        assert_eq!(
+            Error::from(line::Error::Full(0xc3)).to_string(),
+            "line too long, no room for 0xc3"
+        );
+    }
+
+    #[test]
+    fn the_other_errors_show_their_own_messages() {
+        let typed = std::vec![b'a', 0xff];
+        let utf8 = core::str::from_utf8(&typed).unwrap_err();
+        assert_eq!(
+            Error::from(utf8).to_string(),
+            "utf8 conversion: invalid utf-8 sequence of 1 bytes from index 1"
+        );
+        assert_eq!(
+            Error::from(fmt::Error).to_string(),
+            "formatted write: an error occurred when formatting an argument"
+        );
+        assert_eq!(
+            Error::from(line::Error::Serial(Overrun)).to_string(),
+            "serial communication: Overrun"
+        );
+    }
+}
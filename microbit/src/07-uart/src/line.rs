@@ -0,0 +1,328 @@
+//! Editing a line as it is typed: backspace takes back the last character
+//! and Ctrl-U the whole line. Shared by the chapters that read lines off the
+//! serial port, this file is included by path from 08-i2c.
+//!
+//! [`edit`] takes the bytes the caller has no use for itself, such as the
+//! end of the line, and echoes them, or whatever erases what they take
+//! back from the terminal. [`input`] tells the end of the line apart from
+//! the rest, however the terminal ends its lines.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use embedded_hal::serial::Write;
+use heapless::Vec;
+
+pub const BACKSPACE: u8 = 0x08;
+/// What most terminals send for the backspace key
+pub const DELETE: u8 = 0x7f;
+pub const CTRL_U: u8 = 0x15;
+
+/// Back one, over it with a space, and back again
+const ERASE: &[u8] = b"\x08 \x08";
+
+/// The last byte read ended a line with a CR. There is only the one
+/// serial port to read lines off.
+static AFTER_CR: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    /// CR, LF, or CR LF
+    End,
+    /// The LF of a CR LF, which would otherwise end an empty line of its
+    /// own
+    Swallowed,
+    Byte(u8),
+}
+
+/// What `byte`, the next one read, means for the end of the line.
+pub fn input(byte: u8) -> Input {
+    let after_cr = AFTER_CR.load(Ordering::Relaxed);
+    AFTER_CR.store(byte == b'\r', Ordering::Relaxed);
+    match byte {
+        b'\n' if after_cr => Input::Swallowed,
+        b'\r' | b'\n' => Input::End,
+        byte => Input::Byte(byte),
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    Serial(E),
+    /// The line is full, with the byte that didn't fit
+    Full(u8),
+}
+
+/// What a byte did to the line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edit {
+    Typed,
+    /// Took back the last character, if there was one
+    Erased,
+    /// Took back the whole line
+    Killed,
+}
+
+/// Take `byte` into `buffer` and echo it to `serial`, or edit with it.
+///
+/// Only what comes after `start` shows on the terminal's current line, so
+/// nothing before it is ever erased; a line read in parts, each on its
+/// own terminal line, passes where the last part starts. Erasing with
+/// nothing left to erase does nothing at all.
+pub fn edit<S: Write<u8>, const N: usize>(
+    serial: &mut S,
+    buffer: &mut Vec<u8, N>,
+    start: usize,
+    byte: u8,
+) -> Result<Edit, Error<S::Error>> {
+    let edit = match byte {
+        BACKSPACE | DELETE => {
+            if pop_char(buffer, start) {
+                write_all(serial, ERASE)?;
+            }
+            Edit::Erased
+        }
+        CTRL_U => {
+            while pop_char(buffer, start) {
+                write_all(serial, ERASE)?;
+            }
+            Edit::Killed
+        }
+        _ => {
+            buffer.push(byte).map_err(Error::Full)?;
+            write_all(serial, &[byte])?;
+            Edit::Typed
+        }
+    };
+    nb::block!(serial.flush()).map_err(Error::Serial)?;
+    Ok(edit)
+}
+
+/// Take the last character off `buffer`, all of its bytes, but nothing
+/// before `start`. Whether there was one to take.
+fn pop_char<const N: usize>(buffer: &mut Vec<u8, N>, start: usize) -> bool {
+    if buffer.len() <= start {
+        return false;
+    }
+    // The bytes that carry on a character, back to the one that starts it
+    while let Some(byte) = buffer.pop() {
+        if buffer.len() <= start || !(0x80..0xc0).contains(&byte) {
+            break;
+        }
+    }
+    true
+}
+
+fn write_all<S: Write<u8>>(serial: &mut S, bytes: &[u8]) -> Result<(), Error<S::Error>> {
+    for &byte in bytes {
+        nb::block!(serial.write(byte)).map_err(Error::Serial)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::serial::Read;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    /// There is only the one serial port here too: whatever reads lines
+    /// takes this first.
+    static PORT: Mutex<()> = Mutex::new(());
+
+    #[derive(Debug, PartialEq)]
+    struct Broken;
+
+    /// What was typed and has yet to be read, and what went back out.
+    #[derive(Default)]
+    struct Terminal {
+        typed: VecDeque<u8>,
+        shown: Vec<u8>,
+        flushes: usize,
+        broken: bool,
+    }
+
+    impl Terminal {
+        fn typing(typed: &[u8]) -> Terminal {
+            Terminal {
+                typed: typed.iter().copied().collect(),
+                ..Terminal::default()
+            }
+        }
+    }
+
+    impl Read<u8> for Terminal {
+        type Error = Broken;
+
+        fn read(&mut self) -> nb::Result<u8, Broken> {
+            self.typed.pop_front().ok_or(nb::Error::WouldBlock)
+        }
+    }
+
+    impl Write<u8> for Terminal {
+        type Error = Broken;
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), Broken> {
+            if self.broken {
+                return Err(nb::Error::Other(Broken));
+            }
+            self.shown.push(byte);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Broken> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Line {
+        Read(Vec<u8>),
+        /// Too long, with the byte that didn't fit
+        Full(u8),
+    }
+
+    /// Read lines of up to `N` bytes off `terminal` the way the chapters
+    /// do, until nothing more has been typed.
+    fn read_lines<const N: usize>(terminal: &mut Terminal) -> Vec<Line> {
+        let _port = PORT.lock().unwrap();
+        AFTER_CR.store(false, Ordering::Relaxed);
+        let mut lines = Vec::new();
+        let mut buffer: heapless::Vec<u8, N> = heapless::Vec::new();
+        while let Ok(byte) = terminal.read() {
+            match input(byte) {
+                Input::Byte(byte) => match edit(terminal, &mut buffer, 0, byte) {
+                    Ok(_) => {}
+                    Err(Error::Full(byte)) => {
+                        lines.push(Line::Full(byte));
+                        buffer.clear();
+                    }
+                    Err(Error::Serial(err)) => panic!("{:?}", err),
+                },
+                Input::Swallowed => {}
+                Input::End => {
+                    lines.push(Line::Read(buffer.to_vec()));
+                    buffer.clear();
+                }
+            }
+        }
+        lines
+    }
+
+    fn read(text: &str) -> Line {
+        Line::Read(text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn a_cr_an_lf_or_both_end_a_line() {
+        let mut terminal = Terminal::typing(b"one\rtwo\nthree\r\nfour\r");
+        assert_eq!(
+            read_lines::<32>(&mut terminal),
+            [read("one"), read("two"), read("three"), read("four")]
+        );
+        // The line endings are for the caller to answer
+        assert_eq!(terminal.shown, b"onetwothreefour");
+    }
+
+    #[test]
+    fn an_lf_after_a_cr_lf_is_an_empty_line() {
+        let mut terminal = Terminal::typing(b"a\r\n\nb\n\r");
+        assert_eq!(
+            read_lines::<32>(&mut terminal),
+            [read("a"), read(""), read("b"), read("")]
+        );
+    }
+
+    #[test]
+    fn backspace_and_delete_take_back_the_last_character() {
+        let mut terminal = Terminal::typing(b"abc\x08d\x7fe\r");
+        assert_eq!(read_lines::<32>(&mut terminal), [read("abe")]);
+        assert_eq!(terminal.shown, b"abc\x08 \x08d\x08 \x08e");
+    }
+
+    #[test]
+    fn backspace_takes_back_every_byte_of_a_character() {
+        let mut typed = "aé€😀".as_bytes().to_vec();
+        typed.extend_from_slice(b"\x7f\x7f\x7fb\r");
+        let mut terminal = Terminal::typing(&typed);
+        assert_eq!(read_lines::<32>(&mut terminal), [read("ab")]);
+        // One column erased for each
+        let mut shown = "aé€😀".as_bytes().to_vec();
+        shown.extend_from_slice(&ERASE.repeat(3));
+        shown.push(b'b');
+        assert_eq!(terminal.shown, shown);
+    }
+
+    #[test]
+    fn backspace_with_nothing_to_take_back_does_nothing() {
+        let mut terminal = Terminal::typing(b"\x08\x7fa\r");
+        assert_eq!(read_lines::<32>(&mut terminal), [read("a")]);
+        assert_eq!(terminal.shown, b"a");
+    }
+
+    #[test]
+    fn ctrl_u_takes_back_the_whole_line_a_character_at_a_time() {
+        let mut typed = "héllo".as_bytes().to_vec();
+        typed.extend_from_slice(b"\x15x\r");
+        let mut terminal = Terminal::typing(&typed);
+        assert_eq!(read_lines::<32>(&mut terminal), [read("x")]);
+        let mut shown = "héllo".as_bytes().to_vec();
+        shown.extend_from_slice(&ERASE.repeat(5));
+        shown.push(b'x');
+        assert_eq!(terminal.shown, shown);
+    }
+
+    #[test]
+    fn a_line_too_long_says_which_byte_did_not_fit() {
+        let mut terminal = Terminal::typing(b"abcdef\r");
+        assert_eq!(
+            read_lines::<4>(&mut terminal),
+            [Line::Full(b'e'), read("f")]
+        );
+        // Nothing goes out for the byte that didn't fit
+        assert_eq!(terminal.shown, b"abcdf");
+    }
+
+    #[test]
+    fn nothing_before_the_start_is_erased() {
+        let mut terminal = Terminal::default();
+        let mut buffer: heapless::Vec<u8, 8> = heapless::Vec::new();
+        buffer.extend_from_slice("ab".as_bytes()).unwrap();
+        for byte in [BACKSPACE, CTRL_U] {
+            let edit = edit(&mut terminal, &mut buffer, 2, byte);
+            assert!(edit.is_ok());
+        }
+        assert_eq!(buffer, b"ab");
+        assert_eq!(terminal.shown, b"");
+        buffer.extend_from_slice("é".as_bytes()).unwrap();
+        assert_eq!(
+            edit(&mut terminal, &mut buffer, 2, BACKSPACE).unwrap(),
+            Edit::Erased
+        );
+        assert_eq!(buffer, b"ab");
+    }
+
+    #[test]
+    fn every_edit_is_flushed() {
+        let mut terminal = Terminal::default();
+        let mut buffer: heapless::Vec<u8, 8> = heapless::Vec::new();
+        for byte in [b'a', BACKSPACE, b'b', CTRL_U] {
+            edit(&mut terminal, &mut buffer, 0, byte).unwrap();
+        }
+        assert_eq!(terminal.flushes, 4);
+    }
+
+    #[test]
+    fn the_port_failing_is_an_error_of_its_own() {
+        let mut terminal = Terminal {
+            broken: true,
+            ..Terminal::default()
+        };
+        let mut buffer: heapless::Vec<u8, 8> = heapless::Vec::new();
+        assert!(matches!(
+            edit(&mut terminal, &mut buffer, 0, b'a'),
+            Err(Error::Serial(Broken))
+        ));
+    }
+}
@@ -0,0 +1,30 @@
+//! Logging through rtt-target, or with the "defmt" feature through
+//! defmt-rtt, for `probe-rs run` to decode. [`log!`] is either backend's
+//! println, so what it logs has to be something both can format: numbers
+//! and plain text, like everything this chapter logs.
+
+#[cfg(not(feature = "defmt"))]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        rtt_target::rprintln!($($arg)*)
+    };
+}
+
+#[cfg(feature = "defmt")]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        defmt::println!($($arg)*)
+    };
+}
+
+pub(crate) use log;
+
+/// Set up the RTT channel the log goes out on.
+#[cfg(not(feature = "defmt"))]
+pub fn init() {
+    rtt_target::rtt_init_print!();
+}
+
+/// defmt-rtt sets up its channel itself.
+#[cfg(feature = "defmt")]
+pub fn init() {}
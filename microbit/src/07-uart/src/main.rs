@@ -4,8 +4,14 @@
 use core::fmt::Write;
 use cortex_m_rt::entry;
 use heapless::Vec;
+use microbit::display::blocking::Display;
+use microbit::hal::timer::Timer;
+#[cfg(feature = "defmt")]
+use defmt_rtt as _;
+#[cfg(all(feature = "defmt", not(feature = "panic-serial")))]
+use panic_probe as _;
+#[cfg(not(any(feature = "defmt", feature = "panic-serial")))]
 use panic_rtt_target as _;
-use rtt_target::{rprintln, rtt_init_print};
 
 #[cfg(feature = "v1")]
 use microbit::{
@@ -16,39 +22,54 @@ use microbit::{
 
 #[cfg(feature = "v2")]
 use microbit::{
-    hal::prelude::*,
     hal::uarte,
     hal::uarte::{Baudrate, Parity},
 };
+// The embedded-hal traits, unless the echo goes through embedded-io's
+#[cfg(all(feature = "v2", not(feature = "io-echo")))]
+use microbit::hal::prelude::*;
 
+mod errors;
+mod line;
+mod log;
+#[cfg(feature = "panic-serial")]
+mod panic_serial;
+#[path = "../../05-led-roulette/src/pulse.rs"]
+mod pulse;
+mod reverse;
 #[cfg(feature = "v2")]
 mod serial_setup;
 #[cfg(feature = "v2")]
-use serial_setup::UartePort;
+use serial_setup::{Buffers, UartePort};
+
+use errors::SerialError;
+use log::log;
+use reverse::{ends_mid_char, write_reversed};
+
+type Error = SerialError<microbit::hal::uarte::Error>;
 
-#[derive(Debug)]
-enum Error {
-    UarteError(microbit::hal::uarte::Error),
-    WriteError(core::fmt::Error),
-    PushError(u8),
+#[cfg(not(feature = "io-echo"))]
+fn read_byte<T: microbit::hal::uarte::Instance>(serial: &mut UartePort<T>) -> Result<u8, Error> {
+    nb::block!(serial.read()).map_err(SerialError::Uarte)
 }
 
-impl From<u8> for Error {
-    fn from(value: u8) -> Error {
-        return Error::PushError(value);
-    }
+#[cfg(not(feature = "io-echo"))]
+fn flush<T: microbit::hal::uarte::Instance>(serial: &mut UartePort<T>) -> Result<(), Error> {
+    nb::block!(serial.flush()).map_err(SerialError::Uarte)
 }
 
-impl From<core::fmt::Error> for Error {
-    fn from(value: core::fmt::Error) -> Error {
-        return Error::WriteError(value);
-    }
+/// The same through embedded-io, which reads as much as has come in at
+/// once: asked for a byte, that is the byte.
+#[cfg(feature = "io-echo")]
+fn read_byte<T: microbit::hal::uarte::Instance>(serial: &mut UartePort<T>) -> Result<u8, Error> {
+    let mut byte = [0];
+    embedded_io::Read::read(serial, &mut byte).map_err(|err| SerialError::Uarte(err.0))?;
+    Ok(byte[0])
 }
 
-impl From<microbit::hal::uarte::Error> for Error {
-    fn from(value: microbit::hal::uarte::Error) -> Error {
-        return Error::UarteError(value);
-    }
+#[cfg(feature = "io-echo")]
+fn flush<T: microbit::hal::uarte::Instance>(serial: &mut UartePort<T>) -> Result<(), Error> {
+    embedded_io::Write::flush(serial).map_err(|err| SerialError::Uarte(err.0))
 }
 
 fn echo_one_word<T: microbit::hal::uarte::Instance>(
@@ -57,28 +78,54 @@ fn echo_one_word<T: microbit::hal::uarte::Instance>(
 ) -> Result<(), Error> {
     buffer.clear();
     loop {
-        let byte = nb::block!(serial.read())?;
-        rprintln!("Received {}", byte);
-        rprintln!("Buffer length so far: {}", buffer.len());
-        if byte == b'\r' {
-            rprintln!("Enter received, sending!");
-            buffer.reverse();
-            serial.bwrite_all(buffer.as_slice())?;
-            writeln!(serial, "\r")?;
-            nb::block!(serial.flush())?;
-            return Ok(());
-        } else if let Err(_) = buffer.push(byte) {
-            writeln!(serial, "ERROR: Entered string too long, resetting!\r")?;
-            nb::block!(serial.flush())?;
-            return Ok(());
+        let byte = read_byte(serial)?;
+        log!("Received {}", byte);
+        log!("Buffer length so far: {}", buffer.len());
+        let byte = match line::input(byte) {
+            line::Input::Byte(byte) => byte,
+            line::Input::Swallowed => continue,
+            // Nothing to send back
+            line::Input::End if buffer.is_empty() => continue,
+            line::Input::End => {
+                log!("Enter received, sending!");
+                writeln!(serial)?;
+                write_reversed(serial, buffer)?;
+                writeln!(serial)?;
+                flush(serial)?;
+                return Ok(());
+            }
+        };
+        match line::edit(serial, buffer, 0, byte) {
+            Ok(_) => {}
+            Err(line::Error::Full(byte)) => {
+                log!("No room for {}", byte);
+                // A character that starts in the buffer but goes on past
+                // it, whether it got cut at the last byte or the one that
+                // didn't fit
+                if ends_mid_char(buffer) || (0x80..0xc0).contains(&byte) {
+                    writeln!(
+                        serial,
+                        "\nERROR: Entered string too long, cut off partway through a character, resetting!"
+                    )?;
+                } else {
+                    writeln!(serial, "\nERROR: Entered string too long, resetting!")?;
+                }
+                flush(serial)?;
+                return Ok(());
+            }
+            Err(line::Error::Serial(err)) => return Err(SerialError::Uarte(err)),
         }
     }
 }
 
 #[entry]
 fn main() -> ! {
-    rtt_init_print!();
+    log::init();
     let board = microbit::Board::take().unwrap();
+    pulse::heartbeat(
+        &mut Display::new(board.display_pins),
+        &mut Timer::new(board.TIMER0),
+    );
 
     #[cfg(feature = "v1")]
     let mut serial = {
@@ -98,7 +145,9 @@ fn main() -> ! {
             Parity::EXCLUDED,
             Baudrate::BAUD115200,
         );
-        UartePort::new(serial)
+        // The buffers have to outlive the port, and be in RAM for the DMA
+        let buffers = cortex_m::singleton!(: Buffers = Buffers::new()).unwrap();
+        UartePort::new(serial, buffers).unwrap()
     };
 
     let mut buffer: Vec<u8, 32> = Vec::new();
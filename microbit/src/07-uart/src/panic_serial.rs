@@ -0,0 +1,117 @@
+//! A panic handler for the "panic-serial" feature, for a board that runs
+//! off a battery with only a serial cable: panic-rtt-target's message takes
+//! a debugger to show, and without one a panic looks like a hang. This one
+//! writes the message and where it happened to the serial port, and then
+//! blinks a cross on the display for as long as the board has power.
+//! Shared by the chapters with a serial port, this file is included by path
+//! from 08-i2c.
+//!
+//! Nothing the panicking code set up can be relied on, or even that it got
+//! that far: the handler turns the interrupts off, steals the peripherals,
+//! and sets up the serial port from scratch, on the pins to the interface
+//! chip at 115200 baud. The message goes out as it is, text that isn't
+//! ASCII as the UTF-8 it was, and nothing here slices a string. A panic
+//! while writing it, from a `Display` that panics say, goes straight to the
+//! cross.
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embedded_hal::blocking::delay::DelayMs;
+use microbit::display::blocking::Display;
+
+const CROSS: [[u8; 5]; 5] = [
+    [1, 0, 0, 0, 1],
+    [0, 1, 0, 1, 0],
+    [0, 0, 1, 0, 0],
+    [0, 1, 0, 1, 0],
+    [1, 0, 0, 0, 1],
+];
+/// On for this long, then off for as long
+const BLINK_MS: u32 = 500;
+
+/// Set once the message is on its way, so a panic in the middle of it
+/// doesn't start it over
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+#[panic_handler]
+fn panic(info: &PanicInfo<'_>) -> ! {
+    cortex_m::interrupt::disable();
+    // Whatever else had them is never going to run again
+    let (mut serial, pins, mut timer) = unsafe { chip::steal() };
+    if !REPORTED.load(Ordering::Relaxed) {
+        REPORTED.store(true, Ordering::Relaxed);
+        // Nowhere left to say so if this goes wrong too
+        let _ = writeln!(Crlf(&mut serial), "\n{}", info);
+    }
+    let mut display = Display::new(pins);
+    loop {
+        display.show(&mut timer, CROSS, BLINK_MS);
+        display.clear();
+        timer.delay_ms(BLINK_MS);
+    }
+}
+
+/// Every "\n" goes out as "\r\n", the line ending the terminal expects.
+struct Crlf<W>(W);
+
+impl<W: Write> Write for Crlf<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i != 0 {
+                self.0.write_str("\r\n")?;
+            }
+            self.0.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The UARTE on P0.06 and P1.08.
+#[cfg(feature = "v2")]
+mod chip {
+    use microbit::gpio::DisplayPins;
+    use microbit::hal::gpio::{p0, p1, Level};
+    use microbit::hal::timer::Timer;
+    use microbit::hal::uarte::{self, Baudrate, Parity, Uarte};
+    use microbit::pac::{self, TIMER0, UARTE0};
+
+    pub unsafe fn steal() -> (Uarte<UARTE0>, DisplayPins, Timer<TIMER0>) {
+        let p = pac::Peripherals::steal();
+        // The HAL stops a write that was going on, but not a read
+        p.UARTE0.tasks_stoprx.write(|w| w.bits(1));
+        let p0 = p0::Parts::new(p.P0);
+        let p1 = p1::Parts::new(p.P1);
+        let pins = uarte::Pins {
+            txd: p0.p0_06.into_push_pull_output(Level::High).degrade(),
+            rxd: p1.p1_08.into_floating_input().degrade(),
+            cts: None,
+            rts: None,
+        };
+        let serial = Uarte::new(p.UARTE0, pins, Parity::EXCLUDED, Baudrate::BAUD115200);
+        (serial, microbit::display_pins!(p0, p1), Timer::new(p.TIMER0))
+    }
+}
+
+/// The UART on P0.24 and P0.25.
+#[cfg(feature = "v1")]
+mod chip {
+    use microbit::gpio::DisplayPins;
+    use microbit::hal::gpio::{p0, Level};
+    use microbit::hal::timer::Timer;
+    use microbit::hal::uart::{self, Baudrate, Parity, Uart};
+    use microbit::pac::{self, TIMER0, UART0};
+
+    pub unsafe fn steal() -> (Uart<UART0>, DisplayPins, Timer<TIMER0>) {
+        let p = pac::Peripherals::steal();
+        let p0 = p0::Parts::new(p.GPIO);
+        let pins = uart::Pins {
+            txd: p0.p0_24.into_push_pull_output(Level::High).degrade(),
+            rxd: p0.p0_25.into_floating_input().degrade(),
+            cts: None,
+            rts: None,
+        };
+        let serial = Uart::new(p.UART0, pins, Parity::EXCLUDED, Baudrate::BAUD115200);
+        (serial, microbit::display_pins!(p0), Timer::new(p.TIMER0))
+    }
+}
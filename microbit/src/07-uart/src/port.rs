@@ -0,0 +1,237 @@
+//! What [`UartePort`](super::UartePort) does that doesn't touch the UARTE:
+//! gathering bytes in the DMA buffer until it is full or flushed, the line
+//! ending, and what its errors are to `embedded-io`. A module of
+//! serial_setup.rs, and on the host of the stand-in for it that 08-i2c's
+//! tests build instead.
+
+use core::fmt;
+
+use super::Error;
+
+/// How many bytes are waiting in the DMA buffer to go out.
+pub struct Pending(usize);
+
+impl Pending {
+    pub const NONE: Pending = Pending(0);
+
+    /// Take `byte` into `tx`, sending what is waiting there first if there
+    /// is no room left.
+    pub fn push<F>(&mut self, tx: &mut [u8], byte: u8, send: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        if self.0 == tx.len() {
+            self.send(tx, send)?;
+        }
+        tx[self.0] = byte;
+        self.0 += 1;
+        Ok(())
+    }
+
+    /// Send what is waiting in `tx`, if anything. It stops waiting either
+    /// way, gone or lost.
+    pub fn send<F>(&mut self, tx: &[u8], mut send: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        if self.0 == 0 {
+            return Ok(());
+        }
+        let written = self.0;
+        self.0 = 0;
+        send(&tx[..written])
+    }
+
+    /// Take all of `bytes` and send them, in as few transfers as `tx`
+    /// allows, the last one included.
+    pub fn write_all<F>(&mut self, tx: &mut [u8], bytes: &[u8], mut send: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        for &byte in bytes {
+            self.push(tx, byte, &mut send)?;
+        }
+        self.send(tx, send)
+    }
+}
+
+/// Write `s` with `write_all`, every "\n" in it as "\r\n", the line ending
+/// the terminal expects. The only place a line ending gets written.
+pub fn write_lines<F>(s: &str, mut write_all: F) -> fmt::Result
+where
+    F: FnMut(&[u8]) -> Result<(), Error>,
+{
+    let mut lines = s.split('\n');
+    if let Some(first) = lines.next() {
+        write_all(first.as_bytes()).map_err(|_| fmt::Error)?;
+    }
+    for line in lines {
+        write_all(b"\r\n").map_err(|_| fmt::Error)?;
+        write_all(line.as_bytes()).map_err(|_| fmt::Error)?;
+    }
+    Ok(())
+}
+
+/// The UARTE's error, with the kind the `embedded-io` traits ask for.
+#[derive(Debug)]
+pub struct IoError(pub Error);
+
+impl embedded_io::Error for IoError {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        use embedded_io::ErrorKind;
+        match self.0 {
+            Error::TxBufferTooSmall
+            | Error::RxBufferTooSmall
+            | Error::TxBufferTooLong
+            | Error::RxBufferTooLong
+            | Error::BufferNotInRAM => ErrorKind::InvalidInput,
+            Error::Timeout(_) => ErrorKind::TimedOut,
+            // An overrun, a framing or parity error or a break coming in,
+            // or the transmitter stopped, none of which has a kind of its
+            // own
+            Error::Transmit | Error::Receive => ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_io::{Error as _, ErrorKind};
+    use std::string::String;
+    use std::vec::Vec;
+
+    /// A DMA buffer of four bytes, and every transfer out of it.
+    struct Port {
+        tx: [u8; 4],
+        pending: Pending,
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl Port {
+        fn new() -> Port {
+            Port {
+                tx: [0; 4],
+                pending: Pending::NONE,
+                sent: Vec::new(),
+            }
+        }
+
+        fn push(&mut self, byte: u8) -> Result<(), Error> {
+            let sent = &mut self.sent;
+            self.pending.push(&mut self.tx, byte, |bytes| {
+                sent.push(bytes.to_vec());
+                Ok(())
+            })
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            let sent = &mut self.sent;
+            self.pending.send(&self.tx, |bytes| {
+                sent.push(bytes.to_vec());
+                Ok(())
+            })
+        }
+
+        fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+            let sent = &mut self.sent;
+            self.pending.write_all(&mut self.tx, bytes, |bytes| {
+                sent.push(bytes.to_vec());
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn bytes_wait_until_the_buffer_is_full_or_flushed() {
+        let mut port = Port::new();
+        for &byte in b"abcd" {
+            port.push(byte).unwrap();
+        }
+        assert!(port.sent.is_empty());
+        port.push(b'e').unwrap();
+        assert_eq!(port.sent, [b"abcd"]);
+        port.flush().unwrap();
+        assert_eq!(port.sent, [&b"abcd"[..], b"e"]);
+        // Nothing left to send
+        port.flush().unwrap();
+        assert_eq!(port.sent.len(), 2);
+    }
+
+    #[test]
+    fn write_all_sends_the_last_of_it_too() {
+        let mut port = Port::new();
+        port.write_all(b"hello world").unwrap();
+        assert_eq!(port.sent, [&b"hell"[..], b"o wo", b"rld"]);
+        port.write_all(b"").unwrap();
+        assert_eq!(port.sent.len(), 3);
+    }
+
+    #[test]
+    fn write_all_sends_what_was_waiting_first() {
+        let mut port = Port::new();
+        port.push(b'>').unwrap();
+        port.write_all(b"ok").unwrap();
+        assert_eq!(port.sent, [b">ok"]);
+    }
+
+    #[test]
+    fn a_failed_transfer_is_not_sent_again() {
+        let mut pending = Pending::NONE;
+        let mut tx = [0; 4];
+        let mut tries = 0;
+        let failed = pending.write_all(&mut tx, b"ab", |_| {
+            tries += 1;
+            Err(Error::Transmit)
+        });
+        assert!(matches!(failed, Err(Error::Transmit)));
+        assert!(pending.send(&tx, |_| panic!("sent again")).is_ok());
+        assert_eq!(tries, 1);
+    }
+
+    /// What `s` goes out as.
+    fn lines(s: &str) -> String {
+        let mut out = Vec::new();
+        write_lines(s, |bytes| {
+            out.extend_from_slice(bytes);
+            Ok(())
+        })
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn every_lf_goes_out_as_cr_lf() {
+        assert_eq!(lines("one\ntwo\n"), "one\r\ntwo\r\n");
+        assert_eq!(lines("\n\n"), "\r\n\r\n");
+        assert_eq!(lines("no end"), "no end");
+        assert_eq!(lines(""), "");
+        // A CR already there is left alone
+        assert_eq!(lines("a\r\n"), "a\r\r\n");
+    }
+
+    #[test]
+    fn a_failed_write_is_a_formatting_error() {
+        assert_eq!(
+            write_lines("a\nb", |_| Err(Error::Transmit)),
+            Err(fmt::Error)
+        );
+    }
+
+    #[test]
+    fn every_error_has_the_kind_it_should() {
+        let kinds = [
+            (Error::TxBufferTooSmall, ErrorKind::InvalidInput),
+            (Error::RxBufferTooSmall, ErrorKind::InvalidInput),
+            (Error::TxBufferTooLong, ErrorKind::InvalidInput),
+            (Error::RxBufferTooLong, ErrorKind::InvalidInput),
+            (Error::BufferNotInRAM, ErrorKind::InvalidInput),
+            (Error::Timeout(3), ErrorKind::TimedOut),
+            (Error::Transmit, ErrorKind::Other),
+            (Error::Receive, ErrorKind::Other),
+        ];
+        for (err, kind) in kinds {
+            assert_eq!(IoError(err).kind(), kind);
+        }
+    }
+}
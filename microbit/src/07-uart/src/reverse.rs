@@ -0,0 +1,90 @@
+//! Sending a line back reversed, character by character. Only the echo
+//! server uses it, but 08-i2c's host tests build this file by path, the
+//! way they do [`line`](crate::line).
+
+use core::fmt::Write;
+
+/// Whether `bytes` end partway through a character, however valid they
+/// are up to there.
+pub fn ends_mid_char(bytes: &[u8]) -> bool {
+    match core::str::from_utf8(bytes) {
+        Ok(_) => false,
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+/// Send `buffer` back a character at a time, last first. Reversing the
+/// bytes would only work for ASCII, and turn anything else into garbage.
+pub fn write_reversed<W: Write>(serial: &mut W, buffer: &[u8]) -> core::fmt::Result {
+    match core::str::from_utf8(buffer) {
+        Ok(text) => text.chars().rev().try_for_each(|c| serial.write_char(c)),
+        Err(_) if ends_mid_char(buffer) => {
+            write!(
+                serial,
+                "ERROR: Entered string ends partway through a character"
+            )
+        }
+        Err(err) => write!(
+            serial,
+            "ERROR: Entered string is not valid UTF-8 after byte {}",
+            err.valid_up_to()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+
+    fn reversed(bytes: &[u8]) -> String {
+        let mut sent = String::new();
+        write_reversed(&mut sent, bytes).unwrap();
+        sent
+    }
+
+    #[test]
+    fn ascii_comes_back_backwards() {
+        assert_eq!(reversed(b"hello"), "olleh");
+        assert_eq!(reversed(b""), "");
+    }
+
+    #[test]
+    fn two_byte_characters_stay_whole() {
+        assert_eq!(reversed("héllo".as_bytes()), "olléh");
+        assert_eq!(reversed("éè".as_bytes()), "èé");
+    }
+
+    #[test]
+    fn four_byte_characters_stay_whole() {
+        assert_eq!(reversed("a😀b".as_bytes()), "b😀a");
+        assert_eq!(reversed("😀🦀".as_bytes()), "🦀😀");
+    }
+
+    #[test]
+    fn a_line_cut_off_partway_through_a_character_says_so() {
+        let whole = "ab😀".as_bytes();
+        for cut in 3..whole.len() {
+            assert!(ends_mid_char(&whole[..cut]));
+            assert_eq!(
+                reversed(&whole[..cut]),
+                "ERROR: Entered string ends partway through a character"
+            );
+        }
+        assert!(!ends_mid_char(whole));
+    }
+
+    #[test]
+    fn bytes_that_are_not_utf8_say_where() {
+        assert!(!ends_mid_char(b"ab\xffcd"));
+        assert_eq!(
+            reversed(b"ab\xffcd"),
+            "ERROR: Entered string is not valid UTF-8 after byte 2"
+        );
+        // A character that starts and then doesn't go on
+        assert_eq!(
+            reversed(b"a\xc3b"),
+            "ERROR: Entered string is not valid UTF-8 after byte 1"
+        );
+    }
+}
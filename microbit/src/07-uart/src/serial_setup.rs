@@ -1,46 +1,289 @@
+//! The UARTE as a serial port, moving data by DMA. Output goes out a
+//! buffer at a time, input comes in the same way: a paste arrives faster
+//! than it can be echoed, and would overrun the few bytes of FIFO if the
+//! receiver only ever had room for one more. Shared by the chapters with a
+//! UARTE, this file is included by path from 08-i2c, where it is the v2's
+//! console under the port that keeps count and watches for Ctrl-C.
+//!
+//! [`UartePort`] takes whichever of the chip's UARTEs the HAL's sealed
+//! `Instance` lets it, and keeps the whole `Uarte` rather than the halves
+//! the HAL would split it into, so that [`UartePort::free`] can hand it
+//! back to be set up again, at another baud rate say.
+//!
+//! Besides embedded-hal 0.2's serial traits it has the `embedded-io` ones,
+//! for the drivers that have moved on to those; their errors are an
+//! [`IoError`]. What doesn't touch the UARTE is in port.rs, where the host
+//! tests get at it.
+
 use core::fmt;
+use core::sync::atomic::{compiler_fence, Ordering};
 use embedded_hal::blocking::serial as bserial;
 use embedded_hal::serial;
-use microbit::hal::uarte::{Error, Instance, Uarte, UarteRx, UarteTx};
+use microbit::hal::target_constants::EASY_DMA_SIZE;
+use microbit::hal::uarte::{Error, Instance, Uarte};
+use microbit::pac::uarte0;
+
+#[path = "port.rs"]
+mod port;
+pub use port::IoError;
+use port::Pending;
 
-static mut TX_BUF: [u8; 1] = [0; 1];
-static mut RX_BUF: [u8; 1] = [0; 1];
+/// A read ends early once nothing more has come in for this long,
+/// about three bytes' worth at 115200 baud
+const QUIET_US: u32 = 300;
+const QUIET_STEP_US: u32 = 10;
+/// At 64 MHz
+const CYCLES_PER_US: u32 = 64;
 
-pub struct UartePort<T: Instance>(UarteTx<T>, UarteRx<T>);
+/// The DMA buffers, which have to stay put for as long as the port lives.
+/// `RX` and `TX` can be anything up to what one DMA transfer takes, but
+/// not 0.
+pub struct Buffers<const RX: usize = 32, const TX: usize = 32> {
+    rx: [u8; RX],
+    tx: [u8; TX],
+}
 
-impl<T: Instance> UartePort<T> {
-    pub fn new(serial: Uarte<T>) -> UartePort<T> {
-        let (tx, rx) = serial
-            .split(unsafe { &mut TX_BUF }, unsafe { &mut RX_BUF })
-            .unwrap();
-        UartePort(tx, rx)
+impl<const RX: usize, const TX: usize> Buffers<RX, TX> {
+    pub const fn new() -> Buffers<RX, TX> {
+        Buffers {
+            rx: [0; RX],
+            tx: [0; TX],
+        }
     }
 }
 
-impl<T: Instance> fmt::Write for UartePort<T> {
+/// The UARTE and its buffers, with how much is waiting to go out, what the
+/// last read brought in and how much of it has been handed out.
+pub struct UartePort<T: Instance, const RX: usize = 32, const TX: usize = 32> {
+    uarte: Uarte<T>,
+    buffers: &'static mut Buffers<RX, TX>,
+    pending: Pending,
+    received: usize,
+    taken: usize,
+    reading: bool,
+}
+
+fn registers<T: Instance>() -> &'static uarte0::RegisterBlock {
+    unsafe { &*T::ptr() }
+}
+
+impl<T: Instance, const RX: usize, const TX: usize> UartePort<T, RX, TX> {
+    pub fn new(
+        uarte: Uarte<T>,
+        buffers: &'static mut Buffers<RX, TX>,
+    ) -> Result<UartePort<T, RX, TX>, Error> {
+        if RX == 0 {
+            return Err(Error::RxBufferTooSmall);
+        }
+        if RX > EASY_DMA_SIZE {
+            return Err(Error::RxBufferTooLong);
+        }
+        if TX == 0 {
+            return Err(Error::TxBufferTooSmall);
+        }
+        if TX > EASY_DMA_SIZE {
+            return Err(Error::TxBufferTooLong);
+        }
+        Ok(UartePort {
+            uarte,
+            buffers,
+            pending: Pending::NONE,
+            received: 0,
+            taken: 0,
+            reading: false,
+        })
+    }
+
+    /// Stop reading, send what is still waiting to go out, and hand back
+    /// the UARTE and the buffers. Whatever came in and wasn't read is lost.
+    pub fn free(mut self) -> (Uarte<T>, &'static mut Buffers<RX, TX>) {
+        if self.reading {
+            let uarte = registers::<T>();
+            stop_read(uarte);
+            uarte.events_endrx.reset();
+        }
+        // Nobody is left to tell if it doesn't go
+        let _ = self.send();
+        (self.uarte, self.buffers)
+    }
+
+    /// Sends what is waiting in the buffer, and only returns once it is
+    /// gone.
+    fn send(&mut self) -> Result<(), Error> {
+        let uarte = &mut self.uarte;
+        self.pending
+            .send(&self.buffers.tx, |bytes| uarte.write(bytes))
+    }
+
+    /// Sends `bytes` as they are, in as few DMA transfers as the buffer
+    /// allows, and only returns once the last one is done.
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let uarte = &mut self.uarte;
+        self.pending
+            .write_all(&mut self.buffers.tx, bytes, |bytes| uarte.write(bytes))
+    }
+
+    fn start_read(&mut self, uarte: &uarte0::RegisterBlock) {
+        uarte.events_rxdrdy.reset();
+        uarte.events_endrx.reset();
+        uarte.events_error.reset();
+        compiler_fence(Ordering::SeqCst);
+        // The buffer lives as long as the port, and is only read once the
+        // DMA is done with it
+        uarte
+            .rxd
+            .ptr
+            .write(|w| unsafe { w.ptr().bits(self.buffers.rx.as_ptr() as u32) });
+        uarte
+            .rxd
+            .maxcnt
+            .write(|w| unsafe { w.maxcnt().bits(RX as _) });
+        uarte.tasks_startrx.write(|w| unsafe { w.bits(1) });
+        self.reading = true;
+    }
+}
+
+/// Every "\n" goes out as "\r\n", see [`port::write_lines`].
+impl<T: Instance, const RX: usize, const TX: usize> fmt::Write for UartePort<T, RX, TX> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.0.write_str(s)
+        port::write_lines(s, |bytes| self.write_all(bytes))
     }
 }
 
-impl<T: Instance> serial::Write<u8> for UartePort<T> {
+/// Only goes out once the buffer is full or on a flush.
+impl<T: Instance, const RX: usize, const TX: usize> serial::Write<u8> for UartePort<T, RX, TX> {
     type Error = Error;
 
     fn write(&mut self, b: u8) -> nb::Result<(), Self::Error> {
-        self.0.write(b)
+        let uarte = &mut self.uarte;
+        self.pending
+            .push(&mut self.buffers.tx, b, |bytes| uarte.write(bytes))?;
+        Ok(())
     }
 
     fn flush(&mut self) -> nb::Result<(), Self::Error> {
-        self.0.flush()
+        Ok(self.send()?)
     }
 }
 
-impl<T: Instance> bserial::write::Default<u8> for UartePort<T> {}
+impl<T: Instance, const RX: usize, const TX: usize> bserial::write::Default<u8>
+    for UartePort<T, RX, TX>
+{
+}
 
-impl<T: Instance> serial::Read<u8> for UartePort<T> {
+impl<T: Instance, const RX: usize, const TX: usize> embedded_io::ErrorType
+    for UartePort<T, RX, TX>
+{
+    type Error = IoError;
+}
+
+/// Waits for the first byte only, then hands out whatever else the same
+/// DMA read brought in along with it.
+impl<T: Instance, const RX: usize, const TX: usize> embedded_io::Read for UartePort<T, RX, TX> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let (first, rest) = match buf.split_first_mut() {
+            Some(split) => split,
+            None => return Ok(0),
+        };
+        *first = nb::block!(serial::Read::read(self)).map_err(IoError)?;
+        let more = rest.len().min(self.received - self.taken);
+        rest[..more].copy_from_slice(&self.buffers.rx[self.taken..self.taken + more]);
+        self.taken += more;
+        Ok(1 + more)
+    }
+}
+
+/// Takes all of `buf` every time, the way the blocking write does.
+impl<T: Instance, const RX: usize, const TX: usize> embedded_io::Write for UartePort<T, RX, TX> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        bserial::Write::bwrite_all(self, buf).map_err(IoError)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        bserial::Write::bflush(self).map_err(IoError)
+    }
+}
+
+/// Hands out what the last DMA read brought in, a byte at a time, before
+/// starting the next.
+///
+/// A read normally runs until its buffer is full. Once something has come
+/// in, it ends early as soon as the line goes quiet, so a single key press
+/// doesn't have to wait for more.
+impl<T: Instance, const RX: usize, const TX: usize> serial::Read<u8> for UartePort<T, RX, TX> {
     type Error = Error;
 
     fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        self.1.read()
+        if self.taken < self.received {
+            let byte = self.buffers.rx[self.taken];
+            self.taken += 1;
+            return Ok(byte);
+        }
+        let uarte = registers::<T>();
+        if !self.reading {
+            self.start_read(uarte);
+            return Err(nb::Error::WouldBlock);
+        }
+        if uarte.events_endrx.read().bits() == 0 {
+            if uarte.events_rxdrdy.read().bits() == 0 || !quiet(uarte) {
+                return Err(nb::Error::WouldBlock);
+            }
+            stop_read(uarte);
+        }
+        uarte.events_endrx.reset();
+        compiler_fence(Ordering::SeqCst);
+        self.reading = false;
+        self.received = uarte.rxd.amount.read().bits() as usize;
+        self.taken = 0;
+        if uarte.events_error.read().bits() != 0 {
+            uarte.events_error.reset();
+            return Err(nb::Error::Other(Error::Receive));
+        }
+        self.read()
     }
 }
+
+/// Wait out [`QUIET_US`] after a byte, and tell whether no other came in
+/// meanwhile.
+fn quiet(uarte: &uarte0::RegisterBlock) -> bool {
+    for _ in 0..QUIET_US / QUIET_STEP_US {
+        uarte.events_rxdrdy.reset();
+        cortex_m::asm::delay(QUIET_STEP_US * CYCLES_PER_US);
+        if uarte.events_rxdrdy.read().bits() != 0 {
+            return false;
+        }
+    }
+    true
+}
+
+/// End a read early, with whatever is still in the FIFO moved into the
+/// buffer. Leaves ENDRX set.
+fn stop_read(uarte: &uarte0::RegisterBlock) {
+    uarte.tasks_stoprx.write(|w| unsafe { w.bits(1) });
+    while uarte.events_rxto.read().bits() == 0 {}
+    uarte.events_rxto.reset();
+    uarte.tasks_flushrx.write(|w| unsafe { w.bits(1) });
+    while uarte.events_endrx.read().bits() == 0 {}
+}
+
+/// What the chapters take the port for, on the UARTE they use: text and
+/// bytes both ways, through either crate's traits, and the UARTE back out
+/// of it. It only has to build.
+const _: fn() = || {
+    use microbit::pac::UARTE0;
+
+    fn port<P>()
+    where
+        P: fmt::Write
+            + serial::Read<u8, Error = Error>
+            + serial::Write<u8, Error = Error>
+            + bserial::Write<u8>
+            + embedded_io::Read<Error = IoError>
+            + embedded_io::Write<Error = IoError>,
+    {
+    }
+    port::<UartePort<UARTE0>>();
+    port::<UartePort<UARTE0, 1, 1>>();
+    let _: fn(UartePort<UARTE0>) -> (Uarte<UARTE0>, &'static mut Buffers) = UartePort::free;
+};
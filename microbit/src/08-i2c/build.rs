@@ -10,16 +10,14 @@
 use std::env;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
-    File::create(out.join("memory.x"))
-        .unwrap()
-        .write_all(include_bytes!("memory.x"))
-        .unwrap();
+    let mut memory = File::create(out.join("memory.x")).unwrap();
+    memory.write_all(include_bytes!("memory.x")).unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
     // By default, Cargo will re-run a build script whenever
@@ -27,4 +25,44 @@ fn main() {
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
+
+    // The nRF51 has half the flash of the nRF52833, and the firmware keeps
+    // growing. Release builds for it check that there's still room to spare,
+    // in memory.x itself: that's where FLASH is defined, and wherever else
+    // the check went it could come before that.
+    let release = env::var("PROFILE").is_ok_and(|profile| profile == "release");
+    if env::var_os("CARGO_FEATURE_V1").is_some() && release {
+        memory.write_all(include_bytes!("headroom.x")).unwrap();
+    }
+    println!("cargo:rerun-if-changed=headroom.x");
+
+    // defmt keeps its format strings in a section of their own
+    if env::var_os("CARGO_FEATURE_DEFMT").is_some() {
+        println!("cargo:rustc-link-arg=-Tdefmt.x");
+    }
+
+    // The display's gamma table, see `src/frame.rs`.
+    write_gamma_table(&out.join("gamma.rs"));
+}
+
+/// Levels 0 to 9, for both the pixels and the brightness setting.
+const MAX_LEVEL: u32 = 9;
+const GAMMA: f64 = 2.2;
+
+/// For every product of a pixel value and the brightness, the driver level
+/// that looks that bright: the product as a fraction of the largest one,
+/// raised to [`GAMMA`] and rounded to the nearest of the driver's levels.
+fn write_gamma_table(path: &Path) {
+    let max_product = MAX_LEVEL * MAX_LEVEL;
+    let levels: Vec<String> = (0..=max_product)
+        .map(|product| {
+            let fraction = product as f64 / max_product as f64;
+            let level = (fraction.powf(GAMMA) * MAX_LEVEL as f64).round();
+            (level as u8).to_string()
+        })
+        .collect();
+    File::create(path)
+        .unwrap()
+        .write_all(format!("[{}]", levels.join(", ")).as_bytes())
+        .unwrap();
 }
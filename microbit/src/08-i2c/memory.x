@@ -1,6 +1,7 @@
 MEMORY
 {
   /* NOTE K = KiBi = 1024 bytes */
-  FLASH : ORIGIN = 0x00000000, LENGTH = 256K
+  /* The last 24K are kept out of FLASH, see `flash::PAGE_ADDR` */
+  FLASH : ORIGIN = 0x00000000, LENGTH = 232K
   RAM : ORIGIN = 0x20000000, LENGTH = 16K
 }
@@ -0,0 +1,41 @@
+//! Ctrl-C handling shared by every long-running command.
+//!
+//! The serial RX path [`raise`]s the flag as soon as it sees a Ctrl-C, even
+//! in the middle of a line. Loops that run until the user stops them check
+//! [`aborted`] at the top of every iteration and print "^C" on the way out.
+//!
+//! A Ctrl-R or a serial break, on the other hand, asks a streaming command
+//! to print its header line again without stopping. A host that connects
+//! in the middle of a stream uses it to find out what the columns are.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub const CTRL_C: u8 = 0x03;
+pub const CTRL_R: u8 = 0x12;
+
+static ABORTED: AtomicBool = AtomicBool::new(false);
+static HEADER: AtomicBool = AtomicBool::new(false);
+
+pub fn raise() {
+    ABORTED.store(true, Ordering::Relaxed);
+}
+
+pub fn clear() {
+    ABORTED.store(false, Ordering::Relaxed);
+}
+
+pub fn aborted() -> bool {
+    ABORTED.load(Ordering::Relaxed)
+}
+
+pub fn request_header() {
+    HEADER.store(true, Ordering::Relaxed);
+}
+
+/// Whether a header was asked for since the last call. Only the command
+/// loop touches the flag, so this doesn't need to be atomic as a whole.
+pub fn take_header_request() -> bool {
+    let requested = HEADER.load(Ordering::Relaxed);
+    HEADER.store(false, Ordering::Relaxed);
+    requested
+}
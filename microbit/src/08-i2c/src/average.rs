@@ -0,0 +1,100 @@
+//! "magnetometer avg <n>" and "accelerometer avg <n>": the mean, smallest
+//! and largest of n readings, per axis.
+//!
+//! A single reading, the magnetometer's in particular, is noisy. These are
+//! the readings "magnetometer" and "accelerometer" print, calibrated and
+//! filtered the same way. The sums are kept in an i64, which [`MAX_SAMPLES`]
+//! readings of any i32 can't overflow, let alone of the sensor's full scale.
+
+pub const MAG_USAGE: &str = "magnetometer avg <n>";
+pub const ACCEL_USAGE: &str = "accelerometer avg <n>";
+
+/// The most readings one command takes
+pub const MAX_SAMPLES: u32 = 256;
+
+/// `n` as typed, if it is from 1 to [`MAX_SAMPLES`].
+pub fn parse_count(n: &str) -> Option<u32> {
+    n.parse().ok().filter(|n| (1..=MAX_SAMPLES).contains(n))
+}
+
+/// What came out of one axis's readings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    /// Rounded to the nearest, halves up
+    pub mean: i32,
+    pub min: i32,
+    pub max: i32,
+}
+
+/// One axis's readings so far.
+#[derive(Clone, Copy, Debug)]
+pub struct AxisStats {
+    sum: i64,
+    min: i32,
+    max: i32,
+    count: u32,
+}
+
+impl AxisStats {
+    pub const fn new() -> AxisStats {
+        AxisStats {
+            sum: 0,
+            min: i32::MAX,
+            max: i32::MIN,
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, value: i32) {
+        self.sum += value as i64;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.count += 1;
+    }
+
+    /// `None` without a single reading to go by.
+    pub fn finish(&self) -> Option<Summary> {
+        if self.count == 0 {
+            return None;
+        }
+        let count = self.count as i64;
+        // Between min and max, so back in range
+        let mean = (2 * self.sum + count).div_euclid(2 * count) as i32;
+        Some(Summary {
+            mean,
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+/// All three axes.
+#[derive(Clone, Copy, Debug)]
+pub struct Stats([AxisStats; 3]);
+
+impl Stats {
+    pub const fn new() -> Stats {
+        Stats([AxisStats::new(); 3])
+    }
+
+    pub fn push(&mut self, values: [i32; 3]) {
+        for (axis, value) in self.0.iter_mut().zip(values) {
+            axis.push(value);
+        }
+    }
+
+    /// The means, the minimums and the maximums, each as a reading of all
+    /// three axes. `None` like [`AxisStats::finish`].
+    pub fn finish(&self) -> Option<[[i32; 3]; 3]> {
+        let [x, y, z] = [
+            self.0[0].finish()?,
+            self.0[1].finish()?,
+            self.0[2].finish()?,
+        ];
+        Some([
+            [x.mean, y.mean, z.mean],
+            [x.min, y.min, z.min],
+            [x.max, y.max, z.max],
+        ])
+    }
+}
@@ -0,0 +1,131 @@
+//! Which components of a three-axis reading get printed.
+//!
+//! The sensor is always read in full; the mask only applies when a sample
+//! is formatted, and every output path formats through [`Sample`] so no path
+//! can forget about it.
+
+use core::fmt;
+
+pub const USAGE: &str = "axes show <some of x, y and z>";
+
+const NAMES: [char; 3] = ['x', 'y', 'z'];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Axes(u8);
+
+impl Axes {
+    pub const ALL: Axes = Axes(0b111);
+
+    /// Letters in any order, like "z" or "xy". `None` for anything else,
+    /// including no axes at all.
+    pub fn parse(letters: &str) -> Option<Axes> {
+        let mut mask = 0;
+        for letter in letters.chars() {
+            let axis = NAMES.iter().position(|&name| name == letter)?;
+            mask |= 1 << axis;
+        }
+        if mask == 0 {
+            None
+        } else {
+            Some(Axes(mask))
+        }
+    }
+
+    fn shows(self, axis: usize) -> bool {
+        self.0 & (1 << axis) != 0
+    }
+
+    /// The column names, for stream headers.
+    pub fn columns(self) -> Columns {
+        Columns(self)
+    }
+
+    /// The letters of the axes shown, in order.
+    pub fn letters(self) -> impl Iterator<Item = char> {
+        NAMES
+            .iter()
+            .enumerate()
+            .filter(move |&(axis, _)| self.shows(axis))
+            .map(|(_, &name)| name)
+    }
+
+    pub fn encode(self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Anything unrecognized decodes as [`Axes::ALL`].
+    pub fn decode(word: u32) -> Axes {
+        match word {
+            1..=0b111 => Axes(word as u8),
+            _ => Axes::ALL,
+        }
+    }
+}
+
+/// The letters as "axes show" takes them.
+impl fmt::Display for Axes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (axis, name) in NAMES.iter().enumerate() {
+            if self.shows(axis) {
+                write!(f, "{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// "x y z", each name followed by a space.
+pub struct Columns(Axes);
+
+impl fmt::Display for Columns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (axis, name) in NAMES.iter().enumerate() {
+            if self.0.shows(axis) {
+                write!(f, "{} ", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A reading formatted as "x 12 y -3 z 1004", leaving out hidden axes.
+#[derive(Clone, Copy)]
+pub struct Sample {
+    pub axes: Axes,
+    pub values: [i32; 3],
+}
+
+impl Sample {
+    /// Each axis shown, with its letter.
+    pub fn shown(self) -> impl Iterator<Item = (char, i32)> {
+        let axes = self.axes;
+        NAMES
+            .iter()
+            .zip(self.values)
+            .enumerate()
+            .filter(move |&(axis, _)| axes.shows(axis))
+            .map(|(_, (&name, value))| (name, value))
+    }
+}
+
+/// The same as on the serial port, formatted on the host.
+#[cfg(feature = "defmt")]
+impl defmt::Format for Sample {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        for (i, (name, value)) in self.shown().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            defmt::write!(f, "{=str}{=char} {=i32}", separator, name, value);
+        }
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, value)) in self.shown().enumerate() {
+            let separator = if i == 0 { "" } else { " " };
+            write!(f, "{}{} {}", separator, name, value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! A lit dot that rolls around the display as the board tilts, for "ball".
+//!
+//! The ball goes the other way from the demo's spirit level bubble, towards
+//! the lowered edge: its column follows the accelerometer's y and its row
+//! the x. Each axis keeps a position in [`UNIT`]s, 256ths of an LED from
+//! the left or top, and a velocity in units per second, and every reading
+//! moves both on by however long it has been since the last. The
+//! acceleration is [`GAIN`] times the tilt, less a [`DEAD_ZONE_MG`] around
+//! flat, so the ball comes to rest on a table that isn't quite level.
+//! Friction takes the speed down over about a second, and a wall takes away
+//! all the speed the ball had into it.
+//!
+//! All of it is integer arithmetic; the lost remainders make for a little
+//! more friction.
+
+use crate::display::Image;
+
+/// 256ths of an LED, of the position
+pub const UNIT: i32 = 256;
+/// The farthest LED from the left or top
+const MAX_POSITION: i32 = 4 * UNIT;
+/// Units per second squared per mg: 1 g is about 23 LEDs/s²
+pub const GAIN: i32 = 6;
+/// Tilt towards either side, in mg, that still counts as flat, about 3°
+pub const DEAD_ZONE_MG: i32 = 50;
+/// The time over which friction would take all the speed away
+const FRICTION_MS: i32 = 1_000;
+/// The longest one step goes by, so that a reading that was held up
+/// doesn't throw the ball across the display
+pub const MAX_STEP_MS: u32 = 100;
+/// Readings a second below which the ball no longer rolls smoothly
+pub const MIN_HZ: u32 = 20;
+
+const LIT: u8 = 9;
+
+/// The tilt along one axis with the dead zone taken off, in mg.
+pub fn tilt(mg: i32) -> i32 {
+    if mg.abs() <= DEAD_ZONE_MG {
+        0
+    } else {
+        mg - DEAD_ZONE_MG * mg.signum()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Axis {
+    /// In [`UNIT`]s, from 0 to the last LED
+    pub position: i32,
+    /// In units per second
+    pub velocity: i32,
+}
+
+impl Axis {
+    /// At rest, `position` units from the edge.
+    pub const fn at(position: i32) -> Axis {
+        Axis {
+            position,
+            velocity: 0,
+        }
+    }
+
+    /// `step_ms` later under a tilt of `mg`, at most [`MAX_STEP_MS`].
+    pub fn step(&mut self, mg: i32, step_ms: u32) {
+        let ms = step_ms.min(MAX_STEP_MS) as i32;
+        self.velocity += tilt(mg) * GAIN * ms / 1000;
+        self.velocity -= self.velocity * ms / FRICTION_MS;
+        self.position += self.velocity * ms / 1000;
+        // Only the speed into the wall is lost, the way back out is open
+        if self.position < 0 {
+            self.position = 0;
+            self.velocity = self.velocity.max(0);
+        } else if self.position > MAX_POSITION {
+            self.position = MAX_POSITION;
+            self.velocity = self.velocity.min(0);
+        }
+    }
+
+    /// The LED it is nearest to.
+    pub fn led(&self) -> usize {
+        ((self.position + UNIT / 2) / UNIT) as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ball {
+    pub column: Axis,
+    pub row: Axis,
+}
+
+impl Ball {
+    /// At rest in the middle.
+    pub const fn new() -> Ball {
+        Ball {
+            column: Axis::at(2 * UNIT),
+            row: Axis::at(2 * UNIT),
+        }
+    }
+
+    /// `step_ms` later under the acceleration `accel`, in mg.
+    pub fn step(&mut self, accel: [i32; 3], step_ms: u32) {
+        self.column.step(accel[1], step_ms);
+        self.row.step(accel[0], step_ms);
+    }
+
+    pub fn image(&self) -> Image {
+        let mut image = [[0; 5]; 5];
+        image[self.row.led()][self.column.led()] = LIT;
+        image
+    }
+}
@@ -0,0 +1,120 @@
+//! A low battery warning, from a supply measurement once a minute.
+//!
+//! The RTC0 interrupt only marks a measurement as due; the measurement
+//! itself is taken by [`poll`] from the command loop's idle and streaming
+//! loops, so it never competes with a command for the ADC. While the supply
+//! is low the heartbeat blinks three times a second, the prompt is tagged
+//! "[LOW BATT]", and with "audiocues on" every sample chirps.
+//!
+//! The same samples tell whether the board runs off a battery at all, for
+//! [`crate::powersave`]: USB power comes through a regulator at 3.3 V,
+//! a pair of cells stays below [`ON_BATTERY_MV`] and never rises.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::shared::Shared;
+use crate::{feedback, heartbeat, onchip};
+
+pub const DEFAULT_WARN_MV: u16 = 2400;
+pub const MIN_WARN_MV: u16 = 1800;
+pub const MAX_WARN_MV: u16 = 3300;
+pub const USAGE: &str = "battwarn <1800-3300>";
+
+/// How far above the threshold the supply has to come back before the
+/// warning goes away. Larger than the droop a fully lit display causes on
+/// a pair of AAA cells.
+const HYSTERESIS_MV: u16 = 100;
+const SAMPLE_EVERY_TICKS: u32 = 60 * heartbeat::TICK_HZ;
+
+pub const ON_BATTERY_MV: u16 = 3100;
+/// More than the noise between two samples of a steady supply
+const RISING_MV: u16 = 30;
+
+static DUE: AtomicBool = AtomicBool::new(false);
+static LOW: AtomicBool = AtomicBool::new(false);
+static ON_BATTERY: AtomicBool = AtomicBool::new(false);
+static LAST_MV: Shared<Option<u16>> = Shared::new(None);
+static MONITOR: Shared<Monitor> = Shared::new(Monitor::new(DEFAULT_WARN_MV));
+
+/// Decides on the warning from successive samples. Once low, it only goes
+/// back to normal above the threshold plus [`HYSTERESIS_MV`], so a supply
+/// hovering around the threshold doesn't flip it on every sample.
+#[derive(Clone, Copy, Debug)]
+pub struct Monitor {
+    threshold_mv: u16,
+    low: bool,
+}
+
+impl Monitor {
+    pub const fn new(threshold_mv: u16) -> Monitor {
+        Monitor {
+            threshold_mv,
+            low: false,
+        }
+    }
+
+    /// Keeps the current state; the next sample decides against the new
+    /// threshold.
+    pub fn set_threshold(&mut self, threshold_mv: u16) {
+        self.threshold_mv = threshold_mv;
+    }
+
+    /// Feed a sample and get back whether the battery counts as low.
+    pub fn update(&mut self, mv: u16) -> bool {
+        self.low = if self.low {
+            mv < self.threshold_mv.saturating_add(HYSTERESIS_MV)
+        } else {
+            mv < self.threshold_mv
+        };
+        self.low
+    }
+}
+
+/// Set the threshold and take a first sample right away.
+pub fn init(threshold_mv: u16) {
+    set_threshold(threshold_mv);
+    sample();
+}
+
+pub fn set_threshold(threshold_mv: u16) {
+    MONITOR.with(|monitor| monitor.set_threshold(threshold_mv));
+    // Don't wait a minute to see what the new threshold does
+    DUE.store(true, Ordering::Relaxed);
+}
+
+/// Called from the RTC0 interrupt on every heartbeat tick.
+pub fn schedule(now: u32) {
+    if now.is_multiple_of(SAMPLE_EVERY_TICKS) {
+        DUE.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Take the measurement if one is due. Cheap enough to call from any idle
+/// loop.
+pub fn poll() {
+    if DUE.load(Ordering::Relaxed) {
+        DUE.store(false, Ordering::Relaxed);
+        sample();
+    }
+}
+
+fn sample() {
+    let mv = onchip::vdd_mv().max(0) as u16;
+    let low = MONITOR.with(|monitor| monitor.update(mv));
+    LOW.store(low, Ordering::Relaxed);
+    if low {
+        feedback::signal(feedback::Event::LowBattery);
+    }
+    let last = LAST_MV.with(|last| last.replace(mv));
+    let rising = last.is_some_and(|last| mv > last.saturating_add(RISING_MV));
+    ON_BATTERY.store(mv < ON_BATTERY_MV && !rising, Ordering::Relaxed);
+}
+
+pub fn low() -> bool {
+    LOW.load(Ordering::Relaxed)
+}
+
+/// Whether the last sample looked like a battery rather than USB power.
+pub fn on_battery() -> bool {
+    ON_BATTERY.load(Ordering::Relaxed)
+}
@@ -0,0 +1,106 @@
+//! "blinkout <value>": show a number by blinking the whole display, to
+//! read it off a board that has no cable attached.
+//!
+//! Each decimal digit is a group of blinks, as many as the digit's value,
+//! with the length of the blinks telling the groups apart: very long for
+//! hundreds, long for tens, short for units. A zero digit is a single
+//! flicker so that the groups still line up. Leading zeros are left out,
+//! and the whole sequence is played [`ROUNDS`] times.
+
+use heapless::Vec;
+
+use crate::{display, watch};
+
+pub const MAX_VALUE: u16 = 999;
+pub const ROUNDS: usize = 3;
+
+const HUNDREDS_MS: u16 = 1000;
+const TENS_MS: u16 = 600;
+const UNITS_MS: u16 = 200;
+const ZERO_MS: u16 = 60;
+const BLINK_GAP_MS: u16 = 300;
+const GROUP_GAP_MS: u16 = 1000;
+/// Between repetitions
+pub const ROUND_GAP_MS: u16 = 2500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub on: bool,
+    pub ms: u16,
+}
+
+/// Three groups of at most nine blinks, an on and an off step each.
+pub type Schedule = Vec<Step, 54>;
+
+/// One round of blinks for `value`, clamped to [`MAX_VALUE`]. Every blink
+/// is followed by an off step, a longer one at the end of each group.
+pub fn schedule(value: u16) -> Schedule {
+    let value = value.min(MAX_VALUE);
+    let digits = [
+        (value / 100, HUNDREDS_MS),
+        (value / 10 % 10, TENS_MS),
+        (value % 10, UNITS_MS),
+    ];
+    // Skip leading zeros, but always keep the units
+    let first = digits[..2]
+        .iter()
+        .position(|(digit, _)| *digit != 0)
+        .unwrap_or(2);
+
+    let mut steps = Schedule::new();
+    for &(digit, ms) in &digits[first..] {
+        let (blinks, ms) = if digit == 0 {
+            (1, ZERO_MS)
+        } else {
+            (digit, ms)
+        };
+        for _ in 0..blinks {
+            // Can't overflow: 3 groups * 9 blinks * 2 steps
+            steps.push(Step { on: true, ms }).unwrap();
+            steps
+                .push(Step {
+                    on: false,
+                    ms: BLINK_GAP_MS,
+                })
+                .unwrap();
+        }
+        steps.last_mut().unwrap().ms = GROUP_GAP_MS;
+    }
+    steps
+}
+
+/// How often [`play`] gives its caller a chance to stop it.
+const POLL_MS: u16 = 10;
+
+/// Play `value` on the display, calling `keep_going` every few
+/// milliseconds and stopping as soon as it returns an error.
+pub fn play<E>(value: u16, mut keep_going: impl FnMut() -> Result<(), E>) -> Result<(), E> {
+    let result = play_rounds(&schedule(value), &mut keep_going);
+    display::set_background(&[[0; 5]; 5]);
+    result
+}
+
+fn play_rounds<E>(
+    steps: &Schedule,
+    keep_going: &mut impl FnMut() -> Result<(), E>,
+) -> Result<(), E> {
+    for round in 0..ROUNDS {
+        if round > 0 {
+            wait(ROUND_GAP_MS, keep_going)?;
+        }
+        for step in steps {
+            let level = if step.on { 9 } else { 0 };
+            display::set_background(&[[level; 5]; 5]);
+            wait(step.ms, keep_going)?;
+        }
+    }
+    Ok(())
+}
+
+fn wait<E>(ms: u16, keep_going: &mut impl FnMut() -> Result<(), E>) -> Result<(), E> {
+    for _ in 0..ms / POLL_MS {
+        keep_going()?;
+        watch::delay_us(POLL_MS as u32 * 1000);
+    }
+    Ok(())
+}
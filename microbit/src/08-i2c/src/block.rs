@@ -0,0 +1,193 @@
+//! Numbered, checksummed blocks of output, one line each, for data a host
+//! has to get across intact: "provision export" and "capture dump".
+//!
+//! ```text
+//! <tag> <index>/<count> <body> <crc>
+//! ```
+//!
+//! The CRC is a CRC-32 as in zlib, in hex, of everything between the tag
+//! and itself: the position and the body. With the count in every block a
+//! host can tell what's missing, and ask again for just that.
+
+use core::fmt::{self, Write};
+
+/// A CRC-32 that is fed as the text goes out.
+struct Crc32(u32);
+
+impl Crc32 {
+    fn new() -> Crc32 {
+        Crc32(!0)
+    }
+
+    /// Bit by bit; these lines are short enough not to need a table.
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// Passes everything on to `inner`, keeping the CRC of it.
+struct Summed<'a, W> {
+    inner: &'a mut W,
+    crc: Crc32,
+}
+
+impl<W: fmt::Write> fmt::Write for Summed<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.crc.update(s.as_bytes());
+        self.inner.write_str(s)
+    }
+}
+
+/// Write block `index` of `count`, with what `body` writes as its body.
+/// The body must not have a newline in it.
+pub fn write<W: fmt::Write>(
+    w: &mut W,
+    tag: &str,
+    index: usize,
+    count: usize,
+    body: impl FnOnce(&mut dyn fmt::Write) -> fmt::Result,
+) -> fmt::Result {
+    write!(w, "{} ", tag)?;
+    let mut summed = Summed {
+        inner: w,
+        crc: Crc32::new(),
+    };
+    write!(summed, "{}/{} ", index, count)?;
+    body(&mut summed)?;
+    let crc = summed.crc.finish();
+    writeln!(w, " {:08x}", crc)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Not a block with this tag
+    Malformed,
+    /// The block with this index, which failed its CRC
+    Crc(usize),
+}
+
+/// A block read back in, with a CRC that checked out.
+pub struct Block<'a> {
+    pub index: usize,
+    pub count: usize,
+    pub body: &'a str,
+}
+
+/// Read back a line written by [`write`] with `tag`.
+pub fn parse<'a>(line: &'a str, tag: &str) -> Result<Block<'a>, Error> {
+    let rest = line
+        .trim()
+        .strip_prefix(tag)
+        .and_then(|rest| rest.strip_prefix(' '))
+        .ok_or(Error::Malformed)?;
+    let (summed, crc) = rest.rsplit_once(' ').ok_or(Error::Malformed)?;
+    let (position, body) = summed.split_once(' ').ok_or(Error::Malformed)?;
+    let (index, count) = position.split_once('/').ok_or(Error::Malformed)?;
+    let index: usize = index.parse().map_err(|_| Error::Malformed)?;
+    let count: usize = count.parse().map_err(|_| Error::Malformed)?;
+    let crc = u32::from_str_radix(crc, 16).map_err(|_| Error::Malformed)?;
+    if crc32(summed.as_bytes()) != crc {
+        return Err(Error::Crc(index));
+    }
+    if index >= count {
+        return Err(Error::Malformed);
+    }
+    Ok(Block { index, count, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(index: usize, count: usize, body: &str) -> std::string::String {
+        let mut line = std::string::String::new();
+        write(&mut line, "tag", index, count, |w| w.write_str(body)).unwrap();
+        line
+    }
+
+    #[test]
+    fn the_crc_is_zlib_s() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn a_block_is_one_line_with_the_crc_of_its_position_and_body() {
+        let line = line(2, 5, "00ff");
+        assert_eq!(line, format!("tag 2/5 00ff {:08x}\n", crc32(b"2/5 00ff")));
+    }
+
+    #[test]
+    fn a_block_reads_back_as_written() {
+        for (index, count, body) in [(0, 1, "x"), (4, 5, "00ff 11"), (9, 10, "")] {
+            let line = line(index, count, body);
+            let block = parse(&line, "tag").unwrap();
+            assert_eq!((block.index, block.count, block.body), (index, count, body));
+            // Typed or pasted with whatever around it
+            let padded = format!("  {}\r", line.trim_end());
+            assert_eq!(parse(&padded, "tag").unwrap().body, body);
+        }
+    }
+
+    #[test]
+    fn any_change_fails_the_crc() {
+        let line = line(2, 5, "00ff");
+        for changed in [
+            line.replace("00ff", "00fe"),
+            line.replace("2/5", "3/5"),
+            line.replace("2/5", "2/6"),
+        ] {
+            assert!(
+                matches!(parse(&changed, "tag"), Err(Error::Crc(_))),
+                "{}",
+                changed
+            );
+        }
+    }
+
+    #[test]
+    fn anything_else_is_malformed() {
+        let line = line(2, 5, "00ff");
+        let crc = line.trim_end().rsplit(' ').next().unwrap();
+        for bad in [
+            std::string::String::new(),
+            line.replace("tag", "other"),
+            line.replace("tag ", "tag"),
+            line.replace("2/5", "2-5"),
+            line.replace("2/5", "x/5"),
+            line.replace(crc, "crc"),
+            "tag 2/5".into(),
+        ] {
+            assert_eq!(
+                parse(&bad, "tag").err(),
+                Some(Error::Malformed),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn an_index_past_the_count_is_malformed_even_with_its_crc() {
+        assert_eq!(
+            parse(&line(5, 5, "00ff"), "tag").err(),
+            Some(Error::Malformed)
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Which board this is, when there are several on the desk.
+//!
+//! Each board has a short [`Name`], "MB" until "name <text>" changes it,
+//! kept with the settings. It scrolls across the display once at boot,
+//! while the rest of the board starts, so that the one that just reset
+//! stands out. "version" shows it, and so does every `ack` record in the
+//! machine-readable output modes, see [`crate::reply`].
+//!
+//! The heartbeat drives the scrolling, one column per tick. It stops by
+//! itself after one pass, or when the first command runs, whichever comes
+//! first.
+
+use core::fmt;
+
+use crate::display::{self, Image};
+use crate::font;
+use crate::shared::Shared;
+
+pub const NAME_LEN: usize = 8;
+pub const NAME_USAGE: &str = "name [<1 to 8 printable ASCII characters>]";
+const DEFAULT_NAME: &str = "MB";
+const LIT: u8 = 9;
+
+/// Up to [`NAME_LEN`] printable ASCII characters, zero padded, so that it
+/// can be `Copy` like the rest of the settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Name([u8; NAME_LEN]);
+
+impl Name {
+    pub fn as_str(&self) -> &str {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+        // Only ever made from a str, cut at a char boundary
+        core::str::from_utf8(&self.0[..len]).unwrap()
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The name being scrolled, and the tick it started at.
+static BANNER: Shared<Option<(Name, u32)>> = Shared::new(None);
+
+pub fn default_name() -> Name {
+    // Fits, and is printable
+    parse_name(DEFAULT_NAME).unwrap()
+}
+
+/// `text` as a name, if it is one: up to [`NAME_LEN`] printable ASCII
+/// characters, not starting or ending with a space.
+pub fn parse_name(text: &str) -> Option<Name> {
+    let printable = text.chars().all(font::printable);
+    if text.is_empty() || text.len() > NAME_LEN || text.trim() != text || !printable {
+        return None;
+    }
+    let mut bytes = [0; NAME_LEN];
+    bytes[..text.len()].copy_from_slice(text.as_bytes());
+    Some(Name(bytes))
+}
+
+pub fn encode_name(name: &Name) -> [u32; 2] {
+    let bytes = name.0;
+    [
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+    ]
+}
+
+/// Anything that doesn't make a valid name decodes as the default one.
+pub fn decode_name(words: [u32; 2]) -> Name {
+    let mut bytes = [0; NAME_LEN];
+    bytes[..4].copy_from_slice(&words[0].to_le_bytes());
+    bytes[4..].copy_from_slice(&words[1].to_le_bytes());
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+    core::str::from_utf8(&bytes[..len])
+        .ok()
+        .and_then(parse_name)
+        .unwrap_or_else(default_name)
+}
+
+/// "version"
+pub fn version<W: fmt::Write>(w: &mut W, name: &str) -> fmt::Result {
+    let board = if cfg!(feature = "v1") { "v1" } else { "v2" };
+    writeln!(
+        w,
+        "{} {} for the micro:bit {}, board {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        board,
+        name
+    )
+}
+
+/// Scroll `name` across the display, starting at heartbeat tick `now`.
+pub fn start_banner(name: &Name, now: u32) {
+    BANNER.with(|banner| *banner = Some((*name, now)));
+}
+
+/// Stop the banner early, blanking the display if it was still going.
+pub fn stop_banner() {
+    if BANNER.with(|banner| banner.take()).is_some() {
+        display::set_background(&[[0; 5]; 5]);
+    }
+}
+
+/// Called from the RTC0 handler on every tick.
+pub fn tick(now: u32) {
+    let banner = match BANNER.with(|banner| *banner) {
+        Some(banner) => banner,
+        None => return,
+    };
+    let (name, start) = banner;
+    match frame(name.as_str(), now.wrapping_sub(start) as usize) {
+        Some(image) => display::set_background(&image),
+        None => stop_banner(),
+    }
+}
+
+/// The display `step` columns into scrolling `text` in from the right,
+/// `None` once it is gone off to the left.
+fn frame(text: &str, step: usize) -> Option<Image> {
+    // A blank column after every glyph
+    let columns = text.len() * (font::WIDTH + 1);
+    if step >= columns + 5 {
+        return None;
+    }
+    let mut image = [[0; 5]; 5];
+    for col in 0..5 {
+        let column = match (step + col).checked_sub(5) {
+            Some(i) if i < columns && i % (font::WIDTH + 1) < font::WIDTH => {
+                let c = text.as_bytes()[i / (font::WIDTH + 1)] as char;
+                font::glyph(c)[i % (font::WIDTH + 1)]
+            }
+            _ => 0,
+        };
+        for (row, line) in image.iter_mut().enumerate() {
+            if column & (1 << row) != 0 {
+                line[col] = LIT;
+            }
+        }
+    }
+    Some(image)
+}
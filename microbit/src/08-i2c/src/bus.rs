@@ -0,0 +1,193 @@
+//! The internal I2C bus, as seen by the sensor driver.
+//!
+//! [`Guarded`] wraps the HAL bus and checks the command watchdog around
+//! every transaction, so a handler stuck talking to the sensor unwinds with
+//! [`BusError::TimedOut`] without having to poll anything itself.
+//! [`recover`] gets the bus back into shape afterwards.
+
+use cortex_m::asm;
+use embedded_hal::blocking::i2c;
+
+use crate::{drdy, watchdog};
+
+#[derive(Debug)]
+pub enum BusError<E> {
+    Bus(E),
+    TimedOut,
+}
+
+impl<E> From<watchdog::TimedOut> for BusError<E> {
+    fn from(_: watchdog::TimedOut) -> Self {
+        BusError::TimedOut
+    }
+}
+
+pub struct Guarded<I>(I);
+
+impl<I> Guarded<I> {
+    pub fn new(i2c: I) -> Self {
+        Guarded(i2c)
+    }
+}
+
+impl<I, E> i2c::Write for Guarded<I>
+where
+    I: i2c::Write<Error = E>,
+{
+    type Error = BusError<E>;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        watchdog::check()?;
+        let routed = drdy::routed(address, bytes);
+        let bytes = routed.as_ref().map_or(bytes, |routed| &routed[..]);
+        let result = self.0.write(address, bytes);
+        // A transaction cut short by the watchdog fails with some bus error,
+        // report the timeout instead
+        watchdog::check()?;
+        result.map_err(BusError::Bus)
+    }
+}
+
+impl<I, E> i2c::WriteRead for Guarded<I>
+where
+    I: i2c::WriteRead<Error = E>,
+{
+    type Error = BusError<E>;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        watchdog::check()?;
+        let result = self.0.write_read(address, bytes, buffer);
+        watchdog::check()?;
+        result.map_err(BusError::Bus)
+    }
+}
+
+impl<I, E> i2c::Read for Guarded<I>
+where
+    I: i2c::Read<Error = E>,
+{
+    type Error = BusError<E>;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        watchdog::check()?;
+        let result = self.0.read(address, buffer);
+        watchdog::check()?;
+        result.map_err(BusError::Bus)
+    }
+}
+
+/// Half an SCL period at 100 kHz, in CPU cycles.
+#[cfg(feature = "v1")]
+const HALF_PERIOD: u32 = 80;
+#[cfg(feature = "v2")]
+const HALF_PERIOD: u32 = 320;
+
+/// Ask the bus peripheral to end whatever it is doing.
+///
+/// Called from the watchdog interrupt. On the nRF52 this makes a hung TWIM
+/// transaction complete; the nRF51 TWI only gives up once the STOP
+/// condition actually makes it onto the bus.
+pub fn stop() {
+    chip::twi().tasks_stop.write(|w| unsafe { w.bits(1) });
+}
+
+/// Whether SDA and SCL are both high, as they are between transactions. A
+/// device holding either low hangs every transaction there is.
+pub fn idle() -> bool {
+    let lines = (1 << chip::SCL) | (1 << chip::SDA);
+    chip::gpio().in_.read().bits() & lines == lines
+}
+
+/// Free a bus left mid-transaction by an aborted command.
+///
+/// A sensor interrupted halfway through a read may still be holding SDA
+/// low. Clocking SCL until it lets go and then sending a STOP puts it back
+/// into its idle state. The peripheral is disabled meanwhile so the pins
+/// fall back to plain GPIO.
+pub fn recover() {
+    let twi = chip::twi();
+    let gpio = chip::gpio();
+    twi.enable.write(|w| w.enable().disabled());
+
+    let scl = 1 << chip::SCL;
+    let sda = 1 << chip::SDA;
+    gpio.outset.write(|w| unsafe { w.bits(scl | sda) });
+    for &pin in &[chip::SCL, chip::SDA] {
+        gpio.pin_cnf[pin].write(|w| {
+            w.dir().output();
+            w.input().connect();
+            w.pull().pullup();
+            w.drive().s0d1()
+        });
+    }
+
+    for _ in 0..9 {
+        if gpio.in_.read().bits() & sda != 0 {
+            break;
+        }
+        gpio.outclr.write(|w| unsafe { w.bits(scl) });
+        asm::delay(HALF_PERIOD);
+        gpio.outset.write(|w| unsafe { w.bits(scl) });
+        asm::delay(HALF_PERIOD);
+    }
+
+    // STOP: SDA rising while SCL is high
+    gpio.outclr.write(|w| unsafe { w.bits(scl) });
+    asm::delay(HALF_PERIOD);
+    gpio.outclr.write(|w| unsafe { w.bits(sda) });
+    asm::delay(HALF_PERIOD);
+    gpio.outset.write(|w| unsafe { w.bits(scl) });
+    asm::delay(HALF_PERIOD);
+    gpio.outset.write(|w| unsafe { w.bits(sda) });
+    asm::delay(HALF_PERIOD);
+
+    // Back to how the HAL left them
+    for &pin in &[chip::SCL, chip::SDA] {
+        gpio.pin_cnf[pin].write(|w| {
+            w.dir().input();
+            w.input().connect();
+            w.pull().pullup();
+            w.drive().s0d1()
+        });
+    }
+    twi.events_error.reset();
+    twi.events_stopped.reset();
+    twi.enable.write(|w| w.enable().enabled());
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    use microbit::pac;
+
+    pub const SCL: usize = 0;
+    pub const SDA: usize = 30;
+
+    pub fn twi() -> &'static pac::twi0::RegisterBlock {
+        unsafe { &*pac::TWI0::ptr() }
+    }
+
+    pub fn gpio() -> &'static pac::gpio::RegisterBlock {
+        unsafe { &*pac::GPIO::ptr() }
+    }
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    use microbit::pac;
+
+    pub const SCL: usize = 8;
+    pub const SDA: usize = 16;
+
+    pub fn twi() -> &'static pac::twim0::RegisterBlock {
+        unsafe { &*pac::TWIM0::ptr() }
+    }
+
+    pub fn gpio() -> &'static pac::p0::RegisterBlock {
+        unsafe { &*pac::P0::ptr() }
+    }
+}
@@ -0,0 +1,56 @@
+//! Buttons A and B, read straight off their pins: several commands want
+//! them, none of them for long enough to own them.
+
+/// Set up the button pins. The board has pull-ups on both.
+pub fn init() {
+    let gpio = chip::gpio();
+    for &pin in &[chip::BUTTON_A, chip::BUTTON_B] {
+        gpio.pin_cnf[pin].write(|w| {
+            w.dir().input();
+            w.input().connect();
+            w.pull().disabled()
+        });
+    }
+}
+
+/// Whether A is held down.
+#[cfg(feature = "demo")]
+pub fn a() -> bool {
+    chip::gpio().in_.read().bits() & (1 << chip::BUTTON_A) == 0
+}
+
+/// Whether B is held down.
+#[cfg(feature = "idle")]
+pub fn b() -> bool {
+    chip::gpio().in_.read().bits() & (1 << chip::BUTTON_B) == 0
+}
+
+/// Whether A and B are both held down.
+pub fn chord() -> bool {
+    let levels = chip::gpio().in_.read().bits();
+    levels & ((1 << chip::BUTTON_A) | (1 << chip::BUTTON_B)) == 0
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    use microbit::pac;
+
+    pub const BUTTON_A: usize = 17;
+    pub const BUTTON_B: usize = 26;
+
+    pub fn gpio() -> &'static pac::gpio::RegisterBlock {
+        unsafe { &*pac::GPIO::ptr() }
+    }
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    use microbit::pac;
+
+    pub const BUTTON_A: usize = 14;
+    pub const BUTTON_B: usize = 23;
+
+    pub fn gpio() -> &'static pac::p0::RegisterBlock {
+        unsafe { &*pac::P0::ptr() }
+    }
+}
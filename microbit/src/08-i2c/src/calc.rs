@@ -0,0 +1,273 @@
+//! A small integer expression evaluator for the "calc" command.
+//!
+//! Expressions are made of integer literals, the identifiers of
+//! [`Source`], `+ - * /` and parentheses, and are evaluated in `i64` by
+//! recursive descent. Arithmetic saturates rather than overflowing. Every
+//! identifier is looked up as it is reached, so each one gets a fresh
+//! reading.
+
+use core::fmt;
+
+use crate::source::Source;
+
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Byte offset into the expression and what went wrong there.
+    Syntax(usize, &'static str),
+    /// Reading a source failed.
+    Read(E),
+}
+
+struct Parser<'a, F> {
+    expr: &'a [u8],
+    pos: usize,
+    read: F,
+}
+
+pub fn eval<E, F>(expr: &str, read: F) -> Result<i64, Error<E>>
+where
+    F: FnMut(Source) -> Result<i64, E>,
+{
+    let mut parser = Parser {
+        expr: expr.as_bytes(),
+        pos: 0,
+        read,
+    };
+    let value = parser.expr()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(_) => Err(parser.error("unexpected character")),
+    }
+}
+
+impl<'a, E, F> Parser<'a, F>
+where
+    F: FnMut(Source) -> Result<i64, E>,
+{
+    /// The next non-blank byte, without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        while self.expr.get(self.pos) == Some(&b' ') {
+            self.pos += 1;
+        }
+        self.expr.get(self.pos).copied()
+    }
+
+    fn error(&self, msg: &'static str) -> Error<E> {
+        Error::Syntax(self.pos, msg)
+    }
+
+    /// expr = term { ("+" | "-") term }
+    fn expr(&mut self) -> Result<i64, Error<E>> {
+        let mut value = self.term()?;
+        loop {
+            match self.peek() {
+                Some(b'+') => {
+                    self.pos += 1;
+                    value = value.saturating_add(self.term()?);
+                }
+                Some(b'-') => {
+                    self.pos += 1;
+                    value = value.saturating_sub(self.term()?);
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    /// term = factor { ("*" | "/") factor }
+    fn term(&mut self) -> Result<i64, Error<E>> {
+        let mut value = self.factor()?;
+        loop {
+            match self.peek() {
+                Some(b'*') => {
+                    self.pos += 1;
+                    value = value.saturating_mul(self.factor()?);
+                }
+                Some(b'/') => {
+                    let op = self.pos;
+                    self.pos += 1;
+                    let divisor = self.factor()?;
+                    if divisor == 0 {
+                        return Err(Error::Syntax(op, "division by zero"));
+                    }
+                    // Only i64::MIN / -1 doesn't fit
+                    value = value.checked_div(divisor).unwrap_or(i64::MAX);
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    /// factor = "-" factor | "(" expr ")" | number | identifier
+    fn factor(&mut self) -> Result<i64, Error<E>> {
+        match self.peek() {
+            None => Err(self.error("unexpected end of expression")),
+            Some(b'-') => {
+                self.pos += 1;
+                Ok(self.factor()?.saturating_neg())
+            }
+            Some(b'(') => {
+                self.pos += 1;
+                let value = self.expr()?;
+                if self.peek() != Some(b')') {
+                    return Err(self.error("expected ')'"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(b'0'..=b'9') => Ok(self.number()),
+            Some(b'a'..=b'z') => self.identifier(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn number(&mut self) -> i64 {
+        let mut value: i64 = 0;
+        while let Some(digit @ b'0'..=b'9') = self.expr.get(self.pos).copied() {
+            value = value
+                .saturating_mul(10)
+                .saturating_add((digit - b'0') as i64);
+            self.pos += 1;
+        }
+        value
+    }
+
+    fn identifier(&mut self) -> Result<i64, Error<E>> {
+        let start = self.pos;
+        while let Some(b'a'..=b'z' | b'0'..=b'9' | b'.' | b'_') = self.expr.get(self.pos) {
+            self.pos += 1;
+        }
+        // Only ASCII got consumed, so this can't split a character
+        let name = core::str::from_utf8(&self.expr[start..self.pos]).unwrap();
+        match Source::from_name(name) {
+            Some(source) => (self.read)(source).map_err(Error::Read),
+            None => Err(Error::Syntax(start, "unknown identifier")),
+        }
+    }
+}
+
+/// A syntax error that points at the offending character underneath the
+/// expression.
+pub struct Pointed<'a> {
+    pub expr: &'a str,
+    pub pos: usize,
+    pub msg: &'static str,
+}
+
+impl<'a> fmt::Display for Pointed<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at position {}\n  {}\n  {:pos$}^",
+            self.msg,
+            self.pos,
+            self.expr,
+            "",
+            pos = self.pos
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every source reads as a different small number
+    fn read(source: Source) -> Result<i64, ()> {
+        Ok(match source {
+            Source::AccelX => 10,
+            Source::AccelY => 20,
+            Source::AccelZ => 30,
+            Source::MagX => -1,
+            Source::MagY => -2,
+            Source::MagZ => -3,
+            Source::Temp => 25,
+            Source::Vdd => 3300,
+        })
+    }
+
+    fn calc(expr: &str) -> Result<i64, Error<()>> {
+        eval(expr, read)
+    }
+
+    fn syntax(expr: &str) -> (usize, &'static str) {
+        match calc(expr) {
+            Err(Error::Syntax(pos, msg)) => (pos, msg),
+            other => panic!("{:?} for {:?}", other, expr),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(calc("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(calc("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(calc("10 - 4 - 3").unwrap(), 3);
+        assert_eq!(calc("100 / 10 / 5").unwrap(), 2);
+        assert_eq!(calc("2 * -3 + 1").unwrap(), -5);
+        assert_eq!(calc("--4").unwrap(), 4);
+        assert_eq!(calc("7 / 2 * 2").unwrap(), 6);
+    }
+
+    #[test]
+    fn identifiers_are_read_where_they_are() {
+        assert_eq!(calc("accel.x - accel.y").unwrap(), -10);
+        assert_eq!(calc("vdd / (temp - 14)").unwrap(), 300);
+        assert_eq!(calc("mag.x*mag.y*mag.z").unwrap(), -6);
+        let mut reads = 0;
+        let value = eval("accel.z + accel.z", |_| {
+            reads += 1;
+            Ok::<_, ()>(reads)
+        });
+        assert_eq!(value.unwrap(), 3);
+    }
+
+    #[test]
+    fn a_failed_read_stops_the_evaluation() {
+        let value = eval("1 + temp * vdd", |source| match source {
+            Source::Temp => Err(source),
+            _ => Ok(1),
+        });
+        assert!(matches!(value, Err(Error::Read(Source::Temp))));
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error_at_the_slash() {
+        assert_eq!(syntax("5 / 0"), (2, "division by zero"));
+        assert_eq!(syntax("1 + 5 / (accel.x - 10)"), (6, "division by zero"));
+    }
+
+    #[test]
+    fn overflow_saturates() {
+        assert_eq!(calc("9223372036854775807 + 1").unwrap(), i64::MAX);
+        assert_eq!(calc("99999999999999999999").unwrap(), i64::MAX);
+        assert_eq!(calc("-9223372036854775807 - 2").unwrap(), i64::MIN);
+        assert_eq!(calc("4611686018427387904 * 4").unwrap(), i64::MAX);
+        assert_eq!(calc("-4611686018427387904 * 4").unwrap(), i64::MIN);
+        assert_eq!(calc("(-9223372036854775807 - 1) / -1").unwrap(), i64::MAX);
+        assert_eq!(calc("-(-9223372036854775807 - 1)").unwrap(), i64::MAX);
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_character() {
+        assert_eq!(syntax(""), (0, "unexpected end of expression"));
+        assert_eq!(syntax("1 +"), (3, "unexpected end of expression"));
+        assert_eq!(syntax("1 + # 2"), (4, "unexpected character"));
+        assert_eq!(syntax("(1 + 2"), (6, "expected ')'"));
+        assert_eq!(syntax("1 2"), (2, "unexpected character"));
+        assert_eq!(syntax("2 * accel.w"), (4, "unknown identifier"));
+        assert_eq!(syntax("Temp"), (0, "unexpected character"));
+    }
+
+    #[test]
+    fn pointed_puts_a_caret_under_the_position() {
+        let pointed = Pointed {
+            expr: "1 + # 2",
+            pos: 4,
+            msg: "unexpected character",
+        };
+        assert_eq!(
+            format!("{}", pointed),
+            "unexpected character at position 4\n  1 + # 2\n      ^"
+        );
+    }
+}
@@ -0,0 +1,237 @@
+//! Hard-iron calibration of the magnetometer, for "calibrate".
+//!
+//! Whatever on the board is magnetic adds the same field to every reading,
+//! however the board is turned, so headings come out wrong by an amount
+//! that depends on the direction. Turned every way, the readings lie on a
+//! sphere around that offset: halfway between the least and the most each
+//! axis read is where its centre is, and taking it off puts the sphere back
+//! around zero.
+//!
+//! How much of the sphere the readings have covered is told apart in
+//! [`BINS`] directions from its centre as it is so far: the dominant axis
+//! and its sign pick a face of a cube, and the signs of the other two a
+//! quarter of it. The offsets are only taken once every one has had a
+//! reading in it, and every axis has swung far enough for its middle to
+//! mean something.
+//!
+//! The offsets belong to the board, so they are kept in a flash page of
+//! their own rather than with the settings, which "provision" copies from
+//! board to board. [`Calibration::record`] and [`Calibration::from_record`]
+//! are all there is to the record, [`load`], [`save`] and [`clear`] only
+//! move it in and out of flash.
+
+use core::fmt;
+
+use crate::flash;
+
+pub const USAGE: &str = "calibrate [<x> <y> <z>]";
+
+/// How long the turning may take before "calibrate" gives up, three times
+/// what it usually does
+pub const TIMEOUT_MS: u32 = 30_000;
+
+/// Faces of a cube, in quarters
+const BINS: u32 = 24;
+const ALL_BINS: u32 = (1 << BINS) - 1;
+/// How far each axis has to swing, in nT. The earth's field is at least
+/// 25 µT, which a full turn reads one way and then the other.
+const MIN_SPAN_NT: i32 = 30_000;
+/// Further than the sensor reads, in nT
+const MAX_OFFSET_NT: i32 = 5_000_000;
+
+/// Bump the low byte whenever the record layout changes, like the
+/// settings'.
+const MAGIC: u32 = 0x4d43_4101;
+/// The magic, the three offsets and the checksum
+const RECORD_WORDS: usize = 5;
+const PAGE: usize = 5;
+
+/// Why there were no offsets to load at boot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadError {
+    /// Nothing was ever saved, or "calibration clear" erased it
+    Erased,
+    /// Torn by a reset while it was written, or from another program
+    Corrupted,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Erased => write!(f, "magnetometer not calibrated, see \"calibrate\""),
+            LoadError::Corrupted => write!(
+                f,
+                "magnetometer calibration in flash is corrupted, running uncalibrated"
+            ),
+        }
+    }
+}
+
+/// What the hard iron adds to every magnetometer reading, in nT.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Calibration {
+    pub offset: [i32; 3],
+}
+
+impl Calibration {
+    pub const NONE: Calibration = Calibration { offset: [0; 3] };
+
+    /// `raw` with the offsets taken off.
+    pub fn apply(&self, raw: [i32; 3]) -> [i32; 3] {
+        let mut calibrated = raw;
+        for (value, offset) in calibrated.iter_mut().zip(self.offset.iter()) {
+            *value = value.saturating_sub(*offset);
+        }
+        calibrated
+    }
+
+    /// The three offsets, as "calibrate" takes them. `None` for anything
+    /// else, or an offset no sensor could have.
+    pub fn parse(args: &str) -> Option<Calibration> {
+        let mut offset = [0; 3];
+        let mut words = args.split_ascii_whitespace();
+        for value in offset.iter_mut() {
+            *value = words
+                .next()?
+                .parse()
+                .ok()
+                .filter(|nt: &i32| nt.abs() <= MAX_OFFSET_NT)?;
+        }
+        match words.next() {
+            Some(_) => None,
+            None => Some(Calibration { offset }),
+        }
+    }
+
+    /// The record kept in flash.
+    pub fn record(&self) -> [u32; RECORD_WORDS] {
+        let [x, y, z] = self.offset.map(|nt| nt as u32);
+        let mut record = [MAGIC, x, y, z, 0];
+        record[RECORD_WORDS - 1] = flash::checksum(&record[..RECORD_WORDS - 1]);
+        record
+    }
+
+    /// The offsets `record` holds, if it is one [`Calibration::record`]
+    /// wrote and it is intact.
+    pub fn from_record(record: &[u32; RECORD_WORDS]) -> Result<Calibration, LoadError> {
+        if record.iter().all(|&word| word == !0) {
+            return Err(LoadError::Erased);
+        }
+        let (body, sum) = record.split_at(RECORD_WORDS - 1);
+        if body[0] != MAGIC || flash::checksum(body) != sum[0] {
+            return Err(LoadError::Corrupted);
+        }
+        let offset = [body[1] as i32, body[2] as i32, body[3] as i32];
+        if offset.iter().any(|nt| nt.abs() > MAX_OFFSET_NT) {
+            return Err(LoadError::Corrupted);
+        }
+        Ok(Calibration { offset })
+    }
+}
+
+/// The offsets saved last, for boot.
+pub fn load() -> Result<Calibration, LoadError> {
+    let mut record = [0; RECORD_WORDS];
+    flash::read(PAGE, 0, &mut record);
+    Calibration::from_record(&record)
+}
+
+pub fn save(calibration: &Calibration) -> Result<(), flash::LowPower> {
+    flash::erase(PAGE)?;
+    flash::write(PAGE, 0, &calibration.record())
+}
+
+/// Erase the offsets, so that the next boot starts uncalibrated.
+pub fn clear() -> Result<(), flash::LowPower> {
+    flash::erase(PAGE)
+}
+
+/// The readings taken while the board is being turned, as far as the
+/// offsets and the coverage go.
+pub struct Collector {
+    min: [i32; 3],
+    max: [i32; 3],
+    /// One bit per bin with a reading in it
+    covered: u32,
+}
+
+impl Collector {
+    pub fn new() -> Collector {
+        Collector {
+            min: [i32::MAX; 3],
+            max: [i32::MIN; 3],
+            covered: 0,
+        }
+    }
+
+    /// Take a raw reading in nT.
+    pub fn push(&mut self, raw: [i32; 3]) {
+        for (axis, &value) in raw.iter().enumerate() {
+            self.min[axis] = self.min[axis].min(value);
+            self.max[axis] = self.max[axis].max(value);
+        }
+        // Before every axis has swung some way the centre is little more
+        // than the first reading, and everything would seem to be around it
+        if self.spans(MIN_SPAN_NT / 2) {
+            self.covered |= 1 << bin(self.centre(), raw);
+        }
+    }
+
+    fn spans(&self, nt: i32) -> bool {
+        (0..3).all(|axis| self.max[axis].saturating_sub(self.min[axis]) >= nt)
+    }
+
+    fn centre(&self) -> [i32; 3] {
+        let mut centre = [0; 3];
+        for (axis, centre) in centre.iter_mut().enumerate() {
+            *centre = ((self.min[axis] as i64 + self.max[axis] as i64) / 2) as i32;
+        }
+        centre
+    }
+
+    /// How many of the bins have a reading, out of 100.
+    pub fn coverage(&self) -> u8 {
+        (self.covered.count_ones() * 100 / BINS) as u8
+    }
+
+    pub fn complete(&self) -> bool {
+        self.covered == ALL_BINS && self.spans(MIN_SPAN_NT)
+    }
+
+    /// The offsets, once the readings are [`complete`](Collector::complete).
+    pub fn finish(&self) -> Option<Calibration> {
+        self.complete().then(|| Calibration {
+            offset: self.centre(),
+        })
+    }
+
+    /// An LED for every bin covered, filling the display row by row, and
+    /// the last one once it is complete.
+    pub fn image(&self) -> [[u8; 5]; 5] {
+        let lit = self.covered.count_ones() as usize + self.complete() as usize;
+        let mut image = [[0; 5]; 5];
+        for (i, pixel) in image.iter_mut().flatten().enumerate() {
+            if i < lit {
+                *pixel = 9;
+            }
+        }
+        image
+    }
+}
+
+/// Which of the [`BINS`] `raw` falls in, looked at from `centre`.
+fn bin(centre: [i32; 3], raw: [i32; 3]) -> u32 {
+    let mut v = [0i64; 3];
+    for axis in 0..3 {
+        v[axis] = raw[axis] as i64 - centre[axis] as i64;
+    }
+    let mut dominant = 0;
+    for axis in 1..3 {
+        if v[axis].abs() > v[dominant].abs() {
+            dominant = axis;
+        }
+    }
+    let face = dominant as u32 * 2 + (v[dominant] < 0) as u32;
+    let (a, b) = ((dominant + 1) % 3, (dominant + 2) % 3);
+    face * 4 + (v[a] < 0) as u32 * 2 + (v[b] < 0) as u32
+}
@@ -0,0 +1,52 @@
+//! The LED matrix as an embedded-graphics [`DrawTarget`], to draw images
+//! with its primitives and fonts.
+//!
+//! Drawing goes into an image of its own, which goes on the matrix with
+//! [`display::set_background`] once it is done, so a half-drawn frame is
+//! never shown. Pixels outside the 5x5 matrix are dropped.
+//!
+//! [`display::set_background`]: crate::display::set_background
+
+use core::convert::Infallible;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+
+use crate::display::Image;
+
+/// What lit pixels are drawn with.
+const LIT: u8 = 9;
+
+#[derive(Default)]
+pub struct Canvas {
+    image: Image,
+}
+
+impl Canvas {
+    pub fn image(&self) -> Image {
+        self.image
+    }
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(5, 5)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        for Pixel(point, color) in pixels {
+            if (0..5).contains(&point.x) && (0..5).contains(&point.y) {
+                self.image[point.y as usize][point.x as usize] =
+                    if color.is_on() { LIT } else { 0 };
+            }
+        }
+        Ok(())
+    }
+}
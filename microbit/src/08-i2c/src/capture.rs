@@ -0,0 +1,121 @@
+//! "capture": accelerometer samples recorded into RAM, for a host to fetch
+//! when it is ready.
+//!
+//! "capture <n>" records `n` samples at the sensor's data rate, filtered like
+//! any other reading, replacing whatever was captured before. Stopped with
+//! Ctrl-C it keeps what it got so far. The samples stay until "capture
+//! clear" or the next capture, so "capture dump" can be run as often as
+//! needed. It prints them as [`block`]s of [`BLOCK_SAMPLES`], each one line
+//! of `x,y,z` samples in mg separated by `;`:
+//!
+//! ```text
+//! cap 0/40 -12,40,1002;-13,41,1001;... 5f1d2c0a
+//! ```
+//!
+//! A host that missed some, or found one that fails its CRC, asks for the
+//! rest with "capture dump from <block>", without measuring again.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::block;
+
+pub const USAGE: &str = "capture <samples>|dump [from <block>]|clear";
+
+pub const BLOCK_SAMPLES: usize = 50;
+const TAG: &str = "cap";
+
+/// 3K of the nRF51's 16K of RAM
+#[cfg(feature = "v1")]
+pub const CAPACITY: usize = 500;
+#[cfg(feature = "v2")]
+pub const CAPACITY: usize = 2000;
+
+/// In .bss, and only ever handed out once, by [`Capture::take`].
+static mut SAMPLES: [[i16; 3]; CAPACITY] = [[0; 3]; CAPACITY];
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+pub enum Error {
+    Empty,
+    /// Asked for a block past the last one, of this many
+    NoBlock(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "nothing captured"),
+            Error::NoBlock(blocks) => write!(f, "no such block, the capture has {}", blocks),
+        }
+    }
+}
+
+pub struct Capture {
+    samples: &'static mut [[i16; 3]; CAPACITY],
+    len: usize,
+}
+
+impl Capture {
+    /// The one capture buffer. Panics if it has been taken already.
+    pub fn take() -> Capture {
+        let taken = cortex_m::interrupt::free(|_| {
+            let taken = TAKEN.load(Ordering::Relaxed);
+            TAKEN.store(true, Ordering::Relaxed);
+            taken
+        });
+        assert!(!taken, "capture buffer taken twice");
+        Capture {
+            samples: unsafe { &mut *core::ptr::addr_of_mut!(SAMPLES) },
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add a sample, clamped to what fits. Ignored once the buffer is full.
+    pub fn push(&mut self, sample: [i32; 3]) {
+        if let Some(slot) = self.samples.get_mut(self.len) {
+            *slot = sample.map(|axis| axis.clamp(i16::MIN as i32, i16::MAX as i32) as i16);
+            self.len += 1;
+        }
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.len.div_ceil(BLOCK_SAMPLES)
+    }
+
+    /// Check that "capture dump from `from`" has something to dump.
+    pub fn check_from(&self, from: usize) -> Result<(), Error> {
+        if self.is_empty() {
+            return Err(Error::Empty);
+        }
+        if from >= self.blocks() {
+            return Err(Error::NoBlock(self.blocks()));
+        }
+        Ok(())
+    }
+
+    /// Print block `index`, which has to exist.
+    pub fn write_block<W: fmt::Write>(&self, w: &mut W, index: usize) -> fmt::Result {
+        let start = index * BLOCK_SAMPLES;
+        let end = (start + BLOCK_SAMPLES).min(self.len);
+        block::write(w, TAG, index, self.blocks(), |w| {
+            for (i, [x, y, z]) in self.samples[start..end].iter().enumerate() {
+                let separator = if i == 0 { "" } else { ";" };
+                write!(w, "{}{},{},{}", separator, x, y, z)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,118 @@
+//! Who uses which piece of hardware, so that two parts of the firmware
+//! can't configure the same one over each other.
+//!
+//! Anything that sets up a peripheral, a pin by number or a PPI channel
+//! [`claim`]s it first, and gets a [`Token`] back or an [`InUseBy`] naming
+//! the owner. Whatever keeps its hardware for good drops the token, which
+//! keeps the claim; whatever can give it back, such as the audio cues
+//! going off, keeps the token and hands it to [`release`]. "power report"
+//! and "power off" go by the same table.
+//!
+//! The claims made at boot use [`take`]: one of those failing is a bug,
+//! not something to carry on without. On the v1 there is nothing to give
+//! back, the speaker being all there is to the audio cues, so every claim
+//! is for good and [`take`] is all there is.
+
+use core::fmt;
+use heapless::Vec;
+
+use crate::power::Peripheral;
+use crate::shared::Shared;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resource {
+    Peripheral(Peripheral),
+    /// On port 0, for hardware that gets its pins by number
+    Pin(u8),
+    PpiChannel(u8),
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resource::Peripheral(peripheral) => write!(f, "{}", peripheral.name()),
+            Resource::Pin(pin) => write!(f, "P0.{:02}", pin),
+            Resource::PpiChannel(channel) => write!(f, "PPI channel {}", channel),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Owner {
+    pub name: &'static str,
+    /// The command that makes it let go, if there is one
+    pub release_with: Option<&'static str>,
+}
+
+impl Owner {
+    /// For the life of the program
+    pub const fn permanent(name: &'static str) -> Owner {
+        Owner {
+            name,
+            release_with: None,
+        }
+    }
+}
+
+/// Proof of a claim, for [`release`]. Dropping it keeps the claim.
+#[cfg(feature = "v2")]
+#[derive(Debug)]
+pub struct Token(Resource);
+
+#[derive(Debug)]
+pub struct InUseBy {
+    pub resource: Resource,
+    pub owner: Owner,
+}
+
+impl fmt::Display for InUseBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in use by {}", self.resource, self.owner.name)?;
+        if let Some(command) = self.owner.release_with {
+            write!(f, ", run \"{}\" first", command)?;
+        }
+        Ok(())
+    }
+}
+
+/// Enough for every claim the firmware makes, with room to spare.
+const CAPACITY: usize = 16;
+
+static CLAIMS: Shared<Vec<(Resource, Owner), CAPACITY>> = Shared::new(Vec::new());
+
+fn add(resource: Resource, owner: Owner) -> Result<(), InUseBy> {
+    CLAIMS.with(|claims| {
+        if let Some(&(_, owner)) = claims.iter().find(|(claimed, _)| *claimed == resource) {
+            return Err(InUseBy { resource, owner });
+        }
+        // Not a conflict, but as much of a bug
+        assert!(claims.push((resource, owner)).is_ok(), "claims full");
+        Ok(())
+    })
+}
+
+#[cfg(feature = "v2")]
+pub fn claim(resource: Resource, owner: Owner) -> Result<Token, InUseBy> {
+    add(resource, owner).map(|()| Token(resource))
+}
+
+/// Claim something at boot, for good.
+pub fn take(resource: Resource, owner: Owner) {
+    if let Err(err) = add(resource, owner) {
+        panic!("{}", err);
+    }
+}
+
+#[cfg(feature = "v2")]
+pub fn release(token: Token) {
+    CLAIMS.with(|claims| claims.retain(|(claimed, _)| *claimed != token.0));
+}
+
+pub fn owner(resource: Resource) -> Option<Owner> {
+    CLAIMS.with(|claims| {
+        claims
+            .iter()
+            .find(|(claimed, _)| *claimed == resource)
+            .map(|&(_, owner)| owner)
+    })
+}
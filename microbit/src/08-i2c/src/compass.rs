@@ -0,0 +1,94 @@
+//! An arrow on the display towards magnetic north, for "compass show".
+//!
+//! Up on the display is +y and right is +x, as in the demo's needle, so
+//! north is [`heading`](crate::heading) degrees clockwise from up. The
+//! heading picks the nearest of eight arrows drawn ahead of time, see
+//! [`heading::octant`]; the display only has to be handed a new image when
+//! that changes.
+
+use crate::display::Image;
+use crate::heading;
+
+/// How many readings go into each heading, 60 ms at 50 Hz: few enough for
+/// the arrow to follow the board
+pub const SAMPLES: i32 = 3;
+
+const L: u8 = 9;
+
+/// By octant, from up clockwise
+const ARROWS: [Image; 8] = [
+    [
+        [0, 0, L, 0, 0],
+        [0, L, L, L, 0],
+        [L, 0, L, 0, L],
+        [0, 0, L, 0, 0],
+        [0, 0, L, 0, 0],
+    ],
+    [
+        [0, 0, L, L, L],
+        [0, 0, 0, L, L],
+        [0, 0, L, 0, L],
+        [0, L, 0, 0, 0],
+        [L, 0, 0, 0, 0],
+    ],
+    [
+        [0, 0, L, 0, 0],
+        [0, 0, 0, L, 0],
+        [L, L, L, L, L],
+        [0, 0, 0, L, 0],
+        [0, 0, L, 0, 0],
+    ],
+    [
+        [L, 0, 0, 0, 0],
+        [0, L, 0, 0, 0],
+        [0, 0, L, 0, L],
+        [0, 0, 0, L, L],
+        [0, 0, L, L, L],
+    ],
+    [
+        [0, 0, L, 0, 0],
+        [0, 0, L, 0, 0],
+        [L, 0, L, 0, L],
+        [0, L, L, L, 0],
+        [0, 0, L, 0, 0],
+    ],
+    [
+        [0, 0, 0, 0, L],
+        [0, 0, 0, L, 0],
+        [L, 0, L, 0, 0],
+        [L, L, 0, 0, 0],
+        [L, L, L, 0, 0],
+    ],
+    [
+        [0, 0, L, 0, 0],
+        [0, L, 0, 0, 0],
+        [L, L, L, L, L],
+        [0, L, 0, 0, 0],
+        [0, 0, L, 0, 0],
+    ],
+    [
+        [L, L, L, 0, 0],
+        [L, L, 0, 0, 0],
+        [L, 0, L, 0, 0],
+        [0, 0, 0, L, 0],
+        [0, 0, 0, 0, L],
+    ],
+];
+
+/// A dot in the middle, for no horizontal field to point along
+const NOWHERE: Image = [
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+    [0, 0, L, 0, 0],
+    [0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0],
+];
+
+/// The arrow for `heading`, in hundredths of a degree, or the dot for
+/// none.
+pub fn image(heading: Option<i32>) -> &'static Image {
+    match heading {
+        Some(heading) => &ARROWS[heading::octant(heading)],
+        None => &NOWHERE,
+    }
+}
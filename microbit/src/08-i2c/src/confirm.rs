@@ -0,0 +1,275 @@
+//! Physical confirmation for commands that destroy stored data.
+//!
+//! A protected command only runs once somebody holding the board confirms
+//! it within [`TIMEOUT_TICKS`]: either with a double tap, picked out of the
+//! raw accelerometer samples by [`Taps`], or by pressing buttons A and B
+//! together, which works even where the accelerometer can't be relied on.
+//! [`wait`] does the waiting, on whatever [`Inputs`] it is handed.
+
+use core::fmt;
+
+use crate::heartbeat;
+
+pub const TIMEOUT_TICKS: u32 = 3 * heartbeat::TICK_HZ;
+
+/// Sum of the per-axis changes between two samples, in mg, that counts as
+/// a tap. Picking the board up or tilting it stays well below this at 50 Hz.
+const JERK_MG: i32 = 600;
+/// Samples at 50 Hz. Spikes closer together than this are the board still
+/// ringing from the same tap.
+const MIN_GAP: u32 = 4;
+const MAX_GAP: u32 = 25;
+
+#[derive(Debug)]
+pub struct NotConfirmed;
+
+impl fmt::Display for NotConfirmed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not confirmed, nothing changed")
+    }
+}
+
+/// Double tap detection over unfiltered accelerometer samples.
+#[derive(Default)]
+pub struct Taps {
+    previous: Option<[i32; 3]>,
+    /// Whether a first tap has been seen
+    pending: bool,
+    /// Samples since the first tap
+    age: u32,
+}
+
+impl Taps {
+    /// Feed the next sample, true once it completes a double tap.
+    pub fn push(&mut self, sample: [i32; 3]) -> bool {
+        let jerk = self.previous.map_or(0, |previous| {
+            previous
+                .iter()
+                .zip(sample.iter())
+                .map(|(a, b)| (a - b).abs())
+                .sum()
+        });
+        self.previous = Some(sample);
+        self.age = self.age.saturating_add(1);
+        if jerk < JERK_MG || (self.pending && self.age < MIN_GAP) {
+            return false;
+        }
+        if self.pending && self.age <= MAX_GAP {
+            return true;
+        }
+        self.pending = true;
+        self.age = 0;
+        false
+    }
+}
+
+/// What [`wait`] needs of the board.
+pub trait Inputs {
+    type Error;
+
+    /// The heartbeat's ticks, which wrap.
+    fn ticks(&mut self) -> u32;
+    /// Checked every time round, to give up on Ctrl-C and the like.
+    fn keep_going(&mut self) -> Result<(), Self::Error>;
+    /// Whether A and B are both held down.
+    fn chord(&mut self) -> bool;
+    /// A new raw accelerometer sample in mg, if there is one. Without a
+    /// sensor that can be tapped always `None`.
+    fn sample(&mut self) -> Result<Option<[i32; 3]>, Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Tapped,
+    Chord,
+    TimedOut,
+}
+
+/// Wait for a double tap or the chord, for up to [`TIMEOUT_TICKS`].
+pub fn wait<I: Inputs>(inputs: &mut I) -> Result<Outcome, I::Error> {
+    let mut taps = Taps::default();
+    let start = inputs.ticks();
+    loop {
+        inputs.keep_going()?;
+        if inputs.chord() {
+            return Ok(Outcome::Chord);
+        }
+        if inputs.ticks().wrapping_sub(start) >= TIMEOUT_TICKS {
+            return Ok(Outcome::TimedOut);
+        }
+        if let Some(sample) = inputs.sample()? {
+            if taps.push(sample) {
+                return Ok(Outcome::Tapped);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STILL: [i32; 3] = [0, 0, 1000];
+    const TAP: [i32; 3] = [400, 300, 1400];
+
+    /// A board lying still, with each sample taking a tick: `taps` are the
+    /// ticks a tap lands on, `chord` where A and B go down, and `abort`
+    /// where Ctrl-C comes in.
+    #[derive(Default)]
+    struct Board {
+        now: u32,
+        taps: Vec<u32>,
+        chord: Option<u32>,
+        abort: Option<u32>,
+        /// Ticks read, samples taken
+        reads: u32,
+        samples: u32,
+        /// No sensor to tap
+        no_sensor: bool,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Interrupted;
+
+    impl Inputs for Board {
+        type Error = Interrupted;
+
+        fn ticks(&mut self) -> u32 {
+            self.reads += 1;
+            self.now
+        }
+
+        fn keep_going(&mut self) -> Result<(), Interrupted> {
+            match self.abort {
+                Some(at) if self.now.wrapping_sub(at) < u32::MAX / 2 => Err(Interrupted),
+                _ => Ok(()),
+            }
+        }
+
+        fn chord(&mut self) -> bool {
+            self.chord == Some(self.now)
+        }
+
+        fn sample(&mut self) -> Result<Option<[i32; 3]>, Interrupted> {
+            let tapped = self.taps.contains(&self.now);
+            self.now = self.now.wrapping_add(1);
+            if self.no_sensor {
+                return Ok(None);
+            }
+            self.samples += 1;
+            Ok(Some(if tapped { TAP } else { STILL }))
+        }
+    }
+
+    #[test]
+    fn nothing_happening_times_out() {
+        let mut board = Board::default();
+        assert_eq!(wait(&mut board), Ok(Outcome::TimedOut));
+        assert_eq!(board.now, TIMEOUT_TICKS);
+    }
+
+    #[test]
+    fn the_timeout_survives_the_ticks_wrapping() {
+        let mut board = Board {
+            now: u32::MAX - 5,
+            ..Board::default()
+        };
+        assert_eq!(wait(&mut board), Ok(Outcome::TimedOut));
+        assert_eq!(board.now, TIMEOUT_TICKS - 6);
+    }
+
+    #[test]
+    fn without_a_sensor_the_timeout_still_comes() {
+        let mut board = Board {
+            no_sensor: true,
+            taps: vec![1, 10],
+            ..Board::default()
+        };
+        assert_eq!(wait(&mut board), Ok(Outcome::TimedOut));
+        assert_eq!(board.samples, 0);
+    }
+
+    #[test]
+    fn one_tap_is_not_enough() {
+        let mut board = Board {
+            taps: vec![5],
+            ..Board::default()
+        };
+        assert_eq!(wait(&mut board), Ok(Outcome::TimedOut));
+    }
+
+    #[test]
+    fn a_double_tap_confirms() {
+        let mut board = Board {
+            taps: vec![5, 12],
+            ..Board::default()
+        };
+        assert_eq!(wait(&mut board), Ok(Outcome::Tapped));
+        assert!(board.now < TIMEOUT_TICKS);
+    }
+
+    #[test]
+    fn the_chord_confirms() {
+        let mut board = Board {
+            chord: Some(TIMEOUT_TICKS - 1),
+            ..Board::default()
+        };
+        assert_eq!(wait(&mut board), Ok(Outcome::Chord));
+    }
+
+    #[test]
+    fn the_chord_after_the_timeout_is_too_late() {
+        let mut board = Board {
+            chord: Some(TIMEOUT_TICKS + 1),
+            ..Board::default()
+        };
+        assert_eq!(wait(&mut board), Ok(Outcome::TimedOut));
+    }
+
+    #[test]
+    fn ctrl_c_gives_up_at_once() {
+        let mut board = Board {
+            abort: Some(3),
+            taps: vec![5, 12],
+            ..Board::default()
+        };
+        assert_eq!(wait(&mut board), Err(Interrupted));
+        assert_eq!(board.now, 3);
+    }
+
+    #[test]
+    fn taps_too_close_together_are_one_ringing_tap() {
+        let mut taps = Taps::default();
+        let hits: Vec<bool> = [STILL, TAP, STILL, TAP, STILL]
+            .iter()
+            .map(|&sample| taps.push(sample))
+            .collect();
+        assert_eq!(hits, [false; 5]);
+    }
+
+    #[test]
+    fn taps_too_far_apart_start_over() {
+        let mut taps = Taps::default();
+        taps.push(STILL);
+        assert!(!taps.push(TAP));
+        for _ in 0..MAX_GAP {
+            assert!(!taps.push(STILL));
+        }
+        // Too late to be the second, so it is a new first
+        assert!(!taps.push(TAP));
+        for _ in 0..MIN_GAP {
+            assert!(!taps.push(STILL));
+        }
+        assert!(taps.push(TAP));
+    }
+
+    #[test]
+    fn a_gentle_push_is_not_a_tap() {
+        let mut taps = Taps::default();
+        let push = [100, 100, 1200];
+        for i in 0..100 {
+            let sample = if i % 10 == 0 { push } else { STILL };
+            assert!(!taps.push(sample));
+        }
+    }
+}
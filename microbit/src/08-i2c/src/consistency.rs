@@ -0,0 +1,553 @@
+//! "sensorhealth": a watch on whether the LSM303AGR still reads what it
+//! should.
+//!
+//! While the monitor is on, every live reading, the ones commands take and
+//! one a second taken between keystrokes, is checked against what it ought
+//! to be: the magnetometer's field strength against the one captured with
+//! "sensorhealth capture", and the accelerometer's against 1 g. A check
+//! that stays outside its band for longer than [`Config::hold_s`] is
+//! raised. The next prompt warns about it once, "status" and "sensorhealth"
+//! show it for as long as it lasts, and so does every `ack` record in the
+//! machine-readable output modes. It clears again once readings have been
+//! back inside the band for as long.
+//!
+//! Readings more than [`MAX_GAP_TICKS`] apart don't count as one stretch,
+//! in either direction. Without the RTC the clock never moves on, and
+//! nothing is ever raised.
+
+use core::fmt;
+use libm::sqrtf;
+
+use crate::heartbeat;
+use crate::shared::Shared;
+
+pub const USAGE: &str = "sensorhealth [on|off|capture|reference <nT>|bands <mag %> <accel %> <s>]";
+
+const ONE_G_MG: f32 = 1000.0;
+const MAX_BAND_PCT: u8 = 100;
+const MAX_HOLD_S: u8 = 60;
+/// Idle readings are one second apart, a long command may leave longer
+/// between them.
+const MAX_GAP_TICKS: u32 = 2 * heartbeat::TICK_HZ;
+const IDLE_EVERY_TICKS: u32 = heartbeat::TICK_HZ;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    pub enabled: bool,
+    /// How far off the reference the field may be, in percent of it
+    pub mag_band_pct: u8,
+    /// How far off 1 g the acceleration may be, in percent of it
+    pub accel_band_pct: u8,
+    /// How long either has to stay off to raise its check, in seconds
+    pub hold_s: u8,
+    /// The field strength at the bench, 0 until one has been captured
+    pub reference_nt: u32,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::DEFAULT
+    }
+}
+
+impl Config {
+    const DEFAULT: Config = Config {
+        enabled: false,
+        mag_band_pct: 20,
+        accel_band_pct: 10,
+        hold_s: 5,
+        reference_nt: 0,
+    };
+
+    pub fn encode(&self) -> [u32; 2] {
+        [
+            (self.enabled as u32) << 24
+                | (self.hold_s as u32) << 16
+                | (self.accel_band_pct as u32) << 8
+                | self.mag_band_pct as u32,
+            self.reference_nt,
+        ]
+    }
+
+    /// Out of range fields decode as their defaults.
+    pub fn decode(words: [u32; 2]) -> Config {
+        let default = Config::DEFAULT;
+        let byte = |shift: u32| (words[0] >> shift) as u8;
+        let band = |pct: u8, default| match pct {
+            1..=MAX_BAND_PCT => pct,
+            _ => default,
+        };
+        Config {
+            enabled: byte(24) & 1 != 0,
+            mag_band_pct: band(byte(0), default.mag_band_pct),
+            accel_band_pct: band(byte(8), default.accel_band_pct),
+            hold_s: match byte(16) {
+                hold @ 1..=MAX_HOLD_S => hold,
+                _ => default.hold_s,
+            },
+            reference_nt: words[1],
+        }
+    }
+
+    /// The commands that recreate it, for "config export".
+    pub fn export<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(
+            w,
+            "sensorhealth bands {} {} {}",
+            self.mag_band_pct, self.accel_band_pct, self.hold_s
+        )?;
+        writeln!(w, "sensorhealth reference {}", self.reference_nt)?;
+        writeln!(
+            w,
+            "sensorhealth {}",
+            if self.enabled { "on" } else { "off" }
+        )
+    }
+}
+
+/// The arguments of "sensorhealth bands", as percent, percent, seconds.
+pub fn parse_bands(args: &str) -> Option<(u8, u8, u8)> {
+    let mut words = args.split_ascii_whitespace();
+    let mut next = |max: u8| match words.next()?.parse() {
+        Ok(value) if (1..=max).contains(&value) => Some(value),
+        _ => None,
+    };
+    let bands = (next(MAX_BAND_PCT)?, next(MAX_BAND_PCT)?, next(MAX_HOLD_S)?);
+    match words.next() {
+        None => Some(bands),
+        Some(_) => None,
+    }
+}
+
+/// The strength of a field or an acceleration, in the units it came in.
+pub fn magnitude([x, y, z]: [i32; 3]) -> f32 {
+    let (x, y, z) = (x as f32, y as f32, z as f32);
+    sqrtf(x * x + y * y + z * z)
+}
+
+/// Returned by "sensorhealth capture" while the sensor is simulated or
+/// replayed.
+#[derive(Debug)]
+pub struct NotLive;
+
+impl fmt::Display for NotLive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the reference has to come from the live sensor")
+    }
+}
+
+/// Turns a run of readings outside (or back inside) a band into one
+/// decision, once the run has lasted `hold` ticks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Hysteresis {
+    raised: bool,
+    /// When the current run against `raised` started
+    since: Option<u32>,
+    /// When the last reading came in
+    last: Option<u32>,
+}
+
+impl Hysteresis {
+    pub const fn new() -> Hysteresis {
+        Hysteresis {
+            raised: false,
+            since: None,
+            last: None,
+        }
+    }
+
+    pub fn raised(&self) -> bool {
+        self.raised
+    }
+
+    /// A reading at tick `now`, `outside` its band or not. True when this
+    /// raises the check.
+    pub fn update(&mut self, outside: bool, now: u32, hold: u32) -> bool {
+        let gap = self
+            .last
+            .is_some_and(|last| now.wrapping_sub(last) > MAX_GAP_TICKS);
+        self.last = Some(now);
+        if outside == self.raised {
+            self.since = None;
+            return false;
+        }
+        if gap {
+            self.since = None;
+        }
+        let since = *self.since.get_or_insert(now);
+        if now.wrapping_sub(since) < hold {
+            return false;
+        }
+        self.raised = outside;
+        self.since = None;
+        outside
+    }
+}
+
+/// What a check compares and against what.
+#[derive(Clone, Copy)]
+struct Check {
+    name: &'static str,
+    unit: &'static str,
+    state: Hysteresis,
+    /// The latest magnitude
+    last: Option<f32>,
+    /// Raised since the last prompt, and not warned about yet
+    pending: bool,
+}
+
+impl Check {
+    const fn new(name: &'static str, unit: &'static str) -> Check {
+        Check {
+            name,
+            unit,
+            state: Hysteresis::new(),
+            last: None,
+            pending: false,
+        }
+    }
+
+    fn update(&mut self, magnitude: f32, expected: f32, band_pct: u8, now: u32, hold: u32) {
+        let outside = (magnitude - expected).abs() > expected * band_pct as f32 / 100.0;
+        self.last = Some(magnitude);
+        if self.state.update(outside, now, hold) {
+            self.pending = true;
+        }
+    }
+}
+
+struct Monitor {
+    config: Config,
+    mag: Check,
+    accel: Check,
+    /// When the idle loop last took a reading
+    sampled: u32,
+}
+
+impl Monitor {
+    fn hold_ticks(&self) -> u32 {
+        self.config.hold_s as u32 * heartbeat::TICK_HZ
+    }
+
+    fn checks(&self) -> [&Check; 2] {
+        [&self.mag, &self.accel]
+    }
+}
+
+static MONITOR: Shared<Monitor> = Shared::new(Monitor {
+    config: Config::DEFAULT,
+    mag: Check::new("magnetometer", "nT"),
+    accel: Check::new("accelerometer", "mg"),
+    sampled: 0,
+});
+
+/// Start over with `config`, forgetting what was raised under the old one.
+pub fn configure(config: Config) {
+    MONITOR.with(|monitor| {
+        monitor.config = config;
+        monitor.mag = Check::new(monitor.mag.name, monitor.mag.unit);
+        monitor.accel = Check::new(monitor.accel.name, monitor.accel.unit);
+    });
+}
+
+/// Whether the idle loop should take a reading now, which also counts as
+/// having taken it.
+pub fn idle_due() -> bool {
+    let now = heartbeat::ticks();
+    MONITOR.with(|monitor| {
+        if !monitor.config.enabled || now.wrapping_sub(monitor.sampled) < IDLE_EVERY_TICKS {
+            return false;
+        }
+        monitor.sampled = now;
+        true
+    })
+}
+
+/// A live magnetometer reading, in nT.
+pub fn mag(sample: [i32; 3]) {
+    let magnitude = magnitude(sample);
+    let now = heartbeat::ticks();
+    MONITOR.with(|monitor| {
+        let config = monitor.config;
+        if config.enabled && config.reference_nt != 0 {
+            let hold = monitor.hold_ticks();
+            let expected = config.reference_nt as f32;
+            monitor
+                .mag
+                .update(magnitude, expected, config.mag_band_pct, now, hold);
+        }
+    });
+}
+
+/// A live, unfiltered accelerometer reading, in mg.
+pub fn accel(sample: [i32; 3]) {
+    let magnitude = magnitude(sample);
+    let now = heartbeat::ticks();
+    MONITOR.with(|monitor| {
+        let config = monitor.config;
+        if config.enabled {
+            let hold = monitor.hold_ticks();
+            monitor
+                .accel
+                .update(magnitude, ONE_G_MG, config.accel_band_pct, now, hold);
+        }
+    });
+}
+
+/// Whether any check is raised, for the `ack` records.
+pub fn degraded() -> bool {
+    MONITOR.with(|monitor| monitor.checks().iter().any(|check| check.state.raised()))
+}
+
+/// The names of the checks raised since the last call.
+pub fn take_warning() -> Option<Warning> {
+    MONITOR.with(|monitor| {
+        let warning = Warning {
+            mag: monitor.mag.pending,
+            accel: monitor.accel.pending,
+            hold_s: monitor.config.hold_s,
+        };
+        monitor.mag.pending = false;
+        monitor.accel.pending = false;
+        Some(warning).filter(|warning| warning.mag || warning.accel)
+    })
+}
+
+pub struct Warning {
+    mag: bool,
+    accel: bool,
+    hold_s: u8,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let which = match (self.mag, self.accel) {
+            (true, true) => "magnetometer and accelerometer",
+            (true, false) => "magnetometer",
+            _ => "accelerometer",
+        };
+        write!(
+            f,
+            "sensor health: {} readings out of their band for over {} s",
+            which, self.hold_s
+        )
+    }
+}
+
+/// One line for "status".
+pub fn status<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    let (enabled, degraded) = MONITOR.with(|monitor| {
+        let raised = monitor.checks().map(|check| check.state.raised());
+        (monitor.config.enabled, raised)
+    });
+    let state = match (enabled, degraded) {
+        (false, _) => "off",
+        (true, [false, false]) => "ok",
+        (true, [true, false]) => "degraded (magnetometer)",
+        (true, [false, true]) => "degraded (accelerometer)",
+        (true, [true, true]) => "degraded (magnetometer, accelerometer)",
+    };
+    writeln!(w, "sensor health: {}", state)
+}
+
+/// "sensorhealth"
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    let (config, checks) = MONITOR.with(|monitor| (monitor.config, [monitor.mag, monitor.accel]));
+    writeln!(
+        w,
+        "Sensor health monitor {}, raised after {} s",
+        if config.enabled { "on" } else { "off" },
+        config.hold_s
+    )?;
+    let expected = [
+        (config.reference_nt as f32, config.mag_band_pct),
+        (ONE_G_MG, config.accel_band_pct),
+    ];
+    for (check, (expected, band)) in checks.iter().zip(expected.iter()) {
+        write!(w, "  {:<13} ", check.name)?;
+        if *expected == 0.0 {
+            writeln!(w, "no reference, see \"sensorhealth capture\"")?;
+            continue;
+        }
+        write!(
+            w,
+            "{}, expecting {} {} +/- {}%",
+            if check.state.raised() {
+                "degraded"
+            } else {
+                "ok"
+            },
+            *expected as i32,
+            check.unit,
+            band
+        )?;
+        match check.last {
+            Some(last) => writeln!(w, ", last {} {}", last as i32, check.unit)?,
+            None => writeln!(w)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOLD: u32 = 5 * heartbeat::TICK_HZ;
+
+    /// A reading every tick from `start`, outside the band wherever
+    /// `outside` says; the ticks at which the check was raised, and
+    /// whether it still is at the end.
+    fn run(start: u32, outside: impl IntoIterator<Item = bool>) -> (Vec<u32>, bool) {
+        let mut state = Hysteresis::new();
+        let mut raised_at = Vec::new();
+        for (i, outside) in outside.into_iter().enumerate() {
+            let now = start.wrapping_add(i as u32);
+            if state.update(outside, now, HOLD) {
+                raised_at.push(now);
+            }
+        }
+        (raised_at, state.raised())
+    }
+
+    fn ticks(outside: bool, n: u32) -> impl Iterator<Item = bool> {
+        (0..n).map(move |_| outside)
+    }
+
+    #[test]
+    fn a_transient_deviation_is_never_raised() {
+        let readings = ticks(false, 10)
+            .chain(ticks(true, HOLD))
+            .chain(ticks(false, 100));
+        assert_eq!(run(0, readings), (vec![], false));
+    }
+
+    #[test]
+    fn a_sustained_deviation_is_raised_once_after_the_hold() {
+        let readings = ticks(false, 10).chain(ticks(true, 100));
+        assert_eq!(run(0, readings), (vec![10 + HOLD], true));
+    }
+
+    #[test]
+    fn flapping_in_and_out_never_adds_up() {
+        let readings = (0..500).map(|i| i % 8 < 6);
+        assert_eq!(run(0, readings), (vec![], false));
+    }
+
+    #[test]
+    fn it_clears_once_back_inside_for_as_long() {
+        let mut state = Hysteresis::new();
+        for now in 0..=HOLD {
+            state.update(true, now, HOLD);
+        }
+        assert!(state.raised());
+        let back = HOLD + 1;
+        for now in back..back + HOLD {
+            assert!(!state.update(false, now, HOLD));
+            assert!(state.raised(), "cleared at {}", now);
+        }
+        // Clearing isn't raising
+        assert!(!state.update(false, back + HOLD, HOLD));
+        assert!(!state.raised());
+    }
+
+    #[test]
+    fn a_brief_return_inside_keeps_it_raised() {
+        let readings = ticks(true, HOLD + 1)
+            .chain(ticks(false, HOLD - 1))
+            .chain(ticks(true, 10));
+        assert_eq!(run(0, readings), (vec![HOLD], true));
+    }
+
+    #[test]
+    fn readings_far_apart_are_not_one_run() {
+        let mut state = Hysteresis::new();
+        let mut now = 0;
+        for _ in 0..10 {
+            assert!(!state.update(true, now, HOLD));
+            now += MAX_GAP_TICKS + 1;
+        }
+        assert!(!state.raised());
+        // Closer together they are, from the first of them
+        let run = now;
+        while now - run < HOLD {
+            assert!(!state.update(true, now, HOLD));
+            now += MAX_GAP_TICKS;
+        }
+        assert!(state.update(true, now, HOLD));
+    }
+
+    #[test]
+    fn the_clock_wrapping_around_changes_nothing() {
+        let readings = ticks(false, 10).chain(ticks(true, 100));
+        let start = u32::MAX - 20;
+        assert_eq!(
+            run(start, readings),
+            (vec![start.wrapping_add(10 + HOLD)], true)
+        );
+    }
+
+    #[test]
+    fn the_band_is_a_percentage_either_way() {
+        for (magnitude, outside) in [
+            (1000.0, false),
+            (1100.0, false),
+            (900.0, false),
+            (1100.5, true),
+            (899.5, true),
+            (0.0, true),
+        ] {
+            let mut check = Check::new("accelerometer", "mg");
+            for now in 0..=HOLD {
+                check.update(magnitude, ONE_G_MG, 10, now, HOLD);
+            }
+            assert_eq!(check.state.raised(), outside, "{}", magnitude);
+            assert_eq!(check.pending, outside);
+            assert_eq!(check.last, Some(magnitude));
+        }
+    }
+
+    #[test]
+    fn magnitude_is_the_length_of_the_vector() {
+        assert_eq!(magnitude([0, 0, 0]), 0.0);
+        assert_eq!(magnitude([3, -4, 0]), 5.0);
+        assert_eq!(magnitude([-2, 3, 6]), 7.0);
+    }
+
+    #[test]
+    fn bands_parse_within_their_ranges() {
+        assert_eq!(parse_bands("20 10 5"), Some((20, 10, 5)));
+        assert_eq!(parse_bands("100 100 60"), Some((100, 100, 60)));
+        for bad in [
+            "",
+            "20 10",
+            "0 10 5",
+            "20 101 5",
+            "20 10 61",
+            "20 10 5 1",
+            "a b c",
+        ] {
+            assert_eq!(parse_bands(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn config_survives_flash_and_defaults_what_doesnt_fit() {
+        let config = Config {
+            enabled: true,
+            mag_band_pct: 35,
+            accel_band_pct: 7,
+            hold_s: 60,
+            reference_nt: 48_000,
+        };
+        assert_eq!(Config::decode(config.encode()), config);
+        assert_eq!(
+            Config::decode([0xffff_ffff, 0]).hold_s,
+            Config::DEFAULT.hold_s
+        );
+        let erased = Config::decode([0xffff_ffff, 0xffff_ffff]);
+        assert_eq!(erased.mag_band_pct, Config::DEFAULT.mag_band_pct);
+        assert_eq!(erased.accel_band_pct, Config::DEFAULT.accel_band_pct);
+        assert_eq!(Config::decode([0, 0]), Config::DEFAULT);
+    }
+}
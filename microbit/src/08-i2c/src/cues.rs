@@ -0,0 +1,275 @@
+//! "audiocues": the board's feedback as sounds out of the v2's speaker, for
+//! anyone who can't see the LEDs.
+//!
+//! Every cue is what [`crate::feedback::signal`] is told, so it can't tell
+//! anything the LEDs don't. [`play`] only starts the first step and
+//! returns; the PWM0 interrupt at the end of each step starts the next,
+//! and stops the PWM after the last. A cue asked for while another one
+//! plays waits for it, and only the latest one waits, so a second error
+//! doesn't cut the first one's double beep short.
+//!
+//! "audiocues on" claims PWM0 and the speaker pin, see [`crate::claims`],
+//! and "audiocues off" gives them back. The v1 has no speaker, and
+//! "audiocues on" is an error there.
+
+use core::fmt;
+
+use crate::feedback::Event;
+
+pub const USAGE: &str = "audiocues on|off";
+
+/// Sound `event` if cues are on. Never waits for the speaker.
+#[cfg(feature = "v2")]
+pub fn play(event: Event) {
+    if let (true, Some(steps)) = (enabled(), tune::cue(event)) {
+        chip::play(steps);
+    }
+}
+
+/// Nothing to sound it on.
+#[cfg(feature = "v1")]
+pub fn play(_event: Event) {}
+
+/// What each event sounds like, for the v2's speaker.
+#[cfg(feature = "v2")]
+mod tune {
+    use crate::feedback::Event;
+
+    /// A tone, or a rest where `hz` is zero.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Step {
+        pub hz: u32,
+        pub ms: u32,
+    }
+
+    const fn tone(hz: u32, ms: u32) -> Step {
+        Step { hz, ms }
+    }
+
+    const fn rest(ms: u32) -> Step {
+        Step { hz: 0, ms }
+    }
+
+    const ACCEPTED: &[Step] = &[tone(2000, 40)];
+    const ERROR: &[Step] = &[tone(300, 80), rest(60), tone(300, 80)];
+    const RISING: &[Step] = &[tone(600, 30), tone(900, 30), tone(1300, 30), tone(2000, 30)];
+    const FALLING: &[Step] = &[tone(2000, 30), tone(1300, 30), tone(900, 30), tone(600, 30)];
+    const CLICK: &[Step] = &[tone(4000, 5)];
+    const CHIRP: &[Step] = &[tone(3000, 20), tone(3800, 20)];
+
+    /// What `event` sounds like, if anything.
+    pub fn cue(event: Event) -> Option<&'static [Step]> {
+        match event {
+            Event::Accepted => Some(ACCEPTED),
+            Event::Error => Some(ERROR),
+            Event::StreamStarted => Some(RISING),
+            Event::StreamStopped => Some(FALLING),
+            Event::Button => Some(CLICK),
+            Event::LowBattery => Some(CHIRP),
+            // The prompt coming back says as much
+            Event::Finished => None,
+        }
+    }
+}
+
+#[cfg(feature = "v2")]
+pub use chip::init;
+pub use chip::{enabled, set_enabled};
+
+/// One line for "status".
+pub fn status<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    writeln!(w, "audio cues: {}", if enabled() { "on" } else { "off" })
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    use core::fmt;
+
+    #[derive(Debug)]
+    pub struct Refused;
+
+    impl fmt::Display for Refused {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "no speaker on this board")
+        }
+    }
+
+    pub fn enabled() -> bool {
+        false
+    }
+
+    pub fn set_enabled(enabled: bool) -> Result<(), Refused> {
+        match enabled {
+            true => Err(Refused),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use microbit::hal::gpio::{p0::P0_00, Disconnected, Level};
+    use microbit::pac::{self, interrupt, PWM0};
+
+    use super::tune::Step;
+    use crate::claims::{self, InUseBy, Owner, Resource, Token};
+    use crate::power::Peripheral;
+    use crate::shared::Shared;
+
+    /// Whoever holds PWM0 or the speaker pin
+    pub type Refused = InUseBy;
+
+    const OWNER: Owner = Owner {
+        name: "audio cues",
+        release_with: Some("audiocues off"),
+    };
+    const SPEAKER_PIN: u8 = 0;
+
+    /// 16 MHz / 16
+    const CLOCK_HZ: u32 = 1_000_000;
+    /// The counter top for a rest, giving 1 ms periods
+    const REST_TOP: u32 = 1000;
+    /// The output goes low at the compare value, so zero keeps it low.
+    const FALLING_EDGE: u16 = 0x8000;
+
+    static ENABLED: AtomicBool = AtomicBool::new(false);
+    /// For PWM0 and the speaker pin, while on
+    static CLAIMED: Shared<Option<[Token; 2]>> = Shared::new(None);
+    static PLAYER: Shared<Player> = Shared::new(QUIET);
+
+    /// The one waveform value a step plays, repeated: the compare value for
+    /// the speaker's channel, two unused channels and the counter top. Only
+    /// written while the PWM is between steps.
+    static mut WAVE: [u16; 4] = [0; 4];
+
+    struct Player {
+        steps: &'static [Step],
+        next: usize,
+        /// Until the last step has played, not only started
+        playing: bool,
+        queued: Option<&'static [Step]>,
+    }
+
+    const QUIET: Player = Player {
+        steps: &[],
+        next: 0,
+        playing: false,
+        queued: None,
+    };
+
+    impl Player {
+        /// The step to play now, or `None` to stop.
+        fn advance(&mut self) -> Option<Step> {
+            if self.next == self.steps.len() {
+                self.steps = self.queued.take().unwrap_or(&[]);
+                self.next = 0;
+            }
+            let step = self.steps.get(self.next).copied();
+            self.next += 1;
+            self.playing = step.is_some();
+            step
+        }
+    }
+
+    fn pwm() -> &'static pac::pwm0::RegisterBlock {
+        unsafe { &*PWM0::ptr() }
+    }
+
+    /// Take the PWM and the speaker pin, and set them up. Cues stay off
+    /// until [`set_enabled`], which claims them.
+    pub fn init(pwm0: PWM0, speaker: P0_00<Disconnected>) {
+        let pin = speaker.into_push_pull_output(Level::Low).degrade();
+        // CONNECT clear
+        pwm0.psel.out[0].write(|w| unsafe { w.bits(pin.psel_bits()) });
+        pwm0.mode.write(|w| w.updown().up());
+        pwm0.prescaler.write(|w| w.prescaler().div_16());
+        pwm0.decoder
+            .write(|w| w.load().wave_form().mode().refresh_count());
+        pwm0.loop_.write(|w| unsafe { w.bits(0) });
+        pwm0.seq0
+            .ptr
+            .write(|w| unsafe { w.bits(core::ptr::addr_of!(WAVE) as u32) });
+        pwm0.seq0.cnt.write(|w| unsafe { w.bits(4) });
+        pwm0.seq0.enddelay.write(|w| unsafe { w.bits(0) });
+        pwm0.intenset.write(|w| w.seqend0().set());
+        unsafe { pac::NVIC::unmask(pac::Interrupt::PWM0) };
+    }
+
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(enabled: bool) -> Result<(), Refused> {
+        if enabled == self::enabled() {
+            return Ok(());
+        }
+        if enabled {
+            let peripheral = claims::claim(Resource::Peripheral(Peripheral::Pwm0), OWNER)?;
+            let pin = match claims::claim(Resource::Pin(SPEAKER_PIN), OWNER) {
+                Ok(pin) => pin,
+                Err(err) => {
+                    claims::release(peripheral);
+                    return Err(err);
+                }
+            };
+            CLAIMED.with(|claimed| *claimed = Some([peripheral, pin]));
+            pwm().enable.write(|w| w.enable().enabled());
+            ENABLED.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+        ENABLED.store(false, Ordering::Relaxed);
+        PLAYER.with(|player| {
+            // Cut short, the interrupt that would have finished it may
+            // never come
+            pwm().tasks_stop.write(|w| unsafe { w.bits(1) });
+            pwm().enable.write(|w| w.enable().disabled());
+            *player = QUIET;
+        });
+        if let Some([peripheral, pin]) = CLAIMED.with(|claimed| claimed.take()) {
+            claims::release(peripheral);
+            claims::release(pin);
+        }
+        Ok(())
+    }
+
+    pub fn play(steps: &'static [Step]) {
+        let first = PLAYER.with(|player| {
+            if player.playing {
+                player.queued = Some(steps);
+                return None;
+            }
+            player.steps = steps;
+            player.next = 0;
+            player.advance()
+        });
+        // Quiet until now, so the interrupt can't be starting a step too
+        if let Some(step) = first {
+            start(step);
+        }
+    }
+
+    fn start(step: Step) {
+        let (top, compare, periods) = match step.hz {
+            0 => (REST_TOP, 0, step.ms),
+            hz => (CLOCK_HZ / hz, CLOCK_HZ / hz / 2, hz * step.ms / 1000),
+        };
+        unsafe { WAVE = [compare as u16 | FALLING_EDGE, 0, 0, top as u16] };
+        let pwm = pwm();
+        // One period to begin with, the rest repeats
+        pwm.seq0
+            .refresh
+            .write(|w| unsafe { w.bits(periods.max(1) - 1) });
+        pwm.tasks_seqstart[0].write(|w| unsafe { w.bits(1) });
+    }
+
+    #[interrupt]
+    fn PWM0() {
+        let pwm = pwm();
+        pwm.events_seqend[0].reset();
+        match PLAYER.with(|player| player.advance()) {
+            Some(step) => start(step),
+            None => pwm.tasks_stop.write(|w| unsafe { w.bits(1) }),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! "demo": a guided tour of the board for showing it around.
+//!
+//! The tour is a table of [`Segment`]s that [`play`] plays one after the
+//! other, skipping to the next when button A is pressed, see
+//! [`crate::tour`]. The frames themselves are drawn by the functions below,
+//! out of plain readings, the ones with lines and shapes in them on a
+//! [`Canvas`]. Animations move on once per heartbeat tick, the finest clock
+//! there is.
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle};
+
+use crate::blinkout;
+use crate::canvas::Canvas;
+pub use crate::display::Image;
+pub use crate::tour::{play, Segment, Stage};
+
+const BLANK: Image = [[0; 5]; 5];
+const LIT: u8 = 9;
+
+/// "HELLO", a column at a time, the top row in bit 0.
+const GREETING: [u8; 19] = [
+    0x1f, 0x04, 0x1f, 0x00, // H
+    0x1f, 0x15, 0x11, 0x00, // E
+    0x1f, 0x10, 0x10, 0x00, // L
+    0x1f, 0x10, 0x10, 0x00, // L
+    0x0e, 0x11, 0x0e, // O
+];
+const SCROLL_STEP_MS: u32 = 125;
+/// Long enough for the greeting to scroll in from the right and out to
+/// the left once.
+pub const GREETING_MS: u32 = (GREETING.len() as u32 + 5) * SCROLL_STEP_MS;
+
+pub fn greeting(elapsed_ms: u32) -> Image {
+    // The text starts just off the right edge
+    let offset = (elapsed_ms / SCROLL_STEP_MS) as usize % (GREETING.len() + 5);
+    let mut image = BLANK;
+    for col in 0..5 {
+        let column = (offset + col)
+            .checked_sub(5)
+            .and_then(|i| GREETING.get(i))
+            .copied()
+            .unwrap_or(0);
+        for (row, line) in image.iter_mut().enumerate() {
+            if column & (1 << row) != 0 {
+                line[col] = LIT;
+            }
+        }
+    }
+    image
+}
+
+/// The LED running round the edge.
+pub fn roulette(elapsed_ms: u32) -> Image {
+    crate::roulette::frame(elapsed_ms, LIT)
+}
+
+/// Degrees of tilt, in hundredths, per LED the bubble moves away from the
+/// middle.
+const BUBBLE_STEP: i32 = 10_00;
+
+/// A spirit level: the bubble stays in the middle while the board lies
+/// flat and drifts towards whichever edge is raised.
+pub fn bubble(roll: i32, pitch: i32) -> Image {
+    let offset = |angle: i32| (2 + angle / BUBBLE_STEP).clamp(0, 4);
+    let mut canvas = Canvas::default();
+    Pixel(Point::new(offset(-roll), offset(pitch)), BinaryColor::On)
+        .draw(&mut canvas)
+        .unwrap();
+    canvas.image()
+}
+
+/// A needle from the middle out to the edge, towards magnetic north, out
+/// of the horizontal components of the field.
+pub fn needle(x: i32, y: i32) -> Image {
+    let middle = Point::new(2, 2);
+    let longest = x.abs().max(y.abs());
+    let tip = if longest == 0 {
+        middle
+    } else {
+        let reach = |value: i32| (2 * value + longest / 2 * value.signum()) / longest;
+        // Up on the display is +y
+        middle + Point::new(reach(x), -reach(y))
+    };
+    let mut canvas = Canvas::default();
+    Line::new(middle, tip)
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(&mut canvas)
+        .unwrap();
+    canvas.image()
+}
+
+/// The temperature as "blinkout" shows it, over and over.
+pub fn temperature(celsius: i32, elapsed_ms: u32) -> Image {
+    let value = celsius.unsigned_abs().min(blinkout::MAX_VALUE as u32) as u16;
+    let steps = blinkout::schedule(value);
+    let round_ms: u32 =
+        steps.iter().map(|step| step.ms as u32).sum::<u32>() + blinkout::ROUND_GAP_MS as u32;
+    let mut at = elapsed_ms % round_ms;
+    for step in &steps {
+        if at < step.ms as u32 {
+            return if step.on { [[LIT; 5]; 5] } else { BLANK };
+        }
+        at -= step.ms as u32;
+    }
+    BLANK
+}
@@ -0,0 +1,123 @@
+//! The LED matrix, refreshed from the TIMER1 interrupt so that nothing in
+//! the command loop ever has to stop and drive it.
+//!
+//! What is shown comes in three layers, kept apart and only combined when
+//! the frame is handed to the display driver, so none of them ever has to
+//! know about the others: the background, owned by whatever the command
+//! loop is running; the overlay, for marks that have to stay put while the
+//! background changes under them; and the status LEDs on top of both. The
+//! brightness setting is applied at the same point.
+
+use cortex_m::interrupt::{free, CriticalSection};
+use microbit::display::nonblocking::{Display, GreyscaleImage};
+use microbit::gpio::DisplayPins;
+use microbit::pac::{self, interrupt, TIMER1};
+
+use crate::claims::{self, Owner, Resource};
+use crate::frame::Layers;
+use crate::irqstats::{self, Irq};
+use crate::power::Peripheral;
+use crate::shared::Shared;
+use crate::status::{Role, StatusLeds};
+
+pub use crate::frame::{Image, MAX_BRIGHTNESS};
+
+static DISPLAY: Shared<Option<Display<TIMER1>>> = Shared::new(None);
+static LAYERS: Shared<Layers> = Shared::new(Layers::new());
+
+pub fn init(timer: TIMER1, pins: DisplayPins) {
+    claims::take(
+        Resource::Peripheral(Peripheral::Timer1),
+        Owner::permanent("the display"),
+    );
+    let display = Display::new(timer, pins);
+    DISPLAY.with(|slot| *slot = Some(display));
+    unsafe { pac::NVIC::unmask(pac::Interrupt::TIMER1) };
+}
+
+fn refresh(cs: &CriticalSection) {
+    let frame = LAYERS.with_cs(cs, |layers| layers.frame());
+    DISPLAY.with_cs(cs, |display| {
+        if let Some(display) = display {
+            display.show(&GreyscaleImage::new(&frame));
+        }
+    });
+}
+
+pub fn set_background(image: &Image) {
+    free(|cs| {
+        LAYERS.with_cs(cs, |layers| layers.background = *image);
+        refresh(cs);
+    });
+}
+
+/// Light the overlay pixel at `row`, `col` at `value`, or clear it with 0.
+// Only the demo draws on the overlay so far
+#[cfg_attr(not(feature = "demo"), allow(dead_code))]
+pub fn set_overlay_pixel(row: usize, col: usize, value: u8) {
+    free(|cs| {
+        LAYERS.with_cs(cs, |layers| layers.overlay[row][col] = value);
+        refresh(cs);
+    });
+}
+
+#[cfg_attr(not(feature = "demo"), allow(dead_code))]
+pub fn clear_overlay() {
+    free(|cs| {
+        LAYERS.with_cs(cs, |layers| layers.overlay = [[0; 5]; 5]);
+        refresh(cs);
+    });
+}
+
+/// Refresh at half the rate, or back at the driver's own. The driver runs
+/// TIMER1 at 62.5 kHz, one more step of prescaler slows every row and
+/// every greyscale step in it alike.
+pub fn set_slow_refresh(slow: bool) {
+    let timer = unsafe { &*TIMER1::ptr() };
+    let prescaler = if slow { 9 } else { 8 };
+    free(|_| {
+        // The prescaler only changes while the timer is stopped
+        timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+        timer.prescaler.write(|w| unsafe { w.bits(prescaler) });
+        timer.tasks_start.write(|w| unsafe { w.bits(1) });
+    });
+}
+
+pub fn set_brightness(brightness: u8) {
+    free(|cs| {
+        LAYERS.with_cs(cs, |layers| layers.brightness = brightness);
+        refresh(cs);
+    });
+}
+
+/// Off shows the driver's levels as they are, for comparison.
+pub fn set_gamma(gamma: bool) {
+    free(|cs| {
+        LAYERS.with_cs(cs, |layers| layers.gamma = gamma);
+        refresh(cs);
+    });
+}
+
+pub fn set_status_leds(leds: StatusLeds) {
+    free(|cs| {
+        LAYERS.with_cs(cs, |layers| layers.leds = leds);
+        refresh(cs);
+    });
+}
+
+pub fn set_status(role: Role, active: bool) {
+    free(|cs| {
+        LAYERS.with_cs(cs, |layers| layers.active[role.index()] = active);
+        refresh(cs);
+    });
+}
+
+#[interrupt]
+fn TIMER1() {
+    irqstats::enter(Irq::Timer1);
+    DISPLAY.with(|display| {
+        if let Some(display) = display {
+            display.handle_display_event();
+        }
+    });
+}
@@ -0,0 +1,201 @@
+//! Sleeping until the LSM303AGR has a sample, instead of asking it.
+//!
+//! Each half of the sensor can raise a pin when it has a new sample: the
+//! accelerometer's INT1 with I1_ZYXDA in CTRL_REG3_A, the magnetometer's
+//! INT_MAG/DRDY with INT_MAG in CFG_REG_C_M. Both go back down once the
+//! sample is read. [`init`] has GPIOTE catch the rising edges, and a wait
+//! for a sample checks the status register only once its line says there
+//! is something to read, with [`ready`], and otherwise [`sleep`]s with
+//! `wfi`. The other interrupts wake it too, the display refresh most often,
+//! which is when the wait looks at Ctrl-C, the watchdog and its timeout.
+//!
+//! The driver writes CFG_REG_C_M whole when it starts, so the bus adds
+//! INT_MAG to that write on its way, see [`routed`]; CTRL_REG3_A is left
+//! alone by the driver and written before it starts. On the v1 the two
+//! lines have a pin each. On the v2 they share one, and a sample nobody
+//! reads holds it up: while waiting for the magnetometer, the
+//! accelerometer's samples are read and dropped to let it go.
+//!
+//! The handler logs on RTT how many times the wait slept before a sample
+//! came in, which is the idle time between samples made visible. With the
+//! "poll-status" feature, for a board without the lines, none of this is
+//! built and a wait polls the status register as fast as the bus goes.
+
+use crate::poller::Device;
+
+/// The accelerometer half's address, and where INT1 gets its sources
+pub const ACCEL_ADDRESS: u8 = 0x19;
+pub const CTRL_REG3_A: u8 = 0x22;
+/// The accelerometer's data-ready on INT1
+pub const I1_ZYXDA: u8 = 1 << 4;
+
+#[cfg(not(feature = "poll-status"))]
+pub use lines::{init, ready, routed, sleep, SHARED};
+
+#[cfg(not(feature = "poll-status"))]
+mod lines {
+    use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+    use microbit::pac::{self, interrupt};
+
+    use super::{chip, Device};
+    use crate::claims::{self, Owner, Resource};
+    use crate::log::log;
+
+    pub use chip::SHARED;
+
+    /// The magnetometer half's address, and the register with INT_MAG
+    const MAG_ADDRESS: u8 = 0x1e;
+    const CFG_REG_C_M: u8 = 0x62;
+    /// The magnetometer's data-ready on INT_MAG/DRDY
+    const INT_MAG: u8 = 1 << 0;
+
+    const OWNER: Owner = Owner::permanent("the sensor's data-ready lines");
+
+    /// CONFIG: event mode, on a rising edge
+    const MODE_EVENT: u32 = 1;
+    const PSEL_SHIFT: u32 = 8;
+    const POLARITY_LO_TO_HI: u32 = 1 << 16;
+
+    /// One bit per GPIOTE channel whose line rose since [`ready`] last
+    /// looked
+    static ROSE: AtomicU8 = AtomicU8::new(0);
+    /// Times [`sleep`] slept since the last edge
+    static SLEEPS: AtomicU32 = AtomicU32::new(0);
+
+    fn gpiote() -> &'static pac::gpiote::RegisterBlock {
+        unsafe { &*pac::GPIOTE::ptr() }
+    }
+
+    /// The GPIOTE channel watching `device`'s line, and its pin.
+    fn line(device: Device) -> (usize, usize) {
+        match device {
+            Device::Accel => chip::ACCEL_LINE,
+            Device::Mag => chip::MAG_LINE,
+        }
+    }
+
+    /// Catch the lines' rising edges. The pins are inputs; the sensor
+    /// drives them both ways.
+    pub fn init() {
+        let gpiote = gpiote();
+        let gpio = chip::gpio();
+        for &(channel, pin) in chip::LINES {
+            claims::take(Resource::Pin(pin as u8), OWNER);
+            gpio.pin_cnf[pin].write(|w| {
+                w.dir().input();
+                w.input().connect();
+                w.pull().disabled()
+            });
+            gpiote.config[channel].write(|w| unsafe {
+                w.bits(MODE_EVENT | (pin as u32) << PSEL_SHIFT | POLARITY_LO_TO_HI)
+            });
+            gpiote.events_in[channel].reset();
+            gpiote.intenset.write(|w| unsafe { w.bits(1 << channel) });
+        }
+        unsafe { pac::NVIC::unmask(pac::Interrupt::GPIOTE) };
+    }
+
+    /// What to send instead of `bytes` to the sensor at `address`: the
+    /// driver's CFG_REG_C_M with INT_MAG added.
+    pub fn routed(address: u8, bytes: &[u8]) -> Option<[u8; 2]> {
+        match *bytes {
+            [CFG_REG_C_M, value] if address == MAG_ADDRESS => Some([CFG_REG_C_M, value | INT_MAG]),
+            _ => None,
+        }
+    }
+
+    /// Whether `device`'s line rose since the last time, or is still up.
+    pub fn ready(device: Device) -> bool {
+        let (channel, pin) = line(device);
+        let bit = 1 << channel;
+        let rose = cortex_m::interrupt::free(|_| {
+            let rose = ROSE.load(Ordering::Relaxed);
+            ROSE.store(rose & !bit, Ordering::Relaxed);
+            rose & bit != 0
+        });
+        rose || chip::gpio().in_.read().bits() & (1 << pin) != 0
+    }
+
+    /// Sleep until the next interrupt, unless `device`'s line rose already.
+    pub fn sleep(device: Device) {
+        let bit = 1 << line(device).0;
+        // With interrupts off, an edge right before the wfi still ends it,
+        // and its handler runs right after
+        cortex_m::interrupt::free(|_| {
+            if ROSE.load(Ordering::Relaxed) & bit == 0 {
+                SLEEPS.store(SLEEPS.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+                cortex_m::asm::wfi();
+            }
+        });
+    }
+
+    #[interrupt]
+    fn GPIOTE() {
+        let gpiote = gpiote();
+        for &(channel, _) in chip::LINES {
+            if gpiote.events_in[channel].read().bits() == 0 {
+                continue;
+            }
+            gpiote.events_in[channel].reset();
+            ROSE.store(
+                ROSE.load(Ordering::Relaxed) | 1 << channel,
+                Ordering::Relaxed,
+            );
+        }
+        let sleeps = SLEEPS.load(Ordering::Relaxed);
+        if sleeps != 0 {
+            SLEEPS.store(0, Ordering::Relaxed);
+            log!("drdy: sample ready after {} sleeps", sleeps);
+        }
+    }
+}
+
+/// Without the lines every wait goes straight to the status register.
+#[cfg(feature = "poll-status")]
+pub const SHARED: bool = false;
+
+#[cfg(feature = "poll-status")]
+pub fn init() {}
+
+#[cfg(feature = "poll-status")]
+pub fn routed(_address: u8, _bytes: &[u8]) -> Option<[u8; 2]> {
+    None
+}
+
+#[cfg(feature = "poll-status")]
+pub fn ready(_device: Device) -> bool {
+    true
+}
+
+#[cfg(feature = "poll-status")]
+pub fn sleep(_device: Device) {}
+
+/// The GPIOTE channel and the pin for each line, as on the schematics.
+#[cfg(all(feature = "v1", not(feature = "poll-status")))]
+mod chip {
+    use microbit::pac;
+
+    pub const ACCEL_LINE: (usize, usize) = (0, 28);
+    pub const MAG_LINE: (usize, usize) = (1, 29);
+    pub const LINES: &[(usize, usize)] = &[ACCEL_LINE, MAG_LINE];
+    pub const SHARED: bool = false;
+
+    pub fn gpio() -> &'static pac::gpio::RegisterBlock {
+        unsafe { &*pac::GPIO::ptr() }
+    }
+}
+
+/// Both halves on the combined sensor interrupt, P0.25.
+#[cfg(all(feature = "v2", not(feature = "poll-status")))]
+mod chip {
+    use microbit::pac;
+
+    pub const ACCEL_LINE: (usize, usize) = (0, 25);
+    pub const MAG_LINE: (usize, usize) = ACCEL_LINE;
+    pub const LINES: &[(usize, usize)] = &[ACCEL_LINE];
+    pub const SHARED: bool = true;
+
+    pub fn gpio() -> &'static pac::p0::RegisterBlock {
+        unsafe { &*pac::P0::ptr() }
+    }
+}
@@ -0,0 +1,70 @@
+//! Sensor errors that end a command instead of the firmware.
+//!
+//! A NACK or a glitch on the sensor's bus comes back from the driver as an
+//! error like any other; the handler stops with [`Error`], which is printed
+//! and leaves the shell at the prompt. One such error is most likely a
+//! glitch, but [`RESTART_AFTER`] in a row with nothing read in between
+//! means the sensor has lost its settings or the bus is wedged, and the
+//! command loop starts the driver over. If that fails too the sensor is
+//! unavailable until the next reset.
+//!
+//! A sensor that answers but never has a sample, powered down or set up
+//! wrong, stops the command with [`NoData`] instead once a sample is
+//! [`NO_DATA_MS`] late; that counts towards a restart as well.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::bus::BusError;
+
+/// Failed commands in a row before the driver is started over
+pub const RESTART_AFTER: u8 = 5;
+
+/// How much longer than a sample period a sample may take
+pub const NO_DATA_MS: u32 = 500;
+
+/// Commands the sensor failed since it last had a sample
+static CONSECUTIVE: AtomicU8 = AtomicU8::new(0);
+
+/// What the driver said, wrapping what the bus said.
+#[derive(Debug)]
+pub struct Error<E>(pub lsm303agr::Error<BusError<E>, ()>);
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            lsm303agr::Error::Comm(BusError::Bus(err)) => write!(f, "sensor: bus error: {:?}", err),
+            lsm303agr::Error::Comm(BusError::TimedOut) => write!(f, "sensor: timed out"),
+            lsm303agr::Error::Pin(()) => write!(f, "sensor: pin error"),
+            lsm303agr::Error::InvalidInputData => write!(f, "sensor: invalid input data"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NoData;
+
+impl fmt::Display for NoData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sensor timed out waiting for data")
+    }
+}
+
+/// A sample came in, so whatever failed before is over.
+pub fn sampled() {
+    CONSECUTIVE.store(0, Ordering::Relaxed);
+}
+
+/// A command stopped on a sensor error. Whether it's time to start the
+/// driver over, which also starts the count over.
+pub fn failed() -> bool {
+    // The nRF51 has no atomic read-modify-write, but only the command loop
+    // counts
+    let count = CONSECUTIVE.load(Ordering::Relaxed) + 1;
+    if count < RESTART_AFTER {
+        CONSECUTIVE.store(count, Ordering::Relaxed);
+        return false;
+    }
+    CONSECUTIVE.store(0, Ordering::Relaxed);
+    true
+}
@@ -0,0 +1,39 @@
+//! What the board tells about itself without the serial port.
+//!
+//! The command loop and the handlers [`signal`] an [`Event`] rather than
+//! setting a status LED themselves. The LEDs show what they can, and
+//! [`cues`] sounds it with "audiocues on", so the two can't drift apart:
+//! an event one of them should tell about belongs here.
+
+use crate::status::Role;
+use crate::{cues, display};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// A command started
+    Accepted,
+    /// A command that doesn't stream finished
+    Finished,
+    /// Printed, the error LED stays on until the next command
+    Error,
+    StreamStarted,
+    StreamStopped,
+    /// A button press that did something
+    Button,
+    /// A supply sample below the warning threshold, see [`crate::battery`]
+    LowBattery,
+}
+
+pub fn signal(event: Event) {
+    match event {
+        Event::Accepted | Event::StreamStarted => {
+            display::set_status(Role::Error, false);
+            display::set_status(Role::Activity, true);
+        }
+        Event::Finished | Event::StreamStopped => display::set_status(Role::Activity, false),
+        Event::Error => display::set_status(Role::Error, true),
+        // The display changing shows a press, the heartbeat a low battery
+        Event::Button | Event::LowBattery => {}
+    }
+    cues::play(event);
+}
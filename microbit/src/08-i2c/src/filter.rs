@@ -0,0 +1,283 @@
+//! Smoothing for accelerometer readings, applied per axis.
+//!
+//! A moving average gets rid of noise but smears short spikes out over the
+//! whole window. A running median keeps spikes that last longer than half
+//! the window and drops the shorter glitches. Until the window has filled
+//! up, both work on the samples seen so far.
+
+use core::fmt;
+
+/// The longest window any filter kind accepts.
+const MAX_WINDOW: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Off,
+    Average(u8),
+    Median(u8),
+}
+
+pub const USAGE: &str = "filter off|average <2-8>|median <3|5>";
+
+impl Kind {
+    /// `None` for window sizes the kind doesn't support.
+    pub fn average(n: u8) -> Option<Kind> {
+        if (2..=MAX_WINDOW as u8).contains(&n) {
+            Some(Kind::Average(n))
+        } else {
+            None
+        }
+    }
+
+    pub fn median(n: u8) -> Option<Kind> {
+        match n {
+            3 | 5 => Some(Kind::Median(n)),
+            _ => None,
+        }
+    }
+
+    fn window(self) -> usize {
+        match self {
+            Kind::Off => 1,
+            Kind::Average(n) | Kind::Median(n) => n as usize,
+        }
+    }
+
+    /// Packed into one word for the settings record.
+    pub fn encode(self) -> u32 {
+        match self {
+            Kind::Off => 0,
+            Kind::Average(n) => 0x100 | n as u32,
+            Kind::Median(n) => 0x200 | n as u32,
+        }
+    }
+
+    /// Anything unrecognized decodes as [`Kind::Off`].
+    pub fn decode(word: u32) -> Kind {
+        let n = word as u8;
+        match word >> 8 {
+            1 => Kind::average(n),
+            2 => Kind::median(n),
+            _ => None,
+        }
+        .unwrap_or(Kind::Off)
+    }
+}
+
+/// The command that selects this kind, as used by "config export".
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Off => write!(f, "filter off"),
+            Kind::Average(n) => write!(f, "filter average {}", n),
+            Kind::Median(n) => write!(f, "filter median {}", n),
+        }
+    }
+}
+
+/// One axis worth of history.
+#[derive(Clone, Copy)]
+struct Window {
+    /// Samples in arrival order, `next` is the oldest once full.
+    ring: [i32; MAX_WINDOW],
+    /// The same samples, kept sorted by insertion.
+    sorted: [i32; MAX_WINDOW],
+    len: usize,
+    next: usize,
+    sum: i64,
+}
+
+impl Window {
+    const EMPTY: Window = Window {
+        ring: [0; MAX_WINDOW],
+        sorted: [0; MAX_WINDOW],
+        len: 0,
+        next: 0,
+        sum: 0,
+    };
+
+    fn push(&mut self, sample: i32, size: usize) {
+        if self.len == size {
+            let oldest = self.ring[self.next];
+            self.sum -= oldest as i64;
+            let at = self.sorted[..self.len]
+                .iter()
+                .position(|&s| s == oldest)
+                .unwrap();
+            self.sorted.copy_within(at + 1..self.len, at);
+            self.len -= 1;
+        }
+        self.ring[self.next] = sample;
+        self.next = (self.next + 1) % size;
+        self.sum += sample as i64;
+
+        let at = self.sorted[..self.len]
+            .iter()
+            .position(|&s| s > sample)
+            .unwrap_or(self.len);
+        self.sorted.copy_within(at..self.len, at + 1);
+        self.sorted[at] = sample;
+        self.len += 1;
+    }
+
+    fn average(&self) -> i32 {
+        (self.sum / self.len as i64) as i32
+    }
+
+    /// The lower of the two middle samples for an even count.
+    fn median(&self) -> i32 {
+        self.sorted[(self.len - 1) / 2]
+    }
+}
+
+pub struct Filter {
+    kind: Kind,
+    axes: [Window; 3],
+}
+
+impl Filter {
+    pub fn new(kind: Kind) -> Filter {
+        Filter {
+            kind,
+            axes: [Window::EMPTY; 3],
+        }
+    }
+
+    /// Feed one sample per axis and get the filtered values back.
+    pub fn apply(&mut self, sample: [i32; 3]) -> [i32; 3] {
+        let kind = self.kind;
+        let mut out = sample;
+        for (window, out) in self.axes.iter_mut().zip(out.iter_mut()) {
+            window.push(*out, kind.window());
+            *out = match kind {
+                Kind::Off => *out,
+                Kind::Average(_) => window.average(),
+                Kind::Median(_) => window.median(),
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A repeatable run of samples with glitches in it, from a linear
+    /// congruential generator
+    fn samples(count: usize) -> Vec<i32> {
+        let mut state: u32 = 12345;
+        (0..count)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                let sample = (state >> 16) as i32 % 2000 - 1000;
+                // Repeats, so equal samples get in and out of the window
+                if state >> 29 == 0 {
+                    0
+                } else {
+                    sample
+                }
+            })
+            .collect()
+    }
+
+    /// The last `n` samples up to and including `i`, or all of them while
+    /// there are fewer
+    fn window(samples: &[i32], i: usize, n: usize) -> Vec<i32> {
+        samples[(i + 1).saturating_sub(n)..=i].to_vec()
+    }
+
+    fn brute_median(mut window: Vec<i32>) -> i32 {
+        window.sort_unstable();
+        window[(window.len() - 1) / 2]
+    }
+
+    fn brute_average(window: Vec<i32>) -> i32 {
+        (window.iter().map(|&s| s as i64).sum::<i64>() / window.len() as i64) as i32
+    }
+
+    /// Feed `samples` to all three axes, shifted apart, and compare every
+    /// output with `expected` for the same window
+    fn check(kind: Kind, n: usize, expected: fn(Vec<i32>) -> i32) {
+        let samples = samples(200);
+        let mut filter = Filter::new(kind);
+        for i in 0..samples.len() {
+            let out = filter.apply([samples[i], samples[i] + 1, -samples[i]]);
+            let window = window(&samples, i, n);
+            let negated = window.iter().map(|&s| -s).collect();
+            let shifted = window.iter().map(|&s| s + 1).collect();
+            assert_eq!(
+                out,
+                [expected(window), expected(shifted), expected(negated)],
+                "{:?} at sample {}",
+                kind,
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn median_matches_sorting_the_window() {
+        check(Kind::Median(3), 3, brute_median);
+        check(Kind::Median(5), 5, brute_median);
+    }
+
+    #[test]
+    fn median_while_the_window_fills() {
+        let mut filter = Filter::new(Kind::Median(5));
+        let outputs: Vec<i32> = [50, 10, 40, 20, 30, 0]
+            .iter()
+            .map(|&s| filter.apply([s; 3])[0])
+            .collect();
+        // The lower middle of 50 10, then 10 40 50, and so on
+        assert_eq!(outputs, [50, 10, 40, 20, 30, 20]);
+    }
+
+    #[test]
+    fn median_drops_a_single_glitch() {
+        let mut filter = Filter::new(Kind::Median(3));
+        let outputs: Vec<i32> = [1000, 1000, 9000, 1000, 1000]
+            .iter()
+            .map(|&s| filter.apply([s; 3])[0])
+            .collect();
+        assert_eq!(outputs, [1000, 1000, 1000, 1000, 1000]);
+    }
+
+    #[test]
+    fn average_matches_summing_the_window() {
+        for n in 2..=MAX_WINDOW as u8 {
+            check(Kind::Average(n), n as usize, brute_average);
+        }
+    }
+
+    #[test]
+    fn off_passes_samples_through() {
+        let mut filter = Filter::new(Kind::Off);
+        for &sample in &samples(20) {
+            assert_eq!(filter.apply([sample, 1, -sample]), [sample, 1, -sample]);
+        }
+    }
+
+    #[test]
+    fn kinds_only_take_the_windows_they_support() {
+        assert_eq!(Kind::average(1), None);
+        assert_eq!(Kind::average(9), None);
+        assert_eq!(Kind::median(4), None);
+        assert_eq!(Kind::median(5), Some(Kind::Median(5)));
+    }
+
+    #[test]
+    fn kinds_survive_the_settings_record() {
+        for kind in [
+            Kind::Off,
+            Kind::Average(2),
+            Kind::Average(8),
+            Kind::Median(3),
+            Kind::Median(5),
+        ] {
+            assert_eq!(Kind::decode(kind.encode()), kind);
+        }
+        assert_eq!(Kind::decode(0x204), Kind::Off);
+        assert_eq!(Kind::decode(0xffff_ffff), Kind::Off);
+    }
+}
@@ -0,0 +1,96 @@
+//! Just enough of an NVMC driver to keep a few pages of data across resets.
+//!
+//! `memory.x` keeps the last 24K of the 256K we link for out of the `FLASH`
+//! region, so the linker never places code there. Pages are numbered down
+//! from the top of that area, so page 0 is the last page of flash on both
+//! chips; the nRF51 (1K pages) leaves most of the area unused.
+
+use core::fmt;
+use microbit::pac;
+
+use crate::pof;
+
+/// The address of page 0.
+pub const PAGE_ADDR: u32 = 0x0003_f000;
+pub const PAGES: usize = 6;
+
+#[cfg(feature = "v1")]
+pub const PAGE_SIZE: usize = 1024;
+#[cfg(feature = "v2")]
+pub const PAGE_SIZE: usize = 4096;
+
+/// Returned instead of erasing or writing while the supply is too low for it
+/// to be safe, see [`pof`].
+#[derive(Debug)]
+pub struct LowPower;
+
+impl fmt::Display for LowPower {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "supply voltage low, flash not written")
+    }
+}
+
+fn nvmc() -> &'static pac::nvmc::RegisterBlock {
+    unsafe { &*pac::NVMC::ptr() }
+}
+
+fn wait_ready() {
+    while nvmc().ready.read().ready().bit_is_clear() {}
+}
+
+fn page_addr(page: usize) -> u32 {
+    assert!(page < PAGES);
+    PAGE_ADDR - (page * PAGE_SIZE) as u32
+}
+
+fn word_ptr(page: usize, offset: usize) -> *mut u32 {
+    (page_addr(page) as usize + offset * 4) as *mut u32
+}
+
+/// Read `words.len()` words starting `offset` words into `page`.
+pub fn read(page: usize, offset: usize, words: &mut [u32]) {
+    assert!((offset + words.len()) * 4 <= PAGE_SIZE);
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = unsafe { core::ptr::read_volatile(word_ptr(page, offset + i)) };
+    }
+}
+
+/// Erase `page` back to all ones. The CPU stalls while this happens.
+pub fn erase(page: usize) -> Result<(), LowPower> {
+    if !pof::flash_allowed() {
+        return Err(LowPower);
+    }
+    nvmc().config.write(|w| w.wen().een());
+    wait_ready();
+    nvmc()
+        .erasepage()
+        .write(|w| unsafe { w.bits(page_addr(page)) });
+    wait_ready();
+    nvmc().config.write(|w| w.wen().ren());
+    wait_ready();
+    Ok(())
+}
+
+/// Program words into `page`, which can only ever clear bits.
+pub fn write(page: usize, offset: usize, words: &[u32]) -> Result<(), LowPower> {
+    assert!((offset + words.len()) * 4 <= PAGE_SIZE);
+    if !pof::flash_allowed() {
+        return Err(LowPower);
+    }
+    nvmc().config.write(|w| w.wen().wen());
+    wait_ready();
+    for (i, word) in words.iter().enumerate() {
+        unsafe { core::ptr::write_volatile(word_ptr(page, offset + i), *word) };
+        wait_ready();
+    }
+    nvmc().config.write(|w| w.wen().ren());
+    wait_ready();
+    Ok(())
+}
+
+/// The checksum closing every record kept in flash.
+pub fn checksum(words: &[u32]) -> u32 {
+    words.iter().fold(0x811c_9dc5, |sum, word| {
+        (sum ^ word).wrapping_mul(0x0100_0193)
+    })
+}
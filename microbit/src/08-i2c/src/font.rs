@@ -0,0 +1,96 @@
+//! A 3x5 font for printable ASCII, for scrolling text across the display.
+//!
+//! Each glyph is three columns, the top row in bit 0 as in [`crate::demo`]'s
+//! greeting. Lowercase letters are shown as capitals, five rows leave no
+//! room for anything else.
+
+pub const WIDTH: usize = 3;
+
+/// From the space to the backtick, then the braces, the bar and the tilde.
+const GLYPHS: [[u8; WIDTH]; 69] = [
+    [0x00, 0x00, 0x00], // space
+    [0x00, 0x17, 0x00], // !
+    [0x03, 0x00, 0x03], // "
+    [0x1f, 0x0a, 0x1f], // #
+    [0x12, 0x1f, 0x09], // $
+    [0x19, 0x04, 0x13], // %
+    [0x0a, 0x15, 0x1a], // &
+    [0x00, 0x03, 0x00], // '
+    [0x00, 0x0e, 0x11], // (
+    [0x11, 0x0e, 0x00], // )
+    [0x0a, 0x04, 0x0a], // *
+    [0x04, 0x0e, 0x04], // +
+    [0x10, 0x08, 0x00], // ,
+    [0x04, 0x04, 0x04], // -
+    [0x00, 0x10, 0x00], // .
+    [0x18, 0x04, 0x03], // /
+    [0x1f, 0x11, 0x1f], // 0
+    [0x12, 0x1f, 0x10], // 1
+    [0x19, 0x15, 0x12], // 2
+    [0x11, 0x15, 0x0a], // 3
+    [0x07, 0x04, 0x1f], // 4
+    [0x17, 0x15, 0x09], // 5
+    [0x1e, 0x15, 0x1d], // 6
+    [0x01, 0x1d, 0x03], // 7
+    [0x1f, 0x15, 0x1f], // 8
+    [0x17, 0x15, 0x0f], // 9
+    [0x00, 0x0a, 0x00], // :
+    [0x10, 0x0a, 0x00], // ;
+    [0x04, 0x0a, 0x11], // <
+    [0x0a, 0x0a, 0x0a], // =
+    [0x11, 0x0a, 0x04], // >
+    [0x01, 0x15, 0x02], // ?
+    [0x0e, 0x15, 0x16], // @
+    [0x1e, 0x05, 0x1e], // A
+    [0x1f, 0x15, 0x0a], // B
+    [0x0e, 0x11, 0x11], // C
+    [0x1f, 0x11, 0x0e], // D
+    [0x1f, 0x15, 0x11], // E
+    [0x1f, 0x05, 0x01], // F
+    [0x0e, 0x11, 0x1d], // G
+    [0x1f, 0x04, 0x1f], // H
+    [0x11, 0x1f, 0x11], // I
+    [0x08, 0x10, 0x0f], // J
+    [0x1f, 0x04, 0x1b], // K
+    [0x1f, 0x10, 0x10], // L
+    [0x1f, 0x06, 0x1f], // M
+    [0x1f, 0x01, 0x1e], // N
+    [0x0e, 0x11, 0x0e], // O
+    [0x1f, 0x05, 0x02], // P
+    [0x0e, 0x19, 0x16], // Q
+    [0x1f, 0x05, 0x1a], // R
+    [0x12, 0x15, 0x09], // S
+    [0x01, 0x1f, 0x01], // T
+    [0x1f, 0x10, 0x1f], // U
+    [0x0f, 0x10, 0x0f], // V
+    [0x1f, 0x0c, 0x1f], // W
+    [0x1b, 0x04, 0x1b], // X
+    [0x03, 0x1c, 0x03], // Y
+    [0x19, 0x15, 0x13], // Z
+    [0x00, 0x1f, 0x11], // [
+    [0x03, 0x04, 0x18], // \
+    [0x11, 0x1f, 0x00], // ]
+    [0x02, 0x01, 0x02], // ^
+    [0x10, 0x10, 0x10], // _
+    [0x01, 0x02, 0x00], // `
+    [0x04, 0x0e, 0x11], // {
+    [0x00, 0x1f, 0x00], // |
+    [0x11, 0x0e, 0x04], // }
+    [0x04, 0x06, 0x02], // ~
+];
+
+/// Whether `c` has a glyph of its own here.
+pub fn printable(c: char) -> bool {
+    (' '..='~').contains(&c)
+}
+
+/// The columns of `c`, or of `'?'` for anything that isn't printable.
+pub fn glyph(c: char) -> [u8; WIDTH] {
+    let c = c.to_ascii_uppercase();
+    let index = match c {
+        ' '..='`' => c as usize - ' ' as usize,
+        '{'..='~' => c as usize - '{' as usize + ('`' as usize - ' ' as usize + 1),
+        _ => '?' as usize - ' ' as usize,
+    };
+    GLYPHS[index]
+}
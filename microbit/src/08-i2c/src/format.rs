@@ -0,0 +1,142 @@
+//! How readings are printed, for "format".
+//!
+//! [`OutputFormat::Human`] is the "Magnetic field (nT): x 12 y -3 z 40"
+//! the shell has always printed. [`OutputFormat::Csv`] is for a script on
+//! the other end: bare comma-separated values under a header line that
+//! names each column with its unit, as in "mag_x_nT,mag_y_nT,mag_z_nT".
+//! The first column is the time: a stream's time column as "timeformat"
+//! has it, milliseconds since boot unless that was changed, and for single
+//! readings the millisecond they were read, see [`crate::uptime`]. A stream
+//! prints its header when it starts, instead of the "#" line; single
+//! readings print one the first time after the format is set or the board
+//! starts. Axes hidden with "axes show" are left out of both, and lines
+//! end the way "lineend" says, "\r\n" unless it was changed.
+//!
+//! Only "magnetometer" and "accelerometer", on their own and streamed, go
+//! through here. The commands that print other columns than a reading's
+//! three axes keep their own layout.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::axes::{Axes, Sample};
+use crate::recent::Kind;
+
+pub const USAGE: &str = "format human|csv";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Human,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "human" => Some(OutputFormat::Human),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Human => "human",
+            OutputFormat::Csv => "csv",
+        }
+    }
+
+    pub fn encode(self) -> u32 {
+        self as u32
+    }
+
+    /// Anything unrecognized decodes as [`OutputFormat::Human`].
+    pub fn decode(word: u32) -> OutputFormat {
+        match word {
+            1 => OutputFormat::Csv,
+            _ => OutputFormat::Human,
+        }
+    }
+}
+
+/// One bit per [`Kind`] whose single readings had their header already
+static HEADED: AtomicU8 = AtomicU8::new(0);
+
+/// Single readings get a header again, for after the format or the axes
+/// change.
+pub fn forget_headers() {
+    HEADED.store(0, Ordering::Relaxed);
+}
+
+/// Whether a single reading of `kind` is the first since
+/// [`forget_headers`], and so needs a header.
+pub fn take_header(kind: Kind) -> bool {
+    let bit = 1 << kind as u8;
+    // The nRF51 has no atomic read-modify-write, but only the command loop
+    // prints readings
+    let headed = HEADED.load(Ordering::Relaxed);
+    HEADED.store(headed | bit, Ordering::Relaxed);
+    headed & bit == 0
+}
+
+/// The first half of each column name, and the unit that ends it.
+fn prefix(kind: Kind) -> (&'static str, &'static str) {
+    match kind {
+        Kind::Accel => ("accel", "mg"),
+        Kind::Mag => ("mag", "nT"),
+    }
+}
+
+/// The CSV header for readings of `kind`, after the `time` column if there
+/// is one.
+pub fn write_header<W: fmt::Write>(
+    w: &mut W,
+    kind: Kind,
+    time: Option<&str>,
+    axes: Axes,
+) -> fmt::Result {
+    let (name, unit) = prefix(kind);
+    let mut first = true;
+    if let Some(time) = time {
+        write!(w, "{}", time)?;
+        first = false;
+    }
+    for letter in axes.letters() {
+        let separator = if first { "" } else { "," };
+        write!(w, "{}{}_{}_{}", separator, name, letter, unit)?;
+        first = false;
+    }
+    writeln!(w)
+}
+
+/// A reading of `kind` as a line in `format`, after its `stamp` if it has
+/// one. The one way a reading of either sensor gets printed.
+pub fn write_sample<W: fmt::Write>(
+    w: &mut W,
+    format: OutputFormat,
+    kind: Kind,
+    stamp: Option<&dyn fmt::Display>,
+    sample: Sample,
+) -> fmt::Result {
+    match format {
+        OutputFormat::Human => {
+            if let Some(stamp) = stamp {
+                write!(w, "{} ", stamp)?;
+            }
+            writeln!(w, "{}: {}", kind.label(), sample)
+        }
+        OutputFormat::Csv => {
+            let mut first = true;
+            if let Some(stamp) = stamp {
+                write!(w, "{}", stamp)?;
+                first = false;
+            }
+            for (_, value) in sample.shown() {
+                let separator = if first { "" } else { "," };
+                write!(w, "{}{}", separator, value)?;
+                first = false;
+            }
+            writeln!(w)
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! What the display driver is handed: the display's layers combined, and
+//! each pixel's greyscale value dimmed by the brightness setting and
+//! corrected for gamma. Worked out away from the driver, see
+//! [`crate::display`].
+
+use crate::status::{self, StatusLeds, ROLE_COUNT};
+
+pub const MAX_BRIGHTNESS: u8 = 9;
+
+/// A greyscale value from 0 to [`MAX_BRIGHTNESS`] per LED, by row and
+/// column.
+pub type Image = [[u8; 5]; 5];
+
+/// The driver level for every product of a greyscale value and the
+/// brightness, corrected for gamma. Generated by build.rs.
+const GAMMA: [u8; (MAX_BRIGHTNESS * MAX_BRIGHTNESS) as usize + 1] =
+    include!(concat!(env!("OUT_DIR"), "/gamma.rs"));
+
+/// Scale a greyscale value by `brightness`, and only then correct it for
+/// gamma, if that's on, so that the brightness setting dims everything
+/// evenly as it looks. A lit pixel is never turned off completely.
+pub fn scale(value: u8, brightness: u8, gamma: bool) -> u8 {
+    if value == 0 {
+        return 0;
+    }
+    let level = if gamma {
+        GAMMA[(value * brightness) as usize]
+    } else {
+        value * brightness / MAX_BRIGHTNESS
+    };
+    level.max(1)
+}
+
+/// Lay `overlay` over `frame`: wherever it is lit, it wins.
+fn combine(frame: &mut Image, overlay: &Image) {
+    for (pixel, &over) in frame.iter_mut().flatten().zip(overlay.iter().flatten()) {
+        // All ones where the overlay is dark: no branch for every pixel
+        let keep = ((over == 0) as u8).wrapping_neg();
+        *pixel = over | (*pixel & keep);
+    }
+}
+
+/// Everything that goes into a frame, each part set on its own.
+pub struct Layers {
+    pub background: Image,
+    pub overlay: Image,
+    pub leds: StatusLeds,
+    pub active: [bool; ROLE_COUNT],
+    pub brightness: u8,
+    pub gamma: bool,
+}
+
+impl Layers {
+    /// Dark, at full brightness and with gamma on.
+    pub const fn new() -> Layers {
+        Layers {
+            background: [[0; 5]; 5],
+            overlay: [[0; 5]; 5],
+            leds: StatusLeds::NONE,
+            active: [false; ROLE_COUNT],
+            brightness: MAX_BRIGHTNESS,
+            gamma: true,
+        }
+    }
+
+    /// The background, with the overlay and then the status LEDs over it,
+    /// at the driver's levels.
+    pub fn frame(&self) -> Image {
+        let mut frame = self.background;
+        combine(&mut frame, &self.overlay);
+        status::compose(&mut frame, &self.leds, &self.active);
+        for value in frame.iter_mut().flatten() {
+            *value = scale(*value, self.brightness, self.gamma);
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: core::ops::RangeInclusive<u8> = 0..=MAX_BRIGHTNESS;
+
+    #[test]
+    fn the_table_runs_from_off_to_full_without_going_back() {
+        assert_eq!(GAMMA[0], 0);
+        assert_eq!(GAMMA[GAMMA.len() - 1], MAX_BRIGHTNESS);
+        assert!(GAMMA.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn off_is_off_and_full_is_full() {
+        for gamma in [true, false] {
+            for brightness in LEVELS {
+                assert_eq!(scale(0, brightness, gamma), 0);
+            }
+            assert_eq!(scale(MAX_BRIGHTNESS, MAX_BRIGHTNESS, gamma), MAX_BRIGHTNESS);
+        }
+    }
+
+    #[test]
+    fn brighter_values_are_never_darker() {
+        for gamma in [true, false] {
+            for brightness in LEVELS {
+                let levels: Vec<u8> = LEVELS
+                    .map(|value| scale(value, brightness, gamma))
+                    .collect();
+                assert!(
+                    levels.windows(2).all(|pair| pair[0] <= pair[1]),
+                    "{:?} at brightness {}",
+                    levels,
+                    brightness
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn a_lit_pixel_stays_lit_however_dim() {
+        for gamma in [true, false] {
+            for value in 1..=MAX_BRIGHTNESS {
+                assert_eq!(scale(value, 1, gamma), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn gamma_spreads_the_top_levels_out() {
+        let corrected: Vec<u8> = LEVELS
+            .map(|value| scale(value, MAX_BRIGHTNESS, true))
+            .collect();
+        let linear: Vec<u8> = LEVELS
+            .map(|value| scale(value, MAX_BRIGHTNESS, false))
+            .collect();
+        assert_eq!(linear, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(corrected, [0, 1, 1, 1, 2, 2, 4, 5, 7, 9]);
+    }
+
+    #[test]
+    fn the_brightness_scales_before_the_gamma() {
+        // Only the product counts, and the table has it
+        for value in LEVELS {
+            for brightness in LEVELS {
+                let expected = match value {
+                    0 => 0,
+                    _ => GAMMA[(value * brightness) as usize].max(1),
+                };
+                assert_eq!(scale(value, brightness, true), expected);
+                if value != 0 && brightness != 0 {
+                    assert_eq!(
+                        scale(value, brightness, true),
+                        scale(brightness, value, true)
+                    );
+                }
+            }
+        }
+    }
+
+    /// Showing the values as they are.
+    fn plain() -> Layers {
+        Layers {
+            gamma: false,
+            ..Layers::new()
+        }
+    }
+
+    #[test]
+    fn a_lit_overlay_pixel_wins_and_a_dark_one_shows_through() {
+        for background in LEVELS {
+            for over in LEVELS {
+                let mut frame = [[background; 5]; 5];
+                combine(&mut frame, &[[over; 5]; 5]);
+                let expected = if over == 0 { background } else { over };
+                assert_eq!(frame, [[expected; 5]; 5], "{} under {}", background, over);
+            }
+        }
+    }
+
+    #[test]
+    fn combining_goes_pixel_by_pixel() {
+        let mut frame = [[1, 2, 3, 4, 5]; 5];
+        let mut overlay = [[0; 5]; 5];
+        overlay[0][0] = 9;
+        overlay[2][4] = 1;
+        combine(&mut frame, &overlay);
+        assert_eq!(frame[0], [9, 2, 3, 4, 5]);
+        assert_eq!(frame[1], [1, 2, 3, 4, 5]);
+        assert_eq!(frame[2], [1, 2, 3, 4, 1]);
+    }
+
+    #[test]
+    fn the_overlay_stays_put_while_the_background_changes() {
+        let mut layers = plain();
+        layers.overlay[1][3] = 7;
+        for background in [[[0; 5]; 5], [[9; 5]; 5], [[3, 0, 3, 0, 3]; 5]] {
+            layers.background = background;
+            let frame = layers.frame();
+            assert_eq!(frame[1][3], 7);
+            assert_eq!(frame[1][2], background[1][2]);
+            assert_eq!(frame[4][3], background[4][3]);
+        }
+        layers.overlay = [[0; 5]; 5];
+        assert_eq!(layers.frame(), layers.background);
+    }
+
+    #[test]
+    fn the_status_leds_go_over_the_overlay() {
+        let mut layers = plain();
+        layers.overlay = [[1; 5]; 5];
+        layers.leds.set(status::Role::Link, Some((4, 0)));
+        layers.active[status::Role::Link.index()] = true;
+        let frame = layers.frame();
+        assert_eq!(frame[4][0], 5);
+        assert_eq!(frame[4][1], 1);
+        layers.active[status::Role::Link.index()] = false;
+        assert_eq!(layers.frame()[4][0], 1);
+    }
+
+    #[test]
+    fn every_layer_is_dimmed_alike() {
+        let mut layers = plain();
+        layers.background = [[MAX_BRIGHTNESS; 5]; 5];
+        layers.overlay[0][0] = MAX_BRIGHTNESS;
+        layers.brightness = 3;
+        assert_eq!(layers.frame(), [[3; 5]; 5]);
+    }
+}
@@ -0,0 +1,188 @@
+//! Separating gravity from the accelerometer readings, for "linearaccel".
+//!
+//! Gravity is tracked with a slow exponential moving average, a time
+//! constant of about a second at the 50 Hz data rate. The average only
+//! follows while the board is roughly still; during large motion the
+//! estimate is frozen so that a punch doesn't drag it along.
+
+/// 0.5 s of samples at 50 Hz to start the estimate from.
+const PRIME_SAMPLES: i32 = 25;
+/// 1 s at 50 Hz
+const TIME_CONSTANT_SAMPLES: i32 = 50;
+/// Fractional bits kept in the estimate, so small steps don't get lost.
+const SHIFT: u32 = 6;
+/// How far from 1 g (in mg) the magnitude may be for the estimate to move.
+const STILL_MG: i32 = 150;
+
+pub struct Gravity {
+    primed: i32,
+    sum: [i32; 3],
+    /// Scaled by 2^SHIFT
+    estimate: [i32; 3],
+}
+
+impl Gravity {
+    pub fn new() -> Gravity {
+        Gravity {
+            primed: 0,
+            sum: [0; 3],
+            estimate: [0; 3],
+        }
+    }
+
+    /// Feed one sample in mg. Returns it with gravity taken out, or `None`
+    /// while the first estimate is still being averaged.
+    pub fn update(&mut self, sample: [i32; 3]) -> Option<[i32; 3]> {
+        if self.primed < PRIME_SAMPLES {
+            for (sum, s) in self.sum.iter_mut().zip(sample.iter()) {
+                *sum += s;
+            }
+            self.primed += 1;
+            if self.primed == PRIME_SAMPLES {
+                for (estimate, sum) in self.estimate.iter_mut().zip(self.sum.iter()) {
+                    *estimate = (sum << SHIFT) / PRIME_SAMPLES;
+                }
+            }
+            return None;
+        }
+
+        if (magnitude(sample) as i32 - 1000).abs() <= STILL_MG {
+            for (estimate, s) in self.estimate.iter_mut().zip(sample.iter()) {
+                *estimate += ((s << SHIFT) - *estimate) / TIME_CONSTANT_SAMPLES;
+            }
+        }
+        let mut linear = sample;
+        for (linear, estimate) in linear.iter_mut().zip(self.estimate.iter()) {
+            *linear -= estimate >> SHIFT;
+        }
+        Some(linear)
+    }
+}
+
+/// Length of `v`, rounded down.
+pub fn magnitude(v: [i32; 3]) -> u32 {
+    let square: u64 = v.iter().map(|&c| (c as i64 * c as i64) as u64).sum();
+    isqrt(square)
+}
+
+/// Integer square root by bisection on the result bits.
+fn isqrt(n: u64) -> u32 {
+    let mut root: u32 = 0;
+    for bit in (0..32).rev() {
+        let candidate = root | 1 << bit;
+        if candidate as u64 * candidate as u64 <= n {
+            root = candidate;
+        }
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 45° round x, at 1 g
+    const TILTED: [i32; 3] = [0, 707, 707];
+
+    /// A gravity estimate primed lying flat
+    fn primed() -> Gravity {
+        let mut gravity = Gravity::new();
+        for _ in 0..PRIME_SAMPLES {
+            gravity.update([0, 0, 1000]);
+        }
+        gravity
+    }
+
+    /// What the estimate is now, going by what it takes out of `sample`
+    fn estimate(gravity: &mut Gravity, sample: [i32; 3]) -> [i32; 3] {
+        let linear = gravity.update(sample).unwrap();
+        [
+            sample[0] - linear[0],
+            sample[1] - linear[1],
+            sample[2] - linear[2],
+        ]
+    }
+
+    #[test]
+    fn nothing_comes_out_until_half_a_second_is_averaged() {
+        let mut gravity = Gravity::new();
+        for i in 0..PRIME_SAMPLES {
+            // Jittering round 1 g straight down
+            let jitter = [-20, -10, 0, 10, 20][i as usize % 5];
+            assert_eq!(gravity.update([jitter, -jitter, 1000 + jitter]), None);
+        }
+        // The jitter averaged out of the estimate, so none of it is left on
+        // top of a still board
+        assert_eq!(gravity.update([0, 0, 1000]), Some([0, 0, 0]));
+    }
+
+    #[test]
+    fn a_still_board_reads_no_linear_acceleration() {
+        let mut gravity = primed();
+        for _ in 0..100 {
+            assert_eq!(gravity.update([0, 0, 1000]), Some([0, 0, 0]));
+        }
+    }
+
+    #[test]
+    fn the_estimate_follows_a_tilt_in_about_a_second() {
+        let mut gravity = primed();
+        let first = gravity.update(TILTED).unwrap();
+        // The whole of the tilt shows as acceleration at first
+        assert!(first[1] > 650 && first[2] < -250, "{:?}", first);
+        let mut linear = first;
+        for _ in 0..TIME_CONSTANT_SAMPLES {
+            linear = gravity.update(TILTED).unwrap();
+        }
+        // A time constant in, about a third of it is left
+        assert!((200..330).contains(&linear[1]), "{:?}", linear);
+        for _ in 0..5 * TIME_CONSTANT_SAMPLES {
+            linear = gravity.update(TILTED).unwrap();
+        }
+        assert!(linear.iter().all(|c| c.abs() <= 5), "{:?}", linear);
+    }
+
+    #[test]
+    fn shaking_freezes_the_estimate() {
+        let mut gravity = primed();
+        let mut never_shaken = primed();
+        for _ in 0..2 * TIME_CONSTANT_SAMPLES {
+            gravity.update(TILTED);
+            never_shaken.update(TILTED);
+        }
+        let before = estimate(&mut gravity, TILTED);
+        never_shaken.update(TILTED);
+        let shaken = [[1500, 2500, -800], [-1800, -200, 2600], [0, 3000, 700]];
+        for i in 0..3 * TIME_CONSTANT_SAMPLES as usize {
+            let sample = shaken[i % shaken.len()];
+            assert_eq!(estimate(&mut gravity, sample), before, "sample {}", i);
+        }
+        // Still again, the estimate picks up where it was
+        for _ in 0..TIME_CONSTANT_SAMPLES {
+            assert_eq!(gravity.update(TILTED), never_shaken.update(TILTED));
+        }
+    }
+
+    #[test]
+    fn moving_gently_keeps_the_estimate_moving() {
+        let mut gravity = primed();
+        // 100 mg on top of gravity is still close enough to 1 g
+        let linear = gravity.update([100, 0, 1000]).unwrap();
+        let later = (0..10 * TIME_CONSTANT_SAMPLES)
+            .map(|_| gravity.update([100, 0, 1000]).unwrap())
+            .last()
+            .unwrap();
+        assert!((90..=100).contains(&linear[0]), "{:?}", linear);
+        assert!(later[0].abs() <= 5, "{:?}", later);
+    }
+
+    #[test]
+    fn magnitude_rounds_down() {
+        assert_eq!(magnitude([0, 0, 0]), 0);
+        assert_eq!(magnitude([3, 4, 0]), 5);
+        assert_eq!(magnitude([-3, 0, -4]), 5);
+        assert_eq!(magnitude([1, 1, 1]), 1);
+        assert_eq!(magnitude([0, 707, 707]), 999);
+        assert_eq!(magnitude([i32::MIN, i32::MIN, i32::MIN]), 3_719_550_786);
+    }
+}
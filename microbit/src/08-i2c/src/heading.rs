@@ -0,0 +1,116 @@
+//! A compass heading from the magnetometer, for "heading".
+//!
+//! Only x and y go into it, so the board has to lie flat, or the reading
+//! has to be levelled out first, see [`crate::tilt::level`]. The field of
+//! the board itself is only taken off once "calibrate" has found it, see
+//! [`crate::calibration`]. As in the compass chapter, a field along +x
+//! points east and one along +y north. Headings are in hundredths of a
+//! degree clockwise from north, worked out in integers so that the v1,
+//! which has no FPU, doesn't have to emulate one for a flat heading.
+
+use core::fmt;
+
+/// How many readings go into one heading, a fifth of a second at 50 Hz
+pub const SAMPLES: i32 = 10;
+
+/// In hundredths of a degree, like the headings
+const QUARTER_TURN: i64 = 9_000;
+const HALF_TURN: i64 = 18_000;
+const FULL_TURN: i64 = 36_000;
+
+/// Each an eighth of a turn wide, centred on its direction
+const CARDINALS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+/// atan(`n` / `d`) for 0 <= `n` <= `d`, `d` > 0: at most 45°, within a
+/// quarter of a degree. The line through both ends, plus a parabola that
+/// makes up most of the difference.
+fn atan_octant(n: i64, d: i64) -> i64 {
+    (4500 * n * d + 1564 * n * (d - n)) / (d * d)
+}
+
+/// The angle of (`x`, `y`) counterclockwise from +x, 0 up to but not
+/// including 360°. `None` for (0, 0), which has none.
+pub fn atan2(y: i32, x: i32) -> Option<i32> {
+    let (x, y) = (x as i64, y as i64);
+    let (ax, ay) = (x.abs(), y.abs());
+    if ax == 0 && ay == 0 {
+        return None;
+    }
+    // In the first quadrant
+    let angle = if ay <= ax {
+        atan_octant(ay, ax)
+    } else {
+        QUARTER_TURN - atan_octant(ax, ay)
+    };
+    let angle = match (x < 0, y < 0) {
+        (false, false) => angle,
+        (true, false) => HALF_TURN - angle,
+        (true, true) => HALF_TURN + angle,
+        (false, true) => FULL_TURN - angle,
+    };
+    Some((angle % FULL_TURN) as i32)
+}
+
+/// The heading of a field of `x` and `y`, in any unit. `None` if it is
+/// zero in the plane of the board.
+pub fn heading(x: i32, y: i32) -> Option<i32> {
+    let angle = atan2(y, x)? as i64;
+    Some((QUARTER_TURN - angle).rem_euclid(FULL_TURN) as i32)
+}
+
+/// Which of the eight compass points, from N clockwise, `heading` is
+/// nearest to. Each covers the 45° centred on it, so N runs from 337.5° up
+/// to but not including 22.5°.
+pub fn octant(heading: i32) -> usize {
+    ((heading as i64 + FULL_TURN / 16).rem_euclid(FULL_TURN) / (FULL_TURN / 8)) as usize
+}
+
+/// Averages readings, so that one heading doesn't jitter with the noise
+/// in any single one.
+pub struct Average {
+    /// How many it takes
+    samples: i32,
+    sum: [i64; 2],
+    count: i32,
+}
+
+impl Average {
+    /// Taking [`SAMPLES`] readings for a heading that is printed, fewer for
+    /// one that has to keep up.
+    pub fn new(samples: i32) -> Average {
+        Average {
+            samples,
+            sum: [0; 2],
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, x: i32, y: i32) {
+        self.sum[0] += x as i64;
+        self.sum[1] += y as i64;
+        self.count += 1;
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.count >= self.samples
+    }
+
+    /// The heading of the average, as [`heading`] has it.
+    pub fn heading(&self) -> Option<Heading> {
+        let count = self.count.max(1) as i64;
+        let [x, y] = self.sum.map(|sum| (sum / count) as i32);
+        heading(x, y).map(Heading)
+    }
+}
+
+/// Printed as whole degrees and the nearest of [`CARDINALS`], as in
+/// "123 deg SE".
+pub struct Heading(pub i32);
+
+impl fmt::Display for Heading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let degrees = (self.0 + 50) / 100 % 360;
+        let cardinal = CARDINALS[octant(self.0)];
+        write!(f, "{} deg {}", degrees, cardinal)
+    }
+}
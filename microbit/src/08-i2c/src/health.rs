@@ -0,0 +1,91 @@
+//! Which subsystems came up at boot.
+//!
+//! Startup tries every subsystem on its own and carries on without the ones
+//! that fail, so that a board with, say, a dead sensor still has a shell to
+//! find that out with. Commands that need a missing subsystem refuse with
+//! [`Unavailable`] instead of panicking, and "status" lists what's missing.
+//! Only without the peripherals or the serial port is there no shell, see
+//! `main`.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::log::log;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Subsystem {
+    /// `Board::take`, without which there is nothing else
+    Peripherals,
+    Serial,
+    Sensor,
+    Rtc,
+    Rng,
+}
+
+/// Those the shell can do without.
+pub const OPTIONAL: [Subsystem; 3] = [Subsystem::Sensor, Subsystem::Rtc, Subsystem::Rng];
+
+impl Subsystem {
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Peripherals => "peripherals",
+            Subsystem::Serial => "serial",
+            Subsystem::Sensor => "sensor",
+            Subsystem::Rtc => "RTC",
+            Subsystem::Rng => "RNG",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// One bit per failed subsystem. Only written during startup.
+static FAILED: AtomicU8 = AtomicU8::new(0);
+
+#[derive(Debug)]
+pub struct InitError(pub Subsystem);
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed to start", self.0.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct Unavailable(pub Subsystem);
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} not available, it failed to start", self.0.name())
+    }
+}
+
+/// Remember that `err`'s subsystem is missing, and say so over RTT.
+pub fn record(err: InitError) {
+    log!("{}", err);
+    // The nRF51 has no atomic read-modify-write, but nothing else runs yet
+    let failed = FAILED.load(Ordering::Relaxed) | err.0.bit();
+    FAILED.store(failed, Ordering::Relaxed);
+}
+
+pub fn require(subsystem: Subsystem) -> Result<(), Unavailable> {
+    if FAILED.load(Ordering::Relaxed) & subsystem.bit() == 0 {
+        Ok(())
+    } else {
+        Err(Unavailable(subsystem))
+    }
+}
+
+/// "status": one line per subsystem that may be missing.
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    for &subsystem in &OPTIONAL {
+        let state = match require(subsystem) {
+            Ok(()) => "ok",
+            Err(_) => "failed",
+        };
+        writeln!(w, "{}: {}", subsystem.name(), state)?;
+    }
+    Ok(())
+}
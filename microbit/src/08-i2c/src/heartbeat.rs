@@ -0,0 +1,133 @@
+//! A heartbeat on a corner LED, driven entirely from the RTC0 interrupt.
+//!
+//! It blinks once a second for as long as the interrupt runs, no matter
+//! what the command loop is doing. The command loop calls [`feed`] whenever
+//! it is idle; if it hasn't done so for a few seconds it is probably stuck,
+//! and the heartbeat switches to a double blink as an early warning. A
+//! triple blink means the battery is low, see [`crate::battery`]. The power
+//! saver spaces the blinks out to one every few seconds, see
+//! [`crate::powersave`].
+//!
+//! RTC0 runs at [`uptime::COUNTER_HZ`] for the millisecond clock, see
+//! [`crate::uptime`], and ticks off its compare register 0, which moves on
+//! by [`TICK_COUNTS`] at every tick.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use microbit::hal::rtc::{Rtc, RtcCompareReg, RtcInterrupt};
+use microbit::pac::{self, interrupt, RTC0};
+
+use crate::claims::{self, Owner, Resource};
+use crate::health::{InitError, Subsystem};
+use crate::irqstats::{self, Irq};
+use crate::power::Peripheral;
+use crate::shared::Shared;
+use crate::status::Role;
+use crate::{battery, board, display, uptime, watchdog};
+
+pub const TICK_HZ: u32 = 8;
+/// Of the counter, from one tick to the next
+const TICK_COUNTS: u32 = uptime::COUNTER_HZ / TICK_HZ;
+const STARVED_TICKS: u32 = 3 * TICK_HZ;
+
+static RTC: Shared<Option<Rtc<RTC0>>> = Shared::new(None);
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TICKS: AtomicU32 = AtomicU32::new(0);
+static FED: AtomicU32 = AtomicU32::new(0);
+static PERIOD_S: AtomicU32 = AtomicU32::new(1);
+
+/// Start ticking. The LFCLK has to be running already.
+/// Without it the tick count stays at zero.
+pub fn init(rtc0: RTC0, enabled: bool) -> Result<(), InitError> {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    claims::take(
+        Resource::Peripheral(Peripheral::Rtc0),
+        Owner::permanent("the heartbeat and command watchdog"),
+    );
+    let mut rtc = Rtc::new(rtc0, uptime::PRESCALER).map_err(|_| InitError(Subsystem::Rtc))?;
+    rtc.set_compare(RtcCompareReg::Compare0, TICK_COUNTS)
+        .map_err(|_| InitError(Subsystem::Rtc))?;
+    for event in [RtcInterrupt::Compare0, RtcInterrupt::Overflow] {
+        rtc.enable_event(event);
+    }
+    rtc.enable_interrupt(RtcInterrupt::Compare0, None);
+    rtc.enable_interrupt(RtcInterrupt::Overflow, None);
+    rtc.enable_counter();
+    RTC.with(|slot| *slot = Some(rtc));
+    unsafe { pac::NVIC::unmask(pac::Interrupt::RTC0) };
+    Ok(())
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Blink every `period_s` seconds rather than every second.
+pub fn set_period_s(period_s: u32) {
+    PERIOD_S.store(period_s.max(1), Ordering::Relaxed);
+}
+
+/// Ticks since [`init`], at [`TICK_HZ`].
+pub fn ticks() -> u32 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Tell the heartbeat the command loop is still making progress.
+pub fn feed() {
+    FED.store(TICKS.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Whether the LED is lit at `tick` when blinking `blinks` times at the
+/// top of every `period_s` seconds.
+fn lit(tick: u32, blinks: u32, period_s: u32) -> bool {
+    let phase = tick % (TICK_HZ * period_s);
+    phase.is_multiple_of(2) && phase < 2 * blinks
+}
+
+/// Where the counter is at when `tick` is due. Ticks divide the counter's
+/// range evenly, so the wrap in the multiplication doesn't matter.
+fn compare_at(tick: u32) -> u32 {
+    tick.wrapping_mul(TICK_COUNTS) & ((1 << uptime::COUNTER_BITS) - 1)
+}
+
+#[interrupt]
+fn RTC0() {
+    irqstats::enter(Irq::Rtc0);
+    // Only this handler writes TICKS, and the nRF51 has no atomic increment
+    let tick = TICKS.load(Ordering::Relaxed).wrapping_add(1);
+    let ticked = RTC.with(|rtc| {
+        let rtc = match rtc {
+            Some(rtc) => rtc,
+            None => return false,
+        };
+        if rtc.is_event_triggered(RtcInterrupt::Overflow) {
+            rtc.reset_event(RtcInterrupt::Overflow);
+            uptime::overflowed();
+        }
+        if !rtc.is_event_triggered(RtcInterrupt::Compare0) {
+            return false;
+        }
+        rtc.reset_event(RtcInterrupt::Compare0);
+        // Can't fail, it's within the counter's range
+        rtc.set_compare(RtcCompareReg::Compare0, compare_at(tick.wrapping_add(1)))
+            .ok();
+        true
+    });
+    if !ticked {
+        return;
+    }
+    TICKS.store(tick, Ordering::Relaxed);
+    watchdog::tick(tick);
+    battery::schedule(tick);
+    board::tick(tick);
+    let blinks = if battery::low() {
+        3
+    } else if tick.wrapping_sub(FED.load(Ordering::Relaxed)) > STARVED_TICKS {
+        2
+    } else {
+        1
+    };
+    display::set_status(
+        Role::Heartbeat,
+        ENABLED.load(Ordering::Relaxed) && lit(tick, blinks, PERIOD_S.load(Ordering::Relaxed)),
+    );
+}
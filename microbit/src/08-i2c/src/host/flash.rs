@@ -0,0 +1,66 @@
+//! A stand-in for the flash, in RAM and one for each test's thread, with
+//! the same rules: erasing sets every bit, writing can only clear them.
+
+use core::cell::{Cell, RefCell};
+use core::fmt;
+
+pub const PAGES: usize = 6;
+pub const PAGE_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub struct LowPower;
+
+impl fmt::Display for LowPower {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "supply voltage low, flash not written")
+    }
+}
+
+thread_local! {
+    static FLASH: RefCell<[[u32; PAGE_SIZE / 4]; PAGES]> =
+        const { RefCell::new([[0xffff_ffff; PAGE_SIZE / 4]; PAGES]) };
+    static LOW_POWER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// From now on erasing and writing fail, or work again.
+pub fn set_low_power(low: bool) {
+    LOW_POWER.with(|cell| cell.set(low));
+}
+
+fn allowed() -> Result<(), LowPower> {
+    match LOW_POWER.with(Cell::get) {
+        true => Err(LowPower),
+        false => Ok(()),
+    }
+}
+
+pub fn read(page: usize, offset: usize, words: &mut [u32]) {
+    FLASH.with(|flash| words.copy_from_slice(&flash.borrow()[page][offset..offset + words.len()]));
+}
+
+pub fn erase(page: usize) -> Result<(), LowPower> {
+    allowed()?;
+    FLASH.with(|flash| flash.borrow_mut()[page] = [0xffff_ffff; PAGE_SIZE / 4]);
+    Ok(())
+}
+
+pub fn write(page: usize, offset: usize, words: &[u32]) -> Result<(), LowPower> {
+    allowed()?;
+    FLASH.with(|flash| {
+        let mut flash = flash.borrow_mut();
+        for (cell, word) in flash[page][offset..offset + words.len()]
+            .iter_mut()
+            .zip(words)
+        {
+            *cell &= word;
+        }
+    });
+    Ok(())
+}
+
+/// The same as the real one, records written here have to check out there.
+pub fn checksum(words: &[u32]) -> u32 {
+    words.iter().fold(0x811c_9dc5, |sum, word| {
+        (sum ^ word).wrapping_mul(0x0100_0193)
+    })
+}
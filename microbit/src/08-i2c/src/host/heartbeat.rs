@@ -0,0 +1,18 @@
+//! A stand-in for the heartbeat, with its tick rate and a tick count that
+//! each test's thread sets for itself.
+
+use core::cell::Cell;
+
+pub const TICK_HZ: u32 = 8;
+
+thread_local! {
+    static TICKS: Cell<u32> = const { Cell::new(0) };
+}
+
+pub fn ticks() -> u32 {
+    TICKS.with(Cell::get)
+}
+
+pub fn set_ticks(ticks: u32) {
+    TICKS.with(|cell| cell.set(ticks));
+}
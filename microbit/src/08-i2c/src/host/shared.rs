@@ -0,0 +1,16 @@
+//! A stand-in for the shared cells, with a lock where the board has a
+//! critical section.
+
+use std::sync::Mutex;
+
+pub struct Shared<T>(Mutex<T>);
+
+impl<T> Shared<T> {
+    pub const fn new(value: T) -> Shared<T> {
+        Shared(Mutex::new(value))
+    }
+
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.0.lock().unwrap())
+    }
+}
@@ -0,0 +1,237 @@
+//! "i2ctrace": the transactions on the internal I2C bus, for debugging a
+//! driver without a logic analyzer.
+//!
+//! [`Traced`] wraps any bus and, while tracing is on, keeps a [`Record`]
+//! of every transaction in a small ring: a sequence number, the address,
+//! the register (the first byte written), how much went each way and the
+//! start of the payload. Nothing is formatted during a transaction, that
+//! waits for [`drain`], which the command loop calls between samples and
+//! while idle and which puts new records on the RTT log. "i2ctrace dump"
+//! prints what's in the ring over serial and empties it. When the ring
+//! overflows the oldest records go, and the log says how many.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embedded_hal::blocking::i2c;
+
+use crate::log::log;
+use crate::shared::Shared;
+
+pub const USAGE: &str = "i2ctrace on|off|dump";
+
+const RING_LEN: usize = 16;
+/// Bytes of payload kept per transaction.
+const PAYLOAD_LEN: usize = 8;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RING: Shared<Ring> = Shared::new(Ring::new());
+
+#[derive(Clone, Copy)]
+struct Record {
+    seq: u32,
+    address: u8,
+    /// `None` for a write of nothing
+    register: Option<u8>,
+    written: u8,
+    /// `None` for a plain write
+    read: Option<u8>,
+    ok: bool,
+    /// What was written after the register, or what was read
+    payload: [u8; PAYLOAD_LEN],
+}
+
+impl Record {
+    const EMPTY: Record = Record {
+        seq: 0,
+        address: 0,
+        register: None,
+        written: 0,
+        read: None,
+        ok: false,
+        payload: [0; PAYLOAD_LEN],
+    };
+
+    fn payload_len(&self) -> usize {
+        let len = match self.read {
+            Some(read) => read,
+            None => self.written.saturating_sub(1),
+        };
+        len as usize
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "i2c #{} 0x{:02x}", self.seq, self.address)?;
+        if let Some(register) = self.register {
+            write!(f, " reg 0x{:02x}", register)?;
+        }
+        write!(f, " w{}", self.written)?;
+        if let Some(read) = self.read {
+            write!(f, " r{}", read)?;
+        }
+        if !self.ok {
+            return write!(f, " failed");
+        }
+        let len = self.payload_len();
+        if len > 0 {
+            write!(f, ":")?;
+            for byte in &self.payload[..len.min(PAYLOAD_LEN)] {
+                write!(f, " {:02x}", byte)?;
+            }
+            if len > PAYLOAD_LEN {
+                write!(f, " ..")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Ring {
+    records: [Record; RING_LEN],
+    /// Of the next record, which is also how many there have been
+    next: u32,
+    /// The first record not on the RTT log yet
+    logged: u32,
+    /// The first record not dumped yet
+    dumped: u32,
+}
+
+impl Ring {
+    const fn new() -> Ring {
+        Ring {
+            records: [Record::EMPTY; RING_LEN],
+            next: 0,
+            logged: 0,
+            dumped: 0,
+        }
+    }
+
+    fn push(&mut self, mut record: Record) {
+        record.seq = self.next;
+        self.records[self.next as usize % RING_LEN] = record;
+        self.next = self.next.wrapping_add(1);
+    }
+
+    /// The oldest record still in the ring at or after `from`, and how many
+    /// after `from` have been overwritten.
+    fn oldest(&self, from: u32) -> (u32, u32) {
+        let kept = self.next.saturating_sub(RING_LEN as u32);
+        if from < kept {
+            (kept, kept - from)
+        } else {
+            (from, 0)
+        }
+    }
+
+    fn get(&self, seq: u32) -> Record {
+        self.records[seq as usize % RING_LEN]
+    }
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn record(address: u8, bytes: &[u8], read: Option<&[u8]>, ok: bool) {
+    let mut record = Record {
+        address,
+        register: bytes.first().copied(),
+        written: bytes.len() as u8,
+        read: read.map(|buffer| buffer.len() as u8),
+        ok,
+        ..Record::EMPTY
+    };
+    let payload = read.unwrap_or(bytes.get(1..).unwrap_or(&[]));
+    let len = payload.len().min(PAYLOAD_LEN);
+    record.payload[..len].copy_from_slice(&payload[..len]);
+    RING.with(|ring| ring.push(record));
+}
+
+/// Put the records nobody has seen yet on the RTT log, one short critical
+/// section per record.
+pub fn drain() {
+    let (mut seq, lost) = RING.with(|ring| ring.oldest(ring.logged));
+    if lost > 0 {
+        log!("i2c: {} records lost", lost);
+    }
+    while let Some(record) = RING.with(|ring| {
+        if seq == ring.next {
+            return None;
+        }
+        ring.logged = seq.wrapping_add(1);
+        Some(ring.get(seq))
+    }) {
+        log!("{}", record);
+        seq = seq.wrapping_add(1);
+    }
+}
+
+/// "i2ctrace dump": everything in the ring, oldest first, after which it
+/// is empty.
+pub fn dump<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    let (mut seq, lost) = RING.with(|ring| ring.oldest(ring.dumped));
+    if lost > 0 {
+        writeln!(w, "({} older records lost)", lost)?;
+    }
+    while let Some(record) = RING.with(|ring| {
+        if seq == ring.next {
+            return None;
+        }
+        ring.dumped = seq.wrapping_add(1);
+        Some(ring.get(seq))
+    }) {
+        writeln!(w, "{}", record)?;
+        seq = seq.wrapping_add(1);
+    }
+    Ok(())
+}
+
+pub struct Traced<I>(I);
+
+impl<I> Traced<I> {
+    pub fn new(i2c: I) -> Self {
+        Traced(i2c)
+    }
+}
+
+impl<I: i2c::Write> i2c::Write for Traced<I> {
+    type Error = I::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        let result = self.0.write(address, bytes);
+        if ENABLED.load(Ordering::Relaxed) {
+            record(address, bytes, None, result.is_ok());
+        }
+        result
+    }
+}
+
+impl<I: i2c::Read> i2c::Read for Traced<I> {
+    type Error = I::Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let result = self.0.read(address, buffer);
+        if ENABLED.load(Ordering::Relaxed) {
+            record(address, &[], Some(buffer), result.is_ok());
+        }
+        result
+    }
+}
+
+impl<I: i2c::WriteRead> i2c::WriteRead for Traced<I> {
+    type Error = I::Error;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        let result = self.0.write_read(address, bytes, buffer);
+        if ENABLED.load(Ordering::Relaxed) {
+            record(address, bytes, Some(buffer), result.is_ok());
+        }
+        result
+    }
+}
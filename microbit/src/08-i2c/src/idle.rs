@@ -0,0 +1,90 @@
+//! What the display shows while the command loop waits for input.
+//!
+//! Button B steps through the [`Mode`]s. The display is only drawn on once
+//! per heartbeat tick, and not at all while it's off, so that whatever a
+//! command left on it stays until B is pressed. Nothing here runs while a
+//! command does, the command owns the display then.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+
+use crate::display::{self, Image};
+use crate::{battery, buttons, feedback, health, heartbeat, onchip, roulette, uptime};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Off,
+    /// The roulette going round
+    Animation,
+    /// Bars for the supply voltage and the chip temperature, and a dot for
+    /// every subsystem that came up
+    Dashboard,
+}
+
+const MODES: [Mode; 3] = [Mode::Off, Mode::Animation, Mode::Dashboard];
+
+const LIT: u8 = 5;
+/// The range the temperature bar covers, in °C.
+const MIN_CELSIUS: i32 = 10;
+const MAX_CELSIUS: i32 = 35;
+
+static MODE: AtomicU8 = AtomicU8::new(0);
+static HELD: AtomicBool = AtomicBool::new(false);
+/// When the display was last drawn on
+static DRAWN: AtomicU32 = AtomicU32::new(u32::MAX);
+
+/// Called from the command loop whenever it has nothing else to do.
+pub fn poll() {
+    let pressed = buttons::b();
+    if pressed && !HELD.load(Ordering::Relaxed) {
+        let next = (MODE.load(Ordering::Relaxed) as usize + 1) % MODES.len();
+        MODE.store(next as u8, Ordering::Relaxed);
+        if MODES[next] == Mode::Off {
+            display::set_background(&[[0; 5]; 5]);
+        }
+        // Right away, not at the next tick
+        DRAWN.store(u32::MAX, Ordering::Relaxed);
+        feedback::signal(feedback::Event::Button);
+    }
+    HELD.store(pressed, Ordering::Relaxed);
+
+    let tick = heartbeat::ticks();
+    if DRAWN.load(Ordering::Relaxed) == tick {
+        return;
+    }
+    DRAWN.store(tick, Ordering::Relaxed);
+    let image = match MODES[MODE.load(Ordering::Relaxed) as usize] {
+        Mode::Off => return,
+        Mode::Animation => roulette::frame(uptime::millis() as u32, LIT),
+        Mode::Dashboard => dashboard(),
+    };
+    display::set_background(&image);
+}
+
+/// `value` within `min..=max` as a bar of up to 5 LEDs from the bottom of
+/// column `col`.
+fn bar(image: &mut Image, col: usize, value: i32, min: i32, max: i32) {
+    let height = ((value - min) * 5 / (max - min)).clamp(0, 5) as usize;
+    for row in image.iter_mut().rev().take(height) {
+        row[col] = LIT;
+    }
+}
+
+fn dashboard() -> Image {
+    let mut image = [[0; 5]; 5];
+    let min_mv = battery::MIN_WARN_MV as i32;
+    let max_mv = battery::MAX_WARN_MV as i32;
+    bar(&mut image, 0, onchip::vdd_mv(), min_mv, max_mv);
+    bar(
+        &mut image,
+        2,
+        onchip::temperature(),
+        MIN_CELSIUS,
+        MAX_CELSIUS,
+    );
+    for (row, &subsystem) in image.iter_mut().zip(health::OPTIONAL.iter()) {
+        if health::require(subsystem).is_ok() {
+            row[4] = LIT;
+        }
+    }
+    image
+}
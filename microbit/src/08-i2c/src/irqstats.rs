@@ -0,0 +1,165 @@
+//! "irqstats": how long each interrupt waited before its handler ran.
+//!
+//! TIMER2 runs freely at 1 MHz. A PPI channel per handled event captures
+//! it into one of its CC registers the moment the event happens, without
+//! the CPU, and [`enter`] captures it again first thing in the handler. The
+//! difference is the latency, whatever held the interrupt off: a critical
+//! section, another handler, or a flash write stalling the CPU. Each
+//! handler keeps its largest latency and its number of runs.
+//!
+//! Adding a handler takes an [`Irq`] variant with its event in [`init`],
+//! and one call to [`enter`] at the top of the handler.
+//!
+//! On the nRF51, TIMER2 only counts to 16 bits, so a latency beyond 65 ms
+//! reads as what's left after wrapping. A handler that runs without its
+//! event having captured anything, such as the first TIMER1 run before a
+//! compare, counts as a run without a latency.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+use microbit::pac;
+
+use crate::claims::{self, Owner, Resource};
+use crate::power::Peripheral;
+
+pub const USAGE: &str = "irqstats [reset]";
+
+/// 16 MHz / 2^4
+const PRESCALER: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Irq {
+    /// The display refresh
+    Timer1,
+    /// The heartbeat tick
+    Rtc0,
+    /// The power-fail warning
+    PowerClock,
+}
+
+const IRQS: [Irq; 3] = [Irq::Timer1, Irq::Rtc0, Irq::PowerClock];
+
+const OWNER: Owner = Owner::permanent("irqstats");
+
+/// The CC register [`enter`] captures into; the others are one per [`Irq`].
+const ENTRY_CC: usize = 3;
+
+static MAX_US: [AtomicU32; 3] = [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)];
+static RUNS: [AtomicU32; 3] = [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)];
+
+impl Irq {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Irq::Timer1 => "TIMER1",
+            Irq::Rtc0 => "RTC0",
+            Irq::PowerClock => "POWER_CLOCK",
+        }
+    }
+}
+
+fn timer() -> &'static pac::timer0::RegisterBlock {
+    unsafe { &*pac::TIMER2::ptr() }
+}
+
+fn address<T>(register: &T) -> u32 {
+    register as *const T as u32
+}
+
+/// Start the timer and connect the events to it. Call this before the
+/// handlers' own `init`s, so that their first events are caught too.
+pub fn init(timer2: pac::TIMER2) {
+    claims::take(Resource::Peripheral(Peripheral::Timer2), OWNER);
+    timer2.mode.write(|w| unsafe { w.bits(0) });
+    timer2.bitmode.write(|w| unsafe { w.bits(chip::BITMODE) });
+    timer2.prescaler.write(|w| unsafe { w.bits(PRESCALER) });
+    timer2.tasks_clear.write(|w| unsafe { w.bits(1) });
+    timer2.tasks_start.write(|w| unsafe { w.bits(1) });
+
+    let ppi = unsafe { &*pac::PPI::ptr() };
+    let (timer1, rtc0, power) = unsafe {
+        (
+            &*pac::TIMER1::ptr(),
+            &*pac::RTC0::ptr(),
+            &*pac::POWER::ptr(),
+        )
+    };
+    // The display driver interrupts on both of its compares
+    let events = [
+        (address(&timer1.events_compare[0]), Irq::Timer1),
+        (address(&timer1.events_compare[1]), Irq::Timer1),
+        (address(&rtc0.events_compare[0]), Irq::Rtc0),
+        (address(&power.events_pofwarn), Irq::PowerClock),
+    ];
+    for (channel, (event, irq)) in events.iter().enumerate() {
+        claims::take(Resource::PpiChannel(channel as u8), OWNER);
+        let task = address(&timer2.tasks_capture[irq.index()]);
+        ppi.ch[channel].eep.write(|w| unsafe { w.bits(*event) });
+        ppi.ch[channel].tep.write(|w| unsafe { w.bits(task) });
+    }
+    let channels = (1 << events.len()) - 1;
+    ppi.chenset.write(|w| unsafe { w.bits(channels) });
+}
+
+/// Called first thing in `irq`'s handler.
+pub fn enter(irq: Irq) {
+    let timer = timer();
+    timer.tasks_capture[ENTRY_CC].write(|w| unsafe { w.bits(1) });
+    let now = timer.cc[ENTRY_CC].read().bits();
+    let event = timer.cc[irq.index()].read().bits();
+    let latency = now.wrapping_sub(event) & chip::MASK;
+    // An event newer than the entry capture, which happens when the next
+    // one comes in between the two reads, would look like a wrap
+    let i = irq.index();
+    if event != 0 && latency <= chip::MASK / 2 && latency > MAX_US[i].load(Ordering::Relaxed) {
+        MAX_US[i].store(latency, Ordering::Relaxed);
+    }
+    // Only this handler writes its count, and the nRF51 has no atomic
+    // increment
+    RUNS[i].store(
+        RUNS[i].load(Ordering::Relaxed).wrapping_add(1),
+        Ordering::Relaxed,
+    );
+}
+
+/// "irqstats reset"
+pub fn reset() {
+    cortex_m::interrupt::free(|_| {
+        for (max, runs) in MAX_US.iter().zip(RUNS.iter()) {
+            max.store(0, Ordering::Relaxed);
+            runs.store(0, Ordering::Relaxed);
+        }
+    });
+}
+
+/// "irqstats"
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    writeln!(w, "Interrupt latency since boot or reset:")?;
+    for irq in IRQS {
+        let i = irq.index();
+        writeln!(
+            w,
+            "  {:<11} max {:>5} us, {} runs",
+            irq.name(),
+            MAX_US[i].load(Ordering::Relaxed),
+            RUNS[i].load(Ordering::Relaxed)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    /// TIMER1 and TIMER2 only go up to 16 bits on the nRF51
+    pub const BITMODE: u32 = 0;
+    pub const MASK: u32 = 0xffff;
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    pub const BITMODE: u32 = 3;
+    pub const MASK: u32 = u32::MAX;
+}
@@ -0,0 +1,57 @@
+//! The modules with logic worth testing away from the board, built again
+//! for the host to run their tests:
+//!
+//! ``` console
+//! $ cargo test --lib
+//! ```
+//!
+//! Outside of the tests this library is empty. The firmware is `main.rs`,
+//! which declares every module itself, the way 99-final builds it too.
+//! Whatever only the firmware uses goes unused here, and the modules that
+//! reach down to the board find a stand-in in `host/` instead. 07-uart has
+//! no host build of its own, so the files it shares, and the one it keeps
+//! to itself, get their tests run from here too.
+
+#![cfg_attr(not(test), no_std)]
+#![cfg(test)]
+#![allow(dead_code)]
+// References are twice the size they are on the board, so errors that
+// hold a few of them look bigger than they are
+#![allow(clippy::result_large_err)]
+
+mod axes;
+mod block;
+mod calc;
+mod confirm;
+mod consistency;
+#[path = "../../07-uart/src/errors.rs"]
+mod errors;
+mod filter;
+mod format;
+mod frame;
+mod gravity;
+#[path = "../../07-uart/src/line.rs"]
+mod line;
+mod lineend;
+mod menu;
+mod odometer;
+mod poller;
+mod provision;
+mod recent;
+#[path = "../../07-uart/src/reverse.rs"]
+mod reverse;
+mod source;
+mod status;
+mod tilt;
+mod tour;
+
+#[path = "host/flash.rs"]
+mod flash;
+#[path = "host/heartbeat.rs"]
+mod heartbeat;
+#[path = "host/settings.rs"]
+mod settings;
+#[path = "host/shared.rs"]
+mod shared;
+#[path = "host/uarte_port.rs"]
+mod uarte_port;
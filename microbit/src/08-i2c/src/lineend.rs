@@ -0,0 +1,154 @@
+//! What ends a line of output, for "lineend". The port hands everything it
+//! writes to [`write_str`], which spells out every "\n" as the setting says,
+//! so nothing else writes a "\r" or needs to know.
+
+use core::fmt;
+
+/// What ends a line of output. Input lines end at a CR, an LF or both,
+/// whatever this says.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnd {
+    CrLf,
+    Lf,
+}
+
+pub const LINE_END_USAGE: &str = "lineend crlf|lf";
+
+impl LineEnd {
+    pub fn from_name(name: &str) -> Option<LineEnd> {
+        match name {
+            "crlf" => Some(LineEnd::CrLf),
+            "lf" => Some(LineEnd::Lf),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnd::CrLf => "crlf",
+            LineEnd::Lf => "lf",
+        }
+    }
+
+    pub fn encode(self) -> u32 {
+        self as u32
+    }
+
+    /// Anything unrecognized decodes as [`LineEnd::CrLf`].
+    pub fn decode(word: u32) -> LineEnd {
+        match word {
+            1 => LineEnd::Lf,
+            _ => LineEnd::CrLf,
+        }
+    }
+
+    /// The only place a line ending gets spelled out.
+    fn end_line(self) -> &'static str {
+        match self {
+            LineEnd::CrLf => "\r\n",
+            LineEnd::Lf => "\n",
+        }
+    }
+}
+
+/// Write `s` through `raw`, with every "\n" in it ending the line the way
+/// `line_end` does.
+pub fn write_str(
+    line_end: LineEnd,
+    s: &str,
+    mut raw: impl FnMut(&str) -> fmt::Result,
+) -> fmt::Result {
+    let mut lines = s.split('\n');
+    if let Some(first) = lines.next() {
+        raw(first)?;
+    }
+    for line in lines {
+        raw(line_end.end_line())?;
+        raw(line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axes::{Axes, Sample};
+    use crate::format::{self, OutputFormat};
+    use crate::menu::{self, Menu};
+    use crate::recent::Kind;
+    use crate::status::StatusLeds;
+    use core::fmt::Write;
+
+    /// The other end of the serial port, by way of the same translation.
+    struct Terminal {
+        line_end: LineEnd,
+        seen: std::string::String,
+    }
+
+    impl fmt::Write for Terminal {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let line_end = self.line_end;
+            write_str(line_end, s, |part| {
+                self.seen.push_str(part);
+                Ok(())
+            })
+        }
+    }
+
+    /// What a few commands print, the way they print it.
+    fn commands(w: &mut Terminal) -> fmt::Result {
+        menu::banner(w)?;
+        menu::help(w)?;
+        menu::list(w, Menu::Accel)?;
+        StatusLeds::default().export(w)?;
+        let sample = Sample {
+            axes: Axes::ALL,
+            values: [12, -3, 1004],
+        };
+        format::write_header(w, Kind::Accel, Some("ms"), Axes::ALL)?;
+        for output in [OutputFormat::Human, OutputFormat::Csv] {
+            format::write_sample(w, output, Kind::Accel, Some(&1234), sample)?;
+        }
+        write!(w, "\n\nblank lines\n")
+    }
+
+    fn seen(line_end: LineEnd) -> std::string::String {
+        let mut terminal = Terminal {
+            line_end,
+            seen: std::string::String::new(),
+        };
+        commands(&mut terminal).unwrap();
+        terminal.seen
+    }
+
+    #[test]
+    fn lf_never_sends_a_carriage_return() {
+        let seen = seen(LineEnd::Lf);
+        assert!(seen.lines().count() > 40);
+        assert!(!seen.contains('\r'));
+    }
+
+    #[test]
+    fn crlf_sends_a_carriage_return_with_every_line_feed_and_only_then() {
+        let seen = seen(LineEnd::CrLf);
+        assert_eq!(seen.matches("\r\n").count(), seen.matches('\n').count());
+        assert_eq!(seen.matches("\r\n").count(), seen.matches('\r').count());
+        assert!(seen.ends_with("blank lines\r\n"));
+    }
+
+    #[test]
+    fn the_setting_changes_nothing_but_the_line_ends() {
+        assert_eq!(seen(LineEnd::CrLf).replace("\r\n", "\n"), seen(LineEnd::Lf));
+    }
+
+    #[test]
+    fn text_between_the_line_ends_goes_through_in_one_piece() {
+        let mut parts = std::vec::Vec::new();
+        write_str(LineEnd::CrLf, "one\ntwo\n", |part| {
+            parts.push(part.to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(parts, ["one", "\r\n", "two", "\r\n", ""]);
+    }
+}
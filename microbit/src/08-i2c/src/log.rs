@@ -0,0 +1,93 @@
+//! Timestamped RTT logging.
+//!
+//! Every line logged with [`log!`] starts with the milliseconds since boot,
+//! right-aligned in a field wide enough for any `u32`, so that the RTT log
+//! can be lined up with the serial output afterwards: "marker" puts the
+//! same text with the same timestamp into both, see [`marker`].
+//!
+//! Lines logged in a burst mostly share their millisecond, and so their
+//! prefix, with the line before. It is formatted once per millisecond and
+//! kept.
+//!
+//! With the "defmt" feature the lines go out through defmt-rtt instead, to
+//! be decoded by `probe-rs run`. defmt puts the time in front itself, from
+//! the same clock, and the text of a line is formatted on the board as
+//! before: what gets logged here is mostly `Display` and not
+//! `defmt::Format`. Those that are can be logged with `defmt` directly.
+
+use core::fmt::{self, Write};
+use heapless::String;
+#[cfg(not(feature = "defmt"))]
+use rtt_target::{rprintln, rtt_init_print};
+
+use crate::shared::Shared;
+use crate::uptime;
+
+/// "[", ten digits, "] "
+const PREFIX_LEN: usize = 13;
+
+pub type Prefix = String<PREFIX_LEN>;
+
+/// The last prefix, and the millisecond it is for.
+static CACHE: Shared<Option<(u32, Prefix)>> = Shared::new(None);
+
+/// What a line logged right now starts with.
+pub fn prefix() -> Prefix {
+    let ms = uptime::millis() as u32;
+    let cached = CACHE.with(|cache| match cache {
+        Some((cached_ms, prefix)) if *cached_ms == ms => Some(prefix.clone()),
+        _ => None,
+    });
+    cached.unwrap_or_else(|| {
+        let mut prefix = Prefix::new();
+        // Always fits, a u32 has ten digits at most
+        write!(prefix, "[{:>10}] ", ms).unwrap();
+        CACHE.with(|cache| *cache = Some((ms, prefix.clone())));
+        prefix
+    })
+}
+
+/// Like `rprintln!`, with the time in front.
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log::line(format_args!($($arg)*))
+    };
+}
+pub(crate) use log;
+
+/// Set up the RTT channel the log goes out on.
+#[cfg(not(feature = "defmt"))]
+pub fn init() {
+    rtt_init_print!();
+}
+
+#[cfg(not(feature = "defmt"))]
+pub fn line(args: fmt::Arguments<'_>) {
+    rprintln!("{}{}", prefix(), args);
+}
+
+/// defmt-rtt sets up its channel itself.
+#[cfg(feature = "defmt")]
+pub fn init() {}
+
+#[cfg(feature = "defmt")]
+defmt::timestamp!("{=u64:ms}", uptime::millis());
+
+#[cfg(feature = "defmt")]
+pub fn line(args: fmt::Arguments<'_>) {
+    defmt::println!("{}", defmt::Display2Format(&args));
+}
+
+pub const MARKER_USAGE: &str = "marker <text>";
+
+/// "marker": `text` on the RTT log and on `w`, with one timestamp for both.
+pub fn marker<W: Write>(w: &mut W, text: &str) -> fmt::Result {
+    let prefix = prefix();
+    #[cfg(not(feature = "defmt"))]
+    rprintln!("{}marker {}", prefix, text);
+    #[cfg(feature = "defmt")]
+    // defmt's own timestamp comes a moment later, the one that matches is
+    // the text's
+    defmt::println!("{=str}marker {=str}", prefix.as_str(), text);
+    writeln!(w, "{}marker {}", prefix, text)
+}
@@ -3,209 +3,2981 @@
 
 use core::fmt::Write;
 use cortex_m_rt::entry;
+#[cfg(feature = "defmt")]
+use defmt_rtt as _;
+use embedded_hal::blocking::i2c;
 use embedded_hal::serial::Read;
-use heapless::Vec;
-use microbit::hal::uarte::{self, Baudrate, Parity};
-use microbit::pac::UARTE0;
+use heapless::{String, Vec};
+use microbit::hal::clocks::Clocks;
+#[cfg(all(feature = "defmt", not(feature = "panic-serial")))]
+use panic_probe as _;
+#[cfg(not(any(feature = "defmt", feature = "panic-serial")))]
 use panic_rtt_target as _;
-use rtt_target::{rprintln, rtt_init_print};
 
 #[cfg(feature = "v1")]
-use microbit::{hal::twi, pac::twi0::frequency::FREQUENCY_A};
+use microbit::{
+    hal::twi,
+    hal::uart::{self, Baudrate, Parity},
+    pac::twi0::frequency::FREQUENCY_A,
+};
 
 #[cfg(feature = "v2")]
-use microbit::{hal::twim, pac::twim0::frequency::FREQUENCY_A};
+use microbit::{
+    hal::twim,
+    hal::uarte::{self, Baudrate, Parity},
+    pac::twim0::frequency::FREQUENCY_A,
+};
 
-use lsm303agr::{AccelOutputDataRate, Lsm303agr};
+use lsm303agr::{
+    interface::I2cInterface, mode, AccelOutputDataRate, AccelScale, Lsm303agr, MagOutputDataRate,
+    Measurement,
+};
 
+mod abort;
+mod average;
+mod axes;
+mod ball;
+mod battery;
+mod blinkout;
+mod block;
+mod board;
+mod bus;
+mod buttons;
+#[cfg(feature = "calc")]
+mod calc;
+mod calibration;
+#[cfg(feature = "graphics")]
+mod canvas;
+mod capture;
+mod claims;
+mod compass;
+mod confirm;
+mod consistency;
+mod cues;
+#[cfg(feature = "demo")]
+mod demo;
+mod display;
+mod drdy;
+#[path = "../../07-uart/src/errors.rs"]
+mod errors;
+mod fault;
+mod feedback;
+mod filter;
+mod flash;
+mod font;
+mod format;
+mod frame;
+mod gravity;
+mod heading;
+mod health;
+mod heartbeat;
+mod i2ctrace;
+#[cfg(feature = "idle")]
+mod idle;
+mod irqstats;
+#[path = "../../07-uart/src/line.rs"]
+mod line;
+mod lineend;
+mod log;
+mod menu;
+mod odometer;
+mod odr;
+mod onchip;
+mod orientation;
+#[cfg(feature = "panic-serial")]
+#[path = "../../07-uart/src/panic_serial.rs"]
+mod panic_serial;
+mod pof;
+mod poller;
+mod power;
+mod powersave;
+mod progress;
+mod provision;
+mod punch;
+mod raw;
+// Only the pulse itself, played on the display here rather than the
+// blocking one
+#[allow(dead_code)]
+#[path = "../../05-led-roulette/src/pulse.rs"]
+mod pulse;
+mod recent;
+#[cfg(feature = "replay")]
+mod replay;
+mod reply;
+#[cfg(any(feature = "demo", feature = "idle"))]
+mod roulette;
+mod scan;
 mod serial_setup;
-use serial_setup::UartePort;
+mod settings;
+mod shared;
+#[cfg(feature = "simulate")]
+mod sim;
+mod source;
+mod stamp;
+mod stats;
+mod status;
+mod temperature;
+mod textlog;
+mod tilt;
+#[cfg(feature = "demo")]
+mod tour;
+#[cfg(feature = "replay")]
+mod trace;
+#[cfg(feature = "v2")]
+#[path = "../../07-uart/src/serial_setup.rs"]
+mod uarte_port;
+mod uptime;
+mod watch;
+mod watchdog;
+use axes::{Axes, Sample};
+use bus::{BusError, Guarded};
+use errors::SerialError;
+use health::{InitError, Subsystem, Unavailable};
+use i2ctrace::Traced;
+use log::log;
+use menu::Menu;
+use serial_setup::{LineEnd, SerialPort};
+use settings::Settings;
+use source::Source;
+use status::Role;
+use watchdog::TimedOut;
 
-#[derive(Debug)]
-enum FillBufferError {
-    PushError(u8),
-    UarteError(microbit::hal::uarte::Error),
-    Write(core::fmt::Error),
+#[cfg(feature = "v1")]
+type I2c = twi::Twi<microbit::pac::TWI0>;
+
+#[cfg(feature = "v2")]
+type I2c = twim::Twim<microbit::pac::TWIM0>;
+
+/// The bus the same way whatever chip is underneath
+type Bus = Guarded<Traced<I2c>>;
+
+type Lsm = Lsm303agr<I2cInterface<Bus>, mode::MagOneShot>;
+type SensorError = fault::Error<<I2c as i2c::Write>::Error>;
+
+/// Whether `err` is only a device not answering at the address, or not
+/// taking a byte, see [`scan`]. The TWI doesn't tell that apart from
+/// anything else.
+#[cfg(feature = "v1")]
+fn is_nack(_: &twi::Error) -> bool {
+    true
+}
+
+#[cfg(feature = "v2")]
+fn is_nack(err: &twim::Error) -> bool {
+    matches!(err, twim::Error::AddressNack | twim::Error::DataNack)
 }
 
-impl From<u8> for FillBufferError {
-    fn from(value: u8) -> Self {
-        FillBufferError::PushError(value)
+/// The LSM303AGR's data rate at boot, see [`odr`]. Samples from anywhere
+/// else keep to it whatever the sensor is set to.
+const DATA_RATE_HZ: u32 = odr::Rates::START.accel_hz();
+
+/// Where the samples come from.
+enum Feed {
+    Live,
+    #[cfg(feature = "simulate")]
+    Sim(sim::Sim),
+    #[cfg(feature = "replay")]
+    Replay(replay::Replay),
+}
+
+impl Feed {
+    /// What the output gets tagged with when it isn't from the LSM303AGR,
+    /// see [`reply`].
+    fn tag(&self) -> Option<&'static str> {
+        match self {
+            Feed::Live => None,
+            #[cfg(feature = "simulate")]
+            Feed::Sim(_) => Some("sim"),
+            #[cfg(feature = "replay")]
+            Feed::Replay(_) => Some("replay"),
+        }
+    }
+
+    /// The next raw accelerometer sample, `None` when it has to be read
+    /// from the LSM303AGR.
+    fn next_accel(&mut self) -> Option<[i32; 3]> {
+        let sample = match self {
+            Feed::Live => None,
+            #[cfg(feature = "simulate")]
+            Feed::Sim(sim) => Some(sim.accel()),
+            #[cfg(feature = "replay")]
+            Feed::Replay(replay) => Some(replay.next_accel()),
+        };
+        if sample.is_some() {
+            watch::delay_us(1_000_000 / DATA_RATE_HZ);
+        }
+        sample
+    }
+
+    /// Like [`Feed::next_accel`].
+    fn next_mag(&mut self) -> Option<[i32; 3]> {
+        let sample = match self {
+            Feed::Live => None,
+            #[cfg(feature = "simulate")]
+            Feed::Sim(sim) => Some(sim.mag()),
+            #[cfg(feature = "replay")]
+            Feed::Replay(replay) => Some(replay.next_mag()),
+        };
+        if sample.is_some() {
+            watch::delay_us(1_000_000 / DATA_RATE_HZ);
+        }
+        sample
     }
 }
 
-impl From<core::fmt::Error> for FillBufferError {
-    fn from(value: core::fmt::Error) -> Self {
-        FillBufferError::Write(value)
+/// The LSM303AGR, along with the smoothing applied to its accelerometer
+/// and the offsets taken off its magnetometer.
+struct Sensor {
+    /// `None` if it failed to start
+    lsm: Option<Lsm>,
+    filter: filter::Filter,
+    calibration: calibration::Calibration,
+    /// What the LSM303AGR is set to, see [`odr`]
+    rates: odr::Rates,
+    feed: Feed,
+}
+
+impl Sensor {
+    fn lsm(&mut self) -> Result<&mut Lsm, Unavailable> {
+        self.lsm.as_mut().ok_or(Unavailable(Subsystem::Sensor))
+    }
+
+    /// Everything a raw accelerometer sample goes through before it is
+    /// printed or used, wherever it came from.
+    fn process_accel(&mut self, raw: [i32; 3]) -> Measurement {
+        let [x, y, z] = self.filter.apply(raw);
+        Measurement { x, y, z }
+    }
+
+    /// Everything a raw magnetometer sample goes through. The offsets are
+    /// the board's own, so only live samples have them taken off.
+    fn process_mag(&self, raw: [i32; 3]) -> Measurement {
+        let [x, y, z] = if matches!(self.feed, Feed::Live) {
+            self.calibration.apply(raw)
+        } else {
+            raw
+        };
+        Measurement { x, y, z }
+    }
+
+    /// How many samples of `kind` a second come from wherever they come
+    /// from.
+    fn rate_hz(&self, kind: recent::Kind) -> u32 {
+        if !matches!(self.feed, Feed::Live) {
+            return DATA_RATE_HZ;
+        }
+        match kind {
+            recent::Kind::Accel => self.rates.accel_hz(),
+            recent::Kind::Mag => self.rates.mag_hz(),
+        }
+    }
+
+    /// Switch to samples from `feed`.
+    #[cfg(any(feature = "simulate", feature = "replay"))]
+    fn set_feed(&mut self, feed: Feed, filter: filter::Kind) {
+        // Starting over, the filter's history is of the other source
+        self.filter = filter::Filter::new(filter);
+        self.feed = feed;
+    }
+}
+
+const LINE_LEN: usize = 64;
+
+const MAX_PINGS: u8 = 100;
+const PING_INTERVAL_MS: u32 = 100;
+
+/// What goes wrong on the console's serial port
+type PortError = serial_setup::Error;
+
+/// What reading commands takes of a serial port: bytes in and out, going
+/// wrong the same way both ways, and text out. [`SerialPort`] is one.
+trait Console:
+    core::fmt::Write
+    + embedded_hal::serial::Read<u8, Error = ConsoleError<Self>>
+    + embedded_hal::serial::Write<u8>
+{
+}
+
+impl<S> Console for S where
+    S: core::fmt::Write
+        + embedded_hal::serial::Read<u8, Error = ConsoleError<S>>
+        + embedded_hal::serial::Write<u8>
+{
+}
+
+/// How a [`Console`] goes wrong
+type ConsoleError<S> = <S as embedded_hal::serial::Write<u8>>::Error;
+
+/// What the line said that made no sense, as much of it as fits in a
+/// [`WORD_LEN`] so that the error doesn't hold on to the line.
+#[derive(Debug)]
+struct Word {
+    text: String<WORD_LEN>,
+    cut: bool,
+}
+
+const WORD_LEN: usize = 16;
+
+impl Word {
+    fn copy(text: &str) -> Word {
+        let mut word = Word {
+            text: String::new(),
+            cut: false,
+        };
+        for c in text.chars() {
+            if word.text.push(c).is_err() {
+                word.cut = true;
+                break;
+            }
+        }
+        word
     }
 }
 
-impl From<microbit::hal::uarte::Error> for FillBufferError {
-    fn from(value: microbit::hal::uarte::Error) -> Self {
-        FillBufferError::UarteError(value)
+impl core::fmt::Display for Word {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}{}", self.text, if self.cut { "..." } else { "" })
     }
 }
 
+/// `E` is how the serial port goes wrong, see [`Console`].
 #[derive(Debug)]
-enum Error<'a> {
-    Uarte(microbit::hal::uarte::Error),
-    Push(u8),
-    Unrecognized(&'a str),
-    Utf8(core::str::Utf8Error),
-    Write(core::fmt::Error),
+enum Error<E = PortError> {
+    Interrupted,
+    Serial(SerialError<E>),
+    TooLong,
+    Ambiguous(menu::Ambiguous),
+    Unrecognized(Word),
+    UnknownPeripheral(Word),
+    UnknownSource(Word),
+    Usage(&'static str),
+    /// A command that needs the serial port to itself, after "log:"
+    Unloggable,
 }
 
-impl<'a> From<u8> for Error<'a> {
-    fn from(value: u8) -> Self {
-        return Error::Push(value);
+impl<E> From<SerialError<E>> for Error<E> {
+    fn from(value: SerialError<E>) -> Self {
+        Error::Serial(value)
     }
 }
 
-impl<'a> From<microbit::hal::uarte::Error> for Error<'a> {
-    fn from(value: microbit::hal::uarte::Error) -> Self {
-        return Error::Uarte(value);
+impl<E> From<line::Error<E>> for Error<E> {
+    fn from(value: line::Error<E>) -> Self {
+        Error::Serial(value.into())
     }
 }
 
-impl<'a> From<core::str::Utf8Error> for Error<'a> {
+impl<E> From<core::str::Utf8Error> for Error<E> {
     fn from(value: core::str::Utf8Error) -> Self {
-        return Error::Utf8(value);
+        Error::Serial(value.into())
+    }
+}
+
+impl<E> From<core::fmt::Error> for Error<E> {
+    fn from(value: core::fmt::Error) -> Self {
+        Error::Serial(value.into())
     }
 }
 
-impl<'a> From<FillBufferError> for Error<'a> {
-    fn from(value: FillBufferError) -> Self {
+impl<E> From<watch::ParseError<'_>> for Error<E> {
+    fn from(value: watch::ParseError<'_>) -> Self {
         match value {
-            FillBufferError::PushError(err) => Error::Push(err),
-            FillBufferError::UarteError(err) => Error::Uarte(err),
-            FillBufferError::Write(err) => Error::Write(err),
+            watch::ParseError::Usage => Error::Usage(watch::USAGE),
+            watch::ParseError::UnknownSource(name) => Error::UnknownSource(Word::copy(name)),
         }
     }
 }
 
-impl<'a> core::fmt::Display for Error<'a> {
+impl<E: core::fmt::Debug> core::fmt::Display for Error<E> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Error::Uarte(err) => write!(f, "serial communication: {:?}", err),
-            Error::Push(_) => write!(f, "command word too long"),
-            Error::Unrecognized(err) => write!(f, "unrecognized command: {}", err),
-            Error::Utf8(err) => write!(f, "utf8 conversion: {}", err),
-            Error::Write(err) => write!(f, "formatted write: {}", err),
+            Error::Interrupted => write!(f, "^C"),
+            Error::Serial(err) => write!(f, "{}", err),
+            Error::TooLong => write!(f, "command too long"),
+            Error::Ambiguous(err) => write!(f, "{}", err),
+            Error::Unrecognized(err) => {
+                write!(f, "unrecognized command: {}", err)?;
+                match menu::suggest(&err.text) {
+                    Some(name) => write!(f, ", did you mean \"{}\"?", name),
+                    None => Ok(()),
+                }
+            }
+            Error::UnknownPeripheral(err) => write!(f, "unknown peripheral: {}", err),
+            Error::UnknownSource(err) => write!(f, "unknown source: {}", err),
+            Error::Usage(usage) => write!(f, "usage: {}", usage),
+            Error::Unloggable => write!(f, "commands that ask for confirmation can't be logged"),
         }
     }
 }
 
+/// Why a command handler stopped before finishing.
+#[derive(Debug)]
+enum Stop {
+    TimedOut,
+    Interrupted,
+    NotConfirmed,
+    Unavailable(Unavailable),
+    Sensor(SensorError),
+    NoData,
+}
+
+impl From<TimedOut> for Stop {
+    fn from(_: TimedOut) -> Self {
+        Stop::TimedOut
+    }
+}
+
+impl From<Unavailable> for Stop {
+    fn from(value: Unavailable) -> Self {
+        Stop::Unavailable(value)
+    }
+}
+
+/// Where a command's output goes, see [`textlog`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sink {
+    Serial,
+    Log,
+}
+
 enum Command {
     Magnetometer,
     Accelerometer,
+    Stream(recent::Kind),
+    /// "avg", of this many readings
+    Average(recent::Kind, u32),
+    /// Tilt-compensated, or only right with the board lying flat
+    Heading(bool),
+    CompassShow,
+    Ball,
+    Punch,
+    Scan,
+    I2c(raw::Request),
+    Temperature(bool),
+    OdrShow,
+    OdrAccel(AccelOutputDataRate),
+    OdrMag(MagOutputDataRate),
+    Calibrate(Option<calibration::Calibration>),
+    CalibrationClear,
+    PowerReport,
+    PofStatus,
+    PowerOff(power::Peripheral),
+    Heartbeat(bool),
+    Poll,
+    PollStream,
+    PollInterval(poller::Device, u32),
+    AudioCues(bool),
+    Uptime,
+    Status,
+    TableCheck,
+    Marker(String<LINE_LEN>),
+    I2cTrace(bool),
+    I2cTraceDump,
+    IrqStats,
+    IrqStatsReset,
+    ConfigExport,
+    Name(Option<board::Name>),
+    Version,
+    Help,
+    Capture(usize),
+    CaptureDump(usize),
+    CaptureClear,
+    LogRead,
+    SensorHealth,
+    SensorHealthEnable(bool),
+    SensorHealthCapture,
+    SensorHealthReference(u32),
+    SensorHealthBands(u8, u8, u8),
+    ProvisionExport,
+    ProvisionImport,
+    #[cfg(feature = "calc")]
+    Calc(String<LINE_LEN>),
+    Watch(watch::Watch),
+    Filter(filter::Kind),
+    LinearAccel,
+    TiltFilter(u8),
+    TiltStream,
+    /// Streamed, and the threshold in mg
+    Orientation(bool, i32),
+    Brightness(u8),
+    PowerSave(Option<powersave::Mode>),
+    Gamma(bool),
+    StatusLed(Role, Option<(u8, u8)>),
+    Blinkout(u16),
+    BattWarn(u16),
+    Axes(Axes),
+    TimeFormat(stamp::Format),
+    LineEnd(LineEnd),
+    Output(reply::Mode),
+    Format(format::OutputFormat),
+    Recent(recent::Kind, usize),
+    #[cfg(feature = "simulate")]
+    Simulate(Option<sim::Sim>),
+    #[cfg(feature = "replay")]
+    Replay(bool),
+    Ping(u8),
+    #[cfg(feature = "demo")]
+    Demo,
+    ConfigReset,
+    OdometerReset,
+    FlashErase,
+}
+
+impl Command {
+    /// How long the command may run before the watchdog aborts it, if it
+    /// isn't meant to run until the user stops it. Reading at `rates`, see
+    /// [`odr`].
+    fn timeout_ms(&self, rates: &odr::Rates) -> Option<u32> {
+        match self {
+            Command::Magnetometer => Some(1_000),
+            // A read of a byte at every address takes 30 ms at 100 kHz, a
+            // single transaction much less
+            Command::Scan | Command::I2c(_) => Some(1_000),
+            // A fresh sample is never more than one sample away
+            Command::Accelerometer => Some(1_000 + rates.accel_period_ms()),
+            // Simulated and replayed readings come no faster than the usual
+            // rate, whatever the sensor is set to
+            Command::Average(kind, count) => {
+                let period_ms = match kind {
+                    recent::Kind::Accel => rates.accel_period_ms(),
+                    recent::Kind::Mag => rates.mag_period_ms(),
+                };
+                Some(1_000 + count * period_ms.max(1_000 / DATA_RATE_HZ))
+            }
+            // Ten readings, and as many of the accelerometer when tilted
+            Command::Heading(_) => Some(2_000 + heading::SAMPLES as u32 * rates.accel_period_ms()),
+            Command::Ping(count) => {
+                Some(watchdog::DEFAULT_TIMEOUT_MS + *count as u32 * PING_INTERVAL_MS)
+            }
+            Command::Calibrate(None) => {
+                Some(watchdog::DEFAULT_TIMEOUT_MS + calibration::TIMEOUT_MS)
+            }
+            #[cfg(feature = "demo")]
+            Command::Demo => None,
+            Command::Watch(_)
+            | Command::Stream(_)
+            | Command::CompassShow
+            | Command::Ball
+            | Command::Punch
+            | Command::LinearAccel
+            | Command::TiltStream
+            | Command::Orientation(true, _)
+            | Command::PollStream
+            | Command::Blinkout(_)
+            | Command::ProvisionImport
+            | Command::Capture(_)
+            | Command::CaptureDump(_) => None,
+            _ => Some(watchdog::DEFAULT_TIMEOUT_MS),
+        }
+    }
+
+    /// Whether the command streams until stopped, which [`feedback`] tells
+    /// apart from one that answers once.
+    fn streams(&self) -> bool {
+        matches!(
+            self,
+            Command::Watch(_)
+                | Command::Stream(_)
+                | Command::CompassShow
+                | Command::Ball
+                | Command::LinearAccel
+                | Command::TiltStream
+                | Command::Orientation(true, _)
+                | Command::PollStream
+                | Command::Capture(_)
+        )
+    }
+
+    /// Whether the command needs a confirmation on the board, see [`confirm`].
+    fn protected(&self) -> bool {
+        matches!(
+            self,
+            Command::ConfigReset
+                | Command::CalibrationClear
+                | Command::OdometerReset
+                | Command::FlashErase
+                | Command::ProvisionImport
+        )
+    }
+}
+
+/// Wait for the next byte, letting the heartbeat know we're idle rather than stuck.
+fn read_byte<S: Read<u8>>(serial: &mut S, sensor: &mut Sensor) -> Result<u8, S::Error> {
+    loop {
+        match serial.read() {
+            Ok(byte) => return Ok(byte),
+            Err(nb::Error::WouldBlock) => {
+                heartbeat::feed();
+                odometer::tick();
+                battery::poll();
+                powersave::poll();
+                i2ctrace::drain();
+                sample_idle(sensor);
+                #[cfg(feature = "idle")]
+                idle::poll();
+            }
+            Err(nb::Error::Other(err)) => return Err(err),
+        }
+    }
+}
+
+/// A reading now and then for [`consistency`], while nothing else reads
+/// the sensor. Errors are left for the next command to run into.
+fn sample_idle(sensor: &mut Sensor) {
+    let lsm = match (&sensor.feed, sensor.lsm.as_mut()) {
+        (Feed::Live, Some(lsm)) => lsm,
+        _ => return,
+    };
+    if !consistency::idle_due() {
+        return;
+    }
+    if let Ok(status) = lsm.accel_status() {
+        if let (true, Ok(data)) = (status.xyz_new_data, lsm.accel_data()) {
+            consistency::accel([data.x, data.y, data.z]);
+        }
+    }
+    // One shot: this starts the next measurement, and a second later
+    // picks it up
+    if let Ok(data) = lsm.mag_data() {
+        consistency::mag([data.x, data.y, data.z]);
+    }
 }
 
-fn try_fill_buffer_with_echo(
-    serial: &mut UartePort<UARTE0>,
-    buffer: &mut Vec<u8, 16>,
-) -> Result<(), FillBufferError> {
+/// Read one line into `buffer`, with backspace and Ctrl-U to edit it, see
+/// [`line`]. A line ending in `\` continues on the next one, after a "... "
+/// prompt, up to [`LINE_LEN`] in all; the editing doesn't reach back into
+/// the parts before, and Ctrl-C drops every part of it. Lines end in CR, LF
+/// or CR LF, and an empty one is skipped without a word.
+fn try_fill_buffer_with_echo<S: Console>(
+    serial: &mut S,
+    sensor: &mut Sensor,
+    buffer: &mut Vec<u8, LINE_LEN>,
+) -> Result<(), Error<ConsoleError<S>>> {
     buffer.clear();
+    // Where the part on the terminal's current line starts
+    let mut start = 0;
     loop {
-        let byte = nb::block!(serial.read())?;
-        if byte == b'\r' {
-            writeln!(serial, "\r")?;
-            return Ok(());
+        let byte = read_byte(serial, sensor).map_err(SerialError::Uarte)?;
+        match line::input(byte) {
+            line::Input::Byte(abort::CTRL_C) => {
+                abort::clear();
+                return Err(Error::Interrupted);
+            }
+            line::Input::Byte(byte) => {
+                line::edit(serial, buffer, start, byte)?;
+            }
+            line::Input::Swallowed => {}
+            line::Input::End if buffer.is_empty() => {}
+            line::Input::End => {
+                writeln!(serial)?;
+                if buffer.last() == Some(&b'\\') {
+                    buffer.pop();
+                    write!(serial, "... ")?;
+                    start = buffer.len();
+                    continue;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Turn the line in `buffer` into the flat command it stands for in `menu`,
+/// see [`menu`]. `false` if it only moved to another menu.
+fn resolve_menu<E>(menu: &mut Menu, buffer: &mut Vec<u8, LINE_LEN>) -> Result<bool, Error<E>> {
+    let line = core::str::from_utf8(buffer)?;
+    let mut flat: String<LINE_LEN> = String::new();
+    match menu::resolve(*menu, line).map_err(Error::Ambiguous)? {
+        menu::Resolved::Enter(next) => {
+            *menu = next;
+            return Ok(false);
+        }
+        menu::Resolved::Line(head, rest) => {
+            flat.push_str(head).map_err(|()| Error::TooLong)?;
+            if !rest.is_empty() {
+                flat.push(' ').map_err(|()| Error::TooLong)?;
+                flat.push_str(rest).map_err(|()| Error::TooLong)?;
+            }
+        }
+    }
+    buffer.clear();
+    // Same capacity
+    buffer.extend_from_slice(flat.as_bytes()).unwrap();
+    Ok(true)
+}
+
+/// Take a leading "log:" off the line in `buffer`.
+fn take_sink(buffer: &mut Vec<u8, LINE_LEN>) -> Sink {
+    let rest = match buffer.strip_prefix(textlog::PREFIX.as_bytes()) {
+        Some(rest) => rest.trim_ascii_start(),
+        None => return Sink::Serial,
+    };
+    // Shorter than what it came out of
+    let rest = Vec::from_slice(rest).unwrap();
+    *buffer = rest;
+    Sink::Log
+}
+
+/// `None` if the line only moved to another menu. Otherwise the line gets
+/// acknowledged before it is parsed, and `name` is set to what its `done`
+/// record has to quote, see [`reply`].
+fn try_read_command<S: Console>(
+    serial: &mut S,
+    sensor: &mut Sensor,
+    buffer: &mut Vec<u8, LINE_LEN>,
+    menu: &mut Menu,
+    mode: reply::Mode,
+    board: &str,
+    name: &mut reply::Name,
+) -> Result<Option<(Command, Sink)>, Error<ConsoleError<S>>> {
+    try_fill_buffer_with_echo(serial, sensor, buffer)?;
+    let sink = take_sink(buffer);
+    if sink == Sink::Log && buffer.is_empty() {
+        return Err(Error::Usage(textlog::USAGE));
+    }
+    if !resolve_menu(menu, buffer)? {
+        return Ok(None);
+    }
+    let line = core::str::from_utf8(buffer)?;
+    // The host times the pong, so it goes out alone and the same in every
+    // mode, without an ack or a done record
+    if reply::name_of(line) != "ping" {
+        *name = reply::name_of(line);
+        let warn = consistency::degraded().then_some("sensorhealth");
+        reply::ack(serial, mode, line, board, warn)?;
+    }
+    let command = try_parse_command(buffer)?;
+    // The confirmation has to be seen to be given
+    if sink == Sink::Log && command.protected() {
+        return Err(Error::Unloggable);
+    }
+    Ok(Some((command, sink)))
+}
+
+fn try_parse_command<E>(buffer: &[u8]) -> Result<Command, Error<E>> {
+    let line = core::str::from_utf8(buffer)?;
+    #[cfg(feature = "calc")]
+    if let Some(expr) = line.strip_prefix("calc ") {
+        let mut owned = String::new();
+        // Can't fail, it came out of a buffer of the same size
+        owned.push_str(expr.trim()).unwrap();
+        return Ok(Command::Calc(owned));
+    }
+    if let Some(text) = line.strip_prefix("marker ") {
+        let mut owned = String::new();
+        // Can't fail, it came out of a buffer of the same size
+        owned.push_str(text.trim()).unwrap();
+        if owned.is_empty() {
+            return Err(Error::Usage(log::MARKER_USAGE));
+        }
+        return Ok(Command::Marker(owned));
+    }
+    if let Some(args) = line.strip_prefix("statusled ") {
+        let (role, led) = status::parse(args).ok_or(Error::Usage(status::USAGE))?;
+        return Ok(Command::StatusLed(role, led));
+    }
+    if let Some(text) = line.strip_prefix("name ") {
+        let name = board::parse_name(text.trim()).ok_or(Error::Usage(board::NAME_USAGE))?;
+        return Ok(Command::Name(Some(name)));
+    }
+    if let Some(from) = line.strip_prefix("capture dump from ") {
+        let from = from
+            .trim()
+            .parse()
+            .map_err(|_| Error::Usage(capture::USAGE))?;
+        return Ok(Command::CaptureDump(from));
+    }
+    if let Some(args) = line.strip_prefix("sensorhealth bands ") {
+        let (mag, accel, hold) =
+            consistency::parse_bands(args).ok_or(Error::Usage(consistency::USAGE))?;
+        return Ok(Command::SensorHealthBands(mag, accel, hold));
+    }
+    if let Some(args) = line.strip_prefix("calibrate ") {
+        let calibration =
+            calibration::Calibration::parse(args).ok_or(Error::Usage(calibration::USAGE))?;
+        return Ok(Command::Calibrate(Some(calibration)));
+    }
+    if let Some(args) = line.strip_prefix("i2c ") {
+        let request = raw::Request::parse(args).ok_or(Error::Usage(raw::USAGE))?;
+        return Ok(Command::I2c(request));
+    }
+    if let Some(args) = line.strip_prefix("watch ") {
+        return Ok(Command::Watch(watch::parse(args)?));
+    }
+    let mut words = line.split_ascii_whitespace();
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some("magnetometer"), None, _, _) => Ok(Command::Magnetometer),
+        (Some("accelerometer"), None, _, _) => Ok(Command::Accelerometer),
+        (Some("magnetometer"), Some("stream"), None, _) => Ok(Command::Stream(recent::Kind::Mag)),
+        (Some("accelerometer"), Some("stream"), None, _) => {
+            Ok(Command::Stream(recent::Kind::Accel))
+        }
+        (Some("magnetometer"), Some("avg"), Some(n), None) => average::parse_count(n)
+            .map(|n| Command::Average(recent::Kind::Mag, n))
+            .ok_or(Error::Usage(average::MAG_USAGE)),
+        (Some("magnetometer"), Some("avg"), _, _) => Err(Error::Usage(average::MAG_USAGE)),
+        (Some("accelerometer"), Some("avg"), Some(n), None) => average::parse_count(n)
+            .map(|n| Command::Average(recent::Kind::Accel, n))
+            .ok_or(Error::Usage(average::ACCEL_USAGE)),
+        (Some("accelerometer"), Some("avg"), _, _) => Err(Error::Usage(average::ACCEL_USAGE)),
+        (Some("heading"), None, _, _) => Ok(Command::Heading(false)),
+        (Some("heading"), Some("tilted"), None, _) => Ok(Command::Heading(true)),
+        (Some("compass"), Some("show"), None, _) => Ok(Command::CompassShow),
+        (Some("ball"), None, _, _) => Ok(Command::Ball),
+        (Some("punch"), None, _, _) => Ok(Command::Punch),
+        (Some("scan"), None, _, _) => Ok(Command::Scan),
+        (Some("i2c"), _, _, _) => Err(Error::Usage(raw::USAGE)),
+        (Some("temperature"), None, _, _) => Ok(Command::Temperature(false)),
+        (Some("temperature"), Some("both"), None, _) => Ok(Command::Temperature(true)),
+        (Some("temperature"), _, _, _) => Err(Error::Usage(temperature::USAGE)),
+        (Some("odr"), Some("show"), None, _) => Ok(Command::OdrShow),
+        (Some("odr"), Some("accel"), Some(hz), None) => odr::accel(hz)
+            .map(Command::OdrAccel)
+            .ok_or(Error::Usage(odr::ACCEL_USAGE)),
+        (Some("odr"), Some("accel"), _, _) => Err(Error::Usage(odr::ACCEL_USAGE)),
+        (Some("odr"), Some("mag"), Some(hz), None) => odr::mag(hz)
+            .map(Command::OdrMag)
+            .ok_or(Error::Usage(odr::MAG_USAGE)),
+        (Some("odr"), Some("mag"), _, _) => Err(Error::Usage(odr::MAG_USAGE)),
+        (Some("odr"), _, _, _) => Err(Error::Usage(odr::USAGE)),
+        (Some("calibrate"), None, _, _) => Ok(Command::Calibrate(None)),
+        (Some("calibration"), Some("clear"), None, _) => Ok(Command::CalibrationClear),
+        (Some("calibration"), _, _, _) => Err(Error::Usage("calibration clear")),
+        (Some("power"), Some("report"), None, _) => Ok(Command::PowerReport),
+        (Some("pof"), Some("status"), None, _) => Ok(Command::PofStatus),
+        (Some("powersave"), None, _, _) => Ok(Command::PowerSave(None)),
+        (Some("powersave"), Some(mode), None, _) => powersave::Mode::from_name(mode)
+            .map(|mode| Command::PowerSave(Some(mode)))
+            .ok_or(Error::Usage(powersave::USAGE)),
+        (Some("powersave"), _, _, _) => Err(Error::Usage(powersave::USAGE)),
+        (Some("power"), Some("off"), Some(name), None) => power::Peripheral::from_name(name)
+            .map(Command::PowerOff)
+            .ok_or_else(|| Error::UnknownPeripheral(Word::copy(name))),
+        (Some("heartbeat"), Some("on"), None, _) => Ok(Command::Heartbeat(true)),
+        (Some("heartbeat"), Some("off"), None, _) => Ok(Command::Heartbeat(false)),
+        (Some("poll"), None, _, _) => Ok(Command::Poll),
+        (Some("poll"), Some("stream"), None, _) => Ok(Command::PollStream),
+        (Some("poll"), Some(tag), Some(ms), None) => {
+            match (poller::Device::from_tag(tag), ms.parse()) {
+                (Some(device), Ok(ms @ 1..=60_000)) => Ok(Command::PollInterval(device, ms)),
+                _ => Err(Error::Usage(poller::USAGE)),
+            }
+        }
+        (Some("poll"), _, _, _) => Err(Error::Usage(poller::USAGE)),
+        (Some("audiocues"), Some("on"), None, _) => Ok(Command::AudioCues(true)),
+        (Some("audiocues"), Some("off"), None, _) => Ok(Command::AudioCues(false)),
+        (Some("audiocues"), _, _, _) => Err(Error::Usage(cues::USAGE)),
+        (Some("uptime"), None, _, _) => Ok(Command::Uptime),
+        (Some("status"), None, _, _) => Ok(Command::Status),
+        (Some("tablecheck"), None, _, _) => Ok(Command::TableCheck),
+        (Some("marker"), None, _, _) => Err(Error::Usage(log::MARKER_USAGE)),
+        (Some("i2ctrace"), Some("on"), None, _) => Ok(Command::I2cTrace(true)),
+        (Some("i2ctrace"), Some("off"), None, _) => Ok(Command::I2cTrace(false)),
+        (Some("i2ctrace"), Some("dump"), None, _) => Ok(Command::I2cTraceDump),
+        (Some("i2ctrace"), _, _, _) => Err(Error::Usage(i2ctrace::USAGE)),
+        (Some("irqstats"), None, _, _) => Ok(Command::IrqStats),
+        (Some("irqstats"), Some("reset"), None, _) => Ok(Command::IrqStatsReset),
+        (Some("irqstats"), _, _, _) => Err(Error::Usage(irqstats::USAGE)),
+        (Some("config"), Some("export"), None, _) => Ok(Command::ConfigExport),
+        (Some("config"), Some("reset"), None, _) => Ok(Command::ConfigReset),
+        (Some("sensorhealth"), None, _, _) => Ok(Command::SensorHealth),
+        (Some("sensorhealth"), Some("on"), None, _) => Ok(Command::SensorHealthEnable(true)),
+        (Some("sensorhealth"), Some("off"), None, _) => Ok(Command::SensorHealthEnable(false)),
+        (Some("sensorhealth"), Some("capture"), None, _) => Ok(Command::SensorHealthCapture),
+        (Some("sensorhealth"), Some("reference"), Some(nt), None) => nt
+            .parse()
+            .map(Command::SensorHealthReference)
+            .map_err(|_| Error::Usage(consistency::USAGE)),
+        (Some("sensorhealth"), _, _, _) => Err(Error::Usage(consistency::USAGE)),
+        (Some("name"), None, _, _) => Ok(Command::Name(None)),
+        (Some("version"), None, _, _) => Ok(Command::Version),
+        (Some("help"), None, _, _) => Ok(Command::Help),
+        (Some("capture"), Some("dump"), None, _) => Ok(Command::CaptureDump(0)),
+        (Some("capture"), Some("clear"), None, _) => Ok(Command::CaptureClear),
+        (Some("log"), Some("read"), None, _) => Ok(Command::LogRead),
+        (Some("log"), _, _, _) => Err(Error::Usage(textlog::USAGE)),
+        (Some("capture"), Some(count), None, _) => match count.parse() {
+            Ok(count @ 1..=capture::CAPACITY) => Ok(Command::Capture(count)),
+            _ => Err(Error::Usage(capture::USAGE)),
+        },
+        (Some("capture"), _, _, _) => Err(Error::Usage(capture::USAGE)),
+        (Some("provision"), Some("export"), None, _) => Ok(Command::ProvisionExport),
+        (Some("provision"), Some("import"), None, _) => Ok(Command::ProvisionImport),
+        (Some("provision"), _, _, _) => Err(Error::Usage(provision::USAGE)),
+        (Some("odometer"), Some("reset"), None, _) => Ok(Command::OdometerReset),
+        (Some("flash"), Some("erase"), None, _) => Ok(Command::FlashErase),
+        (Some("linearaccel"), None, _, _) => Ok(Command::LinearAccel),
+        (Some("brightness"), Some(level), None, _) => match level.parse() {
+            Ok(level @ 0..=display::MAX_BRIGHTNESS) => Ok(Command::Brightness(level)),
+            _ => Err(Error::Usage("brightness <0-9>")),
+        },
+        (Some("night"), None, _, _) => Ok(Command::Brightness(1)),
+        (Some("gamma"), Some("on"), None, _) => Ok(Command::Gamma(true)),
+        (Some("gamma"), Some("off"), None, _) => Ok(Command::Gamma(false)),
+        (Some("recent"), Some(kind), count, None) => {
+            let count = match count.map(str::parse) {
+                None => Some(recent::DEFAULT_COUNT),
+                Some(Ok(count @ 1..=recent::LEN)) => Some(count),
+                Some(_) => None,
+            };
+            match (recent::Kind::from_name(kind), count) {
+                (Some(kind), Some(count)) => Ok(Command::Recent(kind, count)),
+                _ => Err(Error::Usage(recent::USAGE)),
+            }
+        }
+        #[cfg(feature = "demo")]
+        (Some("demo"), None, _, _) => Ok(Command::Demo),
+        (Some("ping"), count, None, _) => match count.map_or(Ok(1), str::parse) {
+            Ok(count @ 1..=MAX_PINGS) => Ok(Command::Ping(count)),
+            _ => Err(Error::Usage("ping [1-100]")),
+        },
+        #[cfg(feature = "replay")]
+        (Some("replay"), Some(on), None, _) => match on {
+            "on" => Ok(Command::Replay(true)),
+            "off" => Ok(Command::Replay(false)),
+            _ => Err(Error::Usage(replay::USAGE)),
+        },
+        #[cfg(feature = "simulate")]
+        (Some("simulate"), Some("off"), None, _) => Ok(Command::Simulate(None)),
+        #[cfg(feature = "simulate")]
+        (Some("simulate"), Some("on"), noise, None) => {
+            match noise.map_or(Ok(sim::DEFAULT_NOISE), str::parse) {
+                Ok(noise @ 0..=sim::MAX_NOISE) => Ok(Command::Simulate(Some(sim::Sim::new(noise)))),
+                _ => Err(Error::Usage(sim::USAGE)),
+            }
+        }
+        (Some("output"), Some(name), None, _) => reply::Mode::from_name(name)
+            .map(Command::Output)
+            .ok_or(Error::Usage(reply::USAGE)),
+        (Some("format"), Some(name), None, _) => format::OutputFormat::from_name(name)
+            .map(Command::Format)
+            .ok_or(Error::Usage(format::USAGE)),
+        (Some("lineend"), Some(name), None, _) => LineEnd::from_name(name)
+            .map(Command::LineEnd)
+            .ok_or(Error::Usage(serial_setup::LINE_END_USAGE)),
+        (Some("timeformat"), Some(name), None, _) => stamp::Format::from_name(name)
+            .map(Command::TimeFormat)
+            .ok_or(Error::Usage(stamp::USAGE)),
+        (Some("axes"), Some("show"), Some(letters), None) => Axes::parse(letters)
+            .map(Command::Axes)
+            .ok_or(Error::Usage(axes::USAGE)),
+        (Some("battwarn"), Some(mv), None, _) => match mv.parse() {
+            Ok(mv @ battery::MIN_WARN_MV..=battery::MAX_WARN_MV) => Ok(Command::BattWarn(mv)),
+            _ => Err(Error::Usage(battery::USAGE)),
+        },
+        (Some("blinkout"), Some(value), None, _) => match value.parse() {
+            Ok(value @ 0..=blinkout::MAX_VALUE) => Ok(Command::Blinkout(value)),
+            _ => Err(Error::Usage("blinkout <0-999>")),
+        },
+        (Some("tilt"), Some("stream"), None, _) => Ok(Command::TiltStream),
+        (Some("orientation"), None, _, _) => {
+            Ok(Command::Orientation(false, orientation::DEFAULT_MG))
+        }
+        (Some("orientation"), Some("stream"), None, _) => {
+            Ok(Command::Orientation(true, orientation::DEFAULT_MG))
+        }
+        (Some("orientation"), Some("stream"), Some(mg), None) => orientation::parse_threshold(mg)
+            .map(|mg| Command::Orientation(true, mg))
+            .ok_or(Error::Usage(orientation::USAGE)),
+        (Some("orientation"), Some(mg), None, _) => orientation::parse_threshold(mg)
+            .map(|mg| Command::Orientation(false, mg))
+            .ok_or(Error::Usage(orientation::USAGE)),
+        (Some("orientation"), _, _, _) => Err(Error::Usage(orientation::USAGE)),
+        (Some("tiltfilter"), Some(alpha), None, _) => match alpha.parse() {
+            Ok(alpha @ 1..=100) => Ok(Command::TiltFilter(alpha)),
+            _ => Err(Error::Usage("tiltfilter <1-100>")),
+        },
+        (Some("filter"), Some("off"), None, _) => Ok(Command::Filter(filter::Kind::Off)),
+        (Some("filter"), Some(kind), Some(n), None) => {
+            let n = n.parse().map_err(|_| Error::Usage(filter::USAGE))?;
+            match kind {
+                "average" => filter::Kind::average(n),
+                "median" => filter::Kind::median(n),
+                _ => None,
+            }
+            .map(Command::Filter)
+            .ok_or(Error::Usage(filter::USAGE))
         }
-        nb::block!(embedded_hal::serial::Write::write(serial, byte))?;
-        nb::block!(embedded_hal::serial::Write::flush(serial))?;
-        buffer.push(byte)?;
+        _ => Err(Error::Unrecognized(Word::copy(line))),
+    }
+}
+
+/// Report an error to the user and count it.
+fn print_error(serial: &mut SerialPort, err: impl core::fmt::Display) -> core::fmt::Result {
+    stats::count_error();
+    feedback::signal(feedback::Event::Error);
+    writeln!(serial, "*** error ***\n{}", err)
+}
+
+/// Persist `settings`, telling the user if flash is off limits right now.
+fn save_settings(serial: &mut SerialPort, settings: &Settings) {
+    if let Err(err) = settings::save(settings) {
+        print_error(serial, err).unwrap();
     }
 }
 
-fn try_read_command<'a>(
-    serial: &mut UartePort<UARTE0>,
-    buffer: &'a mut Vec<u8, 16>,
-) -> Result<Command, Error<'a>> {
-    try_fill_buffer_with_echo(serial, buffer)?;
-    let word = core::str::from_utf8(buffer)?;
-    match word {
-        "magnetometer" => Ok(Command::Magnetometer),
-        "accelerometer" => Ok(Command::Accelerometer),
-        _ => Err(Error::Unrecognized(word)),
+/// Save `calibration` and have the sensor use it from now on.
+fn save_calibration(
+    serial: &mut SerialPort,
+    sensor: &mut Sensor,
+    calibration: calibration::Calibration,
+) {
+    sensor.calibration = calibration;
+    if let Err(err) = calibration::save(&calibration) {
+        print_error(serial, err).unwrap();
     }
 }
 
-fn read_command(serial: &mut UartePort<UARTE0>) -> Result<Command, core::fmt::Error> {
-    let mut buffer: Vec<u8, 16> = Vec::new();
+fn read_command(
+    serial: &mut SerialPort,
+    sensor: &mut Sensor,
+    menu: &mut Menu,
+    mode: reply::Mode,
+    board: &str,
+    tag: Option<&'static str>,
+) -> Result<(Command, reply::Name, Sink), core::fmt::Error> {
+    let mut buffer: Vec<u8, LINE_LEN> = Vec::new();
     loop {
-        writeln!(
-            serial,
-            "Available commands: \"magnetometer\" and \"accelerometer\": \r"
-        )?;
-        match try_read_command(serial, &mut buffer) {
-            Ok(cmd) => return Ok(cmd),
-            Err(err) => writeln!(serial, "*** error ***\r\n{}\r", err)?,
+        if pof::take_warning() {
+            writeln!(
+                serial,
+                "*** warning ***\nsupply voltage low, flash writes disabled"
+            )?;
+        }
+        if let Some(warning) = consistency::take_warning() {
+            writeln!(serial, "*** warning ***\n{}", warning)?;
+        }
+        match powersave::take_change() {
+            Some(true) => writeln!(serial, "on battery, power saver on")?,
+            Some(false) => writeln!(serial, "off battery, power saver off")?,
+            None => {}
+        }
+        // Nothing at the top, the banner at boot and "help" list everything
+        menu::list(serial, *menu)?;
+        if battery::low() {
+            write!(serial, "[LOW BATT] ")?;
+        }
+        if let Some(tag) = tag {
+            write!(serial, "[")?;
+            for c in tag.chars() {
+                write!(serial, "{}", c.to_ascii_uppercase())?;
+            }
+            write!(serial, "] ")?;
+        }
+        match menu.name() {
+            Some(name) => write!(serial, "{}> ", name)?,
+            None => write!(serial, "> ")?,
+        }
+        let mut name = reply::Name::new();
+        match try_read_command(serial, sensor, &mut buffer, menu, mode, board, &mut name) {
+            Ok(Some((cmd, sink))) => {
+                stats::count_command();
+                return Ok((cmd, name, sink));
+            }
+            Ok(None) => {}
+            // Throw the line away and start over with a fresh prompt
+            Err(Error::Interrupted) => writeln!(serial, "^C")?,
+            Err(err) => {
+                print_error(serial, err)?;
+                // Only lines that got as far as their ack get a done
+                if !name.is_empty() {
+                    reply::done(serial, mode, &name, reply::Status::Error, tag)?;
+                }
+            }
         }
     }
 }
 
-#[entry]
-fn main() -> ! {
-    rtt_init_print!();
-    let board = microbit::Board::take().unwrap();
+/// Pass a watchdog abort or any other sensor error on to the command
+/// handler, which stops with it, see [`fault`].
+fn sensor_result<T>(
+    result: Result<T, lsm303agr::Error<<Bus as i2c::Write>::Error, ()>>,
+) -> Result<T, Stop> {
+    match result {
+        Err(lsm303agr::Error::Comm(BusError::TimedOut)) => Err(Stop::TimedOut),
+        result => result.map_err(|err| Stop::Sensor(fault::Error(err))),
+    }
+}
 
-    #[cfg(feature = "v1")]
-    let mut i2c = { twi::Twi::new(board.TWI0, board.i2c.into(), FREQUENCY_A::K100) };
+/// Checked at the top of every iteration of a long-running handler.
+fn keep_going(serial: &mut SerialPort) -> Result<(), Stop> {
+    odometer::tick();
+    battery::poll();
+    powersave::poll();
+    i2ctrace::drain();
+    watchdog::check()?;
+    serial.poll_abort();
+    if abort::aborted() {
+        return Err(Stop::Interrupted);
+    }
+    Ok(())
+}
 
-    #[cfg(feature = "v2")]
-    let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
+/// The board as a [`confirm::wait`] sees it.
+struct Confirming<'a> {
+    sensor: &'a mut Sensor,
+    serial: &'a mut SerialPort,
+}
 
-    let mut uarte = {
-        let serial = uarte::Uarte::new(
-            board.UARTE0,
-            board.uart.into(),
-            Parity::EXCLUDED,
-            Baudrate::BAUD115200,
-        );
-        UartePort::new(serial)
-    };
-
-    let mut sensor = Lsm303agr::new_with_i2c(i2c);
-    sensor.init().unwrap();
-    sensor.set_accel_odr(AccelOutputDataRate::Hz50).unwrap();
-    sensor
-        .set_mag_odr(lsm303agr::MagOutputDataRate::Hz50)
-        .unwrap();
+impl confirm::Inputs for Confirming<'_> {
+    type Error = Stop;
+
+    fn ticks(&mut self) -> u32 {
+        heartbeat::ticks()
+    }
+
+    fn keep_going(&mut self) -> Result<(), Stop> {
+        keep_going(self.serial)
+    }
+
+    fn chord(&mut self) -> bool {
+        buttons::chord()
+    }
+
+    fn sample(&mut self) -> Result<Option<[i32; 3]>, Stop> {
+        // Unfiltered, smoothing would flatten the taps. Nobody taps a
+        // simulated or recorded board, and the real sensor may be why it
+        // isn't used. Without one the chord is the only way.
+        let lsm = match (&self.sensor.feed, self.sensor.lsm.as_mut()) {
+            (Feed::Live, Some(lsm)) => lsm,
+            _ => return Ok(None),
+        };
+        if !sensor_result(lsm.accel_status())?.xyz_new_data {
+            return Ok(None);
+        }
+        let data = sensor_result(lsm.accel_data())?;
+        Ok(Some([data.x, data.y, data.z]))
+    }
+}
+
+/// Hold a protected command until somebody confirms it on the board.
+fn confirm(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<(), Stop> {
+    // Without ticks there would be no timeout
+    health::require(Subsystem::Rtc)?;
+    writeln!(serial, "double-tap to confirm (or hold A+B)").unwrap();
+    match confirm::wait(&mut Confirming { sensor, serial })? {
+        confirm::Outcome::Tapped => Ok(()),
+        confirm::Outcome::Chord => {
+            feedback::signal(feedback::Event::Button);
+            Ok(())
+        }
+        confirm::Outcome::TimedOut => Err(Stop::NotConfirmed),
+    }
+}
+
+/// Hand the settings the power saver limits to it, see [`powersave`].
+fn configure_power(settings: &Settings) {
+    powersave::configure(powersave::Wanted {
+        mode: settings.powersave,
+        brightness: settings.brightness,
+    });
+}
+
+/// Put the settings that take effect at boot into effect now.
+fn apply_settings(sensor: &mut Sensor, settings: &Settings) {
+    heartbeat::set_enabled(settings.heartbeat);
+    // Stays off on a board without a speaker
+    cues::set_enabled(settings.audio_cues).ok();
+    sensor.filter = filter::Filter::new(settings.filter);
+    configure_power(settings);
+    display::set_status_leds(settings.status_leds);
+    battery::set_threshold(settings.batt_warn_mv);
+    consistency::configure(settings.sensor_health);
+}
+
+fn read_magnetometer(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<Measurement, Stop> {
+    let raw = read_raw_magnetometer(sensor, serial)?;
+    Ok(sensor.process_mag(raw))
+}
+
+/// Without the offsets, for "calibrate" to find them.
+fn read_raw_magnetometer(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<[i32; 3], Stop> {
+    if let Some(raw) = sensor.feed.next_mag() {
+        keep_going(serial)?;
+        return Ok(raw);
+    }
+    let timeout_ms = fault::NO_DATA_MS + sensor.rates.mag_period_ms();
+    let (raw, _) = wait_for_data(sensor, serial, poller::Device::Mag, timeout_ms)?;
+    log!("got value:");
+    consistency::mag(raw);
+    Ok(raw)
+}
 
+/// Wait for the LSM303AGR to have a fresh sample from `device`, and read
+/// it, along with whether it overwrote one nobody read. Stops with
+/// [`Stop::NoData`] if none comes within `timeout_ms`, or waits for as long
+/// as it takes without the RTC to tell the time by, see [`fault`].
+///
+/// The magnetometer only measures when asked to, and a read of it that
+/// comes too early asks, so it has no overrun.
+fn wait_for_data(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    device: poller::Device,
+    timeout_ms: u32,
+) -> Result<([i32; 3], bool), Stop> {
+    let timed = health::require(Subsystem::Rtc).is_ok();
+    let start = uptime::millis();
+    // The first look starts a one-shot measurement, the line can't be up
+    // before that
+    let mut asked = false;
     loop {
-        match read_command(&mut uarte).unwrap() {
-            Command::Magnetometer => {
-                rprintln!("reading magnetometer");
-                loop {
-                    if sensor.mag_status().unwrap().xyz_new_data {
-                        rprintln!("got value:");
-                        let data = sensor.mag_data().unwrap();
-                        writeln!(
-                            uarte,
-                            "Magnetic field (nT): x {} y {} z {}\r",
-                            data.x, data.y, data.z
-                        )
-                        .unwrap();
-                        break;
-                    }
+        keep_going(serial)?;
+        if timed && uptime::millis() - start >= timeout_ms as u64 {
+            return Err(Stop::NoData);
+        }
+        if asked && !drdy::ready(device) {
+            drdy::sleep(device);
+            continue;
+        }
+        asked = true;
+        let lsm = sensor.lsm()?;
+        match device {
+            poller::Device::Accel => {
+                let status = sensor_result(lsm.accel_status())?;
+                if status.xyz_new_data {
+                    let data = sensor_result(lsm.accel_data())?;
+                    fault::sampled();
+                    return Ok(([data.x, data.y, data.z], status.xyz_overrun));
                 }
             }
-            Command::Accelerometer => {
-                rprintln!("reading accelerometer");
-                loop {
-                    if sensor.accel_status().unwrap().xyz_new_data {
-                        rprintln!("got value:");
-                        let data = sensor.accel_data().unwrap();
-                        writeln!(
-                            uarte,
-                            "Acceleration (mg): x {} y {} z {}\r",
-                            data.x, data.y, data.z
-                        )
-                        .unwrap();
-                        break;
+            poller::Device::Mag => match lsm.mag_data() {
+                Ok(data) => {
+                    fault::sampled();
+                    return Ok(([data.x, data.y, data.z], false));
+                }
+                Err(nb::Error::WouldBlock) => {
+                    // An accelerometer sample holding the shared line up
+                    if drdy::SHARED && drdy::ready(device) {
+                        sensor_result(lsm.accel_data())?;
                     }
                 }
+                Err(nb::Error::Other(err)) => return sensor_result(Err(err)),
+            },
+        }
+    }
+}
+
+/// Print the mean, smallest and largest of `count` readings of `kind`, see
+/// [`average`].
+fn print_average(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    settings: &Settings,
+    kind: recent::Kind,
+    count: u32,
+) -> Result<(), Stop> {
+    let mut stats = average::Stats::new();
+    for _ in 0..count {
+        let data = match kind {
+            recent::Kind::Accel => read_accelerometer(sensor, serial)?,
+            recent::Kind::Mag => read_magnetometer(sensor, serial)?,
+        };
+        stats.push([data.x, data.y, data.z]);
+    }
+    // Never fails, there is at least one reading
+    if let Some([mean, min, max]) = stats.finish() {
+        writeln!(serial, "{}, {} readings", kind.label(), count).unwrap();
+        for (name, values) in [("mean", mean), ("min", min), ("max", max)] {
+            let sample = Sample {
+                axes: settings.axes,
+                values,
+            };
+            writeln!(serial, "{}: {}", name, sample).unwrap();
+        }
+    }
+    Ok(())
+}
+
+/// Average `samples` magnetometer readings into a heading, `tilted` each
+/// levelled out by an accelerometer reading taken with it, see
+/// [`tilt::level`]. `None` if there is no horizontal field.
+fn read_heading(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    tilted: bool,
+    samples: i32,
+) -> Result<Option<heading::Heading>, Stop> {
+    let mut average = heading::Average::new(samples);
+    while !average.is_full() {
+        let mag = read_magnetometer(sensor, serial)?;
+        let mag = [mag.x, mag.y, mag.z];
+        let level = if tilted {
+            let accel = read_accelerometer(sensor, serial)?;
+            // In free fall there is no telling which way is up
+            match tilt::level([accel.x, accel.y, accel.z], mag) {
+                Some(level) => level,
+                None => continue,
+            }
+        } else {
+            [mag[0], mag[1]]
+        };
+        average.push(level[0], level[1]);
+    }
+    Ok(average.heading())
+}
+
+/// Point an arrow towards north on the display, tilt-compensated, until
+/// Ctrl-C or any other key, see [`compass`]. The key is taken, like the
+/// one that stops a reading stream.
+fn show_compass(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<(), Stop> {
+    writeln!(serial, "the arrow points north, any key stops it").unwrap();
+    let mut shown = None;
+    let result = loop {
+        let heading = match read_heading(sensor, serial, true, compass::SAMPLES) {
+            Ok(heading) => heading,
+            Err(stop) => break Err(stop),
+        };
+        if serial.take_typeahead().is_some() {
+            break Ok(());
+        }
+        // Handed over only when it changes, the refresh is up to the
+        // display's own interrupt
+        let image = compass::image(heading.map(|heading| heading.0));
+        if shown != Some(image) {
+            display::set_background(image);
+            shown = Some(image);
+        }
+    };
+    display::set_background(&[[0; 5]; 5]);
+    result
+}
+
+/// Roll a dot around the display with the board's tilt until Ctrl-C or
+/// any other key, see [`ball`]. A live accelerometer slower than
+/// [`ball::MIN_HZ`] goes up to 50 Hz meanwhile, and back afterwards.
+fn roll_ball(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<(), Stop> {
+    // The ball moves by the time between readings
+    health::require(Subsystem::Rtc)?;
+    let rates = sensor.rates;
+    let faster = matches!(sensor.feed, Feed::Live) && rates.accel_hz() < ball::MIN_HZ;
+    if faster {
+        sensor_result(sensor.lsm()?.set_accel_odr(AccelOutputDataRate::Hz50))?;
+    }
+    writeln!(serial, "tilt the board to roll the ball, any key stops it").unwrap();
+    let result = follow_ball(sensor, serial);
+    display::set_background(&[[0; 5]; 5]);
+    // Nothing arms the watchdog for the ball either, so the bus is still
+    // there after any stop
+    if faster {
+        sensor_result(sensor.lsm()?.set_accel_odr(rates.accel))?;
+    }
+    result
+}
+
+/// Step the ball on with every reading, until a key.
+fn follow_ball(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<(), Stop> {
+    let mut ball = ball::Ball::new();
+    let mut shown = None;
+    let mut last = uptime::millis();
+    loop {
+        let data = read_accelerometer(sensor, serial)?;
+        if serial.take_typeahead().is_some() {
+            return Ok(());
+        }
+        let now = uptime::millis();
+        ball.step([data.x, data.y, data.z], (now - last) as u32);
+        last = now;
+        // The display refreshes from its own interrupt, it only has to be
+        // handed the image when the ball moves to another LED
+        let image = ball.image();
+        if shown != Some(image) {
+            display::set_background(&image);
+            shown = Some(image);
+        }
+    }
+}
+
+/// Collect raw magnetometer readings while the board is turned every way,
+/// showing how far along it is on the display, see [`calibration`]. `None`
+/// if it isn't turned far enough in time.
+fn run_calibration(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    mode: reply::Mode,
+) -> Result<Option<calibration::Calibration>, Stop> {
+    // Without ticks there would be no timeout
+    health::require(Subsystem::Rtc)?;
+    writeln!(
+        serial,
+        "turn the board in a figure eight, every way up, until all the LEDs light (about 10 s)"
+    )
+    .unwrap();
+    let mut collector = calibration::Collector::new();
+    let mut progress = progress::Progress::new(mode);
+    let start = uptime::millis() as u32;
+    let result = loop {
+        let raw = match read_raw_magnetometer(sensor, serial) {
+            Ok(raw) => raw,
+            Err(stop) => break Err(stop),
+        };
+        collector.push(raw);
+        display::set_background(&collector.image());
+        report_progress(serial, &mut progress, collector.coverage());
+        if collector.complete()
+            || (uptime::millis() as u32).wrapping_sub(start) >= calibration::TIMEOUT_MS
+        {
+            break Ok(collector.finish());
+        }
+    };
+    display::set_background(&[[0; 5]; 5]);
+    progress.finish(serial).unwrap();
+    result
+}
+
+/// Wait for a punch and print how hard it was, see [`punch`]. Any key
+/// gives up waiting. The accelerometer is put back to the rate and scale
+/// it was at however this ends.
+fn measure_punch(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<(), Stop> {
+    // The window is timed
+    health::require(Subsystem::Rtc)?;
+    // A simulated or recorded board is only ever read at the usual scale
+    if !matches!(sensor.feed, Feed::Live) {
+        writeln!(serial, "punch: needs the real sensor").unwrap();
+        return Ok(());
+    }
+    let rates = sensor.rates;
+    let lsm = sensor.lsm()?;
+    let scale = lsm.get_accel_scale();
+    let result = sensor_result(lsm.set_accel_odr(AccelOutputDataRate::Hz400))
+        .and_then(|()| sensor_result(lsm.set_accel_scale(AccelScale::G16)))
+        .and_then(|()| {
+            writeln!(serial, "punch! any key gives up").unwrap();
+            wait_for_punch(lsm, serial)
+        });
+    // Nothing arms the watchdog for a punch, so the bus is still there
+    // after any stop
+    sensor_result(lsm.set_accel_scale(scale))?;
+    sensor_result(lsm.set_accel_odr(rates.accel))?;
+    match result? {
+        Some(punch) => writeln!(serial, "punch: {}", punch).unwrap(),
+        None => writeln!(serial, "punch: none").unwrap(),
+    }
+    Ok(())
+}
+
+/// Raw counts until a punch is over. `None` if a key comes first.
+fn wait_for_punch(lsm: &mut Lsm, serial: &mut SerialPort) -> Result<Option<punch::Punch>, Stop> {
+    let mut meter = punch::Meter::new();
+    loop {
+        keep_going(serial)?;
+        if !meter.started() && serial.take_typeahead().is_some() {
+            return Ok(None);
+        }
+        if sensor_result(lsm.accel_status())?.xyz_new_data {
+            let data = sensor_result(lsm.accel_data_unscaled())?;
+            let counts = [data.x as i32, data.y as i32, data.z as i32];
+            if let Some(punch) = meter.push(counts, uptime::millis() as u32) {
+                return Ok(Some(punch));
+            }
+        }
+    }
+}
+
+/// Print which addresses on the sensor's bus answer, see [`scan`]. The
+/// driver gives the bus up meanwhile, and is started again at the rates
+/// it was at.
+fn scan_bus(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<(), Stop> {
+    if !bus::idle() {
+        print_error(serial, scan::Error::Stuck).unwrap();
+        return Ok(());
+    }
+    match with_bus(sensor, serial, probe_all)? {
+        Ok(found) => {
+            writeln!(serial, "{}", found).unwrap();
+            for (address, label) in found.known() {
+                writeln!(serial, "0x{:02x} {}", address, label).unwrap();
             }
+            writeln!(serial, "{} found", found.count()).unwrap();
+        }
+        Err(err) => print_error(serial, err).unwrap(),
+    }
+    Ok(())
+}
+
+/// Hand the sensor's bus to `f` with the driver out of the way, and start
+/// the driver again afterwards at the rates it was at, saying so if it
+/// doesn't come back.
+fn with_bus<T>(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    f: impl FnOnce(&mut Bus) -> Result<T, TimedOut>,
+) -> Result<T, Stop> {
+    let lsm = sensor.lsm.take().ok_or(Unavailable(Subsystem::Sensor))?;
+    let mut bus = lsm.destroy();
+    let result = f(&mut bus);
+    if result.is_err() {
+        // The driver can only start on a bus that works, and the watchdog
+        // would stop that too
+        bus::recover();
+        watchdog::disarm();
+    }
+    restart_sensor(sensor, serial, bus);
+    Ok(result?)
+}
+
+/// Start the driver on `bus` at the rates it was at, saying so if it
+/// doesn't come back.
+fn restart_sensor(sensor: &mut Sensor, serial: &mut SerialPort, bus: Bus) {
+    sensor.lsm = start_sensor(bus, &sensor.rates)
+        .map_err(health::record)
+        .ok();
+    if sensor.lsm.is_none() {
+        print_error(serial, Unavailable(Subsystem::Sensor)).unwrap();
+    }
+}
+
+/// After [`fault::RESTART_AFTER`] sensor errors in a row: free the bus in
+/// case the sensor is holding it and start the driver over.
+fn recover_sensor(sensor: &mut Sensor, serial: &mut SerialPort) {
+    let lsm = match sensor.lsm.take() {
+        Some(lsm) => lsm,
+        None => return,
+    };
+    let bus = lsm.destroy();
+    bus::recover();
+    restart_sensor(sensor, serial, bus);
+    if sensor.lsm.is_some() {
+        writeln!(
+            serial,
+            "sensor: restarted after {} errors in a row",
+            fault::RESTART_AFTER
+        )
+        .unwrap();
+    }
+}
+
+/// Tell a device at `address` not answering apart from the bus going
+/// wrong, and both from the watchdog, which ends the command.
+fn raw_result<T>(
+    address: u8,
+    result: Result<T, <Bus as i2c::Write>::Error>,
+) -> Result<Result<T, raw::Error>, TimedOut> {
+    match result {
+        Ok(value) => Ok(Ok(value)),
+        Err(BusError::TimedOut) => Err(TimedOut),
+        Err(BusError::Bus(err)) if is_nack(&err) => Ok(Err(raw::Error::Nack(address))),
+        Err(BusError::Bus(_)) => Ok(Err(raw::Error::Fault(address))),
+    }
+}
+
+/// Print the LSM303AGR's temperature, `both` with the nRF's next to it, see
+/// [`temperature`].
+fn print_temperature(sensor: &mut Sensor, serial: &mut SerialPort, both: bool) -> Result<(), Stop> {
+    let result = with_bus(sensor, serial, |bus| {
+        raw_result(temperature::ADDRESS, read_sensor_temperature(bus))
+    })?;
+    let celsius = match result {
+        Ok(out) => temperature::Tenths::from_sensor(out),
+        Err(err) => {
+            print_error(serial, err).unwrap();
+            return Ok(());
+        }
+    };
+    if both {
+        let chip = temperature::Tenths::from_quarters(onchip::temperature_quarters());
+        writeln!(serial, "temperature: sensor {}, chip {}", celsius, chip).unwrap();
+    } else {
+        writeln!(serial, "temperature: {}", celsius).unwrap();
+    }
+    Ok(())
+}
+
+/// OUT_TEMP_L_A and OUT_TEMP_H_A, from a sample taken after the sensor was
+/// enabled. It is disabled again afterwards.
+fn read_sensor_temperature(bus: &mut Bus) -> Result<[u8; 2], <Bus as i2c::Write>::Error> {
+    use temperature::{ADDRESS, OUT_TEMP, STATUS_REG_AUX_A, TDA, TEMP_CFG_REG_A};
+    let mut out = [0; 2];
+    let mut status = [0];
+    i2c::Write::write(bus, ADDRESS, &[TEMP_CFG_REG_A, temperature::ENABLE])?;
+    // Whatever is left from the last time it ran, which clears the flag
+    i2c::WriteRead::write_read(bus, ADDRESS, &[OUT_TEMP], &mut out)?;
+    while status[0] & TDA == 0 {
+        i2c::WriteRead::write_read(bus, ADDRESS, &[STATUS_REG_AUX_A], &mut status)?;
+    }
+    i2c::WriteRead::write_read(bus, ADDRESS, &[OUT_TEMP], &mut out)?;
+    i2c::Write::write(bus, ADDRESS, &[TEMP_CFG_REG_A, 0])?;
+    Ok(out)
+}
+
+/// Do what "i2c read" or "i2c write" asks for, see [`raw`].
+fn raw_transaction(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    request: raw::Request,
+) -> Result<(), Stop> {
+    let mut buffer = [0; raw::MAX_LEN];
+    let result = with_bus(sensor, serial, |bus| {
+        let result = match request {
+            raw::Request::Read {
+                address,
+                register,
+                len,
+            } => i2c::WriteRead::write_read(bus, address, &[register], &mut buffer[..len]),
+            raw::Request::Write {
+                address,
+                register,
+                value,
+            } => i2c::Write::write(bus, address, &[register, value]),
+        };
+        raw_result(request.address(), result)
+    })?;
+    match (result, request) {
+        (
+            Ok(()),
+            raw::Request::Read {
+                address,
+                register,
+                len,
+            },
+        ) => writeln!(
+            serial,
+            "0x{:02x} reg 0x{:02x}: {}",
+            address,
+            register,
+            raw::Hex(&buffer[..len])
+        )
+        .unwrap(),
+        (
+            Ok(()),
+            raw::Request::Write {
+                address,
+                register,
+                value,
+            },
+        ) => writeln!(
+            serial,
+            "0x{:02x} reg 0x{:02x} <- {:02x}",
+            address, register, value
+        )
+        .unwrap(),
+        (Err(err), _) => print_error(serial, err).unwrap(),
+    }
+    Ok(())
+}
+
+fn probe_all(bus: &mut Bus) -> Result<Result<scan::Found, scan::Error>, TimedOut> {
+    let mut found = scan::Found::default();
+    let mut byte = [0];
+    for address in scan::FIRST..scan::END {
+        match i2c::Read::read(bus, address, &mut byte) {
+            Ok(()) => found.insert(address),
+            Err(BusError::TimedOut) => return Err(TimedOut),
+            Err(BusError::Bus(err)) if is_nack(&err) => {}
+            Err(BusError::Bus(_)) => return Ok(Err(scan::Error::Fault(address))),
+        }
+    }
+    Ok(Ok(found))
+}
+
+/// Set the LSM303AGR to `rates` until the next reset, and say so.
+fn set_rates(sensor: &mut Sensor, serial: &mut SerialPort, rates: odr::Rates) -> Result<(), Stop> {
+    let lsm = sensor.lsm()?;
+    sensor_result(lsm.set_accel_odr(rates.accel))?;
+    sensor_result(lsm.set_mag_odr(rates.mag))?;
+    sensor.rates = rates;
+    writeln!(serial, "odr: {}", rates).unwrap();
+    Ok(())
+}
+
+fn read_accelerometer(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<Measurement, Stop> {
+    read_accel_sample(sensor, serial).map(|(data, _)| data)
+}
+
+/// Also tells whether the sensor overwrote data nobody read since the
+/// previous sample.
+fn read_accel_sample(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+) -> Result<(Measurement, bool), Stop> {
+    if let Some(raw) = sensor.feed.next_accel() {
+        keep_going(serial)?;
+        return Ok((sensor.process_accel(raw), false));
+    }
+    let timeout_ms = fault::NO_DATA_MS + sensor.rates.accel_period_ms();
+    let (raw, overrun) = wait_for_data(sensor, serial, poller::Device::Accel, timeout_ms)?;
+    log!("got value:");
+    consistency::accel(raw);
+    Ok((sensor.process_accel(raw), overrun))
+}
+
+/// Take a fresh reading of `source`, in mg, nT, degrees Celsius or mV.
+fn read_source(sensor: &mut Sensor, serial: &mut SerialPort, source: Source) -> Result<i64, Stop> {
+    let value = match source {
+        Source::AccelX => read_accelerometer(sensor, serial)?.x,
+        Source::AccelY => read_accelerometer(sensor, serial)?.y,
+        Source::AccelZ => read_accelerometer(sensor, serial)?.z,
+        Source::MagX => read_magnetometer(sensor, serial)?.x,
+        Source::MagY => read_magnetometer(sensor, serial)?.y,
+        Source::MagZ => read_magnetometer(sensor, serial)?.z,
+        Source::Temp => onchip::temperature(),
+        Source::Vdd => onchip::vdd_mv(),
+    };
+    Ok(value as i64)
+}
+
+/// Sample the watched source until it triggers, or until Ctrl-C with
+/// "repeat".
+fn run_watch(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    watch: &watch::Watch,
+) -> Result<(), Stop> {
+    let mut held = false;
+    loop {
+        keep_going(serial)?;
+        let value = read_source(sensor, serial, watch.source)?;
+        let holds = watch.condition.holds(value, held);
+        if holds && !held {
+            match watch.action {
+                // Negative readings blink out as their magnitude
+                watch::Action::Blinkout => {
+                    let value = value.unsigned_abs().min(blinkout::MAX_VALUE as u64) as u16;
+                    blinkout::play(value, || keep_going(serial))?
+                }
+                action => action.perform(serial, watch.source, value).unwrap(),
+            }
+            if !watch.repeat {
+                return Ok(());
+            }
+        }
+        held = holds;
+        watch::delay_us(watch::SAMPLE_PERIOD_MS * 1000);
+    }
+}
+
+/// Draws one frame of a [`demo`] segment.
+#[cfg(feature = "demo")]
+type DemoHandler = fn(&mut Sensor, &mut SerialPort, u32) -> Result<demo::Image, Stop>;
+
+/// For frames that don't wait for a sensor reading.
+#[cfg(feature = "demo")]
+fn paced(image: demo::Image) -> Result<demo::Image, Stop> {
+    watch::delay_us(10_000);
+    Ok(image)
+}
+
+#[cfg(feature = "demo")]
+const TOUR: [demo::Segment<DemoHandler>; 5] = [
+    demo::Segment {
+        label: "hello",
+        handler: |_, _, ms| paced(demo::greeting(ms)),
+        duration_ms: demo::GREETING_MS,
+    },
+    demo::Segment {
+        label: "roulette",
+        handler: |_, _, ms| paced(demo::roulette(ms)),
+        duration_ms: 5_000,
+    },
+    demo::Segment {
+        label: "spirit level",
+        handler: |sensor, serial, _| {
+            let data = read_accelerometer(sensor, serial)?;
+            let (roll, pitch) = tilt::raw([data.x, data.y, data.z]);
+            Ok(demo::bubble(roll, pitch))
+        },
+        duration_ms: 10_000,
+    },
+    demo::Segment {
+        label: "compass",
+        handler: |sensor, serial, _| {
+            let data = read_magnetometer(sensor, serial)?;
+            Ok(demo::needle(data.x, data.y))
+        },
+        duration_ms: 10_000,
+    },
+    demo::Segment {
+        label: "temperature",
+        handler: |_, _, ms| paced(demo::temperature(onchip::temperature(), ms)),
+        duration_ms: 8_000,
+    },
+];
+
+#[cfg(feature = "demo")]
+struct DemoStage<'a> {
+    sensor: &'a mut Sensor,
+    serial: &'a mut SerialPort,
+    /// Of the segment playing
+    segment: usize,
+}
+
+#[cfg(feature = "demo")]
+impl demo::Stage<DemoHandler> for DemoStage<'_> {
+    type Error = Stop;
+
+    fn now_ms(&mut self) -> u32 {
+        uptime::millis() as u32
+    }
+
+    fn skip_held(&mut self) -> bool {
+        buttons::a()
+    }
+
+    fn begin(&mut self, label: &'static str) -> Result<(), Stop> {
+        // A dim dot along the top row for how far the tour has got, over
+        // whatever the segments draw
+        display::clear_overlay();
+        display::set_overlay_pixel(0, self.segment % 5, 1);
+        self.segment += 1;
+        writeln!(self.serial, "demo: {} (A skips, Ctrl-C stops)", label).unwrap();
+        nb::block!(embedded_hal::serial::Write::flush(self.serial)).unwrap();
+        Ok(())
+    }
+
+    fn frame(&mut self, handler: DemoHandler, elapsed_ms: u32) -> Result<(), Stop> {
+        keep_going(self.serial)?;
+        display::set_background(&handler(self.sensor, self.serial, elapsed_ms)?);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "demo")]
+fn run_demo(sensor: &mut Sensor, serial: &mut SerialPort) -> Result<(), Stop> {
+    // Segments are timed in ticks
+    health::require(Subsystem::Rtc)?;
+    let mut stage = DemoStage {
+        sensor,
+        serial,
+        segment: 0,
+    };
+    let result = demo::play(&mut stage, &TOUR);
+    display::set_background(&[[0; 5]; 5]);
+    display::clear_overlay();
+    result
+}
+
+/// Tell how far along a long-running handler is, and spin the activity LED
+/// meanwhile. The main loop turns it off again when the handler is done.
+fn report_progress(serial: &mut SerialPort, progress: &mut progress::Progress, percent: u8) {
+    progress
+        .update(serial, uptime::millis() as u32, percent)
+        .unwrap();
+    display::set_status(Role::Activity, heartbeat::ticks().is_multiple_of(2));
+}
+
+/// Answer a ping right away, rather than when the transmit buffer fills up.
+fn pong(serial: &mut SerialPort) {
+    writeln!(serial, "pong {}", uptime::millis() as u32).unwrap();
+    nb::block!(embedded_hal::serial::Write::flush(serial)).unwrap();
+}
+
+/// Pong `count` times, [`PING_INTERVAL_MS`] apart.
+fn run_ping(serial: &mut SerialPort, count: u8) -> Result<(), Stop> {
+    for i in 0..count {
+        if i > 0 {
+            // In small steps, to answer Ctrl-C in between
+            for _ in 0..PING_INTERVAL_MS / 10 {
+                keep_going(serial)?;
+                watch::delay_us(10_000);
+            }
+        }
+        pong(serial);
+    }
+    Ok(())
+}
+
+/// "capture <n>": a fresh capture of `count` accelerometer samples.
+fn run_capture(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    capture: &mut capture::Capture,
+    count: usize,
+    mode: reply::Mode,
+) -> Result<(), Stop> {
+    capture.clear();
+    let mut progress = progress::Progress::new(mode);
+    for i in 0..count {
+        report_progress(serial, &mut progress, (i * 100 / count) as u8);
+        // On Ctrl-C what was captured so far stays
+        let data = read_accelerometer(sensor, serial)?;
+        capture.push([data.x, data.y, data.z]);
+    }
+    report_progress(serial, &mut progress, 100);
+    progress.finish(serial).unwrap();
+    writeln!(
+        serial,
+        "{} samples captured, {} blocks",
+        capture.len(),
+        capture.blocks()
+    )
+    .unwrap();
+    Ok(())
+}
+
+/// "capture dump": the blocks from `from` on.
+fn run_capture_dump(
+    serial: &mut SerialPort,
+    capture: &capture::Capture,
+    from: usize,
+) -> Result<(), Stop> {
+    if let Err(err) = capture.check_from(from) {
+        print_error(serial, err).unwrap();
+        return Ok(());
+    }
+    for index in from..capture.blocks() {
+        keep_going(serial)?;
+        capture.write_block(serial, index).unwrap();
+    }
+    Ok(())
+}
+
+/// "provision import": take lines until every one is in, then write them
+/// all. `true` once they are in flash.
+fn run_import(serial: &mut SerialPort, sensor: &mut Sensor) -> Result<bool, Stop> {
+    let lines = provision::Import::lines();
+    writeln!(
+        serial,
+        "paste the {} lines from \"provision export\"",
+        lines
+    )
+    .unwrap();
+    let mut import = provision::Import::new();
+    let mut buffer: Vec<u8, LINE_LEN> = Vec::new();
+    while !import.complete() {
+        let line = match try_fill_buffer_with_echo(serial, sensor, &mut buffer) {
+            Err(Error::Interrupted) => return Err(Stop::Interrupted),
+            Err(err) => Err(err),
+            Ok(()) => core::str::from_utf8(&buffer).map_err(Error::from),
+        };
+        let accepted = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => import.accept(line),
+            Err(err) => {
+                print_error(serial, err).unwrap();
+                continue;
+            }
+        };
+        match accepted {
+            Ok(provision::Accepted::New { index, received }) => {
+                writeln!(serial, "line {} ok, {} of {}", index, received, lines)
+            }
+            Ok(provision::Accepted::Repeat { index }) => {
+                writeln!(serial, "line {} again, ignored", index)
+            }
+            Err(err) => print_error(serial, err),
+        }
+        .unwrap();
+    }
+    match import.install() {
+        Ok(records) => {
+            writeln!(serial, "{} records written and verified", records).unwrap();
+            Ok(true)
+        }
+        Err(err) => {
+            print_error(serial, err).unwrap();
+            Ok(false)
+        }
+    }
+}
+
+/// Print an accelerometer or magnetometer reading in `output_format`, with
+/// its `stamp` if it is streamed, and keep it for "recent".
+fn print_reading(
+    serial: &mut SerialPort,
+    ring: &mut recent::Ring,
+    kind: recent::Kind,
+    output_format: format::OutputFormat,
+    stamp: Option<&stamp::Stamp>,
+    sample: Sample,
+) {
+    let ms = uptime::millis() as u32;
+    let stamp = match (stamp, output_format) {
+        (Some(stamp), _) => Some(stamp as &dyn core::fmt::Display),
+        (None, format::OutputFormat::Human) => None,
+        // A script gets the time as the first column either way. A stream
+        // prints its own header
+        (None, format::OutputFormat::Csv) => {
+            if format::take_header(kind) {
+                format::write_header(serial, kind, Some("ms"), sample.axes).unwrap();
+            }
+            Some(&ms as &dyn core::fmt::Display)
+        }
+    };
+    format::write_sample(serial, output_format, kind, stamp, sample).unwrap();
+    ring.push(recent::Entry { ms, sample });
+}
+
+/// Print a streaming command's header line: what the columns are, their
+/// units and how often a line comes.
+///
+/// Streams print it when they start and then again, between two data lines,
+/// whenever the host asks for it.
+fn emit_header(serial: &mut SerialPort, header: impl core::fmt::Display) {
+    writeln!(serial, "# {}", header).unwrap();
+}
+
+/// Read `device` for the poller without waiting for it. A bus error is the
+/// device's own and only fails this read, a watchdog timeout stops the
+/// stream.
+fn poll_device(sensor: &mut Sensor, device: poller::Device) -> Result<poller::Reading, TimedOut> {
+    use poller::Reading;
+
+    fn polled<T, E>(
+        result: Result<T, lsm303agr::Error<BusError<E>, ()>>,
+    ) -> Result<Option<T>, TimedOut> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(lsm303agr::Error::Comm(BusError::TimedOut)) => Err(TimedOut),
+            Err(_) => Ok(None),
+        }
+    }
+
+    let reading = match device {
+        poller::Device::Accel => {
+            if let Some(raw) = sensor.feed.next_accel() {
+                let data = sensor.process_accel(raw);
+                return Ok(Reading::Values([data.x, data.y, data.z]));
+            }
+            let lsm = match sensor.lsm.as_mut() {
+                Some(lsm) => lsm,
+                None => return Ok(Reading::Failed),
+            };
+            polled(lsm.accel_data())?.map(|data| {
+                let raw = [data.x, data.y, data.z];
+                consistency::accel(raw);
+                let data = sensor.process_accel(raw);
+                [data.x, data.y, data.z]
+            })
+        }
+        poller::Device::Mag => {
+            if let Some(raw) = sensor.feed.next_mag() {
+                let data = sensor.process_mag(raw);
+                return Ok(Reading::Values([data.x, data.y, data.z]));
+            }
+            let lsm = match sensor.lsm.as_mut() {
+                Some(lsm) => lsm,
+                None => return Ok(Reading::Failed),
+            };
+            // One shot: this starts the next measurement, and the next
+            // turn picks it up
+            match lsm.mag_data() {
+                Err(nb::Error::WouldBlock) => return Ok(Reading::NotReady),
+                Err(nb::Error::Other(err)) => polled(Err(err))?,
+                Ok(data) => {
+                    let raw = [data.x, data.y, data.z];
+                    consistency::mag(raw);
+                    let data = sensor.process_mag(raw);
+                    Some([data.x, data.y, data.z])
+                }
+            }
+        }
+    };
+    Ok(reading.map_or(Reading::Failed, Reading::Values))
+}
+
+/// Print whatever `poller` says is due, tagged with its device, until
+/// Ctrl-C.
+fn stream_poll(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    settings: &Settings,
+    poller: &mut poller::Poller,
+) -> Result<(), Stop> {
+    let header = |serial: &mut SerialPort| {
+        let columns = settings.axes.columns();
+        emit_header(serial, format_args!("poll: ms device {}", columns))
+    };
+    abort::take_header_request();
+    header(serial);
+    loop {
+        keep_going(serial)?;
+        let now = heartbeat::ticks();
+        while let Some(device) = poller.next_due(now) {
+            let reading = poll_device(sensor, device)?;
+            if abort::take_header_request() {
+                header(serial);
+            }
+            let ms = uptime::millis() as u32;
+            if let poller::Reading::Values(values) = reading {
+                let sample = Sample {
+                    axes: settings.axes,
+                    values,
+                };
+                writeln!(serial, "{} {} {}", ms, device.tag(), sample).unwrap();
+            }
+            if poller.served(now, &reading) {
+                log!("{} stopped answering", device.tag());
+                writeln!(serial, "{} {} failed", ms, device.tag()).unwrap();
+            }
+        }
+    }
+}
+
+/// Stream acceleration with gravity taken out until Ctrl-C.
+fn stream_linear_accel(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    settings: &Settings,
+) -> Result<(), Stop> {
+    let rate_hz = sensor.rate_hz(recent::Kind::Accel);
+    let time = settings.time_format.column();
+    let header = |serial: &mut SerialPort| {
+        let columns = settings.axes.columns();
+        emit_header(
+            serial,
+            format_args!(
+                "linearaccel: {} {}magnitude, mg, {} Hz",
+                time, columns, rate_hz
+            ),
+        )
+    };
+    let mut gravity = gravity::Gravity::new();
+    let mut clock = stamp::Clock::start(settings.time_format, rate_hz);
+    abort::take_header_request();
+    header(serial);
+    loop {
+        let (data, overrun) = read_accel_sample(sensor, serial)?;
+        let stamp = clock.count(overrun);
+        if let Some(linear) = gravity.update([data.x, data.y, data.z]) {
+            if abort::take_header_request() {
+                header(serial);
+            }
+            let sample = Sample {
+                axes: settings.axes,
+                values: linear,
+            };
+            writeln!(
+                serial,
+                "{} Linear acceleration (mg): {} magnitude {}",
+                stamp,
+                sample,
+                gravity::magnitude(linear)
+            )
+            .unwrap();
+            clock.printed();
+        }
+    }
+}
+
+/// Stream filtered roll and pitch at 20 Hz, or at the accelerometer's rate
+/// if that is slower, until Ctrl-C.
+fn stream_tilt(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    settings: &Settings,
+) -> Result<(), Stop> {
+    const PRINT_HZ: u32 = 20;
+    let rate_hz = sensor.rate_hz(recent::Kind::Accel);
+    let print_hz = PRINT_HZ.min(rate_hz);
+    let time = settings.time_format.column();
+    let header = |serial: &mut SerialPort| {
+        emit_header(
+            serial,
+            format_args!("tilt: {} roll pitch, deg, {} Hz", time, print_hz),
+        )
+    };
+    let mut filter = tilt::TiltFilter::new(settings.tilt_alpha);
+    let mut clock = stamp::Clock::start(settings.time_format, rate_hz);
+    // Every sample goes through the filter, but only 20 out of however
+    // many arrive each second get printed
+    let mut credit = 0;
+    abort::take_header_request();
+    header(serial);
+    loop {
+        let (data, overrun) = read_accel_sample(sensor, serial)?;
+        let stamp = clock.count(overrun);
+        let (roll, pitch) = filter.update(tilt::raw([data.x, data.y, data.z]));
+        credit += print_hz;
+        if credit >= rate_hz {
+            credit -= rate_hz;
+            if abort::take_header_request() {
+                header(serial);
+            }
+            writeln!(
+                serial,
+                "{} Tilt (deg): roll {} pitch {}",
+                stamp,
+                tilt::Centi(roll),
+                tilt::Centi(pitch)
+            )
+            .unwrap();
+            clock.printed();
+        }
+    }
+}
+
+/// Print which face of the board is up, see [`orientation`].
+fn print_orientation(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    threshold_mg: i32,
+) -> Result<(), Stop> {
+    let data = read_accelerometer(sensor, serial)?;
+    match orientation::classify([data.x, data.y, data.z], threshold_mg) {
+        Some(face) => writeln!(serial, "orientation: {}", face).unwrap(),
+        None => writeln!(serial, "orientation: between faces").unwrap(),
+    }
+    Ok(())
+}
+
+/// Print the face up every time it changes, starting with the first that
+/// is clear, until Ctrl-C.
+fn stream_orientation(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    threshold_mg: i32,
+) -> Result<(), Stop> {
+    let mut tracker = orientation::Tracker::new(threshold_mg);
+    loop {
+        let data = read_accelerometer(sensor, serial)?;
+        if let Some(face) = tracker.update([data.x, data.y, data.z]) {
+            writeln!(serial, "orientation: {}", face).unwrap();
+        }
+    }
+}
+
+/// Stream `kind`'s readings as they come, at the rate they come at, until
+/// Ctrl-C or any other key. The key is taken, it never gets to the next
+/// command line.
+fn stream_readings(
+    sensor: &mut Sensor,
+    serial: &mut SerialPort,
+    settings: &Settings,
+    ring: &mut recent::Ring,
+    kind: recent::Kind,
+) -> Result<(), Stop> {
+    let time = settings.time_format.column();
+    let rate_hz = sensor.rate_hz(kind);
+    let (name, unit) = match kind {
+        recent::Kind::Accel => ("accelerometer", "mg"),
+        recent::Kind::Mag => ("magnetometer", "nT"),
+    };
+    let header = |serial: &mut SerialPort| match settings.format {
+        format::OutputFormat::Human => {
+            let columns = settings.axes.columns();
+            emit_header(
+                serial,
+                format_args!("{}: {} {}in {}, {} Hz", name, time, columns, unit, rate_hz),
+            )
+        }
+        format::OutputFormat::Csv => {
+            format::write_header(serial, kind, Some(time), settings.axes).unwrap()
+        }
+    };
+    let mut clock = stamp::Clock::start(settings.time_format, rate_hz);
+    abort::take_header_request();
+    header(serial);
+    loop {
+        let (data, overrun) = match kind {
+            recent::Kind::Accel => read_accel_sample(sensor, serial)?,
+            recent::Kind::Mag => (read_magnetometer(sensor, serial)?, false),
+        };
+        // The read polled for it on the way
+        if serial.take_typeahead().is_some() {
+            return Ok(());
+        }
+        let stamp = clock.count(overrun);
+        if abort::take_header_request() {
+            header(serial);
+        }
+        let sample = Sample {
+            axes: settings.axes,
+            values: [data.x, data.y, data.z],
+        };
+        print_reading(serial, ring, kind, settings.format, Some(&stamp), sample);
+        clock.printed();
+    }
+}
+
+/// What the command loop runs on, and only it: nothing in here is ever
+/// touched by an interrupt handler. The rest is in [`shared`].
+struct Context {
+    settings: Settings,
+    uarte: SerialPort,
+    sensor: Sensor,
+    capture: capture::Capture,
+    /// Why the magnetometer runs without offsets, if it does
+    uncalibrated: Option<calibration::LoadError>,
+}
+
+/// Start the LSM303AGR at `rates`.
+fn start_sensor(mut bus: Bus, rates: &odr::Rates) -> Result<Lsm, InitError> {
+    // The driver leaves INT1 alone, see [`drdy`]
+    let int1 = if cfg!(feature = "poll-status") {
+        0
+    } else {
+        drdy::I1_ZYXDA
+    };
+    i2c::Write::write(&mut bus, drdy::ACCEL_ADDRESS, &[drdy::CTRL_REG3_A, int1])
+        .map_err(|_| InitError(Subsystem::Sensor))?;
+    let mut lsm = Lsm303agr::new_with_i2c(bus);
+    lsm.init()
+        .and_then(|()| lsm.set_accel_odr(rates.accel))
+        .and_then(|()| lsm.set_mag_odr(rates.mag))
+        .map_err(|_| InitError(Subsystem::Sensor))?;
+    Ok(lsm)
+}
+
+/// Bring up whatever comes up. Only the subsystems without which there is
+/// no shell to enter are errors, the others are recorded in [`health`].
+fn init() -> Result<Context, InitError> {
+    let board = microbit::Board::take().ok_or(InitError(Subsystem::Peripherals))?;
+    #[cfg(all(debug_assertions, feature = "v2"))]
+    shared::start_timing(board.DCB, board.DWT);
+    let settings = settings::load().unwrap_or_default();
+    let calibration = calibration::load();
+
+    // The RTC driving the heartbeat runs off the LFCLK
+    Clocks::new(board.CLOCK).start_lfclk();
+    irqstats::init(board.TIMER2);
+    display::init(board.TIMER1, board.display_pins);
+    #[cfg(feature = "v2")]
+    cues::init(board.PWM0, board.speaker_pin);
+    cues::set_enabled(settings.audio_cues).ok();
+    configure_power(&settings);
+    display::set_status_leds(settings.status_leds);
+    pulse::play(|image, ms| {
+        display::set_background(image);
+        watch::delay_us(ms * 1000);
+    });
+    // Scrolls for as long as the rest takes to start, and then some
+    board::start_banner(&settings.name, heartbeat::ticks());
+    heartbeat::init(board.RTC0, settings.heartbeat).unwrap_or_else(health::record);
+    pof::init();
+    battery::init(settings.batt_warn_mv);
+    // Now that there is a first supply sample to go by
+    powersave::poll();
+    consistency::configure(settings.sensor_health);
+    odometer::init();
+    buttons::init();
+    drdy::init();
+    #[cfg(feature = "simulate")]
+    sim::self_test().unwrap_or_else(health::record);
+
+    #[cfg(feature = "v1")]
+    let (i2c_peripheral, console) = (power::Peripheral::Twi0, power::Peripheral::Uart0);
+    #[cfg(feature = "v2")]
+    let (i2c_peripheral, console) = (power::Peripheral::Twim0, power::Peripheral::Uarte0);
+    claims::take(
+        claims::Resource::Peripheral(i2c_peripheral),
+        claims::Owner::permanent("the LSM303AGR driver"),
+    );
+    claims::take(
+        claims::Resource::Peripheral(console),
+        claims::Owner::permanent("the console"),
+    );
+
+    #[cfg(feature = "v1")]
+    let i2c = { twi::Twi::new(board.TWI0, board.i2c.into(), FREQUENCY_A::K100) };
+
+    #[cfg(feature = "v2")]
+    let i2c = { twim::Twim::new(board.TWIM0, board.i2c_internal.into(), FREQUENCY_A::K100) };
+
+    #[cfg(feature = "v1")]
+    let serial = uart::Uart::new(
+        board.UART0,
+        board.uart.into(),
+        Parity::EXCLUDED,
+        Baudrate::BAUD115200,
+    );
+
+    #[cfg(feature = "v2")]
+    let serial = uarte::Uarte::new(
+        board.UARTE0,
+        board.uart.into(),
+        Parity::EXCLUDED,
+        Baudrate::BAUD115200,
+    );
+
+    let uarte = {
+        let buffers = unsafe { &mut *core::ptr::addr_of_mut!(SERIAL_BUFFERS) };
+        let mut port =
+            SerialPort::new(serial, buffers).map_err(|_| InitError(Subsystem::Serial))?;
+        port.set_line_end(settings.line_end);
+        port
+    };
+
+    let sensor = Sensor {
+        lsm: start_sensor(Guarded::new(Traced::new(i2c)), &odr::Rates::START)
+            .map_err(health::record)
+            .ok(),
+        filter: filter::Filter::new(settings.filter),
+        calibration: calibration.unwrap_or(calibration::Calibration::NONE),
+        rates: odr::Rates::START,
+        feed: Feed::Live,
+    };
+    Ok(Context {
+        settings,
+        uarte,
+        sensor,
+        capture: capture::Capture::take(),
+        uncalibrated: calibration.err(),
+    })
+}
+
+/// The console's DMA buffers. In .bss, and only ever handed out once, by
+/// [`init`].
+static mut SERIAL_BUFFERS: serial_setup::Buffers = serial_setup::Buffers::new();
+
+/// A cross, for when there is no serial port to say what went wrong on.
+const INIT_FAILED: display::Image = [
+    [9, 0, 0, 0, 9],
+    [0, 9, 0, 9, 0],
+    [0, 0, 9, 0, 0],
+    [0, 9, 0, 9, 0],
+    [9, 0, 0, 0, 9],
+];
+
+#[entry]
+fn main() -> ! {
+    log::init();
+    let Context {
+        mut settings,
+        mut uarte,
+        mut sensor,
+        mut capture,
+        uncalibrated,
+    } = match init() {
+        Ok(context) => context,
+        Err(err) => {
+            log!("{}, giving up", err);
+            // Shows nothing without the peripherals, the display needs them
+            display::set_background(&INIT_FAILED);
+            loop {
+                cortex_m::asm::wfi();
+            }
+        }
+    };
+
+    menu::banner(&mut uarte).unwrap();
+    if let Some(err) = uncalibrated {
+        writeln!(uarte, "{}", err).unwrap();
+    }
+    let mut menu = Menu::Root;
+    let mut recent_accel = recent::Ring::new();
+    let mut recent_mag = recent::Ring::new();
+    let mut poller = poller::Poller::new();
+    loop {
+        // The mode the ack went out in, even if the command changes it
+        let mode = settings.output;
+        let tag = sensor.feed.tag();
+        let (command, name, sink) = read_command(
+            &mut uarte,
+            &mut sensor,
+            &mut menu,
+            mode,
+            settings.name.as_str(),
+            tag,
+        )
+        .unwrap();
+        board::stop_banner();
+        let errors = stats::session().errors;
+        abort::clear();
+        let streams = command.streams();
+        feedback::signal(if streams {
+            feedback::Event::StreamStarted
+        } else {
+            feedback::Event::Accepted
+        });
+        if let Some(timeout_ms) = command.timeout_ms(&sensor.rates) {
+            watchdog::arm(timeout_ms);
+        }
+        if sink == Sink::Log {
+            uarte.divert(textlog::Text::new());
+        }
+        let confirmed = if command.protected() {
+            confirm(&mut sensor, &mut uarte)
+        } else {
+            Ok(())
+        };
+        let result = confirmed.and_then(|()| match command {
+            Command::Magnetometer => {
+                log!("reading magnetometer");
+                read_magnetometer(&mut sensor, &mut uarte).map(|data| {
+                    let sample = Sample {
+                        axes: settings.axes,
+                        values: [data.x, data.y, data.z],
+                    };
+                    print_reading(
+                        &mut uarte,
+                        &mut recent_mag,
+                        recent::Kind::Mag,
+                        settings.format,
+                        None,
+                        sample,
+                    )
+                })
+            }
+            Command::Accelerometer => {
+                log!("reading accelerometer");
+                read_accelerometer(&mut sensor, &mut uarte).map(|data| {
+                    let sample = Sample {
+                        axes: settings.axes,
+                        values: [data.x, data.y, data.z],
+                    };
+                    print_reading(
+                        &mut uarte,
+                        &mut recent_accel,
+                        recent::Kind::Accel,
+                        settings.format,
+                        None,
+                        sample,
+                    )
+                })
+            }
+            Command::Average(kind, count) => {
+                print_average(&mut sensor, &mut uarte, &settings, kind, count)
+            }
+            Command::Heading(tilted) => {
+                log!("reading heading");
+                read_heading(&mut sensor, &mut uarte, tilted, heading::SAMPLES).map(|heading| {
+                    match heading {
+                        Some(heading) => writeln!(uarte, "heading: {}", heading).unwrap(),
+                        None => writeln!(uarte, "heading: no horizontal field").unwrap(),
+                    }
+                })
+            }
+            Command::CompassShow => show_compass(&mut sensor, &mut uarte),
+            Command::Ball => roll_ball(&mut sensor, &mut uarte),
+            Command::Punch => measure_punch(&mut sensor, &mut uarte),
+            Command::Scan => scan_bus(&mut sensor, &mut uarte),
+            Command::I2c(request) => raw_transaction(&mut sensor, &mut uarte, request),
+            Command::Temperature(both) => print_temperature(&mut sensor, &mut uarte, both),
+            Command::OdrShow => {
+                writeln!(uarte, "odr: {}", sensor.rates).unwrap();
+                Ok(())
+            }
+            Command::OdrAccel(accel) => {
+                let rates = odr::Rates {
+                    accel,
+                    ..sensor.rates
+                };
+                set_rates(&mut sensor, &mut uarte, rates)
+            }
+            Command::OdrMag(mag) => {
+                let rates = odr::Rates {
+                    mag,
+                    ..sensor.rates
+                };
+                set_rates(&mut sensor, &mut uarte, rates)
+            }
+            Command::Calibrate(None) => {
+                run_calibration(&mut sensor, &mut uarte, mode).map(|found| match found {
+                    Some(found) => {
+                        let [x, y, z] = found.offset;
+                        writeln!(uarte, "offsets x {} y {} z {} nT", x, y, z).unwrap();
+                        save_calibration(&mut uarte, &mut sensor, found);
+                    }
+                    None => writeln!(
+                        uarte,
+                        "not turned every way in time, the offsets stay as they were"
+                    )
+                    .unwrap(),
+                })
+            }
+            Command::Calibrate(Some(calibration)) => {
+                save_calibration(&mut uarte, &mut sensor, calibration);
+                Ok(())
+            }
+            Command::CalibrationClear => {
+                match calibration::clear() {
+                    Ok(()) => sensor.calibration = calibration::Calibration::NONE,
+                    Err(err) => print_error(&mut uarte, err).unwrap(),
+                }
+                Ok(())
+            }
+            Command::PowerReport => {
+                power::report(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::PofStatus => {
+                pof::report(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::PowerSave(None) => {
+                powersave::report(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::PowerSave(Some(mode)) => {
+                settings.powersave = mode;
+                configure_power(&settings);
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::PowerOff(peripheral) => {
+                match power::power_off(peripheral) {
+                    Ok(()) => writeln!(uarte, "{} powered off", peripheral.name()).unwrap(),
+                    Err(err) => print_error(&mut uarte, err).unwrap(),
+                }
+                Ok(())
+            }
+            Command::Heartbeat(enabled) => {
+                heartbeat::set_enabled(enabled);
+                settings.heartbeat = enabled;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::AudioCues(enabled) => {
+                match cues::set_enabled(enabled) {
+                    Ok(()) => {
+                        settings.audio_cues = enabled;
+                        save_settings(&mut uarte, &settings);
+                    }
+                    Err(err) => print_error(&mut uarte, err).unwrap(),
+                }
+                Ok(())
+            }
+            Command::Uptime => {
+                let serial = uarte.stats();
+                stats::report(&mut uarte, serial).unwrap();
+                Ok(())
+            }
+            Command::Status => {
+                health::report(&mut uarte).unwrap();
+                consistency::status(&mut uarte).unwrap();
+                powersave::status(&mut uarte).unwrap();
+                cues::status(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::Marker(text) => {
+                log::marker(&mut uarte, &text).unwrap();
+                Ok(())
+            }
+            Command::I2cTrace(enabled) => {
+                i2ctrace::set_enabled(enabled);
+                Ok(())
+            }
+            Command::I2cTraceDump => {
+                i2ctrace::dump(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::IrqStats => {
+                irqstats::report(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::IrqStatsReset => {
+                irqstats::reset();
+                Ok(())
+            }
+            Command::Help => {
+                menu::help(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::TableCheck => {
+                match menu::check() {
+                    Ok(()) => writeln!(uarte, "command tables ok").unwrap(),
+                    Err(problem) => print_error(&mut uarte, problem).unwrap(),
+                }
+                Ok(())
+            }
+            Command::ConfigExport => {
+                settings::export(&mut uarte, &settings).unwrap();
+                Ok(())
+            }
+            Command::SensorHealth => {
+                consistency::report(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::SensorHealthEnable(enabled) => {
+                settings.sensor_health.enabled = enabled;
+                consistency::configure(settings.sensor_health);
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::SensorHealthCapture => match sensor.feed.tag() {
+                // The monitor only ever sees live readings
+                Some(_) => {
+                    print_error(&mut uarte, consistency::NotLive).unwrap();
+                    Ok(())
+                }
+                None => read_magnetometer(&mut sensor, &mut uarte).map(|data| {
+                    let nt = consistency::magnitude([data.x, data.y, data.z]) as u32;
+                    writeln!(uarte, "reference field {} nT", nt).unwrap();
+                    settings.sensor_health.reference_nt = nt;
+                    consistency::configure(settings.sensor_health);
+                    save_settings(&mut uarte, &settings);
+                }),
+            },
+            Command::SensorHealthReference(nt) => {
+                settings.sensor_health.reference_nt = nt;
+                consistency::configure(settings.sensor_health);
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::SensorHealthBands(mag, accel, hold) => {
+                let config = &mut settings.sensor_health;
+                config.mag_band_pct = mag;
+                config.accel_band_pct = accel;
+                config.hold_s = hold;
+                consistency::configure(settings.sensor_health);
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::Name(None) => {
+                writeln!(uarte, "{}", settings.name).unwrap();
+                Ok(())
+            }
+            Command::Name(Some(name)) => {
+                settings.name = name;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::Version => {
+                board::version(&mut uarte, settings.name.as_str()).unwrap();
+                Ok(())
+            }
+            Command::Capture(count) => {
+                run_capture(&mut sensor, &mut uarte, &mut capture, count, mode)
+            }
+            Command::CaptureDump(from) => run_capture_dump(&mut uarte, &capture, from),
+            Command::CaptureClear => {
+                capture.clear();
+                Ok(())
+            }
+            Command::LogRead => {
+                textlog::read(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::ProvisionExport => {
+                match provision::export() {
+                    Ok(lines) => write!(uarte, "{}", lines).unwrap(),
+                    Err(err) => print_error(&mut uarte, err).unwrap(),
+                }
+                Ok(())
+            }
+            Command::ProvisionImport => run_import(&mut uarte, &mut sensor).map(|written| {
+                if written {
+                    // Whatever made it into flash, as the next boot would
+                    settings = settings::load().unwrap_or_default();
+                    apply_settings(&mut sensor, &settings);
+                }
+            }),
+            Command::Filter(kind) => {
+                sensor.filter = filter::Filter::new(kind);
+                settings.filter = kind;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::Blinkout(value) => blinkout::play(value, || keep_going(&mut uarte)),
+            Command::StatusLed(role, led) => {
+                settings.status_leds.set(role, led);
+                display::set_status_leds(settings.status_leds);
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::Brightness(level) => {
+                settings.brightness = level;
+                configure_power(&settings);
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::Gamma(gamma) => {
+                display::set_gamma(gamma);
+                Ok(())
+            }
+            Command::Recent(kind, count) => {
+                let ring = match kind {
+                    recent::Kind::Accel => &recent_accel,
+                    recent::Kind::Mag => &recent_mag,
+                };
+                for entry in ring.last(count) {
+                    writeln!(uarte, "{} ms {}: {}", entry.ms, kind.label(), entry.sample).unwrap();
+                }
+                if ring.count() < count {
+                    writeln!(uarte, "({} readings so far)", ring.count()).unwrap();
+                }
+                Ok(())
+            }
+            Command::Ping(count) => run_ping(&mut uarte, count),
+            #[cfg(feature = "demo")]
+            Command::Demo => run_demo(&mut sensor, &mut uarte),
+            #[cfg(feature = "simulate")]
+            Command::Simulate(sim) => {
+                if sim.is_some_and(|sim| sim.needs_rng()) {
+                    health::require(Subsystem::Rng)?;
+                }
+                sensor.set_feed(sim.map_or(Feed::Live, Feed::Sim), settings.filter);
+                Ok(())
+            }
+            #[cfg(feature = "replay")]
+            Command::Replay(on) => {
+                let feed = if on {
+                    Feed::Replay(replay::Replay::default())
+                } else {
+                    Feed::Live
+                };
+                sensor.set_feed(feed, settings.filter);
+                Ok(())
+            }
+            Command::Output(mode) => {
+                settings.output = mode;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::Format(output_format) => {
+                settings.format = output_format;
+                format::forget_headers();
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::LineEnd(line_end) => {
+                uarte.set_line_end(line_end);
+                settings.line_end = line_end;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::TimeFormat(format) => {
+                settings.time_format = format;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::Axes(axes) => {
+                settings.axes = axes;
+                format::forget_headers();
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::BattWarn(mv) => {
+                battery::set_threshold(mv);
+                settings.batt_warn_mv = mv;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::ConfigReset => {
+                settings = Settings::default();
+                apply_settings(&mut sensor, &settings);
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::OdometerReset => {
+                if let Err(err) = odometer::reset() {
+                    print_error(&mut uarte, err).unwrap();
+                }
+                Ok(())
+            }
+            Command::FlashErase => {
+                let mut progress = progress::Progress::new(mode);
+                // The odometer log starts over on the freshly erased pages
+                let erased = (0..flash::PAGES)
+                    .try_for_each(|page| {
+                        let percent = (page * 100 / flash::PAGES) as u8;
+                        report_progress(&mut uarte, &mut progress, percent);
+                        flash::erase(page)
+                    })
+                    .and_then(|()| odometer::reset());
+                report_progress(&mut uarte, &mut progress, 100);
+                progress.finish(&mut uarte).unwrap();
+                match erased {
+                    Ok(()) => {
+                        // Nothing to go back to at the next boot either
+                        settings = Settings::default();
+                        apply_settings(&mut sensor, &settings);
+                        sensor.calibration = calibration::Calibration::NONE;
+                    }
+                    Err(err) => print_error(&mut uarte, err).unwrap(),
+                }
+                Ok(())
+            }
+            Command::TiltFilter(alpha) => {
+                settings.tilt_alpha = alpha;
+                save_settings(&mut uarte, &settings);
+                Ok(())
+            }
+            Command::TiltStream => stream_tilt(&mut sensor, &mut uarte, &settings),
+            Command::Orientation(false, threshold_mg) => {
+                print_orientation(&mut sensor, &mut uarte, threshold_mg)
+            }
+            Command::Orientation(true, threshold_mg) => {
+                stream_orientation(&mut sensor, &mut uarte, threshold_mg)
+            }
+            Command::Stream(kind) => {
+                let ring = match kind {
+                    recent::Kind::Accel => &mut recent_accel,
+                    recent::Kind::Mag => &mut recent_mag,
+                };
+                stream_readings(&mut sensor, &mut uarte, &settings, ring, kind)
+            }
+            Command::LinearAccel => stream_linear_accel(&mut sensor, &mut uarte, &settings),
+            Command::Poll => {
+                poller.report(&mut uarte).unwrap();
+                Ok(())
+            }
+            Command::PollStream => stream_poll(&mut sensor, &mut uarte, &settings, &mut poller),
+            Command::PollInterval(device, ms) => {
+                let ticks = poller::ticks(ms);
+                if let Err(err) = poller.register(device, ticks, heartbeat::ticks()) {
+                    print_error(&mut uarte, err).unwrap();
+                }
+                Ok(())
+            }
+            Command::Watch(watch) => run_watch(&mut sensor, &mut uarte, &watch),
+            #[cfg(feature = "calc")]
+            Command::Calc(expr) => {
+                match calc::eval(&expr, |source| read_source(&mut sensor, &mut uarte, source)) {
+                    Ok(value) => {
+                        writeln!(uarte, "{}", value).unwrap();
+                        Ok(())
+                    }
+                    Err(calc::Error::Read(stop)) => Err(stop),
+                    Err(calc::Error::Syntax(pos, msg)) => {
+                        let err = calc::Pointed {
+                            expr: &expr,
+                            pos,
+                            msg,
+                        };
+                        print_error(&mut uarte, err).unwrap();
+                        Ok(())
+                    }
+                }
+            }
+        });
+        watchdog::disarm();
+        if let Some(text) = uarte.undivert() {
+            match textlog::append(&text) {
+                Ok(()) if text.dropped() == 0 => {
+                    writeln!(uarte, "{} bytes logged", text.kept()).unwrap()
+                }
+                Ok(()) => writeln!(
+                    uarte,
+                    "{} bytes logged, {} dropped",
+                    text.kept(),
+                    text.dropped()
+                )
+                .unwrap(),
+                Err(err) => print_error(&mut uarte, err).unwrap(),
+            }
+        }
+        feedback::signal(if streams {
+            feedback::Event::StreamStopped
+        } else {
+            feedback::Event::Finished
+        });
+        let status = match result {
+            Ok(()) if stats::session().errors == errors => reply::Status::Ok,
+            // The handler printed an error of its own
+            Ok(()) => reply::Status::Error,
+            Err(Stop::Interrupted) => reply::Status::Interrupted,
+            Err(Stop::TimedOut) => reply::Status::TimedOut,
+            Err(Stop::NotConfirmed) => reply::Status::NotConfirmed,
+            Err(Stop::Unavailable(_)) => reply::Status::Error,
+            Err(Stop::Sensor(_)) => reply::Status::Error,
+            Err(Stop::NoData) => reply::Status::TimedOut,
+        };
+        match result {
+            Ok(()) => {}
+            Err(Stop::Interrupted) => writeln!(uarte, "^C").unwrap(),
+            Err(Stop::TimedOut) => {
+                print_error(&mut uarte, TimedOut).unwrap();
+                bus::recover();
+            }
+            Err(Stop::NotConfirmed) => print_error(&mut uarte, confirm::NotConfirmed).unwrap(),
+            Err(Stop::Unavailable(err)) => print_error(&mut uarte, err).unwrap(),
+            Err(Stop::Sensor(err)) => {
+                print_error(&mut uarte, err).unwrap();
+                if fault::failed() {
+                    recover_sensor(&mut sensor, &mut uarte);
+                }
+            }
+            Err(Stop::NoData) => {
+                print_error(&mut uarte, fault::NoData).unwrap();
+                if fault::failed() {
+                    recover_sensor(&mut sensor, &mut uarte);
+                }
+            }
+        }
+        // Only lines that got an ack get a done
+        if !name.is_empty() {
+            reply::done(&mut uarte, mode, &name, status, tag).unwrap();
         }
         nb::block!(embedded_hal::serial::Write::flush(&mut uarte)).unwrap();
     }
@@ -0,0 +1,1072 @@
+//! Submenus over the flat command set.
+//!
+//! Typing a menu's name on its own enters it, and from then on its short
+//! names stand for the flat commands they are listed with: in "accel",
+//! "read" means "accelerometer". Anything a menu doesn't know falls through
+//! to the flat commands, so nothing becomes unreachable from inside a
+//! menu, and "exit" goes back to the top. From the top a menu's short names
+//! also work when qualified with the menu name, as in "accel read".
+//!
+//! Only the names are translated; the command line that comes out goes
+//! through the same matcher as one typed at the top. That includes
+//! abbreviations, see [`resolve`].
+//!
+//! With names coming from several features, the tables are [`check`]ed
+//! while compiling, so that a build in which two of them clash fails.
+
+use core::fmt;
+use heapless::{String, Vec};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Menu {
+    Root,
+    Accel,
+    Mag,
+    Display,
+    System,
+}
+
+type Entries = &'static [(&'static str, &'static str)];
+
+/// Every menu's short names, and the flat command names they stand for.
+const MENUS: &[(Menu, &str, Entries)] = &[
+    (
+        Menu::Accel,
+        "accel",
+        &[
+            ("read", "accelerometer"),
+            ("avg", "accelerometer avg"),
+            ("stream", "linearaccel"),
+            ("tilt", "tilt stream"),
+            ("orientation", "orientation"),
+            ("tiltfilter", "tiltfilter"),
+            ("filter", "filter"),
+            ("axes", "axes"),
+            ("capture", "capture"),
+            ("punch", "punch"),
+            ("ball", "ball"),
+            ("rate", "odr accel"),
+        ],
+    ),
+    (
+        Menu::Mag,
+        "mag",
+        &[
+            ("read", "magnetometer"),
+            ("avg", "magnetometer avg"),
+            ("heading", "heading"),
+            ("compass", "compass show"),
+            ("calibrate", "calibrate"),
+            ("axes", "axes"),
+            ("health", "sensorhealth"),
+            ("poll", "poll"),
+            ("rate", "odr mag"),
+        ],
+    ),
+    (
+        Menu::Display,
+        "display",
+        &[
+            ("brightness", "brightness"),
+            ("night", "night"),
+            ("gamma", "gamma"),
+            ("statusled", "statusled"),
+            ("blinkout", "blinkout"),
+            #[cfg(feature = "demo")]
+            ("demo", "demo"),
+        ],
+    ),
+    (
+        Menu::System,
+        "system",
+        &[
+            ("power", "power"),
+            ("powersave", "powersave"),
+            ("pof", "pof"),
+            ("heartbeat", "heartbeat"),
+            ("audiocues", "audiocues"),
+            ("status", "status"),
+            ("marker", "marker"),
+            ("log", "log"),
+            ("i2ctrace", "i2ctrace"),
+            ("scan", "scan"),
+            ("i2c", "i2c"),
+            ("temperature", "temperature"),
+            ("irqstats", "irqstats"),
+            ("name", "name"),
+            ("version", "version"),
+            ("battwarn", "battwarn"),
+            ("timeformat", "timeformat"),
+            ("lineend", "lineend"),
+            ("output", "output"),
+            ("format", "format"),
+            #[cfg(feature = "simulate")]
+            ("simulate", "simulate"),
+            #[cfg(feature = "replay")]
+            ("replay", "replay"),
+            ("uptime", "uptime"),
+            ("config", "config"),
+            ("provision", "provision"),
+            ("odometer", "odometer"),
+            ("flash", "flash"),
+        ],
+    ),
+];
+
+/// Flat commands that come with one of the optional cargo features, or
+/// with none of them.
+struct Group {
+    /// The first word of each
+    names: &'static [&'static str],
+    /// Each the way the banner shows it, and what `help` says it does
+    usages: &'static [(&'static str, &'static str)],
+}
+
+const GROUPS: &[Group] = &[
+    Group {
+        names: &[
+            "accelerometer",
+            "audiocues",
+            "axes",
+            "ball",
+            "battwarn",
+            "blinkout",
+            "brightness",
+            "calibrate",
+            "calibration",
+            "capture",
+            "compass",
+            "config",
+            "filter",
+            "flash",
+            "format",
+            "gamma",
+            "heading",
+            "heartbeat",
+            "help",
+            "i2c",
+            "i2ctrace",
+            "irqstats",
+            "lineend",
+            "linearaccel",
+            "log",
+            "magnetometer",
+            "marker",
+            "name",
+            "night",
+            "odometer",
+            "odr",
+            "orientation",
+            "output",
+            "ping",
+            "pof",
+            "poll",
+            "power",
+            "powersave",
+            "provision",
+            "punch",
+            "recent",
+            "scan",
+            "sensorhealth",
+            "status",
+            "statusled",
+            "tablecheck",
+            "temperature",
+            "tilt",
+            "tiltfilter",
+            "timeformat",
+            "uptime",
+            "version",
+            "watch",
+        ],
+        usages: &[
+            (
+                "magnetometer [stream]",
+                "one magnetometer reading in nT, or them all until a key",
+            ),
+            (
+                "magnetometer avg <n>",
+                "mean, min and max of up to 256 magnetometer readings",
+            ),
+            (
+                "accelerometer [stream]",
+                "one accelerometer reading in mg, or them all until a key",
+            ),
+            (
+                "accelerometer avg <n>",
+                "mean, min and max of up to 256 accelerometer readings",
+            ),
+            (
+                "heading [tilted]",
+                "compass heading, lying flat or tilted any way",
+            ),
+            (
+                "compass show",
+                "an arrow on the display towards north, until a key",
+            ),
+            ("punch", "wait for a punch and print how hard it was, in g"),
+            ("ball", "a dot on the display that rolls as the board tilts"),
+            (
+                "odr show|accel <hz>|mag <hz>",
+                "the sensor's data rates, or set one until the next reset",
+            ),
+            (
+                "calibrate [<x> <y> <z>]",
+                "find the magnetometer's offsets by turning the board, or set them",
+            ),
+            (
+                "calibration clear",
+                "forget the offsets, back to uncalibrated",
+            ),
+            (
+                "power report",
+                "which peripherals are powered, and the HFCLK's source",
+            ),
+            ("pof status", "the power-fail comparator's state"),
+            (
+                "power off <peripheral>",
+                "power down a peripheral nothing is using",
+            ),
+            ("powersave [on|off|auto]", "show or set the power saver"),
+            (
+                "heartbeat on|off",
+                "blink the corner LED once a second, or don't",
+            ),
+            (
+                "audiocues on|off",
+                "beep as commands start and end, or don't",
+            ),
+            (
+                "uptime",
+                "time since boot, boots, and this session's counts",
+            ),
+            (
+                "version",
+                "the firmware's name and version, and the board's name",
+            ),
+            ("help", "this list"),
+            ("name [<text>]", "show or set the board's name"),
+            ("status", "how the sensor, RTC and RNG are doing"),
+            ("tablecheck", "check the command tables"),
+            (
+                "marker <text>",
+                "the same line in the serial output and the RTT log",
+            ),
+            ("log read", "what \"log:\" kept in flash"),
+            (
+                "log: <command>",
+                "run a command, keeping what it writes in flash",
+            ),
+            (
+                "i2ctrace on|off|dump",
+                "trace the I2C transactions, or show the trace",
+            ),
+            ("scan", "which addresses on the sensor's I2C bus answer"),
+            (
+                "i2c read <addr> <reg> [<len>]",
+                "read up to 16 bytes from a register, in hex",
+            ),
+            (
+                "i2c write <addr> <reg> <byte>",
+                "write a byte to a register, all in hex",
+            ),
+            (
+                "temperature [both]",
+                "the LSM303AGR's temperature, or it and the nRF's",
+            ),
+            (
+                "irqstats [reset]",
+                "interrupt latencies, or start them over",
+            ),
+            ("config export", "the settings, as commands"),
+            ("config reset", "the settings back to their defaults"),
+            (
+                "provision export|import",
+                "copy the flash records from one board to another",
+            ),
+            ("odometer reset", "start the boot and runtime counts over"),
+            ("flash erase", "erase everything kept in flash"),
+            (
+                "watch ...",
+                "do something when a reading crosses a threshold",
+            ),
+            ("filter ...", "smooth the readings"),
+            (
+                "capture <n>|dump|clear",
+                "record readings, show them, or drop them",
+            ),
+            ("sensorhealth ...", "the sensor consistency monitor"),
+            ("linearaccel", "stream acceleration without gravity"),
+            ("poll [stream]", "poll both sensors once, or until Ctrl-C"),
+            ("poll <device> <ms>", "how often to poll a sensor"),
+            ("tilt stream", "stream roll and pitch"),
+            (
+                "orientation [stream] [<mg>]",
+                "which face is up, or every time that changes",
+            ),
+            ("tiltfilter <percent>", "how much each tilt reading counts"),
+            ("brightness <0-9>", "how bright the display is"),
+            ("night", "the display at its dimmest"),
+            ("gamma on|off", "gamma-correct the display, or don't"),
+            ("battwarn <mv>", "the battery voltage to warn at"),
+            ("axes show <xyz>", "which axes readings show"),
+            ("timeformat ms|samples|rel", "how readings are timestamped"),
+            ("lineend crlf|lf", "what ends a line of output"),
+            ("output human|csv|json", "the format replies come in"),
+            ("format human|csv", "the format readings come in"),
+            ("recent accel|mag [n]", "the last readings"),
+            ("ping [n]", "answer with a pong, n times"),
+            ("statusled ...", "pick the LED that shows a status"),
+            ("blinkout <value>", "blink a number out on the display"),
+        ],
+    },
+    #[cfg(feature = "calc")]
+    Group {
+        names: &["calc"],
+        usages: &[("calc <expr>", "work out an expression over the readings")],
+    },
+    #[cfg(feature = "demo")]
+    Group {
+        names: &["demo"],
+        usages: &[("demo", "a guided tour of the board")],
+    },
+    #[cfg(feature = "simulate")]
+    Group {
+        names: &["simulate"],
+        usages: &[(
+            "simulate on [noise]|off",
+            "made-up readings instead of the sensor's",
+        )],
+    },
+    #[cfg(feature = "replay")]
+    Group {
+        names: &["replay"],
+        usages: &[("replay on|off", "recorded readings instead of the sensor's")],
+    },
+];
+
+/// Something wrong with the tables, so that a name wouldn't reach the
+/// command it is listed for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Problem {
+    /// A name or usage with nothing in it
+    Empty,
+    /// A command or menu name twice, or one that is both
+    Duplicate(&'static str),
+    /// "exit" as any other name than the way out of a menu
+    Exit,
+    /// A menu's short name twice
+    DuplicateShort(&'static str, &'static str),
+    /// A menu's short name that is also a flat name, but stands for
+    /// another command
+    Shadowing(&'static str, &'static str),
+    /// A menu's short name for a command that isn't built in
+    Dangling(&'static str, &'static str),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Empty => write!(f, "empty name or usage"),
+            Problem::Duplicate(name) => write!(f, "\"{}\" is there twice", name),
+            Problem::Exit => write!(f, "\"exit\" is taken by the menus"),
+            Problem::DuplicateShort(menu, short) => {
+                write!(f, "\"{} {}\" is there twice", menu, short)
+            }
+            Problem::Shadowing(menu, short) => {
+                write!(
+                    f,
+                    "\"{} {}\" shadows the command \"{}\"",
+                    menu, short, short
+                )
+            }
+            Problem::Dangling(menu, short) => {
+                write!(
+                    f,
+                    "\"{} {}\" stands for a command that isn't there",
+                    menu, short
+                )
+            }
+        }
+    }
+}
+
+/// `str` equality, for `const fn`s.
+const fn same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Whether `line` is the command called `name`, with or without arguments.
+const fn runs(line: &str, name: &str) -> bool {
+    let (line, name) = (line.as_bytes(), name.as_bytes());
+    if line.len() < name.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < name.len() {
+        if line[i] != name[i] {
+            return false;
+        }
+        i += 1;
+    }
+    line.len() == name.len() || line[name.len()] == b' '
+}
+
+/// How many times `name` is a flat or a menu name.
+const fn uses(menus: &[(Menu, &str, Entries)], groups: &[Group], name: &str) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < menus.len() {
+        count += same(menus[i].1, name) as usize;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < groups.len() {
+        let mut j = 0;
+        while j < groups[i].names.len() {
+            count += same(groups[i].names[j], name) as usize;
+            j += 1;
+        }
+        i += 1;
+    }
+    count
+}
+
+/// The flat name `line` runs, if any.
+const fn command_of(groups: &[Group], line: &str) -> Option<&'static str> {
+    let mut i = 0;
+    while i < groups.len() {
+        let mut j = 0;
+        while j < groups[i].names.len() {
+            if runs(line, groups[i].names[j]) {
+                return Some(groups[i].names[j]);
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The first thing wrong with the tables, if anything.
+const fn find_problem(
+    menus: &[(Menu, &'static str, Entries)],
+    groups: &[Group],
+) -> Option<Problem> {
+    let mut i = 0;
+    while i < groups.len() {
+        let group = &groups[i];
+        let mut j = 0;
+        while j < group.names.len() {
+            let name = group.names[j];
+            if name.is_empty() {
+                return Some(Problem::Empty);
+            }
+            if same(name, "exit") {
+                return Some(Problem::Exit);
+            }
+            if uses(menus, groups, name) > 1 {
+                return Some(Problem::Duplicate(name));
+            }
+            j += 1;
+        }
+        let mut j = 0;
+        while j < group.usages.len() {
+            let (usage, description) = group.usages[j];
+            if usage.is_empty() || description.is_empty() {
+                return Some(Problem::Empty);
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    let mut i = 0;
+    while i < menus.len() {
+        let (_, menu, entries) = menus[i];
+        if menu.is_empty() {
+            return Some(Problem::Empty);
+        }
+        if same(menu, "exit") {
+            return Some(Problem::Exit);
+        }
+        if uses(menus, groups, menu) > 1 {
+            return Some(Problem::Duplicate(menu));
+        }
+        let mut j = 0;
+        while j < entries.len() {
+            let (short, line) = entries[j];
+            if short.is_empty() {
+                return Some(Problem::Empty);
+            }
+            if same(short, "exit") {
+                return Some(Problem::Exit);
+            }
+            let mut k = j + 1;
+            while k < entries.len() {
+                if same(entries[k].0, short) {
+                    return Some(Problem::DuplicateShort(menu, short));
+                }
+                k += 1;
+            }
+            match command_of(groups, line) {
+                None => return Some(Problem::Dangling(menu, short)),
+                Some(command) => {
+                    if uses(menus, groups, short) > 0 && !same(command, short) {
+                        return Some(Problem::Shadowing(menu, short));
+                    }
+                }
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    None
+}
+
+// Only a plain `&str` makes it into the compiler's message, the line it
+// points at tells what's wrong with it
+const _: () = match find_problem(MENUS, GROUPS) {
+    None => {}
+    Some(Problem::Empty) => panic!("empty name or usage in the command tables"),
+    Some(Problem::Duplicate(name)) => panic!("{}", name),
+    Some(Problem::Exit) => panic!("\"exit\" is taken by the menus"),
+    Some(Problem::DuplicateShort(_, short)) => panic!("{}", short),
+    Some(Problem::Shadowing(_, short)) => panic!("{}", short),
+    Some(Problem::Dangling(_, short)) => panic!("{}", short),
+};
+
+/// "tablecheck": the same checks on the tables as while compiling, for
+/// whoever doubts them.
+pub fn check() -> Result<(), Problem> {
+    match find_problem(MENUS, GROUPS) {
+        None => Ok(()),
+        Some(problem) => Err(problem),
+    }
+}
+
+/// Longer than any name.
+const NAME_LEN: usize = 16;
+const MAX_CANDIDATES: usize = 8;
+
+/// An abbreviation that fits more than one name.
+#[derive(Debug, PartialEq)]
+pub struct Ambiguous {
+    word: String<NAME_LEN>,
+    candidates: Vec<&'static str, MAX_CANDIDATES>,
+}
+
+impl fmt::Display for Ambiguous {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ambiguous command \"{}\", could be", self.word)?;
+        for (i, name) in self.candidates.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{} {}", separator, name)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a line typed in some menu comes down to.
+#[derive(Debug, PartialEq)]
+pub enum Resolved<'a> {
+    /// Switch to this menu, there is no command to run.
+    Enter(Menu),
+    /// Run the flat command made of these two parts, either of which may be
+    /// empty.
+    Line(&'a str, &'a str),
+}
+
+impl Menu {
+    fn entry(self) -> Option<&'static (Menu, &'static str, Entries)> {
+        MENUS.iter().find(|(menu, _, _)| *menu == self)
+    }
+
+    /// `None` at the top.
+    pub fn name(self) -> Option<&'static str> {
+        self.entry().map(|(_, name, _)| *name)
+    }
+
+    fn from_name(name: &str) -> Option<Menu> {
+        MENUS
+            .iter()
+            .find(|(_, menu_name, _)| *menu_name == name)
+            .map(|(menu, _, _)| *menu)
+    }
+
+    fn lookup(self, word: &str) -> Option<&'static str> {
+        let (_, _, entries) = self.entry()?;
+        entries
+            .iter()
+            .find(|(short, _)| *short == word)
+            .map(|(_, command)| *command)
+    }
+}
+
+fn split_word(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    match line.find(' ') {
+        Some(at) => (&line[..at], line[at..].trim_start()),
+        None => (line, ""),
+    }
+}
+
+/// Whether `word` is the start of `name`, in any case.
+fn starts(name: &str, word: &str) -> bool {
+    name.len() >= word.len() && name.as_bytes()[..word.len()].eq_ignore_ascii_case(word.as_bytes())
+}
+
+/// The name `word` stands for among `names`: the name itself, or else the
+/// only one it is the start of, in any case. `None` if there is no such
+/// name, which an empty `word` never stands for.
+fn complete(
+    word: &str,
+    names: impl Iterator<Item = &'static str> + Clone,
+) -> Result<Option<&'static str>, Ambiguous> {
+    if word.is_empty() {
+        return Ok(None);
+    }
+    if let Some(name) = names.clone().find(|name| name.eq_ignore_ascii_case(word)) {
+        return Ok(Some(name));
+    }
+    let mut matching = names.filter(|name| starts(name, word));
+    match (matching.next(), matching.clone().next()) {
+        (None, _) => Ok(None),
+        (Some(name), None) => Ok(Some(name)),
+        (Some(first), Some(_)) => {
+            let mut candidates = Vec::new();
+            for name in core::iter::once(first).chain(matching) {
+                if candidates.push(name).is_err() {
+                    break;
+                }
+            }
+            let mut abbreviation = String::new();
+            // Never longer than the names it is the start of
+            abbreviation.push_str(word).unwrap();
+            Err(Ambiguous {
+                word: abbreviation,
+                candidates,
+            })
+        }
+    }
+}
+
+/// Everything that can be typed as the first word at the top.
+fn top_names() -> impl Iterator<Item = &'static str> + Clone {
+    MENUS
+        .iter()
+        .map(|(_, name, _)| *name)
+        .chain(GROUPS.iter().flat_map(|group| group.names.iter().copied()))
+}
+
+fn short_names(entries: Entries) -> impl Iterator<Item = &'static str> + Clone {
+    entries.iter().map(|(short, _)| *short)
+}
+
+/// Names can be abbreviated to any start that isn't shared with another
+/// name, and typed in any case. A name typed out in full always wins over
+/// an abbreviation, and inside a menu its short names are tried before the
+/// flat ones.
+pub fn resolve(menu: Menu, line: &str) -> Result<Resolved<'_>, Ambiguous> {
+    let (word, rest) = split_word(line);
+    if let Some((_, _, entries)) = menu.entry() {
+        let local = short_names(entries).chain(core::iter::once("exit"));
+        let flat = top_names().any(|name| name.eq_ignore_ascii_case(word));
+        match complete(word, local) {
+            Ok(Some(short)) if short.eq_ignore_ascii_case(word) || !flat => {
+                if short == "exit" && rest.is_empty() {
+                    return Ok(Resolved::Enter(Menu::Root));
+                }
+                if let Some(command) = menu.lookup(short) {
+                    return Ok(Resolved::Line(command, rest));
+                }
+            }
+            Err(ambiguous) if !flat => return Err(ambiguous),
+            _ => {}
+        }
+        return resolve(Menu::Root, line);
+    }
+    let word = complete(word, top_names())?.unwrap_or(word);
+    match Menu::from_name(word) {
+        Some(menu) if rest.is_empty() => Ok(Resolved::Enter(menu)),
+        Some(menu) => {
+            let (short, args) = split_word(rest);
+            let entries = menu.entry().map_or(&[][..], |(_, _, entries)| *entries);
+            match complete(short, short_names(entries))?.and_then(|short| menu.lookup(short)) {
+                Some(command) => Ok(Resolved::Line(command, args)),
+                None => Ok(Resolved::Line(word, rest)),
+            }
+        }
+        None => Ok(Resolved::Line(word, rest)),
+    }
+}
+
+/// `"a", "b" and "c"`, or just `"a"`.
+fn write_list<W: fmt::Write>(w: &mut W, items: impl Iterator<Item = &'static str>) -> fmt::Result {
+    let mut items = items.peekable();
+    let mut first = true;
+    while let Some(item) = items.next() {
+        let separator = match (first, items.peek()) {
+            (true, _) => "",
+            (false, Some(_)) => ", ",
+            (false, None) => " and ",
+        };
+        write!(w, "{}\"{}\"", separator, item)?;
+        first = false;
+    }
+    Ok(())
+}
+
+fn usages() -> impl Iterator<Item = (&'static str, &'static str)> {
+    GROUPS.iter().flat_map(|group| group.usages.iter().copied())
+}
+
+/// What there is to type at the top: every flat command built in, and the
+/// menus. Shown once, at boot; after that "help" says the same at more
+/// length.
+pub fn banner<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    write!(w, "Available commands: ")?;
+    // The last command isn't the last item, the menus come after it
+    for (i, (usage, _)) in usages().enumerate() {
+        let separator = if i == 0 { "" } else { ", " };
+        write!(w, "{}\"{}\"", separator, usage)?;
+    }
+    write!(w, " and the ")?;
+    write_list(w, MENUS.iter().map(|(_, name, _)| *name))?;
+    writeln!(w, " menus. \"help\" says what each does.")
+}
+
+/// Wide enough for every usage
+const USAGE_WIDTH: usize = 26;
+
+/// "help": every flat command built in with what it does, then the menus.
+pub fn help<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    for (usage, description) in usages() {
+        writeln!(
+            w,
+            "  {:<width$} {}",
+            usage,
+            description,
+            width = USAGE_WIDTH
+        )?;
+    }
+    write!(w, "Type a menu's name to enter it: ")?;
+    write_list(w, MENUS.iter().map(|(_, name, _)| *name))?;
+    writeln!(w)
+}
+
+/// How alike `word` and `name` are, in any case: how much of the start
+/// and of the end they share, never counting a letter twice. A letter
+/// missing, doubled or mistyped in the middle leaves the rest to count.
+fn likeness(word: &str, name: &str) -> usize {
+    let (word, name) = (word.as_bytes(), name.as_bytes());
+    let shorter = word.len().min(name.len());
+    let start = word
+        .iter()
+        .zip(name)
+        .take_while(|(a, b)| a.eq_ignore_ascii_case(b))
+        .count();
+    let end = word
+        .iter()
+        .rev()
+        .zip(name.iter().rev())
+        .take_while(|(a, b)| a.eq_ignore_ascii_case(b))
+        .count();
+    (start + end).min(shorter)
+}
+
+/// The name the first word of `line` was probably meant to be, if it
+/// isn't one. Only a name that shares at least half the word, and at least
+/// two letters, is worth suggesting; of those, the most alike, and the
+/// first listed of the equally alike.
+pub fn suggest(line: &str) -> Option<&'static str> {
+    let (word, _) = split_word(line);
+    if top_names().any(|name| name.eq_ignore_ascii_case(word)) {
+        return None;
+    }
+    let needed = 2.max(word.len().div_ceil(2));
+    let mut best = None;
+    let mut best_likeness = needed - 1;
+    for name in top_names() {
+        let likeness = likeness(word, name);
+        if likeness > best_likeness {
+            best = Some(name);
+            best_likeness = likeness;
+        }
+    }
+    best
+}
+
+/// The short names available in `menu`, for its prompt.
+pub fn list<W: fmt::Write>(w: &mut W, menu: Menu) -> fmt::Result {
+    if let Some((_, name, entries)) = menu.entry() {
+        write!(w, "{} commands:", name)?;
+        for (short, _) in entries.iter() {
+            write!(w, " \"{}\"", short)?;
+        }
+        writeln!(w, " and \"exit\"")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(menu: Menu, typed: &str) -> (&str, &str) {
+        match resolve(menu, typed) {
+            Ok(Resolved::Line(command, rest)) => (command, rest),
+            other => panic!("{:?} for {:?}", other, typed),
+        }
+    }
+
+    fn candidates(menu: Menu, typed: &str) -> std::vec::Vec<&'static str> {
+        match resolve(menu, typed) {
+            Err(ambiguous) => ambiguous.candidates.iter().copied().collect(),
+            other => panic!("{:?} for {:?}", other, typed),
+        }
+    }
+
+    #[test]
+    fn a_unique_start_stands_for_the_name() {
+        assert_eq!(line(Menu::Root, "accele 5"), ("accelerometer", "5"));
+        assert_eq!(line(Menu::Root, "ori"), ("orientation", ""));
+        assert_eq!(line(Menu::Root, "ver"), ("version", ""));
+        assert_eq!(line(Menu::Root, "temp"), ("temperature", ""));
+        assert_eq!(
+            line(Menu::Root, "ACCELE  avg 8 "),
+            ("accelerometer", "avg 8")
+        );
+    }
+
+    #[test]
+    fn a_name_typed_out_wins_over_the_longer_ones_it_starts() {
+        assert_eq!(line(Menu::Root, "tilt stream"), ("tilt", "stream"));
+        assert_eq!(line(Menu::Root, "i2c"), ("i2c", ""));
+        assert_eq!(line(Menu::Root, "Power"), ("power", ""));
+        assert_eq!(line(Menu::Root, "status"), ("status", ""));
+    }
+
+    #[test]
+    fn a_shared_start_lists_the_candidates() {
+        assert_eq!(
+            candidates(Menu::Root, "cal save"),
+            ["calibrate", "calibration"]
+        );
+        // The accel menu's name is one of them
+        assert_eq!(candidates(Menu::Root, "acc 5"), ["accel", "accelerometer"]);
+        assert_eq!(
+            resolve(Menu::Root, "pow").unwrap_err().to_string(),
+            "ambiguous command \"pow\", could be power, powersave"
+        );
+    }
+
+    #[test]
+    fn nothing_typed_is_nothing_to_run() {
+        assert_eq!(resolve(Menu::Root, ""), Ok(Resolved::Line("", "")));
+        assert_eq!(resolve(Menu::Root, "   "), Ok(Resolved::Line("", "")));
+        assert_eq!(complete("", top_names()), Ok(None));
+        assert_eq!(resolve(Menu::Accel, ""), Ok(Resolved::Line("", "")));
+    }
+
+    #[test]
+    fn an_unknown_word_goes_through_unchanged() {
+        assert_eq!(line(Menu::Root, "xyzzy 1 2"), ("xyzzy", "1 2"));
+        assert_eq!(line(Menu::Mag, "xyzzy"), ("xyzzy", ""));
+    }
+
+    #[test]
+    fn a_menu_name_enters_it() {
+        assert_eq!(
+            resolve(Menu::Root, "accel"),
+            Ok(Resolved::Enter(Menu::Accel))
+        );
+        // Typed out in full, rather than the start of "magnetometer"
+        assert_eq!(resolve(Menu::Root, "mag"), Ok(Resolved::Enter(Menu::Mag)));
+        assert_eq!(
+            resolve(Menu::Root, "sys"),
+            Ok(Resolved::Enter(Menu::System))
+        );
+        assert_eq!(
+            resolve(Menu::Accel, "exit"),
+            Ok(Resolved::Enter(Menu::Root))
+        );
+        assert_eq!(resolve(Menu::Accel, "ex"), Ok(Resolved::Enter(Menu::Root)));
+    }
+
+    #[test]
+    fn a_qualified_short_name_runs_its_command() {
+        assert_eq!(line(Menu::Root, "accel read"), ("accelerometer", ""));
+        assert_eq!(line(Menu::Root, "mag av 16"), ("magnetometer avg", "16"));
+        assert_eq!(line(Menu::Root, "sys up"), ("uptime", ""));
+        // Not a short name, so the menu's name goes through as it is
+        assert_eq!(line(Menu::Root, "accel 5"), ("accel", "5"));
+    }
+
+    #[test]
+    fn in_a_menu_its_short_names_come_first() {
+        assert_eq!(line(Menu::Accel, "read"), ("accelerometer", ""));
+        assert_eq!(line(Menu::Accel, "tilt"), ("tilt stream", ""));
+        assert_eq!(line(Menu::Mag, "cal"), ("calibrate", ""));
+        assert_eq!(line(Menu::Mag, "rate 20"), ("odr mag", "20"));
+        assert_eq!(candidates(Menu::Accel, "ti"), ["tilt", "tiltfilter"]);
+    }
+
+    #[test]
+    fn in_a_menu_a_flat_name_typed_out_wins_over_a_short_name_it_starts() {
+        // "statusled" is the display menu's, "status" a flat command
+        assert_eq!(line(Menu::Display, "status"), ("status", ""));
+        assert_eq!(line(Menu::Display, "statusl on"), ("statusled", "on"));
+    }
+
+    #[test]
+    fn in_a_menu_the_flat_names_are_still_there() {
+        assert_eq!(line(Menu::Accel, "version"), ("version", ""));
+        assert_eq!(line(Menu::Accel, "ver"), ("version", ""));
+        assert_eq!(line(Menu::Mag, "temp"), ("temperature", ""));
+    }
+
+    /// Flat commands "a", "b" and "c", with a usage each.
+    const FLAT: Group = Group {
+        names: &["a", "b", "c"],
+        usages: &[("a", "does a"), ("b", "does b"), ("c <n>", "does c")],
+    };
+
+    #[test]
+    fn the_tables_built_in_are_fine() {
+        assert_eq!(check(), Ok(()));
+    }
+
+    #[test]
+    fn tables_without_a_clash_are_fine() {
+        let menus = [(
+            Menu::Accel,
+            "m",
+            &[("x", "a"), ("y", "c 5"), ("b", "b")][..],
+        )];
+        assert_eq!(find_problem(&menus, &[FLAT]), None);
+    }
+
+    #[test]
+    fn a_name_in_two_groups_clashes() {
+        let more = Group {
+            names: &["d", "b"],
+            usages: &[("d", "does d")],
+        };
+        assert_eq!(
+            find_problem(&[], &[FLAT, more]),
+            Some(Problem::Duplicate("b"))
+        );
+    }
+
+    #[test]
+    fn a_menu_named_like_a_command_clashes() {
+        let menus = [(Menu::Accel, "c", &[("x", "a")][..])];
+        assert_eq!(find_problem(&menus, &[FLAT]), Some(Problem::Duplicate("c")));
+        let menus = [
+            (Menu::Accel, "m", &[("x", "a")][..]),
+            (Menu::Mag, "m", &[("y", "b")][..]),
+        ];
+        assert_eq!(find_problem(&menus, &[FLAT]), Some(Problem::Duplicate("m")));
+    }
+
+    #[test]
+    fn exit_is_the_menus_own() {
+        let exit = Group {
+            names: &["exit"],
+            usages: &[("exit", "leaves")],
+        };
+        assert_eq!(find_problem(&[], &[FLAT, exit]), Some(Problem::Exit));
+        let menus = [(Menu::Accel, "exit", &[("x", "a")][..])];
+        assert_eq!(find_problem(&menus, &[FLAT]), Some(Problem::Exit));
+        let menus = [(Menu::Accel, "m", &[("exit", "a")][..])];
+        assert_eq!(find_problem(&menus, &[FLAT]), Some(Problem::Exit));
+    }
+
+    #[test]
+    fn empty_names_and_help_are_caught() {
+        let nameless = Group {
+            names: &[""],
+            usages: &[],
+        };
+        assert_eq!(find_problem(&[], &[FLAT, nameless]), Some(Problem::Empty));
+        let unexplained = Group {
+            names: &["d"],
+            usages: &[("d", "")],
+        };
+        assert_eq!(
+            find_problem(&[], &[FLAT, unexplained]),
+            Some(Problem::Empty)
+        );
+        let menus = [(Menu::Accel, "", &[("x", "a")][..])];
+        assert_eq!(find_problem(&menus, &[FLAT]), Some(Problem::Empty));
+        let menus = [(Menu::Accel, "m", &[("", "a")][..])];
+        assert_eq!(find_problem(&menus, &[FLAT]), Some(Problem::Empty));
+    }
+
+    #[test]
+    fn a_short_name_twice_in_a_menu_clashes() {
+        let menus = [(Menu::Accel, "m", &[("x", "a"), ("y", "b"), ("x", "c")][..])];
+        assert_eq!(
+            find_problem(&menus, &[FLAT]),
+            Some(Problem::DuplicateShort("m", "x"))
+        );
+        // In two menus it is two names
+        let menus = [
+            (Menu::Accel, "m", &[("x", "a")][..]),
+            (Menu::Mag, "n", &[("x", "b")][..]),
+        ];
+        assert_eq!(find_problem(&menus, &[FLAT]), None);
+    }
+
+    #[test]
+    fn a_short_name_may_not_shadow_another_command() {
+        let menus = [(Menu::Accel, "m", &[("b", "a")][..])];
+        assert_eq!(
+            find_problem(&menus, &[FLAT]),
+            Some(Problem::Shadowing("m", "b"))
+        );
+        let menus = [
+            (Menu::Accel, "m", &[("x", "a")][..]),
+            (Menu::Mag, "n", &[("m", "b")][..]),
+        ];
+        assert_eq!(
+            find_problem(&menus, &[FLAT]),
+            Some(Problem::Shadowing("n", "m"))
+        );
+    }
+
+    #[test]
+    fn a_short_name_needs_a_command_to_stand_for() {
+        let menus = [(Menu::Accel, "m", &[("x", "d")][..])];
+        assert_eq!(
+            find_problem(&menus, &[FLAT]),
+            Some(Problem::Dangling("m", "x"))
+        );
+        // A start of a name isn't it
+        let menus = [(Menu::Accel, "m", &[("x", "ab")][..])];
+        assert_eq!(
+            find_problem(&menus, &[FLAT]),
+            Some(Problem::Dangling("m", "x"))
+        );
+    }
+
+    #[test]
+    fn problems_name_the_offender() {
+        assert_eq!(
+            Problem::Shadowing("m", "b").to_string(),
+            "\"m b\" shadows the command \"b\""
+        );
+        assert_eq!(Problem::Duplicate("b").to_string(), "\"b\" is there twice");
+    }
+}
@@ -0,0 +1,336 @@
+//! Lifetime counters: how often the board has booted and how many minutes
+//! it has run in total.
+//!
+//! The counters are appended as checksummed records to a log that spans
+//! two flash pages. Appending never touches the latest record, and a page
+//! is only erased once the newest record lives in the other one, so losing
+//! power at any point leaves at least one intact record behind. A record
+//! torn by a power loss fails its checksum and is skipped. At boot the
+//! newest valid record across both pages wins.
+//!
+//! A record is written at every boot and then after every
+//! [`SAVE_EVERY_MINUTES`] of uptime, to keep flash wear down.
+
+use crate::shared::Shared;
+use crate::{flash, heartbeat};
+
+const MAGIC: u32 = 0x4f44_4f01;
+const RECORD_WORDS: usize = 4;
+const SLOTS: usize = flash::PAGE_SIZE / (RECORD_WORDS * 4);
+const PAGES: [usize; 2] = [1, 2];
+const ERASED: u32 = 0xffff_ffff;
+
+pub const SAVE_EVERY_MINUTES: u32 = 10;
+const TICKS_PER_MINUTE: u32 = 60 * heartbeat::TICK_HZ;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Counters {
+    pub boots: u32,
+    pub minutes: u32,
+}
+
+struct Log {
+    /// As of the last record written
+    saved: Counters,
+    /// Runtime minutes from before this boot
+    base_minutes: u32,
+    /// Index into [`PAGES`] of the page holding the newest record
+    page: usize,
+    next_slot: usize,
+}
+
+static LOG: Shared<Option<Log>> = Shared::new(None);
+
+fn read_slot(page: usize, slot: usize) -> [u32; RECORD_WORDS] {
+    let mut record = [0; RECORD_WORDS];
+    flash::read(page, slot * RECORD_WORDS, &mut record);
+    record
+}
+
+fn decode(record: &[u32; RECORD_WORDS]) -> Option<Counters> {
+    let (body, sum) = record.split_at(RECORD_WORDS - 1);
+    if body[0] != MAGIC || flash::checksum(body) != sum[0] {
+        return None;
+    }
+    Some(Counters {
+        boots: body[1],
+        minutes: body[2],
+    })
+}
+
+/// The number of slots in use on a page. Torn records count as used: their
+/// words can't be programmed again without an erase.
+fn used_slots(page: usize) -> usize {
+    (0..SLOTS)
+        .position(|slot| read_slot(page, slot).iter().all(|&word| word == ERASED))
+        .unwrap_or(SLOTS)
+}
+
+/// The newest valid record and where it is.
+fn scan() -> Option<(Counters, usize)> {
+    let mut newest: Option<(Counters, usize)> = None;
+    for (i, &page) in PAGES.iter().enumerate() {
+        for slot in 0..used_slots(page) {
+            if let Some(counters) = decode(&read_slot(page, slot)) {
+                if newest.is_none_or(|(best, _)| counters >= best) {
+                    newest = Some((counters, i));
+                }
+            }
+        }
+    }
+    newest
+}
+
+impl Log {
+    /// The log as the newest record left it, with this boot counted.
+    fn open() -> Log {
+        let (saved, page) = scan().unwrap_or((Counters::default(), 0));
+        let mut log = Log {
+            saved,
+            base_minutes: saved.minutes,
+            page,
+            next_slot: used_slots(PAGES[page]),
+        };
+        log.append(Counters {
+            boots: saved.boots.wrapping_add(1),
+            minutes: saved.minutes,
+        });
+        log
+    }
+
+    /// A record that can't be written because of a low supply is dropped,
+    /// the next one comes [`SAVE_EVERY_MINUTES`] later as usual.
+    fn append(&mut self, counters: Counters) {
+        self.saved = counters;
+        if self.next_slot == SLOTS {
+            // Move on to the other page; the newest record stays where it
+            // is until the next one has been written
+            let other = 1 - self.page;
+            if flash::erase(PAGES[other]).is_err() {
+                return;
+            }
+            self.page = other;
+            self.next_slot = 0;
+        }
+        let mut record = [MAGIC, counters.boots, counters.minutes, 0];
+        record[RECORD_WORDS - 1] = flash::checksum(&record[..RECORD_WORDS - 1]);
+        if flash::write(PAGES[self.page], self.next_slot * RECORD_WORDS, &record).is_ok() {
+            self.next_slot += 1;
+        }
+    }
+
+    fn now(&self) -> Counters {
+        let uptime = heartbeat::ticks() / TICKS_PER_MINUTE;
+        Counters {
+            boots: self.saved.boots,
+            minutes: self.base_minutes.wrapping_add(uptime),
+        }
+    }
+
+    fn due(&self) -> bool {
+        self.now().minutes.wrapping_sub(self.saved.minutes) >= SAVE_EVERY_MINUTES
+    }
+}
+
+/// Count this boot.
+pub fn init() {
+    let log = Log::open();
+    LOG.with(|slot| *slot = Some(log));
+}
+
+/// Work on the log outside a critical section, flash writes take far too
+/// long for one. Only the command loop uses the log, so nothing misses it
+/// meanwhile.
+fn update(f: impl FnOnce(&mut Log)) {
+    if let Some(mut log) = LOG.with(Option::take) {
+        f(&mut log);
+        LOG.with(|slot| *slot = Some(log));
+    }
+}
+
+/// Start counting from zero again, with this boot as the first.
+pub fn reset() -> Result<(), flash::LowPower> {
+    for &page in &PAGES {
+        flash::erase(page)?;
+    }
+    update(|log| {
+        // So that the runtime so far doesn't count
+        log.base_minutes = 0u32.wrapping_sub(heartbeat::ticks() / TICKS_PER_MINUTE);
+        log.page = 0;
+        log.next_slot = 0;
+        log.append(Counters {
+            boots: 1,
+            minutes: 0,
+        });
+    });
+    Ok(())
+}
+
+/// Write a record if another [`SAVE_EVERY_MINUTES`] have passed. Cheap
+/// enough to call from any idle loop.
+pub fn tick() {
+    let due = LOG.with(|log| log.as_ref().is_some_and(Log::due));
+    if due {
+        update(|log| log.append(log.now()));
+    }
+}
+
+pub fn counters() -> Counters {
+    LOG.with(|log| log.as_ref().map_or(Counters::default(), Log::now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reset, with the flash as it was and the uptime back to zero.
+    fn boot() -> Log {
+        heartbeat::set_ticks(0);
+        Log::open()
+    }
+
+    fn record(page: usize, slot: usize) -> Option<Counters> {
+        decode(&read_slot(PAGES[page], slot))
+    }
+
+    fn counters(boots: u32, minutes: u32) -> Counters {
+        Counters { boots, minutes }
+    }
+
+    /// Append records until the page in use is full.
+    fn fill_page(log: &mut Log) {
+        while log.next_slot < SLOTS {
+            let next = counters(log.saved.boots, log.saved.minutes + 1);
+            log.append(next);
+        }
+    }
+
+    #[test]
+    fn blank_flash_is_the_first_boot() {
+        let log = boot();
+        assert_eq!(log.now(), counters(1, 0));
+        assert_eq!(scan(), Some((counters(1, 0), 0)));
+        assert_eq!(used_slots(PAGES[0]), 1);
+        assert_eq!(used_slots(PAGES[1]), 0);
+    }
+
+    #[test]
+    fn every_boot_counts() {
+        for boots in 1..=5 {
+            assert_eq!(boot().now(), counters(boots, 0));
+        }
+        assert_eq!(used_slots(PAGES[0]), 5);
+    }
+
+    #[test]
+    fn runtime_is_saved_every_ten_minutes() {
+        let mut log = boot();
+        heartbeat::set_ticks(SAVE_EVERY_MINUTES * TICKS_PER_MINUTE - 1);
+        assert!(!log.due());
+        heartbeat::set_ticks(SAVE_EVERY_MINUTES * TICKS_PER_MINUTE);
+        assert!(log.due());
+        log.append(log.now());
+        assert!(!log.due());
+        heartbeat::set_ticks(15 * TICKS_PER_MINUTE);
+        assert_eq!(log.now(), counters(1, 15));
+        // The five minutes since the last record are lost
+        assert_eq!(boot().now(), counters(2, 10));
+    }
+
+    #[test]
+    fn a_torn_record_is_skipped_and_never_written_over() {
+        for torn in 1..RECORD_WORDS {
+            let mut log = boot();
+            log.append(counters(log.saved.boots, 10));
+            let mut whole = [MAGIC, log.saved.boots, 20, 0];
+            whole[RECORD_WORDS - 1] = flash::checksum(&whole[..RECORD_WORDS - 1]);
+            let slot = log.next_slot;
+            flash::write(PAGES[0], slot * RECORD_WORDS, &whole[..torn]).unwrap();
+
+            let log = boot();
+            assert_eq!(log.now(), counters(whole[1] + 1, 10), "{} words", torn);
+            assert_eq!(record(0, slot), None);
+            assert_eq!(record(0, slot + 1), Some(log.saved));
+        }
+    }
+
+    #[test]
+    fn a_record_with_a_bad_checksum_is_skipped() {
+        let mut log = boot();
+        let bad = [MAGIC, 7, 70, 0];
+        flash::write(PAGES[0], log.next_slot * RECORD_WORDS, &bad).unwrap();
+        log.next_slot += 1;
+        log.append(counters(1, 10));
+        assert_eq!(boot().now(), counters(2, 10));
+    }
+
+    #[test]
+    fn a_full_page_rolls_over_to_the_other() {
+        let mut log = boot();
+        fill_page(&mut log);
+        let last = log.saved;
+        assert_eq!(used_slots(PAGES[1]), 0);
+        assert_eq!(scan(), Some((last, 0)));
+
+        let log = boot();
+        assert_eq!(log.now(), counters(2, last.minutes));
+        assert_eq!((log.page, log.next_slot), (1, 1));
+        // The old page stays as it was until the new one fills up
+        assert_eq!(used_slots(PAGES[0]), SLOTS);
+        assert_eq!(record(0, SLOTS - 1), Some(last));
+
+        let mut log = log;
+        fill_page(&mut log);
+        let last = log.saved;
+        let log = boot();
+        assert_eq!(log.now(), counters(3, last.minutes));
+        assert_eq!((log.page, log.next_slot), (0, 1));
+        assert_eq!(used_slots(PAGES[0]), 1);
+        assert_eq!(scan(), Some((log.now(), 0)));
+    }
+
+    #[test]
+    fn power_lost_while_rolling_over_keeps_the_full_page() {
+        let mut log = boot();
+        fill_page(&mut log);
+        let last = log.saved;
+        // The other page erased, and the first record on it torn
+        flash::erase(PAGES[1]).unwrap();
+        flash::write(PAGES[1], 0, &[MAGIC, last.boots + 1]).unwrap();
+
+        let log = boot();
+        assert_eq!(log.now(), counters(last.boots + 1, last.minutes));
+        // Rolled over again, this time all the way
+        assert_eq!((log.page, log.next_slot), (1, 1));
+        assert_eq!(record(1, 0), Some(log.now()));
+        assert_eq!(record(0, SLOTS - 1), Some(last));
+    }
+
+    #[test]
+    fn nothing_is_written_on_a_low_supply() {
+        flash::set_low_power(true);
+        let log = boot();
+        assert_eq!(log.now(), counters(1, 0));
+        assert_eq!(scan(), None);
+        flash::set_low_power(false);
+        // The boot that couldn't be written doesn't count
+        assert_eq!(boot().now(), counters(1, 0));
+        assert_eq!(scan(), Some((counters(1, 0), 0)));
+    }
+
+    #[test]
+    fn a_low_supply_holds_off_the_rollover() {
+        let mut log = boot();
+        fill_page(&mut log);
+        let last = log.saved;
+        flash::set_low_power(true);
+        log.append(counters(last.boots, last.minutes + 10));
+        assert_eq!((log.page, log.next_slot), (0, SLOTS));
+        assert_eq!(scan(), Some((last, 0)));
+
+        flash::set_low_power(false);
+        log.append(counters(last.boots, last.minutes + 20));
+        assert_eq!((log.page, log.next_slot), (1, 1));
+        assert_eq!(scan(), Some((counters(last.boots, last.minutes + 20), 1)));
+    }
+}
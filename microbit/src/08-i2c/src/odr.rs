@@ -0,0 +1,103 @@
+//! The LSM303AGR's output data rates, for "odr".
+//!
+//! The sensor starts at [`Rates::START`], and a rate set with "odr" lasts
+//! until the next reset. Everything that counts in samples rather than
+//! time, such as the filters and the gravity estimate, was tuned at 50 Hz
+//! and takes longer or shorter in proportion at any other rate. Simulated
+//! and replayed samples keep to 50 Hz whatever the sensor is set to.
+//!
+//! The magnetometer is read one-shot, a measurement whenever a command
+//! asks for one, so how often it reads is still up to the command.
+
+use core::fmt;
+
+use lsm303agr::{AccelOutputDataRate, MagOutputDataRate};
+
+pub const USAGE: &str = "odr show|accel <hz>|mag <hz>";
+pub const ACCEL_USAGE: &str = "odr accel 1|10|25|50|100|200|400";
+pub const MAG_USAGE: &str = "odr mag 10|20|50|100";
+
+/// The ones in normal mode the shell offers, slowest first
+const ACCEL: [(u32, AccelOutputDataRate); 7] = [
+    (1, AccelOutputDataRate::Hz1),
+    (10, AccelOutputDataRate::Hz10),
+    (25, AccelOutputDataRate::Hz25),
+    (50, AccelOutputDataRate::Hz50),
+    (100, AccelOutputDataRate::Hz100),
+    (200, AccelOutputDataRate::Hz200),
+    (400, AccelOutputDataRate::Hz400),
+];
+
+const MAG: [(u32, MagOutputDataRate); 4] = [
+    (10, MagOutputDataRate::Hz10),
+    (20, MagOutputDataRate::Hz20),
+    (50, MagOutputDataRate::Hz50),
+    (100, MagOutputDataRate::Hz100),
+];
+
+/// The accelerometer's rate for `hz` as typed, if it has one.
+pub fn accel(hz: &str) -> Option<AccelOutputDataRate> {
+    let hz: u32 = hz.parse().ok()?;
+    ACCEL.iter().find(|(n, _)| *n == hz).map(|&(_, rate)| rate)
+}
+
+/// Like [`accel`].
+pub fn mag(hz: &str) -> Option<MagOutputDataRate> {
+    let hz: u32 = hz.parse().ok()?;
+    MAG.iter().find(|(n, _)| *n == hz).map(|&(_, rate)| rate)
+}
+
+/// What the sensor is set to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rates {
+    pub accel: AccelOutputDataRate,
+    pub mag: MagOutputDataRate,
+}
+
+impl Rates {
+    pub const START: Rates = Rates {
+        accel: AccelOutputDataRate::Hz50,
+        mag: MagOutputDataRate::Hz50,
+    };
+
+    pub const fn accel_hz(&self) -> u32 {
+        match self.accel {
+            AccelOutputDataRate::Hz1 => 1,
+            AccelOutputDataRate::Hz10 => 10,
+            AccelOutputDataRate::Hz25 => 25,
+            AccelOutputDataRate::Hz50 => 50,
+            AccelOutputDataRate::Hz100 => 100,
+            AccelOutputDataRate::Hz200 => 200,
+            AccelOutputDataRate::Hz400 => 400,
+            AccelOutputDataRate::Khz1_344 => 1_344,
+            AccelOutputDataRate::Khz1_620LowPower => 1_620,
+            AccelOutputDataRate::Khz5_376LowPower => 5_376,
+        }
+    }
+
+    pub const fn mag_hz(&self) -> u32 {
+        match self.mag {
+            MagOutputDataRate::Hz10 => 10,
+            MagOutputDataRate::Hz20 => 20,
+            MagOutputDataRate::Hz50 => 50,
+            MagOutputDataRate::Hz100 => 100,
+        }
+    }
+
+    /// How long the accelerometer takes for a sample, in ms, rounded up.
+    pub fn accel_period_ms(&self) -> u32 {
+        1000u32.div_ceil(self.accel_hz())
+    }
+
+    /// Like [`Rates::accel_period_ms`].
+    pub fn mag_period_ms(&self) -> u32 {
+        1000u32.div_ceil(self.mag_hz())
+    }
+}
+
+/// Printed as "odr show" has it, as in "accel 50 Hz, mag 50 Hz".
+impl fmt::Display for Rates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "accel {} Hz, mag {} Hz", self.accel_hz(), self.mag_hz())
+    }
+}
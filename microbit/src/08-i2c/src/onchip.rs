@@ -0,0 +1,109 @@
+//! One-off readings from the chip's own sensors: the die temperature and
+//! the supply voltage.
+//!
+//! The board support crate doesn't hand out these peripherals, and each
+//! reading powers its peripheral up and back down, so the registers are
+//! driven directly.
+
+/// Die temperature in whole degrees Celsius.
+pub fn temperature() -> i32 {
+    temperature_quarters() / 4
+}
+
+/// Die temperature in quarters of a degree Celsius, as the TEMP peripheral
+/// measures it.
+pub fn temperature_quarters() -> i32 {
+    let temp = unsafe { &*chip::pac::TEMP::ptr() };
+    temp.events_datardy.reset();
+    temp.tasks_start.write(|w| unsafe { w.bits(1) });
+    while temp.events_datardy.read().bits() == 0 {}
+    temp.events_datardy.reset();
+    let quarters = temp.temp.read().bits() as i32;
+    temp.tasks_stop.write(|w| unsafe { w.bits(1) });
+    quarters
+}
+
+/// Supply voltage in millivolts.
+pub fn vdd_mv() -> i32 {
+    chip::vdd_raw() * chip::FULL_SCALE_MV / chip::RANGE
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    pub use microbit::pac;
+
+    /// VDD / 3 against the 1.2 V bandgap
+    pub const FULL_SCALE_MV: i32 = 3600;
+    pub const RANGE: i32 = 1 << 10;
+
+    pub fn vdd_raw() -> i32 {
+        let adc = unsafe { &*pac::ADC::ptr() };
+        adc.config.write(|w| {
+            w.res()._10bit();
+            w.inpsel().supply_one_third_prescaling();
+            w.refsel().vbg()
+        });
+        adc.enable.write(|w| w.enable().enabled());
+        adc.events_end.reset();
+        adc.tasks_start.write(|w| unsafe { w.bits(1) });
+        while adc.events_end.read().bits() == 0 {}
+        adc.events_end.reset();
+        let raw = adc.result.read().result().bits() as i32;
+        adc.enable.write(|w| w.enable().disabled());
+        raw
+    }
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    pub use microbit::pac;
+
+    use core::sync::atomic::{compiler_fence, Ordering};
+
+    /// VDD at gain 1/6 against the 0.6 V internal reference
+    pub const FULL_SCALE_MV: i32 = 3600;
+    pub const RANGE: i32 = 1 << 12;
+
+    pub fn vdd_raw() -> i32 {
+        let saadc = unsafe { &*pac::SAADC::ptr() };
+        saadc.enable.write(|w| w.enable().enabled());
+        saadc.resolution.write(|w| w.val()._12bit());
+        saadc.oversample.write(|w| w.oversample().bypass());
+        saadc.samplerate.write(|w| w.mode().task());
+        saadc.ch[0].config.write(|w| {
+            w.refsel().internal();
+            w.gain().gain1_6();
+            w.tacq()._10us();
+            w.mode().se();
+            w.resp().bypass();
+            w.resn().bypass();
+            w.burst().disabled();
+            w
+        });
+        saadc.ch[0].pselp.write(|w| w.pselp().vdd());
+        saadc.ch[0].pseln.write(|w| w.pseln().nc());
+
+        // The result is written by EasyDMA, so it has to live in RAM
+        let mut result: i16 = 0;
+        saadc
+            .result
+            .ptr
+            .write(|w| unsafe { w.ptr().bits(&mut result as *mut i16 as u32) });
+        saadc.result.maxcnt.write(|w| unsafe { w.maxcnt().bits(1) });
+
+        saadc.events_started.reset();
+        saadc.tasks_start.write(|w| unsafe { w.bits(1) });
+        while saadc.events_started.read().bits() == 0 {}
+        saadc.events_end.reset();
+        saadc.tasks_sample.write(|w| unsafe { w.bits(1) });
+        while saadc.events_end.read().bits() == 0 {}
+        compiler_fence(Ordering::SeqCst);
+
+        saadc.events_stopped.reset();
+        saadc.tasks_stop.write(|w| unsafe { w.bits(1) });
+        while saadc.events_stopped.read().bits() == 0 {}
+        saadc.enable.write(|w| w.enable().disabled());
+        // Slightly below ground reads as a small negative number
+        result.max(0) as i32
+    }
+}
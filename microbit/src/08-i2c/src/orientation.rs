@@ -0,0 +1,123 @@
+//! Which way up the board is, for "orientation".
+//!
+//! One of the six faces is up when gravity lies mostly along its axis: the
+//! component towards it is at least the threshold, [`DEFAULT_MG`] unless
+//! "orientation" is given another. Between two faces, with the board near
+//! 45°, neither is. The axes are the demo's spirit level's: lying flat
+//! reads +z, the edge with the USB connector up reads +x and the left edge
+//! up +y.
+//!
+//! "orientation stream" goes through a [`Tracker`], which keeps to the
+//! face it has until that face's component drops [`HYSTERESIS_MG`] below
+//! the threshold and another face clears it, and prints only the changes,
+//! so a board held near the edge between two faces doesn't flicker.
+
+use core::fmt;
+
+pub const USAGE: &str = "orientation [stream] [<mg>]";
+
+/// About 37° away from the face's axis at 1 g
+pub const DEFAULT_MG: i32 = 800;
+/// The range a threshold can be set to. Below about 580 mg a pure 1 g
+/// always clears it, which would leave no between.
+pub const MIN_MG: i32 = 600;
+pub const MAX_MG: i32 = 950;
+/// How far below the threshold the face the tracker has may go
+pub const HYSTERESIS_MG: i32 = 150;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Face {
+    Flat,
+    UpsideDown,
+    UsbUp,
+    UsbDown,
+    LeftEdgeUp,
+    RightEdgeUp,
+}
+
+const FACES: [Face; 6] = [
+    Face::Flat,
+    Face::UpsideDown,
+    Face::UsbUp,
+    Face::UsbDown,
+    Face::LeftEdgeUp,
+    Face::RightEdgeUp,
+];
+
+impl Face {
+    pub fn name(self) -> &'static str {
+        match self {
+            Face::Flat => "flat",
+            Face::UpsideDown => "upside down",
+            Face::UsbUp => "USB up",
+            Face::UsbDown => "USB down",
+            Face::LeftEdgeUp => "left edge up",
+            Face::RightEdgeUp => "right edge up",
+        }
+    }
+
+    /// The part of `accel` towards this face, in mg.
+    fn component(self, accel: [i32; 3]) -> i32 {
+        let [x, y, z] = accel;
+        match self {
+            Face::Flat => z,
+            Face::UpsideDown => -z,
+            Face::UsbUp => x,
+            Face::UsbDown => -x,
+            Face::LeftEdgeUp => y,
+            Face::RightEdgeUp => -y,
+        }
+    }
+}
+
+impl fmt::Display for Face {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// `mg` as typed, if it is a threshold from [`MIN_MG`] to [`MAX_MG`].
+pub fn parse_threshold(mg: &str) -> Option<i32> {
+    mg.parse().ok().filter(|mg| (MIN_MG..=MAX_MG).contains(mg))
+}
+
+/// The face up for an acceleration `accel` in mg: the one with the largest
+/// component, if that is at least `threshold_mg`.
+pub fn classify(accel: [i32; 3], threshold_mg: i32) -> Option<Face> {
+    FACES
+        .iter()
+        .copied()
+        .max_by_key(|face| face.component(accel))
+        .filter(|face| face.component(accel) >= threshold_mg)
+}
+
+/// The face up so far, changed only for another one that is clearly up.
+pub struct Tracker {
+    threshold_mg: i32,
+    face: Option<Face>,
+}
+
+impl Tracker {
+    pub fn new(threshold_mg: i32) -> Tracker {
+        Tracker {
+            threshold_mg,
+            face: None,
+        }
+    }
+
+    /// Take a reading. Returns the face up if it just changed, the first
+    /// clear one included.
+    pub fn update(&mut self, accel: [i32; 3]) -> Option<Face> {
+        if let Some(face) = self.face {
+            if face.component(accel) >= self.threshold_mg - HYSTERESIS_MG {
+                return None;
+            }
+        }
+        let face = classify(accel, self.threshold_mg)?;
+        if self.face == Some(face) {
+            return None;
+        }
+        self.face = Some(face);
+        Some(face)
+    }
+}
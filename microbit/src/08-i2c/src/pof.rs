@@ -0,0 +1,96 @@
+//! Keeping flash writes away from a dying supply, using the POWER
+//! peripheral's power-fail comparator.
+//!
+//! An erase or write interrupted by a brown-out can leave a page half
+//! done. When the supply drops below the comparator threshold the POFWARN
+//! interrupt sets a flag that stops [`crate::flash`] from touching the
+//! NVMC. The comparator only reports falling supplies, so once the flag is
+//! up, every check measures VDD and clears it again when the supply is
+//! back above the threshold plus some hysteresis.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use microbit::pac::{self, interrupt};
+
+use crate::irqstats::{self, Irq};
+use crate::onchip;
+
+const HYSTERESIS_MV: i32 = 150;
+
+static LOW: AtomicBool = AtomicBool::new(false);
+static WARN: AtomicBool = AtomicBool::new(false);
+
+fn power() -> &'static pac::power::RegisterBlock {
+    unsafe { &*pac::POWER::ptr() }
+}
+
+pub fn init() {
+    chip::enable_comparator(power());
+    power().events_pofwarn.reset();
+    power().intenset.write(|w| w.pofwarn().set());
+    unsafe { pac::NVIC::unmask(pac::Interrupt::POWER_CLOCK) };
+}
+
+/// Whether flash may be written right now.
+pub fn flash_allowed() -> bool {
+    if LOW.load(Ordering::Relaxed) && onchip::vdd_mv() > chip::THRESHOLD_MV + HYSTERESIS_MV {
+        LOW.store(false, Ordering::Relaxed);
+    }
+    !LOW.load(Ordering::Relaxed)
+}
+
+/// True once after each power-fail warning, for the command loop to tell
+/// the user about it.
+pub fn take_warning() -> bool {
+    // No atomic swap on the nRF51
+    cortex_m::interrupt::free(|_| {
+        let warn = WARN.load(Ordering::Relaxed);
+        WARN.store(false, Ordering::Relaxed);
+        warn
+    })
+}
+
+/// For "pof status".
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    let allowed = flash_allowed();
+    writeln!(
+        w,
+        "Power-fail threshold {} mV, VDD {} mV, flash writes {}",
+        chip::THRESHOLD_MV,
+        onchip::vdd_mv(),
+        if allowed { "enabled" } else { "disabled" }
+    )
+}
+
+#[interrupt]
+fn POWER_CLOCK() {
+    irqstats::enter(Irq::PowerClock);
+    if power().events_pofwarn.read().bits() != 0 {
+        power().events_pofwarn.reset();
+        LOW.store(true, Ordering::Relaxed);
+        WARN.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    use microbit::pac;
+
+    /// The closest the nRF51 gets to 2.8 V
+    pub const THRESHOLD_MV: i32 = 2700;
+
+    pub fn enable_comparator(power: &pac::power::RegisterBlock) {
+        power.pofcon.write(|w| w.pof().enabled().threshold().v27());
+    }
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    use microbit::pac;
+
+    pub const THRESHOLD_MV: i32 = 2800;
+
+    pub fn enable_comparator(power: &pac::power::RegisterBlock) {
+        power.pofcon.write(|w| w.pof().enabled().threshold().v28());
+    }
+}
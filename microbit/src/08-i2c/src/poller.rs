@@ -0,0 +1,393 @@
+//! "poll": every sensor on one schedule, each at its own interval.
+//!
+//! The [`Poller`] holds up to [`MAX_ENTRIES`] [`Entry`]s, one per
+//! [`Device`], and goes by the heartbeat tick. "poll stream" asks it for
+//! whatever is due, reads that and prints it tagged with the device, until
+//! Ctrl-C. Entries are served round-robin from the one after the last
+//! served, and one that fails is counted and put off until its next turn
+//! like one that answered, so a device that stops answering can't hold
+//! the others up.
+//!
+//! An entry that falls behind, because a command kept the stream from
+//! running, doesn't make up for it with a burst of reads. It is served
+//! once and skips the intervals it missed, counting them. All the tick
+//! arithmetic wraps, see [`Schedule`].
+//!
+//! For now the devices are the LSM303AGR's two halves; a sensor on the
+//! external bus would be one more [`Device`].
+
+use core::fmt;
+use heapless::Vec;
+
+use crate::heartbeat;
+
+pub const USAGE: &str = "poll [stream]|poll <device> <ms>";
+pub const MAX_ENTRIES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Device {
+    Accel,
+    Mag,
+}
+
+impl Device {
+    pub fn from_tag(tag: &str) -> Option<Device> {
+        match tag {
+            "accel" => Some(Device::Accel),
+            "mag" => Some(Device::Mag),
+            _ => None,
+        }
+    }
+
+    /// What its lines in the stream start with
+    pub fn tag(self) -> &'static str {
+        match self {
+            Device::Accel => "accel",
+            Device::Mag => "mag",
+        }
+    }
+}
+
+/// When an entry is next due, in heartbeat ticks.
+///
+/// Ticks are compared by their difference, so a due time past the point
+/// where the tick count wraps still comes after one before it, as long as
+/// the two are less than half the range apart.
+#[derive(Clone, Copy, Debug)]
+pub struct Schedule {
+    interval: u32,
+    next: u32,
+}
+
+impl Schedule {
+    /// Due at `now` first, then every `interval` ticks, at least one.
+    pub fn new(interval: u32, now: u32) -> Schedule {
+        Schedule {
+            interval: interval.max(1),
+            next: now,
+        }
+    }
+
+    pub fn is_due(&self, now: u32) -> bool {
+        now.wrapping_sub(self.next) as i32 >= 0
+    }
+
+    /// Move on to the first due time after `now`, keeping to the same
+    /// phase. Returns how many due times in between were skipped.
+    pub fn served(&mut self, now: u32) -> u32 {
+        let missed = now.wrapping_sub(self.next) / self.interval;
+        self.next = self
+            .next
+            .wrapping_add(missed.wrapping_add(1).wrapping_mul(self.interval));
+        missed
+    }
+}
+
+/// How a read went.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reading {
+    Values([i32; 3]),
+    /// Nothing new yet, such as a one-shot measurement still running
+    NotReady,
+    Failed,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub device: Device,
+    schedule: Schedule,
+    reads: u32,
+    errors: u32,
+    missed: u32,
+    /// Failed the last time it was read
+    failing: bool,
+}
+
+#[derive(Debug)]
+pub struct Full;
+
+impl fmt::Display for Full {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no room for more than {} devices", MAX_ENTRIES)
+    }
+}
+
+pub struct Poller {
+    entries: Vec<Entry, MAX_ENTRIES>,
+    /// The entry served last
+    last: usize,
+}
+
+impl Poller {
+    /// The accelerometer at 8 Hz, and the magnetometer twice a second. Every
+    /// other read of it starts a one-shot measurement that the next one picks
+    /// up, which makes one reading a second.
+    pub fn new() -> Poller {
+        let mut poller = Poller {
+            entries: Vec::new(),
+            last: 0,
+        };
+        let now = heartbeat::ticks();
+        poller.register(Device::Accel, 1, now).unwrap();
+        poller
+            .register(Device::Mag, heartbeat::TICK_HZ / 2, now)
+            .unwrap();
+        poller
+    }
+
+    /// Poll `device` every `interval` ticks, or change its interval if it
+    /// is polled already.
+    pub fn register(&mut self, device: Device, interval: u32, now: u32) -> Result<(), Full> {
+        let schedule = Schedule::new(interval, now);
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.device == device) {
+            entry.schedule = schedule;
+            return Ok(());
+        }
+        self.entries
+            .push(Entry {
+                device,
+                schedule,
+                reads: 0,
+                errors: 0,
+                missed: 0,
+                failing: false,
+            })
+            .map_err(|_| Full)
+    }
+
+    /// The device of the first entry due at `now`, looking from the one
+    /// after the last served. Keep asking until there is none.
+    pub fn next_due(&mut self, now: u32) -> Option<Device> {
+        let count = self.entries.len();
+        let index = (1..=count)
+            .map(|offset| (self.last + offset) % count)
+            .find(|&index| self.entries[index].schedule.is_due(now))?;
+        self.last = index;
+        Some(self.entries[index].device)
+    }
+
+    /// Put the entry [`next_due`](Poller::next_due) returned off until its
+    /// next turn. Returns whether it just started failing.
+    pub fn served(&mut self, now: u32, reading: &Reading) -> bool {
+        let entry = &mut self.entries[self.last];
+        entry.missed = entry.missed.wrapping_add(entry.schedule.served(now));
+        let was_failing = entry.failing;
+        entry.failing = *reading == Reading::Failed;
+        match reading {
+            Reading::Values(_) => entry.reads = entry.reads.wrapping_add(1),
+            Reading::NotReady => {}
+            Reading::Failed => entry.errors = entry.errors.wrapping_add(1),
+        }
+        entry.failing && !was_failing
+    }
+
+    /// "poll"
+    pub fn report<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "device  every     reads    errors    missed")?;
+        for entry in &self.entries {
+            writeln!(
+                w,
+                "{:<7} {:>5} ms {:>8} {:>9} {:>9}{}",
+                entry.device.tag(),
+                entry.schedule.interval * 1000 / heartbeat::TICK_HZ,
+                entry.reads,
+                entry.errors,
+                entry.missed,
+                if entry.failing { "  failing" } else { "" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `ms` in heartbeat ticks, rounded up.
+pub fn ticks(ms: u32) -> u32 {
+    (ms * heartbeat::TICK_HZ).div_ceil(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The accelerometer every tick and the magnetometer every fourth,
+    /// from `now`.
+    fn poller(now: u32) -> Poller {
+        let mut poller = Poller {
+            entries: Vec::new(),
+            last: 0,
+        };
+        poller.register(Device::Accel, 1, now).unwrap();
+        poller.register(Device::Mag, 4, now).unwrap();
+        poller
+    }
+
+    /// Serve everything due at `now` the way "poll stream" does, with
+    /// what `read` makes of each device.
+    fn serve(
+        poller: &mut Poller,
+        now: u32,
+        read: impl Fn(Device) -> Reading,
+    ) -> std::vec::Vec<Device> {
+        let mut served = std::vec::Vec::new();
+        while let Some(device) = poller.next_due(now) {
+            poller.served(now, &read(device));
+            served.push(device);
+        }
+        served
+    }
+
+    fn answers(_: Device) -> Reading {
+        Reading::Values([1, 2, 3])
+    }
+
+    fn entry(poller: &Poller, device: Device) -> Entry {
+        *poller
+            .entries
+            .iter()
+            .find(|entry| entry.device == device)
+            .unwrap()
+    }
+
+    #[test]
+    fn due_from_the_start_then_every_interval() {
+        let mut schedule = Schedule::new(5, 100);
+        assert!(!schedule.is_due(99));
+        assert!(schedule.is_due(100));
+        assert_eq!(schedule.served(100), 0);
+        assert!(!schedule.is_due(104));
+        assert!(schedule.is_due(105));
+        assert!(schedule.is_due(200));
+    }
+
+    #[test]
+    fn an_interval_is_at_least_a_tick() {
+        let mut schedule = Schedule::new(0, 7);
+        assert_eq!(schedule.served(7), 0);
+        assert!(!schedule.is_due(7));
+        assert!(schedule.is_due(8));
+    }
+
+    #[test]
+    fn falling_behind_skips_what_was_missed_and_keeps_the_phase() {
+        let mut schedule = Schedule::new(10, 3);
+        // Due at 3, 13, 23, 33; served at 37
+        assert_eq!(schedule.served(37), 3);
+        assert!(!schedule.is_due(42));
+        assert!(schedule.is_due(43));
+        // Late by less than an interval misses nothing
+        assert_eq!(schedule.served(52), 0);
+        assert!(schedule.is_due(53));
+    }
+
+    #[test]
+    fn due_times_keep_their_order_across_the_wrap() {
+        let start = u32::MAX - 2;
+        let mut schedule = Schedule::new(4, start);
+        assert!(schedule.is_due(start));
+        assert_eq!(schedule.served(start), 0);
+        // Due at 1, after the wrap, and not before
+        assert!(!schedule.is_due(u32::MAX));
+        assert!(!schedule.is_due(0));
+        assert!(schedule.is_due(1));
+        assert_eq!(schedule.served(1 + 4 * 2), 2);
+        assert!(schedule.is_due(13));
+        assert!(!schedule.is_due(12));
+    }
+
+    #[test]
+    fn every_device_gets_its_turn() {
+        let mut poller = poller(0);
+        let mut reads = std::vec::Vec::new();
+        for now in 0..8 {
+            reads.push(serve(&mut poller, now, answers));
+        }
+        use Device::{Accel, Mag};
+        assert_eq!(
+            reads,
+            [
+                vec![Mag, Accel],
+                vec![Accel],
+                vec![Accel],
+                vec![Accel],
+                vec![Mag, Accel],
+                vec![Accel],
+                vec![Accel],
+                vec![Accel],
+            ]
+        );
+    }
+
+    #[test]
+    fn a_device_that_fails_holds_nobody_up() {
+        let mut poller = poller(0);
+        let read = |device| match device {
+            Device::Mag => Reading::Failed,
+            Device::Accel => Reading::Values([0, 0, 1000]),
+        };
+        let mut started_failing = 0;
+        for now in 0..40 {
+            while let Some(device) = poller.next_due(now) {
+                started_failing += poller.served(now, &read(device)) as u32;
+            }
+        }
+        let (accel, mag) = (entry(&poller, Device::Accel), entry(&poller, Device::Mag));
+        assert_eq!((accel.reads, accel.errors, accel.failing), (40, 0, false));
+        assert_eq!((mag.reads, mag.errors, mag.failing), (0, 10, true));
+        // Told once, not at every failure
+        assert_eq!(started_failing, 1);
+    }
+
+    #[test]
+    fn a_device_that_recovers_stops_failing() {
+        let mut poller = poller(0);
+        assert!(poller.next_due(0).is_some());
+        assert!(poller.served(0, &Reading::Failed));
+        assert!(!poller.served(0, &Reading::NotReady));
+        let served = entry(&poller, poller.entries[poller.last].device);
+        assert_eq!((served.reads, served.errors, served.failing), (0, 1, false));
+    }
+
+    #[test]
+    fn after_a_long_command_each_is_read_once() {
+        let mut poller = poller(0);
+        serve(&mut poller, 0, answers);
+        // Nothing polled for a hundred ticks
+        assert_eq!(serve(&mut poller, 100, answers).len(), 2);
+        assert_eq!(serve(&mut poller, 100, answers), []);
+        assert_eq!(entry(&poller, Device::Accel).missed, 99);
+        assert_eq!(entry(&poller, Device::Mag).missed, 24);
+        assert_eq!(serve(&mut poller, 101, answers), [Device::Accel]);
+    }
+
+    #[test]
+    fn registering_again_changes_the_interval() {
+        let mut poller = poller(0);
+        poller.register(Device::Mag, 2, 10).unwrap();
+        assert_eq!(poller.entries.len(), 2);
+        assert_eq!(entry(&poller, Device::Mag).schedule.interval, 2);
+        assert!(!entry(&poller, Device::Mag).schedule.is_due(9));
+    }
+
+    #[test]
+    fn milliseconds_round_up_to_ticks() {
+        assert_eq!(ticks(0), 0);
+        assert_eq!(ticks(1), 1);
+        assert_eq!(ticks(125), 1);
+        assert_eq!(ticks(126), 2);
+        assert_eq!(ticks(1000), heartbeat::TICK_HZ);
+    }
+
+    #[test]
+    fn the_report_shows_every_count() {
+        let mut poller = poller(0);
+        serve(&mut poller, 0, |_| Reading::Failed);
+        let mut report = std::string::String::new();
+        poller.report(&mut report).unwrap();
+        assert_eq!(
+            report,
+            "device  every     reads    errors    missed\n\
+             accel     125 ms        0         1         0  failing\n\
+             mag       500 ms        0         1         0  failing\n"
+        );
+    }
+}
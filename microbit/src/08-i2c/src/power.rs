@@ -0,0 +1,471 @@
+//! Peek at the peripherals that may be left running, to chase down idle current.
+//!
+//! Most nRF peripherals only draw current while they are enabled or started,
+//! so the report reads the relevant registers straight through the PAC. The
+//! register layout differs between the nRF51 (micro:bit v1) and the nRF52833
+//! (micro:bit v2), hence one `chip` module per board revision.
+
+use core::fmt;
+
+use crate::claims::{self, InUseBy, Owner, Resource};
+
+pub use chip::Peripheral;
+
+#[derive(Debug)]
+pub enum Error {
+    InUse(InUseBy),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InUse(in_use) => write!(f, "refusing to power off {}", in_use),
+        }
+    }
+}
+
+impl Peripheral {
+    pub fn from_name(name: &str) -> Option<Peripheral> {
+        chip::PERIPHERALS
+            .iter()
+            .copied()
+            .find(|peripheral| peripheral.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Who in this program depends on the peripheral, if anybody, see
+    /// [`claims`].
+    fn owner(self) -> Option<Owner> {
+        claims::owner(Resource::Peripheral(self))
+    }
+}
+
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    writeln!(w, "Peripheral power state:")?;
+    for peripheral in chip::PERIPHERALS {
+        let state = if peripheral.is_on() { "on" } else { "off" };
+        match peripheral.owner() {
+            Some(owner) => writeln!(
+                w,
+                "  {:<7} {:<3} ({})",
+                peripheral.name(),
+                state,
+                owner.name
+            )?,
+            None => writeln!(w, "  {:<7} {}", peripheral.name(), state)?,
+        }
+    }
+    let (running, xtal) = chip::hfclk();
+    writeln!(
+        w,
+        "  {:<7} {}, source: {}",
+        "HFCLK",
+        if running { "running" } else { "stopped" },
+        if xtal { "crystal" } else { "RC oscillator" }
+    )
+}
+
+pub fn power_off(peripheral: Peripheral) -> Result<(), Error> {
+    if let Some(owner) = peripheral.owner() {
+        return Err(Error::InUse(InUseBy {
+            resource: Resource::Peripheral(peripheral),
+            owner,
+        }));
+    }
+    peripheral.turn_off();
+    Ok(())
+}
+
+/// Busy wait for roughly `us` microseconds.
+fn delay_us(us: u32) {
+    cortex_m::asm::delay(us * chip::CYCLES_PER_US);
+}
+
+/// Captures the counter twice; a timer that isn't started doesn't move.
+///
+/// CC[3] is used because neither the HAL timer nor the display driver touch it.
+fn timer_running(timer: &chip::pac::timer0::RegisterBlock) -> bool {
+    timer.tasks_capture[3].write(|w| unsafe { w.bits(1) });
+    let first = timer.cc[3].read().bits();
+    // Even the slowest prescaler ticks every 32us
+    delay_us(64);
+    timer.tasks_capture[3].write(|w| unsafe { w.bits(1) });
+    timer.cc[3].read().bits() != first
+}
+
+/// The RTC has no status register either, so watch the counter for two ticks.
+fn rtc_running(rtc: &chip::pac::rtc0::RegisterBlock) -> bool {
+    let clock = unsafe { &*chip::pac::CLOCK::ptr() };
+    if !clock.lfclkstat.read().state().is_running() {
+        return false;
+    }
+    let ticks = rtc.prescaler.read().bits() + 2;
+    let first = rtc.counter.read().bits();
+    // One LFCLK tick is ~30.5us
+    delay_us(ticks * 31);
+    rtc.counter.read().bits() != first
+}
+
+/// A started RNG keeps raising VALRDY, a stopped one never does.
+fn rng_running(rng: &chip::pac::rng::RegisterBlock) -> bool {
+    rng.events_valrdy.write(|w| unsafe { w.bits(0) });
+    // Generating a value with bias correction takes ~120us
+    delay_us(250);
+    rng.events_valrdy.read().bits() != 0
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    pub use microbit::pac;
+
+    pub const CYCLES_PER_US: u32 = 16;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Peripheral {
+        Uart0,
+        Twi0,
+        Twi1,
+        Timer0,
+        Timer1,
+        Timer2,
+        Rtc0,
+        Rtc1,
+        Adc,
+        Radio,
+        Rng,
+    }
+
+    pub const PERIPHERALS: &[Peripheral] = &[
+        Peripheral::Uart0,
+        Peripheral::Twi0,
+        Peripheral::Twi1,
+        Peripheral::Timer0,
+        Peripheral::Timer1,
+        Peripheral::Timer2,
+        Peripheral::Rtc0,
+        Peripheral::Rtc1,
+        Peripheral::Adc,
+        Peripheral::Radio,
+        Peripheral::Rng,
+    ];
+
+    pub fn hfclk() -> (bool, bool) {
+        let clock = unsafe { &*pac::CLOCK::ptr() };
+        let stat = clock.hfclkstat.read();
+        (stat.state().is_running(), stat.src().is_xtal())
+    }
+
+    fn timer(peripheral: Peripheral) -> Option<&'static pac::timer0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Timer0 => Some(&*pac::TIMER0::ptr()),
+                Peripheral::Timer1 => Some(&*pac::TIMER1::ptr()),
+                Peripheral::Timer2 => Some(&*pac::TIMER2::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    fn rtc(peripheral: Peripheral) -> Option<&'static pac::rtc0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Rtc0 => Some(&*pac::RTC0::ptr()),
+                Peripheral::Rtc1 => Some(&*pac::RTC1::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    fn twi(peripheral: Peripheral) -> Option<&'static pac::twi0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Twi0 => Some(&*pac::TWI0::ptr()),
+                Peripheral::Twi1 => Some(&*pac::TWI1::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    impl Peripheral {
+        pub fn name(self) -> &'static str {
+            match self {
+                Peripheral::Uart0 => "UART0",
+                Peripheral::Twi0 => "TWI0",
+                Peripheral::Twi1 => "TWI1",
+                Peripheral::Timer0 => "TIMER0",
+                Peripheral::Timer1 => "TIMER1",
+                Peripheral::Timer2 => "TIMER2",
+                Peripheral::Rtc0 => "RTC0",
+                Peripheral::Rtc1 => "RTC1",
+                Peripheral::Adc => "ADC",
+                Peripheral::Radio => "RADIO",
+                Peripheral::Rng => "RNG",
+            }
+        }
+
+        pub fn is_on(self) -> bool {
+            if let Some(timer) = timer(self) {
+                return super::timer_running(timer);
+            }
+            if let Some(rtc) = rtc(self) {
+                return super::rtc_running(rtc);
+            }
+            if let Some(twi) = twi(self) {
+                return twi.enable.read().enable().is_enabled();
+            }
+            unsafe {
+                match self {
+                    Peripheral::Uart0 => (*pac::UART0::ptr()).enable.read().enable().is_enabled(),
+                    Peripheral::Adc => (*pac::ADC::ptr()).enable.read().enable().is_enabled(),
+                    Peripheral::Radio => !(*pac::RADIO::ptr()).state.read().state().is_disabled(),
+                    Peripheral::Rng => super::rng_running(&*pac::RNG::ptr()),
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        pub fn turn_off(self) {
+            if let Some(timer) = timer(self) {
+                timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+                timer.tasks_shutdown.write(|w| unsafe { w.bits(1) });
+                return;
+            }
+            if let Some(rtc) = rtc(self) {
+                rtc.tasks_stop.write(|w| unsafe { w.bits(1) });
+                return;
+            }
+            if let Some(twi) = twi(self) {
+                twi.tasks_stop.write(|w| unsafe { w.bits(1) });
+                twi.enable.write(|w| w.enable().disabled());
+                return;
+            }
+            unsafe {
+                match self {
+                    Peripheral::Uart0 => {
+                        let uart = &*pac::UART0::ptr();
+                        uart.tasks_stoptx.write(|w| w.bits(1));
+                        uart.tasks_stoprx.write(|w| w.bits(1));
+                        uart.enable.write(|w| w.enable().disabled());
+                    }
+                    Peripheral::Adc => {
+                        let adc = &*pac::ADC::ptr();
+                        adc.tasks_stop.write(|w| w.bits(1));
+                        adc.enable.write(|w| w.enable().disabled());
+                    }
+                    Peripheral::Radio => (*pac::RADIO::ptr()).tasks_disable.write(|w| w.bits(1)),
+                    Peripheral::Rng => (*pac::RNG::ptr()).tasks_stop.write(|w| w.bits(1)),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    pub use microbit::pac;
+
+    pub const CYCLES_PER_US: u32 = 64;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Peripheral {
+        Uarte0,
+        Uarte1,
+        Twim0,
+        Twim1,
+        Timer0,
+        Timer1,
+        Timer2,
+        Timer3,
+        Timer4,
+        Rtc0,
+        Rtc1,
+        Rtc2,
+        Pwm0,
+        Pwm1,
+        Pwm2,
+        Pwm3,
+        Saadc,
+        Radio,
+        Rng,
+    }
+
+    pub const PERIPHERALS: &[Peripheral] = &[
+        Peripheral::Uarte0,
+        Peripheral::Uarte1,
+        Peripheral::Twim0,
+        Peripheral::Twim1,
+        Peripheral::Timer0,
+        Peripheral::Timer1,
+        Peripheral::Timer2,
+        Peripheral::Timer3,
+        Peripheral::Timer4,
+        Peripheral::Rtc0,
+        Peripheral::Rtc1,
+        Peripheral::Rtc2,
+        Peripheral::Pwm0,
+        Peripheral::Pwm1,
+        Peripheral::Pwm2,
+        Peripheral::Pwm3,
+        Peripheral::Saadc,
+        Peripheral::Radio,
+        Peripheral::Rng,
+    ];
+
+    pub fn hfclk() -> (bool, bool) {
+        let clock = unsafe { &*pac::CLOCK::ptr() };
+        let stat = clock.hfclkstat.read();
+        (stat.state().is_running(), stat.src().is_xtal())
+    }
+
+    fn timer(peripheral: Peripheral) -> Option<&'static pac::timer0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Timer0 => Some(&*pac::TIMER0::ptr()),
+                Peripheral::Timer1 => Some(&*pac::TIMER1::ptr()),
+                Peripheral::Timer2 => Some(&*pac::TIMER2::ptr()),
+                Peripheral::Timer3 => Some(&*pac::TIMER3::ptr()),
+                Peripheral::Timer4 => Some(&*pac::TIMER4::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    fn rtc(peripheral: Peripheral) -> Option<&'static pac::rtc0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Rtc0 => Some(&*pac::RTC0::ptr()),
+                Peripheral::Rtc1 => Some(&*pac::RTC1::ptr()),
+                Peripheral::Rtc2 => Some(&*pac::RTC2::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    fn pwm(peripheral: Peripheral) -> Option<&'static pac::pwm0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Pwm0 => Some(&*pac::PWM0::ptr()),
+                Peripheral::Pwm1 => Some(&*pac::PWM1::ptr()),
+                Peripheral::Pwm2 => Some(&*pac::PWM2::ptr()),
+                Peripheral::Pwm3 => Some(&*pac::PWM3::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    fn uarte(peripheral: Peripheral) -> Option<&'static pac::uarte0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Uarte0 => Some(&*pac::UARTE0::ptr()),
+                Peripheral::Uarte1 => Some(&*pac::UARTE1::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    fn twim(peripheral: Peripheral) -> Option<&'static pac::twim0::RegisterBlock> {
+        unsafe {
+            match peripheral {
+                Peripheral::Twim0 => Some(&*pac::TWIM0::ptr()),
+                Peripheral::Twim1 => Some(&*pac::TWIM1::ptr()),
+                _ => None,
+            }
+        }
+    }
+
+    impl Peripheral {
+        pub fn name(self) -> &'static str {
+            match self {
+                Peripheral::Uarte0 => "UARTE0",
+                Peripheral::Uarte1 => "UARTE1",
+                Peripheral::Twim0 => "TWIM0",
+                Peripheral::Twim1 => "TWIM1",
+                Peripheral::Timer0 => "TIMER0",
+                Peripheral::Timer1 => "TIMER1",
+                Peripheral::Timer2 => "TIMER2",
+                Peripheral::Timer3 => "TIMER3",
+                Peripheral::Timer4 => "TIMER4",
+                Peripheral::Rtc0 => "RTC0",
+                Peripheral::Rtc1 => "RTC1",
+                Peripheral::Rtc2 => "RTC2",
+                Peripheral::Pwm0 => "PWM0",
+                Peripheral::Pwm1 => "PWM1",
+                Peripheral::Pwm2 => "PWM2",
+                Peripheral::Pwm3 => "PWM3",
+                Peripheral::Saadc => "SAADC",
+                Peripheral::Radio => "RADIO",
+                Peripheral::Rng => "RNG",
+            }
+        }
+
+        pub fn is_on(self) -> bool {
+            if let Some(timer) = timer(self) {
+                return super::timer_running(timer);
+            }
+            if let Some(rtc) = rtc(self) {
+                return super::rtc_running(rtc);
+            }
+            if let Some(pwm) = pwm(self) {
+                return pwm.enable.read().enable().is_enabled();
+            }
+            if let Some(uarte) = uarte(self) {
+                return uarte.enable.read().enable().is_enabled();
+            }
+            if let Some(twim) = twim(self) {
+                return twim.enable.read().enable().is_enabled();
+            }
+            unsafe {
+                match self {
+                    Peripheral::Saadc => (*pac::SAADC::ptr()).enable.read().enable().is_enabled(),
+                    Peripheral::Radio => !(*pac::RADIO::ptr()).state.read().state().is_disabled(),
+                    Peripheral::Rng => super::rng_running(&*pac::RNG::ptr()),
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        pub fn turn_off(self) {
+            if let Some(timer) = timer(self) {
+                timer.tasks_stop.write(|w| unsafe { w.bits(1) });
+                return;
+            }
+            if let Some(rtc) = rtc(self) {
+                rtc.tasks_stop.write(|w| unsafe { w.bits(1) });
+                return;
+            }
+            if let Some(pwm) = pwm(self) {
+                pwm.tasks_stop.write(|w| unsafe { w.bits(1) });
+                pwm.enable.write(|w| w.enable().disabled());
+                return;
+            }
+            if let Some(uarte) = uarte(self) {
+                uarte.tasks_stoptx.write(|w| unsafe { w.bits(1) });
+                uarte.tasks_stoprx.write(|w| unsafe { w.bits(1) });
+                uarte.enable.write(|w| w.enable().disabled());
+                return;
+            }
+            if let Some(twim) = twim(self) {
+                twim.tasks_stop.write(|w| unsafe { w.bits(1) });
+                twim.enable.write(|w| w.enable().disabled());
+                return;
+            }
+            unsafe {
+                match self {
+                    Peripheral::Saadc => {
+                        let saadc = &*pac::SAADC::ptr();
+                        // Disabling mid-conversion is undefined, stop it first
+                        if saadc.status.read().status().is_busy() {
+                            saadc.events_stopped.write(|w| w.bits(0));
+                            saadc.tasks_stop.write(|w| w.bits(1));
+                            while saadc.events_stopped.read().bits() == 0 {}
+                        }
+                        saadc.enable.write(|w| w.enable().disabled());
+                    }
+                    Peripheral::Radio => (*pac::RADIO::ptr()).tasks_disable.write(|w| w.bits(1)),
+                    Peripheral::Rng => (*pac::RNG::ptr()).tasks_stop.write(|w| w.bits(1)),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
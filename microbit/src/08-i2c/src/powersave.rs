@@ -0,0 +1,208 @@
+//! "powersave": a profile that spends less of the battery.
+//!
+//! By default the profile follows the supply: on when [`battery`] tells a
+//! battery from USB power, off again once the board is back on USB.
+//! "powersave on" and "powersave off" hold it either way, and "powersave
+//! auto" goes back to following. While it is on the display is capped at
+//! [`SAVER_BRIGHTNESS`] and refreshed at half the rate, the heartbeat
+//! blinks once every [`SAVER_HEARTBEAT_S`] seconds, and the sensor's output
+//! data rate is held to [`SAVER_MAX_ODR_HZ`], the rate it is started at.
+//!
+//! Everything the profile limits goes from the settings to the hardware
+//! through [`clamp`], and only there. A new setting the profile should limit
+//! belongs in [`Wanted`] and [`Budget`], not straight in its driver.
+
+use core::fmt;
+
+use crate::log::log;
+use crate::shared::Shared;
+use crate::{battery, display, heartbeat};
+
+pub const USAGE: &str = "powersave [on|off|auto]";
+
+pub const SAVER_BRIGHTNESS: u8 = 3;
+pub const SAVER_HEARTBEAT_S: u32 = 4;
+pub const SAVER_MAX_ODR_HZ: u16 = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// On while on battery
+    Auto,
+    On,
+    Off,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "auto" => Some(Mode::Auto),
+            "on" => Some(Mode::On),
+            "off" => Some(Mode::Off),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Auto => "auto",
+            Mode::On => "on",
+            Mode::Off => "off",
+        }
+    }
+
+    pub fn encode(self) -> u32 {
+        self as u32
+    }
+
+    /// Anything unrecognized decodes as [`Mode::Auto`].
+    pub fn decode(word: u32) -> Mode {
+        match word {
+            1 => Mode::On,
+            2 => Mode::Off,
+            _ => Mode::Auto,
+        }
+    }
+}
+
+/// The settings the profile limits, as the user set them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wanted {
+    pub mode: Mode,
+    pub brightness: u8,
+}
+
+/// What the hardware gets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+    pub brightness: u8,
+    pub slow_refresh: bool,
+    pub heartbeat_period_s: u32,
+    /// The highest output data rate the sensor may run at, if capped
+    pub max_odr_hz: Option<u16>,
+}
+
+/// `wanted`, limited to the profile if `saving`.
+pub fn clamp(wanted: Wanted, saving: bool) -> Budget {
+    if !saving {
+        return Budget {
+            brightness: wanted.brightness,
+            slow_refresh: false,
+            heartbeat_period_s: 1,
+            max_odr_hz: None,
+        };
+    }
+    Budget {
+        brightness: wanted.brightness.min(SAVER_BRIGHTNESS),
+        slow_refresh: true,
+        heartbeat_period_s: SAVER_HEARTBEAT_S,
+        max_odr_hz: Some(SAVER_MAX_ODR_HZ),
+    }
+}
+
+struct State {
+    wanted: Wanted,
+    saving: bool,
+    /// Changed on its own since the last prompt, and not told yet
+    changed: bool,
+}
+
+static STATE: Shared<State> = Shared::new(State {
+    wanted: Wanted {
+        mode: Mode::Auto,
+        brightness: display::MAX_BRIGHTNESS,
+    },
+    saving: false,
+    changed: false,
+});
+
+fn saving(mode: Mode) -> bool {
+    match mode {
+        Mode::Auto => battery::on_battery(),
+        Mode::On => true,
+        Mode::Off => false,
+    }
+}
+
+fn apply(budget: Budget) {
+    display::set_brightness(budget.brightness);
+    display::set_slow_refresh(budget.slow_refresh);
+    heartbeat::set_period_s(budget.heartbeat_period_s);
+}
+
+/// Take on new settings, and put them into effect through [`clamp`].
+pub fn configure(wanted: Wanted) {
+    let saving = saving(wanted.mode);
+    STATE.with(|state| {
+        state.wanted = wanted;
+        state.saving = saving;
+    });
+    apply(clamp(wanted, saving));
+}
+
+/// Follow the supply, in auto mode. Cheap enough to call from any idle
+/// loop.
+pub fn poll() {
+    let wanted = STATE.with(|state| state.wanted);
+    let saving = saving(wanted.mode);
+    let changed = STATE.with(|state| {
+        let changed = state.saving != saving;
+        state.saving = saving;
+        state.changed |= changed;
+        changed
+    });
+    if changed {
+        log!("power saver {}", if saving { "on" } else { "off" });
+        apply(clamp(wanted, saving));
+    }
+}
+
+/// The budget in effect, for whatever sets a limited value later.
+pub fn budget() -> Budget {
+    STATE.with(|state| clamp(state.wanted, state.saving))
+}
+
+/// Whether the profile went on or off by itself since the last call, and
+/// which.
+pub fn take_change() -> Option<bool> {
+    STATE.with(|state| {
+        let changed = core::mem::replace(&mut state.changed, false);
+        changed.then_some(state.saving)
+    })
+}
+
+/// One line for "status".
+pub fn status<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    let (mode, saving) = STATE.with(|state| (state.wanted.mode, state.saving));
+    writeln!(
+        w,
+        "power saver: {} ({})",
+        if saving { "on" } else { "off" },
+        mode.name()
+    )
+}
+
+/// "powersave"
+pub fn report<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    status(w)?;
+    writeln!(
+        w,
+        "  supply        {}",
+        if battery::on_battery() {
+            "battery"
+        } else {
+            "USB"
+        }
+    )?;
+    let budget = budget();
+    writeln!(w, "  brightness    {}", budget.brightness)?;
+    writeln!(
+        w,
+        "  refresh       {}",
+        if budget.slow_refresh { "half" } else { "full" }
+    )?;
+    writeln!(w, "  heartbeat     every {} s", budget.heartbeat_period_s)?;
+    match budget.max_odr_hz {
+        Some(hz) => writeln!(w, "  sensor rate   up to {} Hz", hz),
+        None => writeln!(w, "  sensor rate   not capped"),
+    }
+}
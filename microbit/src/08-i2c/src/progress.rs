@@ -0,0 +1,84 @@
+//! How far along a long-running command is.
+//!
+//! In human mode a single status line is redrawn in place, going back to
+//! its start with a carriage return and blanking out whatever the previous
+//! version had beyond the end of the new one. Host programs get a
+//! `progress,<n>` record instead, or the JSON equivalent, but no more than
+//! [`RECORDS_PER_S`] a second so that they don't drown out the output the
+//! command is run for.
+
+use core::fmt::{self, Write};
+use heapless::String;
+
+use crate::reply::Mode;
+
+const RECORDS_PER_S: u32 = 2;
+const RECORD_GAP_MS: u32 = 1000 / RECORDS_PER_S;
+
+/// Longer than any status line.
+const LINE_LEN: usize = 24;
+
+pub struct Progress {
+    mode: Mode,
+    /// What was reported last, if anything
+    percent: Option<u8>,
+    /// When the last record went out
+    record_ms: u32,
+    /// Of the status line as it is on the terminal
+    width: usize,
+}
+
+impl Progress {
+    pub fn new(mode: Mode) -> Progress {
+        Progress {
+            mode,
+            percent: None,
+            record_ms: 0,
+            width: 0,
+        }
+    }
+
+    /// Report `percent`, up to 100, if it's news and there's room for it.
+    pub fn update<W: Write>(&mut self, w: &mut W, now_ms: u32, percent: u8) -> fmt::Result {
+        let percent = percent.min(100);
+        if self.percent == Some(percent) {
+            return Ok(());
+        }
+        let mode = self.mode;
+        match mode {
+            Mode::Human => {
+                let mut line: String<LINE_LEN> = String::new();
+                write!(line, "progress {}%", percent)?;
+                write!(w, "\r{}", line)?;
+                for _ in line.len()..self.width {
+                    write!(w, " ")?;
+                }
+                self.width = line.len();
+            }
+            Mode::Csv if self.record_due(now_ms) => writeln!(w, "progress,{}", percent)?,
+            Mode::Json if self.record_due(now_ms) => writeln!(w, "{{\"progress\":{}}}", percent)?,
+            // Skipped, to be reported with the next update that has room
+            Mode::Csv | Mode::Json => return Ok(()),
+        }
+        self.percent = Some(percent);
+        Ok(())
+    }
+
+    fn record_due(&mut self, now_ms: u32) -> bool {
+        if self.percent.is_some() && now_ms.wrapping_sub(self.record_ms) < RECORD_GAP_MS {
+            return false;
+        }
+        self.record_ms = now_ms;
+        true
+    }
+
+    /// Leave the status line behind, so that whatever comes next starts
+    /// on a line of its own.
+    pub fn finish<W: Write>(&mut self, w: &mut W) -> fmt::Result {
+        if self.width > 0 {
+            writeln!(w)?;
+            self.width = 0;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+//! "provision": copy one board's flash records to another over serial.
+//!
+//! "provision export" prints the records in [`ITEMS`] as [`block`]s of hex:
+//!
+//! ```text
+//! prov 0/5 53544710000000010000000000000032 b5dd69c8
+//! ```
+//!
+//! "provision import" takes them back in any order, ignores a line it has
+//! seen before and rejects one that fails its CRC or belongs to a set of a
+//! different size, see [`Import`]. Nothing is written until every line is
+//! in and every record passes its own check. Each record is then written
+//! and read back. A record torn by a reset halfway through fails its own
+//! checksum at the next boot like any other, which is why only records
+//! that carry one can be listed here.
+//!
+//! Neither the odometer nor the magnetometer calibration is in the set,
+//! their counters and offsets belong to the board.
+
+use core::fmt;
+
+use crate::block;
+use crate::flash;
+use crate::settings;
+
+pub const USAGE: &str = "provision export|import";
+
+const TAG: &str = "prov";
+const LINE_WORDS: usize = 4;
+
+/// One record in flash that gets copied.
+struct Item {
+    name: &'static str,
+    page: usize,
+    words: usize,
+    /// Whether a record passes its own check
+    valid: fn(&[u32]) -> bool,
+}
+
+const ITEMS: [Item; 1] = [Item {
+    name: "settings",
+    page: settings::PAGE,
+    words: settings::RECORD_WORDS,
+    valid: settings::valid,
+}];
+
+const fn total_words() -> usize {
+    let mut total = 0;
+    let mut i = 0;
+    while i < ITEMS.len() {
+        total += ITEMS[i].words;
+        i += 1;
+    }
+    total
+}
+
+const TOTAL_WORDS: usize = total_words();
+const LINES: usize = TOTAL_WORDS.div_ceil(LINE_WORDS);
+
+// One bit per line in `Import::received`
+const _: () = assert!(LINES <= 32);
+
+#[derive(Debug)]
+pub enum Error {
+    /// Exporting a record that isn't in flash
+    NoRecord(&'static str),
+    Malformed,
+    Crc(usize),
+    /// From a set of this many lines
+    Count(usize),
+    /// A line received before, with other data
+    Conflict(usize),
+    /// A record that fails its own check, once all lines are in
+    Invalid(&'static str),
+    LowPower(flash::LowPower),
+    /// A record that didn't read back the way it was written
+    Readback(&'static str),
+}
+
+impl From<block::Error> for Error {
+    fn from(value: block::Error) -> Self {
+        match value {
+            block::Error::Malformed => Error::Malformed,
+            block::Error::Crc(index) => Error::Crc(index),
+        }
+    }
+}
+
+impl From<flash::LowPower> for Error {
+    fn from(value: flash::LowPower) -> Self {
+        Error::LowPower(value)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoRecord(name) => write!(f, "no valid {} record in flash to export", name),
+            Error::Malformed => write!(f, "not a provision line"),
+            Error::Crc(index) => write!(f, "line {} failed its CRC", index),
+            Error::Count(count) => write!(
+                f,
+                "line from a set of {}, this firmware expects {}",
+                count, LINES
+            ),
+            Error::Conflict(index) => {
+                write!(f, "line {} differs from the one received before", index)
+            }
+            Error::Invalid(name) => write!(f, "{} record doesn't verify, flash not written", name),
+            Error::LowPower(err) => write!(f, "{}", err),
+            Error::Readback(name) => write!(f, "{} record didn't read back as written", name),
+        }
+    }
+}
+
+/// The words from `hex`, eight digits each, or `None` unless it is exactly
+/// `words.len()` of them and nothing else.
+fn decode_hex(hex: &str, words: &mut [u32]) -> Option<()> {
+    // `from_str_radix` would take a sign as well
+    if hex.len() != words.len() * 8 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = u32::from_str_radix(&hex[i * 8..i * 8 + 8], 16).ok()?;
+    }
+    Some(())
+}
+
+/// The words on line `index`.
+fn line_range(index: usize) -> core::ops::Range<usize> {
+    let start = index * LINE_WORDS;
+    start..(start + LINE_WORDS).min(TOTAL_WORDS)
+}
+
+fn read_all() -> Result<[u32; TOTAL_WORDS], Error> {
+    let mut words = [0; TOTAL_WORDS];
+    let mut start = 0;
+    for item in &ITEMS {
+        let record = &mut words[start..start + item.words];
+        flash::read(item.page, 0, record);
+        if !(item.valid)(record) {
+            return Err(Error::NoRecord(item.name));
+        }
+        start += item.words;
+    }
+    Ok(words)
+}
+
+/// The records as they are in flash, ready to print as the lines of
+/// "provision export".
+pub struct Export([u32; TOTAL_WORDS]);
+
+pub fn export() -> Result<Export, Error> {
+    read_all().map(Export)
+}
+
+impl fmt::Display for Export {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for index in 0..LINES {
+            block::write(f, TAG, index, LINES, |w| {
+                for word in &self.0[line_range(index)] {
+                    write!(w, "{:08x}", word)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// What became of a line handed to [`Import::accept`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Accepted {
+    New { index: usize, received: usize },
+    Repeat { index: usize },
+}
+
+pub struct Import {
+    words: [u32; TOTAL_WORDS],
+    /// One bit per line
+    received: u32,
+}
+
+impl Import {
+    pub fn new() -> Import {
+        Import {
+            words: [0; TOTAL_WORDS],
+            received: 0,
+        }
+    }
+
+    pub const fn lines() -> usize {
+        LINES
+    }
+
+    pub fn complete(&self) -> bool {
+        self.received.count_ones() as usize == LINES
+    }
+
+    /// Take one line from "provision export". A line that is rejected
+    /// leaves everything received so far as it was.
+    pub fn accept(&mut self, line: &str) -> Result<Accepted, Error> {
+        let block::Block { index, count, body } = block::parse(line, TAG)?;
+        if count != LINES {
+            return Err(Error::Count(count));
+        }
+        let mut words = [0; LINE_WORDS];
+        let range = line_range(index);
+        let words = &mut words[..range.len()];
+        decode_hex(body, words).ok_or(Error::Malformed)?;
+
+        let bit = 1 << index;
+        if self.received & bit != 0 {
+            if self.words[range] != *words {
+                return Err(Error::Conflict(index));
+            }
+            return Ok(Accepted::Repeat { index });
+        }
+        self.words[range].copy_from_slice(words);
+        self.received |= bit;
+        Ok(Accepted::New {
+            index,
+            received: self.received.count_ones() as usize,
+        })
+    }
+
+    /// Write every record, once all of them are in and verify, and read
+    /// each back. Returns how many were written.
+    pub fn install(&self) -> Result<usize, Error> {
+        assert!(self.complete());
+        let mut start = 0;
+        for item in &ITEMS {
+            if !(item.valid)(&self.words[start..start + item.words]) {
+                return Err(Error::Invalid(item.name));
+            }
+            start += item.words;
+        }
+        let mut back = [0; TOTAL_WORDS];
+        let mut start = 0;
+        for item in &ITEMS {
+            let range = start..start + item.words;
+            flash::erase(item.page)?;
+            flash::write(item.page, 0, &self.words[range.clone()])?;
+            flash::read(item.page, 0, &mut back[range.clone()]);
+            if back[range.clone()] != self.words[range] {
+                return Err(Error::Readback(item.name));
+            }
+            start += item.words;
+        }
+        Ok(ITEMS.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The lines "provision export" prints for `words`.
+    fn lines_of(words: [u32; TOTAL_WORDS]) -> Vec<String> {
+        Export(words)
+            .to_string()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// A board with the settings record made from `seed` in flash.
+    fn board(seed: u32) {
+        flash::erase(settings::PAGE).unwrap();
+        flash::write(settings::PAGE, 0, &settings::record(seed)).unwrap();
+    }
+
+    fn in_flash() -> [u32; TOTAL_WORDS] {
+        let mut words = [0; TOTAL_WORDS];
+        flash::read(settings::PAGE, 0, &mut words);
+        words
+    }
+
+    /// A line like `line` but with `body` in it, and the CRC to match.
+    fn reframe(line: &str, count: usize, body: &str) -> String {
+        let index: usize = line.split([' ', '/']).nth(1).unwrap().parse().unwrap();
+        let mut reframed = String::new();
+        block::write(&mut reframed, TAG, index, count, |w| w.write_str(body)).unwrap();
+        reframed
+    }
+
+    #[test]
+    fn hex_is_eight_digits_a_word() {
+        let mut words = [0; 2];
+        assert_eq!(decode_hex("00000001DEADbeef", &mut words), Some(()));
+        assert_eq!(words, [1, 0xdead_beef]);
+        for bad in [
+            "0000000100000002ff",
+            "00000001",
+            "0000000g00000002",
+            "+0000001ffffffff",
+        ] {
+            assert_eq!(decode_hex(bad, &mut words), None, "{}", bad);
+        }
+        // Eight bytes, but not eight digits
+        assert_eq!(decode_hex("0000000é000000", &mut words), None);
+    }
+
+    #[test]
+    fn nothing_to_export_from_a_blank_board() {
+        assert!(matches!(export(), Err(Error::NoRecord("settings"))));
+    }
+
+    #[test]
+    fn export_numbers_its_lines() {
+        board(7);
+        let lines: Vec<String> = export()
+            .unwrap()
+            .to_string()
+            .lines()
+            .map(str::to_owned)
+            .collect();
+        assert_eq!(lines, lines_of(settings::record(7)));
+        assert_eq!(lines.len(), LINES);
+        for (index, line) in lines.iter().enumerate() {
+            assert!(
+                line.starts_with(&format!("prov {}/{} ", index, LINES)),
+                "{}",
+                line
+            );
+        }
+        // The last line only has what is left over
+        let body = |line: &str| line.split(' ').nth(2).unwrap().len();
+        assert_eq!(body(&lines[0]), LINE_WORDS * 8);
+        assert_eq!(
+            body(&lines[LINES - 1]),
+            (TOTAL_WORDS - (LINES - 1) * LINE_WORDS) * 8
+        );
+    }
+
+    #[test]
+    fn lines_come_in_any_order_and_repeats_are_ignored() {
+        let lines = lines_of(settings::record(7));
+        let mut import = Import::new();
+        for (n, index) in (0..LINES).rev().enumerate() {
+            assert!(!import.complete());
+            assert_eq!(
+                import.accept(&lines[index]).unwrap(),
+                Accepted::New {
+                    index,
+                    received: n + 1
+                }
+            );
+            assert_eq!(
+                import.accept(&lines[index]).unwrap(),
+                Accepted::Repeat { index }
+            );
+        }
+        assert!(import.complete());
+        assert_eq!(import.words, settings::record(7));
+    }
+
+    #[test]
+    fn a_rejected_line_leaves_the_import_as_it_was() {
+        let lines = lines_of(settings::record(7));
+        let other = lines_of(settings::record(8));
+        let mut import = Import::new();
+        import.accept(&lines[2]).unwrap();
+        let mut tampered = lines[3].clone().into_bytes();
+        let digit = format!("prov 3/{} ", LINES).len();
+        tampered[digit] = if tampered[digit] == b'0' { b'1' } else { b'0' };
+        let tampered = String::from_utf8(tampered).unwrap();
+        let rejected = [
+            (
+                other[2].as_str(),
+                "line 2 differs from the one received before",
+            ),
+            (&tampered, "line 3 failed its CRC"),
+            ("prov hello", "not a provision line"),
+            ("capture 0/5 00000000 00000000", "not a provision line"),
+        ];
+        for (line, message) in rejected {
+            assert_eq!(import.accept(line).unwrap_err().to_string(), message);
+            assert_eq!(import.received, 1 << 2);
+            assert_eq!(
+                import.words[line_range(2)],
+                settings::record(7)[line_range(2)]
+            );
+        }
+        assert_eq!(
+            import.accept(&lines[2]).unwrap(),
+            Accepted::Repeat { index: 2 }
+        );
+    }
+
+    #[test]
+    fn lines_need_the_right_count_and_length() {
+        let lines = lines_of(settings::record(7));
+        let body = lines[0].split(' ').nth(2).unwrap();
+        let mut import = Import::new();
+        assert!(matches!(
+            import.accept(&reframe(&lines[0], LINES + 1, body)),
+            Err(Error::Count(count)) if count == LINES + 1
+        ));
+        for body in [&body[8..], "+0000001ffffffffffffffffffffffff"] {
+            assert!(matches!(
+                import.accept(&reframe(&lines[0], LINES, body)),
+                Err(Error::Malformed)
+            ));
+        }
+        assert_eq!(import.received, 0);
+    }
+
+    #[test]
+    fn install_replaces_the_record_and_reads_it_back() {
+        let mut import = Import::new();
+        for line in lines_of(settings::record(7)) {
+            import.accept(&line).unwrap();
+        }
+        board(3);
+        assert_eq!(import.install().unwrap(), ITEMS.len());
+        assert_eq!(in_flash(), settings::record(7));
+        assert!(settings::valid(&in_flash()));
+    }
+
+    #[test]
+    fn a_record_that_fails_its_own_check_is_never_written() {
+        let mut words = settings::record(7);
+        words[5] ^= 1;
+        let mut import = Import::new();
+        for line in lines_of(words) {
+            // Every line passes its CRC, they were made from the bad record
+            import.accept(&line).unwrap();
+        }
+        board(3);
+        assert!(matches!(import.install(), Err(Error::Invalid("settings"))));
+        assert_eq!(in_flash(), settings::record(3));
+    }
+
+    #[test]
+    fn a_low_supply_leaves_the_old_record() {
+        let mut import = Import::new();
+        for line in lines_of(settings::record(7)) {
+            import.accept(&line).unwrap();
+        }
+        board(3);
+        flash::set_low_power(true);
+        assert!(matches!(import.install(), Err(Error::LowPower(_))));
+        assert_eq!(in_flash(), settings::record(3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn install_waits_for_every_line() {
+        let mut import = Import::new();
+        import.accept(&lines_of(settings::record(7))[0]).unwrap();
+        let _ = import.install();
+    }
+}
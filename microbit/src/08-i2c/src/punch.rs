@@ -0,0 +1,92 @@
+//! The hardest a punch accelerates the board, for "punch".
+//!
+//! Nothing happens until the magnitude of a reading goes over
+//! [`START_MG`], well clear of the 1 g the board reads lying still; from
+//! then on [`Meter`] keeps the largest for [`WINDOW_MS`] and that is the
+//! punch. The readings are raw counts at ±16 g and 400 Hz, since a punch
+//! is over in a few tens of milliseconds, and unfiltered, since smoothing
+//! would flatten the very peak being looked for.
+//!
+//! A count at either end of the range may be the sensor clipping rather
+//! than the acceleration, so a punch with one in it is only known to be
+//! more than the sensor reads.
+
+use core::fmt;
+
+use crate::gravity;
+
+/// A second, long enough for the board to stop shaking after any punch
+pub const WINDOW_MS: u32 = 1_000;
+
+/// Well clear of gravity, or of the board being picked up
+const START_MG: u32 = 2_000;
+
+/// What one count is at ±16 g in normal mode, from the datasheet. The
+/// driver scales it by 32 instead, which would make every punch two
+/// thirds of what it is.
+const MG_PER_COUNT: i32 = 48;
+
+/// The ends of the 10-bit range normal mode reads in
+const MIN_COUNT: i32 = -512;
+const MAX_COUNT: i32 = 511;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Punch {
+    /// In mg
+    Peak(u32),
+    /// An axis read the end of the range at some point
+    Saturated,
+}
+
+impl fmt::Display for Punch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Punch::Peak(mg) => write!(f, "{}.{:02} g", mg / 1000, mg % 1000 / 10),
+            Punch::Saturated => write!(f, ">16 g"),
+        }
+    }
+}
+
+/// Waits for a punch, then measures it.
+pub struct Meter {
+    /// When the punch started, in ms, once it has
+    start: Option<u32>,
+    peak: u32,
+    saturated: bool,
+}
+
+impl Meter {
+    pub fn new() -> Meter {
+        Meter {
+            start: None,
+            peak: 0,
+            saturated: false,
+        }
+    }
+
+    pub fn started(&self) -> bool {
+        self.start.is_some()
+    }
+
+    /// Take a reading in raw counts, read at `ms`. The punch, once the
+    /// window after it started is over.
+    pub fn push(&mut self, counts: [i32; 3], ms: u32) -> Option<Punch> {
+        let magnitude = gravity::magnitude(counts.map(|count| count * MG_PER_COUNT));
+        let start = match self.start {
+            Some(start) => start,
+            None if magnitude > START_MG => *self.start.insert(ms),
+            None => return None,
+        };
+        self.peak = self.peak.max(magnitude);
+        self.saturated |= counts
+            .iter()
+            .any(|&count| count <= MIN_COUNT || count >= MAX_COUNT);
+        if ms.wrapping_sub(start) < WINDOW_MS {
+            return None;
+        }
+        Some(match self.saturated {
+            true => Punch::Saturated,
+            false => Punch::Peak(self.peak),
+        })
+    }
+}
@@ -0,0 +1,118 @@
+//! "i2c read" and "i2c write": raw transactions on the sensor's bus, for
+//! poking at a device the firmware has no driver for, or at one it has.
+//!
+//! A read writes the register and reads the bytes back in one transaction,
+//! a write sends the register and the byte. Addresses, registers and bytes
+//! are in hex, with or without "0x"; the length is decimal. The LSM303AGR
+//! only moves on to the next register during a read when the top bit of
+//! the register is set, as in "i2c read 19 a8 6" for all three axes.
+//!
+//! The driver gives up the bus meanwhile and starts over afterwards, so a
+//! write to one of the LSM303AGR's own settings only lasts until then.
+
+use core::fmt;
+
+pub const USAGE: &str = "i2c read <addr> <reg> [<len>] | i2c write <addr> <reg> <byte>";
+
+/// The most "i2c read" reads at once
+pub const MAX_LEN: usize = 16;
+
+/// The highest 7-bit address
+const MAX_ADDRESS: u8 = 0x7f;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Request {
+    Read {
+        address: u8,
+        register: u8,
+        len: usize,
+    },
+    Write {
+        address: u8,
+        register: u8,
+        value: u8,
+    },
+}
+
+impl Request {
+    /// What comes after "i2c", `None` unless there is exactly what
+    /// [`USAGE`] says.
+    pub fn parse(args: &str) -> Option<Request> {
+        let mut words = args.split_ascii_whitespace();
+        let (verb, address, register) = (words.next()?, words.next()?, words.next()?);
+        let address = parse_byte(address).filter(|&address| address <= MAX_ADDRESS)?;
+        let register = parse_byte(register)?;
+        let request = match (verb, words.next()) {
+            ("read", None) => Request::Read {
+                address,
+                register,
+                len: 1,
+            },
+            ("read", Some(len)) => Request::Read {
+                address,
+                register,
+                len: len.parse().ok().filter(|len| (1..=MAX_LEN).contains(len))?,
+            },
+            ("write", Some(value)) => Request::Write {
+                address,
+                register,
+                value: parse_byte(value)?,
+            },
+            _ => return None,
+        };
+        match words.next() {
+            Some(_) => None,
+            None => Some(request),
+        }
+    }
+
+    pub fn address(&self) -> u8 {
+        match *self {
+            Request::Read { address, .. } | Request::Write { address, .. } => address,
+        }
+    }
+}
+
+/// `1e`, `1E`, `0x1e` or `0X1E`: one or two hex digits.
+pub fn parse_byte(word: &str) -> Option<u8> {
+    let digits = word
+        .strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .unwrap_or(word);
+    // from_str_radix would take a sign as well
+    if digits.is_empty() || digits.len() > 2 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(digits, 16).ok()
+}
+
+/// Printed two hex digits a byte, a space between them, as in "1e 00 ff".
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+pub enum Error {
+    /// The device NACKed, or there is none
+    Nack(u8),
+    /// Anything else that went wrong on the bus
+    Fault(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Nack(address) => write!(f, "i2c: no ACK from 0x{:02x}", address),
+            Error::Fault(address) => write!(f, "i2c: bus error talking to 0x{:02x}", address),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! The last few accelerometer and magnetometer readings, as printed.
+//!
+//! Readings are recorded by the same helper that prints them, mask and all,
+//! so "recent" shows exactly what scrolled past.
+
+use crate::axes::Sample;
+
+pub const LEN: usize = 64;
+pub const USAGE: &str = "recent accel|mag [1-64]";
+/// How many "recent" shows without a count.
+pub const DEFAULT_COUNT: usize = 10;
+
+#[derive(Clone, Copy)]
+pub struct Entry {
+    /// Since boot, wrapping around after 49 days
+    pub ms: u32,
+    pub sample: Sample,
+}
+
+pub struct Ring {
+    entries: [Option<Entry>; LEN],
+    /// Where the next entry goes
+    next: usize,
+}
+
+impl Ring {
+    pub const fn new() -> Ring {
+        Ring {
+            entries: [None; LEN],
+            next: 0,
+        }
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        self.entries[self.next] = Some(entry);
+        self.next = (self.next + 1) % LEN;
+    }
+
+    pub fn count(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_some()).count()
+    }
+
+    /// The last `n` entries, oldest first. Fewer if fewer were pushed.
+    pub fn last(&self, n: usize) -> impl Iterator<Item = &Entry> {
+        let n = n.min(self.count());
+        let start = (self.next + LEN - n) % LEN;
+        (0..n).filter_map(move |i| self.entries[(start + i) % LEN].as_ref())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Accel,
+    Mag,
+}
+
+impl Kind {
+    /// What the reading is printed as.
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Accel => "Acceleration (mg)",
+            Kind::Mag => "Magnetic field (nT)",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Kind> {
+        match name {
+            "accel" => Some(Kind::Accel),
+            "mag" => Some(Kind::Mag),
+            _ => None,
+        }
+    }
+}
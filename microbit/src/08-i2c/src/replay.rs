@@ -0,0 +1,33 @@
+//! Recorded samples in place of the LSM303AGR's, for "replay on".
+//!
+//! The samples in [`trace`] go through the same filter and formatting as
+//! live ones, so a stream of them looks just like the stream the recording
+//! was made from. Accelerometer and magnetometer samples are taken in
+//! order, each from their own start, and the trace starts over when it
+//! runs out. Every "replay on" starts from the beginning, so the same
+//! commands give the same output every time.
+
+use crate::trace;
+
+pub const USAGE: &str = "replay on|off";
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Replay {
+    /// Of the next samples
+    accel: usize,
+    mag: usize,
+}
+
+impl Replay {
+    pub fn next_accel(&mut self) -> [i32; 3] {
+        let [x, y, z] = trace::ACCEL[self.accel];
+        self.accel = (self.accel + 1) % trace::ACCEL.len();
+        [x as i32, y as i32, z as i32]
+    }
+
+    pub fn next_mag(&mut self) -> [i32; 3] {
+        let sample = trace::MAG[self.mag];
+        self.mag = (self.mag + 1) % trace::MAG.len();
+        sample
+    }
+}
@@ -0,0 +1,182 @@
+//! Bracketing every command's output for host programs.
+//!
+//! In the machine-readable output modes each command line that gets past
+//! the menus is acknowledged with an `ack` record before anything else is
+//! printed for it, and a `done` record with its outcome follows the last
+//! of its output, errors included. A host that sends commands back to back
+//! can pair every response with its request this way. Human mode prints
+//! neither, and neither does "ping" in any mode: its pong is timed by the
+//! host and comes alone.
+//!
+//! While the sensor is simulated or replayed, `done` says so, so that a
+//! host logging the replies can't take made-up readings for live ones.
+
+use core::fmt;
+use heapless::String;
+
+pub const USAGE: &str = "output human|csv|json";
+
+/// Longer than any command name.
+const NAME_LEN: usize = 16;
+
+/// What a `done` record quotes as the command.
+pub type Name = String<NAME_LEN>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Human,
+    Csv,
+    Json,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "human" => Some(Mode::Human),
+            "csv" => Some(Mode::Csv),
+            "json" => Some(Mode::Json),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Human => "human",
+            Mode::Csv => "csv",
+            Mode::Json => "json",
+        }
+    }
+
+    pub fn encode(self) -> u32 {
+        self as u32
+    }
+
+    /// Anything unrecognized decodes as [`Mode::Human`].
+    pub fn decode(word: u32) -> Mode {
+        match word {
+            1 => Mode::Csv,
+            2 => Mode::Json,
+            _ => Mode::Human,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Ok,
+    Error,
+    Interrupted,
+    TimedOut,
+    NotConfirmed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Error => "error",
+            Status::Interrupted => "interrupted",
+            Status::TimedOut => "timeout",
+            Status::NotConfirmed => "unconfirmed",
+        }
+    }
+}
+
+/// The command name out of a command line, cut short if it doesn't fit.
+pub fn name_of(line: &str) -> Name {
+    let mut name = Name::new();
+    for c in line.split_ascii_whitespace().next().unwrap_or("").chars() {
+        if name.push(c).is_err() {
+            break;
+        }
+    }
+    name
+}
+
+/// A JSON string without the quotes.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' | '\\' => write!(f, "\\{}", c)?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `board` is the board's name, see [`crate::board`], and `warn` names a
+/// check that is failing, see [`crate::consistency`]. Both come after the
+/// arguments, as `board=<name>` and `warn=<name>` in CSV, where no argument
+/// has an `=`.
+pub fn ack<W: fmt::Write>(
+    w: &mut W,
+    mode: Mode,
+    line: &str,
+    board: &str,
+    warn: Option<&str>,
+) -> fmt::Result {
+    let mut words = line.split_ascii_whitespace();
+    let name = words.next().unwrap_or("");
+    match mode {
+        Mode::Human => Ok(()),
+        Mode::Csv => {
+            write!(w, "ack,{}", name)?;
+            for arg in words {
+                write!(w, ",{}", arg)?;
+            }
+            write!(w, ",board={}", board)?;
+            if let Some(warn) = warn {
+                write!(w, ",warn={}", warn)?;
+            }
+            writeln!(w)
+        }
+        Mode::Json => {
+            write!(w, "{{\"ack\":\"{}\",\"args\":[", Escaped(name))?;
+            for (i, arg) in words.enumerate() {
+                let separator = if i == 0 { "" } else { "," };
+                write!(w, "{}\"{}\"", separator, Escaped(arg))?;
+            }
+            write!(w, "],\"board\":\"{}\"", Escaped(board))?;
+            if let Some(warn) = warn {
+                write!(w, ",\"warn\":\"{}\"", warn)?;
+            }
+            writeln!(w, "}}")
+        }
+    }
+}
+
+pub fn done<W: fmt::Write>(
+    w: &mut W,
+    mode: Mode,
+    name: &str,
+    status: Status,
+    tag: Option<&str>,
+) -> fmt::Result {
+    match mode {
+        Mode::Human => Ok(()),
+        Mode::Csv => {
+            write!(w, "done,{},{}", name, status.name())?;
+            if let Some(tag) = tag {
+                write!(w, ",{}", tag)?;
+            }
+            writeln!(w)
+        }
+        Mode::Json => {
+            write!(
+                w,
+                "{{\"done\":\"{}\",\"status\":\"{}\"",
+                Escaped(name),
+                status.name()
+            )?;
+            if let Some(tag) = tag {
+                write!(w, ",\"{}\":true", tag)?;
+            }
+            writeln!(w, "}}")
+        }
+    }
+}
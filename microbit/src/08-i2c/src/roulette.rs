@@ -0,0 +1,32 @@
+//! The LED running round the edge, as in the LED roulette chapter, for
+//! anything that wants to show it.
+
+use crate::display::Image;
+
+/// The 16 LEDs around the edge, clockwise from the top left, as (row, col).
+const BORDER: [(usize, usize); 16] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 4),
+    (2, 4),
+    (3, 4),
+    (4, 4),
+    (4, 3),
+    (4, 2),
+    (4, 1),
+    (4, 0),
+    (3, 0),
+    (2, 0),
+    (1, 0),
+];
+const STEP_MS: u32 = 125;
+
+pub fn frame(elapsed_ms: u32, level: u8) -> Image {
+    let (row, col) = BORDER[(elapsed_ms / STEP_MS) as usize % BORDER.len()];
+    let mut image = [[0; 5]; 5];
+    image[row][col] = level;
+    image
+}
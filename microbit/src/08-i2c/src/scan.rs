@@ -0,0 +1,91 @@
+//! "scan": which addresses on the sensor's I2C bus have a device behind
+//! them.
+//!
+//! Every address from [`FIRST`] up to but not including [`END`], the ones
+//! the I2C spec leaves to devices, gets a read of one byte: a device ACKs
+//! its address, an empty one NACKs it. The TWIM can't do a write of
+//! nothing, the more usual probe, and no device minds a read. The v1's TWI
+//! doesn't tell a NACK from any other failure, so there every failure is an
+//! empty address, and only a bus held low before the scan is an error.
+//!
+//! On the v1 the edge connector's SDA and SCL are the sensor's bus; the v2
+//! has a bus of its own for the edge connector, which this doesn't scan.
+
+use core::fmt;
+
+pub const FIRST: u8 = 0x08;
+pub const END: u8 = 0x78;
+
+/// The devices on the board, to label them with
+const KNOWN: [(u8, &str); 2] = [
+    (0x19, "LSM303AGR accelerometer"),
+    (0x1e, "LSM303AGR magnetometer"),
+];
+
+pub enum Error {
+    /// SDA or SCL low before anything was sent
+    Stuck,
+    /// Something other than a NACK at this address
+    Fault(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Stuck => write!(f, "scan: SDA or SCL is held low, nothing can talk"),
+            Error::Fault(address) => write!(f, "scan: bus error at 0x{:02x}", address),
+        }
+    }
+}
+
+/// The addresses that answered.
+#[derive(Default)]
+pub struct Found(u128);
+
+impl Found {
+    pub fn insert(&mut self, address: u8) {
+        self.0 |= 1 << address;
+    }
+
+    pub fn contains(&self, address: u8) -> bool {
+        self.0 & 1 << address != 0
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// The ones on the board that answered, with their labels, lowest
+    /// first.
+    pub fn known(&self) -> impl Iterator<Item = (u8, &'static str)> + '_ {
+        KNOWN
+            .iter()
+            .copied()
+            .filter(move |&(address, _)| self.contains(address))
+    }
+}
+
+/// A row of 16 addresses per line, as i2cdetect prints them: the address
+/// where a device answered, "--" where none did, blank where nothing was
+/// asked.
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "   ")?;
+        for column in 0..16 {
+            write!(f, "  {:x}", column)?;
+        }
+        for row in (0..0x80u8).step_by(16) {
+            write!(f, "\n{:02x}:", row)?;
+            for address in row..(row + 16).min(END) {
+                if address < FIRST {
+                    write!(f, "   ")?;
+                } else if self.contains(address) {
+                    write!(f, " {:02x}", address)?;
+                } else {
+                    write!(f, " --")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,33 +1,131 @@
 use core::fmt;
 use embedded_hal::blocking::serial as bserial;
 use embedded_hal::serial;
-use microbit::hal::uarte::{Error, Instance, Uarte, UarteRx, UarteTx};
+#[cfg(feature = "v2")]
+use embedded_hal::serial::Read;
 
-static mut TX_BUF: [u8; 1] = [0; 1];
-static mut RX_BUF: [u8; 1] = [0; 1];
+use crate::abort::{self, CTRL_C, CTRL_R};
+use crate::lineend;
+use crate::textlog::Text;
 
-pub struct UartePort<T: Instance>(UarteTx<T>, UarteRx<T>);
+pub use crate::lineend::{LineEnd, LINE_END_USAGE};
+pub use chip::{Buffers, Error, Serial};
 
-impl<T: Instance> UartePort<T> {
-    pub fn new(serial: Uarte<T>) -> UartePort<T> {
-        let (tx, rx) = serial
-            .split(unsafe { &mut TX_BUF }, unsafe { &mut RX_BUF })
-            .unwrap();
-        UartePort(tx, rx)
+/// Bytes moved over the port since boot.
+#[derive(Clone, Copy, Default)]
+pub struct SerialStats {
+    pub tx: u32,
+    pub rx: u32,
+}
+
+/// Moves up to `RX` bytes in and `TX` bytes out at a time, see
+/// [`Buffers`].
+///
+/// The second field holds a byte picked up by [`SerialPort::poll_abort`]
+/// until the next read, the last the text written while diverted.
+pub struct SerialPort<const RX: usize = 32, const TX: usize = 32>(
+    chip::Port<RX, TX>,
+    Option<u8>,
+    SerialStats,
+    LineEnd,
+    Option<Text>,
+);
+
+impl<const RX: usize, const TX: usize> SerialPort<RX, TX> {
+    pub fn new(
+        serial: Serial,
+        buffers: &'static mut Buffers<RX, TX>,
+    ) -> Result<SerialPort<RX, TX>, Error> {
+        Ok(SerialPort(
+            chip::Port::new(serial, buffers)?,
+            None,
+            SerialStats::default(),
+            LineEnd::CrLf,
+            None,
+        ))
+    }
+
+    pub fn set_line_end(&mut self, line_end: LineEnd) {
+        self.3 = line_end;
+    }
+
+    fn write_raw(&mut self, s: &str) -> fmt::Result {
+        self.0.write_all(s.as_bytes()).map_err(|_| fmt::Error)?;
+        self.2.tx = self.2.tx.wrapping_add(s.len() as u32);
+        Ok(())
+    }
+
+    /// Keep what gets written from now on in `text` instead of sending it,
+    /// see [`crate::textlog`]. Reading and echoed bytes are left alone.
+    pub fn divert(&mut self, text: Text) {
+        self.4 = Some(text);
+    }
+
+    /// Send again, and hand back what was kept meanwhile.
+    pub fn undivert(&mut self) -> Option<Text> {
+        self.4.take()
+    }
+
+    pub fn stats(&self) -> SerialStats {
+        self.2
+    }
+
+    /// Look for a Ctrl-C, a Ctrl-R or a break without waiting for input,
+    /// for loops that don't otherwise read from the serial port.
+    pub fn poll_abort(&mut self) {
+        let byte = self.0.read();
+        if byte.is_ok() {
+            self.2.rx = self.2.rx.wrapping_add(1);
+        }
+        // The break itself comes in as a zero byte or a failed read,
+        // neither of which is worth keeping
+        if chip::take_break() {
+            abort::request_header();
+            return;
+        }
+        match byte {
+            Ok(CTRL_C) => abort::raise(),
+            Ok(CTRL_R) => abort::request_header(),
+            // Only the first byte of any typeahead is kept
+            Ok(byte) => {
+                self.1.get_or_insert(byte);
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Take the byte [`SerialPort::poll_abort`] kept, if there is one, for
+    /// a loop that stops on any key. Whatever came in right behind it, such
+    /// as the rest of an escape sequence or the LF after a CR, goes too:
+    /// none of it was meant for the next command.
+    pub fn take_typeahead(&mut self) -> Option<u8> {
+        let byte = self.1.take()?;
+        while self.0.read().is_ok() {
+            self.2.rx = self.2.rx.wrapping_add(1);
+        }
+        Some(byte)
     }
 }
 
-impl<T: Instance> fmt::Write for UartePort<T> {
+/// Every "\n" goes out as the line ending set, see [`lineend`].
+impl<const RX: usize, const TX: usize> fmt::Write for SerialPort<RX, TX> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.0.write_str(s)
+        if let Some(text) = &mut self.4 {
+            text.push(s);
+            return Ok(());
+        }
+        let line_end = self.3;
+        lineend::write_str(line_end, s, |part| self.write_raw(part))
     }
 }
 
-impl<T: Instance> serial::Write<u8> for UartePort<T> {
+impl<const RX: usize, const TX: usize> serial::Write<u8> for SerialPort<RX, TX> {
     type Error = Error;
 
     fn write(&mut self, b: u8) -> nb::Result<(), Self::Error> {
-        self.0.write(b)
+        self.0.write(b)?;
+        self.2.tx = self.2.tx.wrapping_add(1);
+        Ok(())
     }
 
     fn flush(&mut self) -> nb::Result<(), Self::Error> {
@@ -35,12 +133,110 @@ impl<T: Instance> serial::Write<u8> for UartePort<T> {
     }
 }
 
-impl<T: Instance> bserial::write::Default<u8> for UartePort<T> {}
+impl<const RX: usize, const TX: usize> bserial::write::Default<u8> for SerialPort<RX, TX> {}
 
-impl<T: Instance> serial::Read<u8> for UartePort<T> {
+impl<const RX: usize, const TX: usize> serial::Read<u8> for SerialPort<RX, TX> {
     type Error = Error;
 
     fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        self.1.read()
+        if let Some(byte) = self.1.take() {
+            return Ok(byte);
+        }
+        let byte = self.0.read()?;
+        self.2.rx = self.2.rx.wrapping_add(1);
+        if byte == CTRL_C {
+            abort::raise();
+        }
+        Ok(byte)
+    }
+}
+
+/// The UARTE, by way of the port shared with 07-uart, see
+/// [`crate::uarte_port`].
+#[cfg(feature = "v2")]
+mod chip {
+    use microbit::hal::uarte::Uarte;
+    use microbit::pac::UARTE0;
+
+    use crate::uarte_port::UartePort;
+
+    pub use crate::uarte_port::Buffers;
+    pub use microbit::hal::uarte::Error;
+
+    pub type Serial = Uarte<UARTE0>;
+
+    pub type Port<const RX: usize, const TX: usize> = UartePort<UARTE0, RX, TX>;
+
+    /// Whether a break came in since the last call.
+    pub fn take_break() -> bool {
+        let uarte = unsafe { &*UARTE0::ptr() };
+        let present = uarte.errorsrc.read().break_().is_present();
+        if present {
+            // Write one to clear
+            uarte.errorsrc.write(|w| w.break_().present());
+        }
+        present
+    }
+}
+
+/// The UART, which moves a byte at a time through its own registers and
+/// never fails to: writes that can't go wrong become writes that go wrong
+/// the same way reads do.
+#[cfg(feature = "v1")]
+mod chip {
+    use embedded_hal::serial::{Read, Write};
+    use microbit::hal::uart::Uart;
+    use microbit::pac::UART0;
+
+    pub use microbit::hal::uart::Error;
+
+    pub type Serial = Uart<UART0>;
+
+    /// Nothing: the UART has no DMA, only a FIFO of its own. The sizes
+    /// are there so that the port is the same type on both chips.
+    pub struct Buffers<const RX: usize = 32, const TX: usize = 32>;
+
+    impl<const RX: usize, const TX: usize> Buffers<RX, TX> {
+        pub const fn new() -> Buffers<RX, TX> {
+            Buffers
+        }
+    }
+
+    pub struct Port<const RX: usize, const TX: usize>(Uart<UART0>);
+
+    impl<const RX: usize, const TX: usize> Port<RX, TX> {
+        pub fn new(serial: Serial, _: &'static mut Buffers<RX, TX>) -> Result<Port<RX, TX>, Error> {
+            Ok(Port(serial))
+        }
+
+        pub fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+            for &b in bytes {
+                nb::block!(self.write(b))?;
+            }
+            Ok(())
+        }
+
+        pub fn write(&mut self, b: u8) -> nb::Result<(), Error> {
+            self.0.write(b).map_err(|err| err.map(|void| match void {}))
+        }
+
+        pub fn flush(&mut self) -> nb::Result<(), Error> {
+            self.0.flush().map_err(|err| err.map(|void| match void {}))
+        }
+
+        pub fn read(&mut self) -> nb::Result<u8, Error> {
+            self.0.read()
+        }
+    }
+
+    /// Whether a break came in since the last call.
+    pub fn take_break() -> bool {
+        let uart = unsafe { &*UART0::ptr() };
+        let present = uart.errorsrc.read().break_().is_present();
+        if present {
+            // Write one to clear
+            uart.errorsrc.write(|w| w.break_().clear());
+        }
+        present
     }
 }
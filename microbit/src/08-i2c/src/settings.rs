@@ -0,0 +1,173 @@
+//! Settings that survive a reset, stored as one checksummed record at the
+//! start of flash page 0.
+
+use core::fmt;
+
+use crate::axes::Axes;
+use crate::battery;
+use crate::board;
+use crate::consistency;
+use crate::display;
+use crate::filter;
+use crate::flash;
+use crate::format::OutputFormat;
+use crate::powersave;
+use crate::reply;
+use crate::serial_setup::LineEnd;
+use crate::stamp;
+use crate::status::StatusLeds;
+use crate::tilt;
+
+/// Bump the low byte whenever the record layout changes, so that records
+/// written by an older program are ignored rather than misread.
+const MAGIC: u32 = 0x5354_4711;
+const PAYLOAD_WORDS: usize = 17;
+pub const RECORD_WORDS: usize = PAYLOAD_WORDS + 2;
+pub const PAGE: usize = 0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    pub heartbeat: bool,
+    pub filter: filter::Kind,
+    /// Percent, 1 to 100
+    pub tilt_alpha: u8,
+    /// 0 to [`display::MAX_BRIGHTNESS`]
+    pub brightness: u8,
+    pub status_leds: StatusLeds,
+    /// [`battery::MIN_WARN_MV`] to [`battery::MAX_WARN_MV`]
+    pub batt_warn_mv: u16,
+    /// Which components sensor readings show
+    pub axes: Axes,
+    /// The first column of streamed output
+    pub time_format: stamp::Format,
+    pub line_end: LineEnd,
+    pub output: reply::Mode,
+    /// How readings are printed, see [`crate::format`]
+    pub format: OutputFormat,
+    pub sensor_health: consistency::Config,
+    /// What the board goes by, see [`board`]
+    pub name: board::Name,
+    pub powersave: powersave::Mode,
+    /// Off on a board without a speaker whatever it says, see [`crate::cues`]
+    pub audio_cues: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            heartbeat: true,
+            filter: filter::Kind::Off,
+            tilt_alpha: tilt::DEFAULT_ALPHA,
+            brightness: display::MAX_BRIGHTNESS,
+            status_leds: StatusLeds::default(),
+            batt_warn_mv: battery::DEFAULT_WARN_MV,
+            axes: Axes::ALL,
+            time_format: stamp::Format::Millis,
+            line_end: LineEnd::CrLf,
+            output: reply::Mode::Human,
+            format: OutputFormat::Human,
+            sensor_health: consistency::Config::default(),
+            name: board::default_name(),
+            powersave: powersave::Mode::Auto,
+            audio_cues: false,
+        }
+    }
+}
+
+impl Settings {
+    fn encode(&self) -> [u32; PAYLOAD_WORDS] {
+        let [health, reference] = self.sensor_health.encode();
+        let [name_0, name_1] = board::encode_name(&self.name);
+        [
+            self.heartbeat as u32,
+            self.filter.encode(),
+            self.tilt_alpha as u32,
+            self.brightness as u32,
+            self.status_leds.encode(),
+            self.batt_warn_mv as u32,
+            self.axes.encode(),
+            self.time_format.encode(),
+            self.line_end.encode(),
+            self.output.encode(),
+            health,
+            reference,
+            name_0,
+            name_1,
+            self.powersave.encode(),
+            self.audio_cues as u32,
+            self.format.encode(),
+        ]
+    }
+
+    fn decode(payload: &[u32]) -> Settings {
+        Settings {
+            heartbeat: payload[0] != 0,
+            filter: filter::Kind::decode(payload[1]),
+            tilt_alpha: match payload[2] {
+                alpha @ 1..=100 => alpha as u8,
+                _ => tilt::DEFAULT_ALPHA,
+            },
+            brightness: (payload[3] as u8).min(display::MAX_BRIGHTNESS),
+            status_leds: StatusLeds::decode(payload[4]),
+            batt_warn_mv: Some(payload[5] as u16)
+                .filter(|mv| (battery::MIN_WARN_MV..=battery::MAX_WARN_MV).contains(mv))
+                .unwrap_or(battery::DEFAULT_WARN_MV),
+            axes: Axes::decode(payload[6]),
+            time_format: stamp::Format::decode(payload[7]),
+            line_end: LineEnd::decode(payload[8]),
+            output: reply::Mode::decode(payload[9]),
+            sensor_health: consistency::Config::decode([payload[10], payload[11]]),
+            name: board::decode_name([payload[12], payload[13]]),
+            powersave: powersave::Mode::decode(payload[14]),
+            audio_cues: payload[15] != 0,
+            format: OutputFormat::decode(payload[16]),
+        }
+    }
+}
+
+/// Write `settings` out as the shell commands that recreate them, one per
+/// line, for "config export". Every field needs a line here.
+pub fn export<W: fmt::Write>(w: &mut W, settings: &Settings) -> fmt::Result {
+    let on_off = |on| if on { "on" } else { "off" };
+    writeln!(w, "heartbeat {}", on_off(settings.heartbeat))?;
+    writeln!(w, "audiocues {}", on_off(settings.audio_cues))?;
+    writeln!(w, "{}", settings.filter)?;
+    writeln!(w, "tiltfilter {}", settings.tilt_alpha)?;
+    writeln!(w, "brightness {}", settings.brightness)?;
+    writeln!(w, "powersave {}", settings.powersave.name())?;
+    writeln!(w, "battwarn {}", settings.batt_warn_mv)?;
+    writeln!(w, "axes show {}", settings.axes)?;
+    writeln!(w, "timeformat {}", settings.time_format.name())?;
+    writeln!(w, "lineend {}", settings.line_end.name())?;
+    writeln!(w, "output {}", settings.output.name())?;
+    writeln!(w, "format {}", settings.format.name())?;
+    settings.sensor_health.export(w)?;
+    writeln!(w, "name {}", settings.name)?;
+    settings.status_leds.export(w)
+}
+
+/// Load the stored settings, or `None` if the page is erased or corrupted.
+pub fn load() -> Option<Settings> {
+    let mut record = [0; RECORD_WORDS];
+    flash::read(PAGE, 0, &mut record);
+    if !valid(&record) {
+        return None;
+    }
+    Some(Settings::decode(&record[1..=PAYLOAD_WORDS]))
+}
+
+/// Whether `record` is a settings record with an intact checksum, as
+/// written by this program.
+pub fn valid(record: &[u32]) -> bool {
+    let (body, sum) = record.split_at(RECORD_WORDS - 1);
+    body[0] == MAGIC && flash::checksum(body) == sum[0]
+}
+
+pub fn save(settings: &Settings) -> Result<(), flash::LowPower> {
+    let mut record = [0; RECORD_WORDS];
+    record[0] = MAGIC;
+    record[1..=PAYLOAD_WORDS].copy_from_slice(&settings.encode());
+    record[RECORD_WORDS - 1] = flash::checksum(&record[..RECORD_WORDS - 1]);
+    flash::erase(PAGE)?;
+    flash::write(PAGE, 0, &record)
+}
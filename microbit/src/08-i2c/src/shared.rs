@@ -0,0 +1,95 @@
+//! State that interrupt handlers and the command loop both get at.
+//!
+//! There are two kinds, and one way of handling each:
+//!
+//! - Flags and counters that one side sets and the other polls, such as
+//!   the Ctrl-C flag in [`crate::abort`] or the tick count in
+//!   [`crate::heartbeat`], are atomics, with plain loads and stores since
+//!   the nRF51 has no read-modify-write.
+//! - Anything bigger lives in a [`Shared`] cell, which only hands out its
+//!   contents inside a critical section, for as long as a closure runs.
+//!   The part of the command loop's own state that handlers may touch is
+//!   the [`State`] here, the rest of it stays in `main`.
+//!
+//! Every static stays private to its module, which offers functions to go
+//! with it rather than the static itself.
+//!
+//! A critical section holds off every interrupt, the display refresh
+//! among them, so the closures have to be short: copy out or in, and do
+//! the work outside. Debug builds on the v2 time each one with the cycle
+//! counter and panic when one takes more than [`MAX_CYCLES`]. The nRF51's
+//! Cortex-M0 doesn't have one.
+
+use core::cell::RefCell;
+use cortex_m::interrupt::{free, CriticalSection, Mutex};
+
+use crate::stats::SessionStats;
+
+/// 200 µs at 64 MHz, a fraction of a display refresh period.
+#[cfg(all(debug_assertions, feature = "v2"))]
+pub const MAX_CYCLES: u32 = 12_800;
+
+pub struct Shared<T>(Mutex<RefCell<T>>);
+
+impl<T> Shared<T> {
+    pub const fn new(value: T) -> Shared<T> {
+        Shared(Mutex::new(RefCell::new(value)))
+    }
+
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        free(|cs| self.with_cs(cs, f))
+    }
+
+    /// For code in a critical section already, to get at several cells in
+    /// one.
+    pub fn with_cs<R>(&self, cs: &CriticalSection, f: impl FnOnce(&mut T) -> R) -> R {
+        let start = cycles();
+        let result = f(&mut self.0.borrow(cs).borrow_mut());
+        check(start);
+        result
+    }
+}
+
+/// The part of the command loop's state that interrupt handlers may touch
+/// as well.
+pub struct State {
+    pub stats: SessionStats,
+}
+
+static STATE: Shared<State> = Shared::new(State {
+    stats: SessionStats::new(),
+});
+
+pub fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    STATE.with(f)
+}
+
+/// Start the cycle counter for timing critical sections.
+#[cfg(all(debug_assertions, feature = "v2"))]
+pub fn start_timing(mut dcb: cortex_m::peripheral::DCB, mut dwt: cortex_m::peripheral::DWT) {
+    dcb.enable_trace();
+    dwt.enable_cycle_counter();
+}
+
+#[cfg(all(debug_assertions, feature = "v2"))]
+fn cycles() -> u32 {
+    cortex_m::peripheral::DWT::cycle_count()
+}
+
+#[cfg(all(debug_assertions, feature = "v2"))]
+fn check(start: u32) {
+    let taken = cycles().wrapping_sub(start);
+    assert!(
+        taken <= MAX_CYCLES,
+        "critical section took {} cycles",
+        taken
+    );
+}
+
+#[cfg(not(all(debug_assertions, feature = "v2")))]
+fn cycles() -> u32 {
+    0
+}
+
+#[cfg(not(all(debug_assertions, feature = "v2")))]
+fn check(_start: u32) {}
@@ -0,0 +1,107 @@
+//! Made-up sensor readings, for demos without a working LSM303AGR.
+//!
+//! The board pretends to turn slowly about its X axis, once every
+//! [`PERIOD_MS`], in a magnetic field like the one in central Europe. Both
+//! readings come from the same rotation so tilt, heading and anything else
+//! built on them stay consistent with each other. On top of that goes
+//! noise from the hardware RNG, in percent of each vector's length.
+
+use core::f32::consts::PI;
+use libm::{cosf, sinf};
+use microbit::pac;
+
+use crate::health::{InitError, Subsystem};
+use crate::uptime;
+
+pub const USAGE: &str = "simulate on [<noise 0-20>]|off";
+pub const DEFAULT_NOISE: u8 = 2;
+pub const MAX_NOISE: u8 = 20;
+
+const PERIOD_MS: u32 = 20_000;
+const GRAVITY_MG: f32 = 1000.0;
+/// North and down components of the earth's field
+const NORTH_NT: f32 = 20_000.0;
+const DOWN_NT: f32 = 44_000.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sim {
+    noise: u8,
+}
+
+impl Sim {
+    pub fn new(noise: u8) -> Sim {
+        Sim { noise }
+    }
+
+    /// Without noise there's no need for the RNG.
+    pub fn needs_rng(&self) -> bool {
+        self.noise > 0
+    }
+
+    /// In mg, like the accelerometer.
+    pub fn accel(&self) -> [i32; 3] {
+        let (sin, cos) = angle();
+        self.noisy([0.0, GRAVITY_MG * sin, GRAVITY_MG * cos], GRAVITY_MG)
+    }
+
+    /// In nT, like the magnetometer.
+    pub fn mag(&self) -> [i32; 3] {
+        let (sin, cos) = angle();
+        self.noisy(
+            [NORTH_NT, -DOWN_NT * sin, -DOWN_NT * cos],
+            NORTH_NT + DOWN_NT,
+        )
+    }
+
+    fn noisy(&self, vector: [f32; 3], length: f32) -> [i32; 3] {
+        let amplitude = length * self.noise as f32 / 100.0;
+        let mut out = [0; 3];
+        for (out, value) in out.iter_mut().zip(vector.iter()) {
+            let noise = if self.noise == 0 {
+                0.0
+            } else {
+                // -1 to 1
+                (random_byte() as f32 - 127.5) / 127.5
+            };
+            *out = (value + noise * amplitude) as i32;
+        }
+        out
+    }
+}
+
+/// Sine and cosine of how far the board has turned by now.
+fn angle() -> (f32, f32) {
+    // Skips a bit of a turn when the u32 wraps, after 49 days
+    let ms = uptime::millis() as u32 % PERIOD_MS;
+    let angle = 2.0 * PI * ms as f32 / PERIOD_MS as f32;
+    (sinf(angle), cosf(angle))
+}
+
+/// Far longer than the RNG takes for a byte, which is tens of µs.
+const SELF_TEST_POLLS: u32 = 100_000;
+
+/// Check that the RNG comes up with a value at all, [`random_byte`] would
+/// wait for one forever.
+pub fn self_test() -> Result<(), InitError> {
+    let rng = unsafe { &*pac::RNG::ptr() };
+    rng.events_valrdy.reset();
+    rng.tasks_start.write(|w| unsafe { w.bits(1) });
+    let ready = (0..SELF_TEST_POLLS).any(|_| rng.events_valrdy.read().bits() != 0);
+    rng.tasks_stop.write(|w| unsafe { w.bits(1) });
+    rng.events_valrdy.reset();
+    if ready {
+        Ok(())
+    } else {
+        Err(InitError(Subsystem::Rng))
+    }
+}
+
+fn random_byte() -> u8 {
+    let rng = unsafe { &*pac::RNG::ptr() };
+    rng.events_valrdy.reset();
+    rng.tasks_start.write(|w| unsafe { w.bits(1) });
+    while rng.events_valrdy.read().bits() == 0 {}
+    rng.tasks_stop.write(|w| unsafe { w.bits(1) });
+    rng.events_valrdy.reset();
+    rng.value.read().value().bits()
+}
@@ -0,0 +1,41 @@
+//! The readings that can be named on the command line.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    AccelX,
+    AccelY,
+    AccelZ,
+    MagX,
+    MagY,
+    MagZ,
+    Temp,
+    Vdd,
+}
+
+const NAMES: &[(Source, &str)] = &[
+    (Source::AccelX, "accel.x"),
+    (Source::AccelY, "accel.y"),
+    (Source::AccelZ, "accel.z"),
+    (Source::MagX, "mag.x"),
+    (Source::MagY, "mag.y"),
+    (Source::MagZ, "mag.z"),
+    (Source::Temp, "temp"),
+    (Source::Vdd, "vdd"),
+];
+
+impl Source {
+    pub fn from_name(name: &str) -> Option<Source> {
+        NAMES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(source, _)| *source)
+    }
+
+    pub fn name(self) -> &'static str {
+        NAMES
+            .iter()
+            .find(|(source, _)| *source == self)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+}
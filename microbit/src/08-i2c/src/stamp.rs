@@ -0,0 +1,135 @@
+//! The time column at the start of every line a streaming command prints.
+//!
+//! "ms" is when each sample was read, in milliseconds since boot, see
+//! [`crate::uptime`]. "samples" and "rel" count samples at the sensor's
+//! data rate from the start of the stream instead, which keeps them evenly
+//! spaced however late each read comes. When the sensor reports it had to
+//! overwrite data nobody read, the next stamp is marked with a "!": samples
+//! are missing before it, and the counted columns have fallen behind by
+//! that much.
+
+use core::fmt;
+
+use crate::uptime;
+
+pub const USAGE: &str = "timeformat ms|samples|rel";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Milliseconds since boot when the sample was read, wrapping around
+    /// after 49 days
+    Millis,
+    /// Samples since the stream started, from 0
+    Samples,
+    /// Seconds since the stream started, to the millisecond
+    Relative,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "ms" => Some(Format::Millis),
+            "samples" => Some(Format::Samples),
+            "rel" => Some(Format::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Millis => "ms",
+            Format::Samples => "samples",
+            Format::Relative => "rel",
+        }
+    }
+
+    /// What the column holds, for stream headers.
+    pub fn column(self) -> &'static str {
+        match self {
+            Format::Millis => "ms",
+            Format::Samples => "sample",
+            Format::Relative => "s",
+        }
+    }
+
+    pub fn encode(self) -> u32 {
+        self as u32
+    }
+
+    /// Anything unrecognized decodes as [`Format::Millis`].
+    pub fn decode(word: u32) -> Format {
+        match word {
+            1 => Format::Samples,
+            2 => Format::Relative,
+            _ => Format::Millis,
+        }
+    }
+}
+
+/// Time within one stream.
+pub struct Clock {
+    format: Format,
+    period_ms: u32,
+    /// Of the next sample
+    index: u32,
+    gap: bool,
+}
+
+impl Clock {
+    /// A stream whose samples arrive at `rate_hz` starts now.
+    pub fn start(format: Format, rate_hz: u32) -> Clock {
+        Clock {
+            format,
+            period_ms: 1000 / rate_hz,
+            index: 0,
+            gap: false,
+        }
+    }
+
+    /// Count one sample read from the sensor, printed or not. `overrun` if
+    /// the sensor dropped some before it.
+    pub fn count(&mut self, overrun: bool) -> Stamp {
+        self.gap |= overrun;
+        let stamp = Stamp {
+            format: self.format,
+            index: self.index,
+            // Formatting a u64 costs a software division on the nRF51
+            ms: uptime::millis() as u32,
+            offset_ms: self.index.wrapping_mul(self.period_ms),
+            gap: self.gap,
+        };
+        self.index = self.index.wrapping_add(1);
+        stamp
+    }
+
+    /// The gap marker goes on the first stamp printed after the gap, which
+    /// may be a few samples later in a decimated stream.
+    pub fn printed(&mut self) {
+        self.gap = false;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Stamp {
+    format: Format,
+    index: u32,
+    ms: u32,
+    offset_ms: u32,
+    gap: bool,
+}
+
+impl fmt::Display for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.format {
+            Format::Millis => write!(f, "{}", self.ms)?,
+            Format::Samples => write!(f, "{}", self.index)?,
+            Format::Relative => {
+                write!(f, "{}.{:03}", self.offset_ms / 1000, self.offset_ms % 1000)?
+            }
+        }
+        if self.gap {
+            write!(f, "!")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+//! Session statistics for the "uptime" command.
+//!
+//! Nothing here formats a `u64`: on the nRF51 that drags in the software
+//! 64-bit division, so the time breakdown uses [`divmod`] instead.
+
+use core::fmt;
+
+use crate::serial_setup::SerialStats;
+use crate::{odometer, shared, uptime};
+
+/// Counted by the command loop: `commands` once a command has been parsed,
+/// `errors` every time an error gets printed. Kept in [`shared::State`].
+#[derive(Clone, Copy)]
+pub struct SessionStats {
+    pub commands: u32,
+    pub errors: u32,
+}
+
+impl SessionStats {
+    pub const fn new() -> SessionStats {
+        SessionStats {
+            commands: 0,
+            errors: 0,
+        }
+    }
+}
+
+pub fn count_command() {
+    shared::with_state(|state| state.stats.commands = state.stats.commands.wrapping_add(1));
+}
+
+pub fn count_error() {
+    shared::with_state(|state| state.stats.errors = state.stats.errors.wrapping_add(1));
+}
+
+/// The counts so far.
+pub fn session() -> SessionStats {
+    shared::with_state(|state| state.stats)
+}
+
+/// `(n / d, n % d)` by shift and subtract.
+fn divmod(n: u64, d: u32) -> (u64, u32) {
+    let d = d as u64;
+    let mut quotient = 0;
+    let mut remainder = 0;
+    for bit in (0..64).rev() {
+        remainder = (remainder << 1) | ((n >> bit) & 1);
+        if remainder >= d {
+            remainder -= d;
+            quotient |= 1 << bit;
+        }
+    }
+    (quotient, remainder as u32)
+}
+
+pub fn report<W: fmt::Write>(w: &mut W, serial: SerialStats) -> fmt::Result {
+    let session = session();
+    let (seconds, _) = divmod(uptime::millis(), 1000);
+    let (minutes, s) = divmod(seconds, 60);
+    let (hours, m) = divmod(minutes, 60);
+    let (days, h) = divmod(hours, 24);
+    writeln!(w, "Uptime: {}d {}h {}m {}s", days as u32, h, m, s)?;
+    let lifetime = odometer::counters();
+    writeln!(
+        w,
+        "Boots: {}, total runtime: {}h {}m",
+        lifetime.boots,
+        lifetime.minutes / 60,
+        lifetime.minutes % 60
+    )?;
+    writeln!(
+        w,
+        "Commands: {}, errors: {}",
+        session.commands, session.errors
+    )?;
+    writeln!(
+        w,
+        "Serial: {} bytes sent, {} received",
+        serial.tx, serial.rx
+    )
+}
@@ -0,0 +1,123 @@
+//! Which matrix LEDs show which status, and how they combine when two
+//! roles share one LED.
+//!
+//! Roles are listed in priority order: when several roles assigned to the
+//! same LED are active at once, the first one listed decides what it
+//! shows. An LED whose roles are all inactive shows the image underneath.
+
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Error,
+    Link,
+    Activity,
+    Heartbeat,
+}
+
+pub const ROLE_COUNT: usize = 4;
+
+/// In priority order, highest first, with the brightness each role is
+/// shown at.
+const ROLES: [(Role, &str, u8); ROLE_COUNT] = [
+    (Role::Error, "error", 9),
+    (Role::Link, "link", 5),
+    (Role::Activity, "activity", 3),
+    (Role::Heartbeat, "heartbeat", 9),
+];
+
+pub const USAGE: &str = "statusled error|link|activity|heartbeat <row> <col>|off";
+
+impl Role {
+    /// Where the role sits in [`ROLES`], and in the flags handed to
+    /// [`compose`].
+    pub fn index(self) -> usize {
+        ROLES.iter().position(|(role, _, _)| *role == self).unwrap()
+    }
+}
+
+/// (row, column) for every role, or `None` when it isn't shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatusLeds([Option<(u8, u8)>; ROLE_COUNT]);
+
+impl Default for StatusLeds {
+    fn default() -> StatusLeds {
+        let mut leds = StatusLeds::NONE;
+        leds.set(Role::Heartbeat, Some((0, 4)));
+        leds.set(Role::Activity, Some((0, 0)));
+        leds.set(Role::Error, Some((4, 4)));
+        leds.set(Role::Link, Some((4, 0)));
+        leds
+    }
+}
+
+impl StatusLeds {
+    pub const NONE: StatusLeds = StatusLeds([None; ROLE_COUNT]);
+
+    pub fn set(&mut self, role: Role, led: Option<(u8, u8)>) {
+        self.0[role.index()] = led;
+    }
+
+    /// One byte per role, `row * 5 + col` or 0xff when off.
+    pub fn encode(&self) -> u32 {
+        self.0.iter().rev().fold(0, |word, led| {
+            let byte = match led {
+                Some((row, col)) => row * 5 + col,
+                None => 0xff,
+            };
+            word << 8 | byte as u32
+        })
+    }
+
+    pub fn decode(word: u32) -> StatusLeds {
+        let mut leds = StatusLeds::NONE;
+        for (i, led) in leds.0.iter_mut().enumerate() {
+            let byte = (word >> (8 * i)) as u8;
+            if byte < 25 {
+                *led = Some((byte / 5, byte % 5));
+            }
+        }
+        leds
+    }
+
+    /// The "statusled" commands that recreate this assignment.
+    pub fn export<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        for ((_, name, _), led) in ROLES.iter().zip(self.0.iter()) {
+            match led {
+                Some((row, col)) => writeln!(w, "statusled {} {} {}", name, row, col)?,
+                None => writeln!(w, "statusled {} off", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draw the active roles over `frame`, `active` being indexed like the
+/// roles are declared.
+pub fn compose(frame: &mut [[u8; 5]; 5], leds: &StatusLeds, active: &[bool; ROLE_COUNT]) {
+    // Lowest priority first, so that higher ones paint over it
+    for (i, (_, _, level)) in ROLES.iter().enumerate().rev() {
+        if let (true, Some((row, col))) = (active[i], leds.0[i]) {
+            frame[row as usize][col as usize] = *level;
+        }
+    }
+}
+
+/// Parse everything after "statusled ".
+pub fn parse(args: &str) -> Option<(Role, Option<(u8, u8)>)> {
+    let mut words = args.split_ascii_whitespace();
+    let name = words.next()?;
+    let role = ROLES.iter().find(|(_, n, _)| *n == name)?.0;
+    let led = match (words.next()?, words.next(), words.next()) {
+        ("off", None, _) => None,
+        (row, Some(col), None) => {
+            let (row, col) = (row.parse().ok()?, col.parse().ok()?);
+            if row >= 5 || col >= 5 {
+                return None;
+            }
+            Some((row, col))
+        }
+        _ => return None,
+    };
+    Some((role, led))
+}
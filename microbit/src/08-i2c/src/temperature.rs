@@ -0,0 +1,52 @@
+//! The LSM303AGR's own temperature sensor, for "temperature".
+//!
+//! The driver doesn't know about it, so its registers are read over the
+//! bus directly, the way "i2c read" does, see [`crate::raw`]. It samples at
+//! the accelerometer's rate once enabled; the first sample after that can
+//! be one from whenever it last ran, so the one that counts is the next
+//! that comes after it. It reads a difference from 25 °C, in 256ths of a
+//! degree though only whole degrees of it change in normal mode.
+//!
+//! "temperature both" puts the nRF's own die temperature next to it, see
+//! [`crate::onchip`].
+
+use core::fmt;
+
+pub const USAGE: &str = "temperature [both]";
+
+/// The accelerometer half of the LSM303AGR, which the sensor belongs to
+pub const ADDRESS: u8 = 0x19;
+pub const TEMP_CFG_REG_A: u8 = 0x1f;
+/// TEMP_EN, both bits
+pub const ENABLE: u8 = 0b1100_0000;
+pub const STATUS_REG_AUX_A: u8 = 0x07;
+/// A new sample is ready
+pub const TDA: u8 = 1 << 2;
+/// OUT_TEMP_L_A, with the top bit set to carry on to OUT_TEMP_H_A
+pub const OUT_TEMP: u8 = 0x0c | 0x80;
+
+/// In tenths of a degree Celsius, printed with one decimal as in
+/// "23.5 deg C".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tenths(pub i32);
+
+impl Tenths {
+    /// From OUT_TEMP_L_A and OUT_TEMP_H_A, rounded down.
+    pub fn from_sensor(out: [u8; 2]) -> Tenths {
+        let raw = i16::from_le_bytes(out) as i32;
+        Tenths(250 + (raw * 10).div_euclid(256))
+    }
+
+    /// From the nRF's TEMP peripheral, which counts in quarters.
+    pub fn from_quarters(quarters: i32) -> Tenths {
+        Tenths((quarters * 10).div_euclid(4))
+    }
+}
+
+impl fmt::Display for Tenths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let tenths = self.0.abs();
+        write!(f, "{}{}.{} deg C", sign, tenths / 10, tenths % 10)
+    }
+}
@@ -0,0 +1,282 @@
+//! "log:": a command's output kept in flash rather than sent, for a board
+//! that runs without anyone watching.
+//!
+//! "log: <command>" runs the command with everything it writes going into
+//! a [`Text`] in RAM instead of out of the serial port, and appends that to
+//! the log as one record, stamped with the boot it came from and the
+//! milliseconds since. What doesn't fit into [`TEXT_CAP`] bytes is dropped,
+//! and the record says how much. "log read" plays every record back, oldest
+//! first.
+//!
+//! The log takes two pages in turn. Once the newer one has no room for the
+//! next record the older one is erased and written next, so there is always
+//! at least a page's worth of the latest records. A record torn by a reset
+//! fails its checksum and ends its page early, the next one goes to the
+//! other page. "flash erase" clears the log.
+
+use core::fmt;
+use heapless::String;
+
+use crate::flash;
+use crate::odometer;
+use crate::uptime;
+
+pub const USAGE: &str = "log read|log: <command>";
+
+/// The line prefix that sends a command's output here.
+pub const PREFIX: &str = "log:";
+
+const PAGES: [usize; 2] = [3, 4];
+const PAGE_WORDS: usize = flash::PAGE_SIZE / 4;
+
+/// "TL", in the top half of a record's first word
+const MAGIC: u32 = 0x544c;
+
+#[cfg(feature = "v1")]
+pub const TEXT_CAP: usize = 256;
+#[cfg(feature = "v2")]
+pub const TEXT_CAP: usize = 512;
+
+/// Length and magic, sequence number, boot, milliseconds, bytes dropped
+const HEADER_WORDS: usize = 5;
+const TEXT_WORDS: usize = TEXT_CAP / 4;
+const MAX_RECORD_WORDS: usize = HEADER_WORDS + TEXT_WORDS + 1;
+
+const _: () = assert!(TEXT_CAP.is_multiple_of(4) && MAX_RECORD_WORDS <= PAGE_WORDS);
+
+/// A command's output, as it was written.
+pub struct Text {
+    text: String<TEXT_CAP>,
+    dropped: u32,
+    boot: u32,
+    ms: u32,
+}
+
+impl Text {
+    /// Empty, stamped with the time it starts.
+    pub fn new() -> Text {
+        Text {
+            text: String::new(),
+            dropped: 0,
+            boot: odometer::counters().boots,
+            ms: uptime::millis() as u32,
+        }
+    }
+
+    /// As much of `s` as still fits, counting the rest as dropped.
+    pub fn push(&mut self, s: &str) {
+        if self.dropped == 0 && self.text.push_str(s).is_ok() {
+            return;
+        }
+        for c in s.chars() {
+            if self.dropped != 0 || self.text.push(c).is_err() {
+                self.dropped += c.len_utf8() as u32;
+            }
+        }
+    }
+
+    /// How many bytes made it in
+    pub fn kept(&self) -> usize {
+        self.text.len()
+    }
+
+    /// How many bytes didn't
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+/// A record's header, as read back.
+#[derive(Clone, Copy)]
+struct Record {
+    seq: u32,
+    boot: u32,
+    ms: u32,
+    dropped: u32,
+    len: usize,
+    /// Where in its page the record starts, in words
+    offset: usize,
+}
+
+impl Record {
+    fn words(&self) -> usize {
+        HEADER_WORDS + self.len.div_ceil(4) + 1
+    }
+}
+
+/// Walks the records in a page, in the order they were written, until the
+/// first one that isn't whole.
+struct Records {
+    page: usize,
+    offset: usize,
+    /// Stopped at erased flash, rather than at something damaged
+    erased: bool,
+}
+
+impl Records {
+    fn new(page: usize) -> Records {
+        Records {
+            page,
+            offset: 0,
+            erased: false,
+        }
+    }
+}
+
+impl Iterator for Records {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        if self.offset + HEADER_WORDS + 1 > PAGE_WORDS {
+            return None;
+        }
+        let mut header = [0; HEADER_WORDS];
+        flash::read(self.page, self.offset, &mut header);
+        if header[0] == !0 {
+            self.erased = true;
+            return None;
+        }
+        let len = (header[0] & 0xffff) as usize;
+        if header[0] >> 16 != MAGIC || len > TEXT_CAP {
+            return None;
+        }
+        let record = Record {
+            seq: header[1],
+            boot: header[2],
+            ms: header[3],
+            dropped: header[4],
+            len,
+            offset: self.offset,
+        };
+        if self.offset + record.words() > PAGE_WORDS {
+            return None;
+        }
+        let mut words = [0; MAX_RECORD_WORDS];
+        let words = &mut words[..record.words()];
+        flash::read(self.page, self.offset, words);
+        let (body, sum) = words.split_at(words.len() - 1);
+        if flash::checksum(body) != sum[0] {
+            return None;
+        }
+        self.offset += record.words();
+        Some(record)
+    }
+}
+
+/// What a page holds, as far as appending to it goes.
+struct Scan {
+    first: Option<u32>,
+    last: Option<u32>,
+    /// The offset after the last whole record, if the rest is erased
+    free_from: Option<usize>,
+}
+
+fn scan(page: usize) -> Scan {
+    let mut records = Records::new(page);
+    let mut first = None;
+    let mut last = None;
+    for record in records.by_ref() {
+        first.get_or_insert(record.seq);
+        last = Some(record.seq);
+    }
+    Scan {
+        first,
+        last,
+        free_from: records.erased.then_some(records.offset),
+    }
+}
+
+/// The indices into [`PAGES`], older page first. An empty page counts as
+/// the older one.
+fn by_age(scans: &[Scan; 2]) -> [usize; 2] {
+    match (scans[0].first, scans[1].first) {
+        (Some(a), Some(b)) if b < a => [1, 0],
+        (Some(_), None) => [1, 0],
+        _ => [0, 1],
+    }
+}
+
+/// Append `text` as the newest record.
+pub fn append(text: &Text) -> Result<(), flash::LowPower> {
+    let scans = PAGES.map(scan);
+    let [older, newer] = by_age(&scans);
+    let seq = scans
+        .iter()
+        .filter_map(|scan| scan.last)
+        .max()
+        .map_or(0, |last| last.wrapping_add(1));
+
+    let bytes = text.text.as_bytes();
+    let mut record = [0; MAX_RECORD_WORDS];
+    record[..HEADER_WORDS].copy_from_slice(&[
+        MAGIC << 16 | bytes.len() as u32,
+        seq,
+        text.boot,
+        text.ms,
+        text.dropped,
+    ]);
+    for (word, chunk) in record[HEADER_WORDS..].iter_mut().zip(bytes.chunks(4)) {
+        let mut le = [0; 4];
+        le[..chunk.len()].copy_from_slice(chunk);
+        *word = u32::from_le_bytes(le);
+    }
+    let words = HEADER_WORDS + bytes.len().div_ceil(4) + 1;
+    record[words - 1] = flash::checksum(&record[..words - 1]);
+    let record = &record[..words];
+
+    let (page, offset) = match scans[newer].free_from {
+        Some(offset) if offset + words <= PAGE_WORDS => (newer, offset),
+        // Nothing whole on it, only what a reset left behind
+        _ if scans[newer].first.is_none() => {
+            flash::erase(PAGES[newer])?;
+            (newer, 0)
+        }
+        _ => {
+            flash::erase(PAGES[older])?;
+            (older, 0)
+        }
+    };
+    flash::write(PAGES[page], offset, record)
+}
+
+/// "log read"
+pub fn read<W: fmt::Write>(w: &mut W) -> fmt::Result {
+    let scans = PAGES.map(scan);
+    let mut count = 0;
+    for index in by_age(&scans) {
+        let page = PAGES[index];
+        for record in Records::new(page) {
+            count += 1;
+            writeln!(
+                w,
+                "record {}, boot {} at {} ms:",
+                record.seq, record.boot, record.ms
+            )?;
+            write_text(w, page, &record)?;
+            if record.dropped != 0 {
+                writeln!(w, "(truncated, {} bytes dropped)", record.dropped)?;
+            }
+        }
+    }
+    match count {
+        0 => writeln!(w, "log empty"),
+        _ => writeln!(w, "{} records", count),
+    }
+}
+
+fn write_text<W: fmt::Write>(w: &mut W, page: usize, record: &Record) -> fmt::Result {
+    let mut words = [0; TEXT_WORDS];
+    let words = &mut words[..record.len.div_ceil(4)];
+    flash::read(page, record.offset + HEADER_WORDS, words);
+    let mut bytes = [0; TEXT_CAP];
+    for (chunk, word) in bytes.chunks_mut(4).zip(words.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    // It passed its checksum, so it is what a `String` held
+    let text = core::str::from_utf8(&bytes[..record.len]).unwrap_or("(not text)");
+    write!(w, "{}", text)?;
+    if !text.is_empty() && !text.ends_with('\n') {
+        writeln!(w)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! Roll and pitch from the accelerometer, for "tilt stream" and "heading
+//! tilted".
+//!
+//! Angles are in hundredths of a degree. The raw angles jump around under
+//! vibration, so they go through a single-pole low-pass filter, set with
+//! "tiltfilter <alpha_percent>": each step moves the output that
+//! percentage of the way towards the new raw angle.
+//!
+//! [`level`] turns a magnetometer reading back by the same roll and pitch,
+//! so that a compass heading doesn't depend on the board lying flat.
+
+use core::fmt;
+use libm::{atan2f, sqrtf};
+
+const HALF_TURN: i32 = 18_000;
+const FULL_TURN: i32 = 36_000;
+
+pub const DEFAULT_ALPHA: u8 = 30;
+
+/// Roll (around x) and pitch (around y) for an acceleration in mg.
+pub fn raw(accel: [i32; 3]) -> (i32, i32) {
+    let [x, y, z] = [accel[0] as f32, accel[1] as f32, accel[2] as f32];
+    let degrees = |radians: f32| (radians * (HALF_TURN as f32 / core::f32::consts::PI)) as i32;
+    let roll = degrees(atan2f(y, z));
+    let pitch = degrees(atan2f(-x, sqrtf(y * y + z * z)));
+    (roll, pitch)
+}
+
+/// The x and y of `mag` as it would read with the board lying flat, going
+/// by the roll and pitch of `accel`, as in [`raw`]: undoing the roll first,
+/// around x, and then the pitch, around y. `None` for no acceleration at
+/// all, which has no way up.
+pub fn level(accel: [i32; 3], mag: [i32; 3]) -> Option<[i32; 2]> {
+    let [ax, ay, az] = [accel[0] as f32, accel[1] as f32, accel[2] as f32];
+    let [mx, my, mz] = [mag[0] as f32, mag[1] as f32, mag[2] as f32];
+    let g = sqrtf(ax * ax + ay * ay + az * az);
+    if g == 0.0 {
+        return None;
+    }
+    // On its side, with x straight up or down, any roll will do
+    let yz = sqrtf(ay * ay + az * az);
+    let (sin_roll, cos_roll) = if yz == 0.0 {
+        (0.0, 1.0)
+    } else {
+        (ay / yz, az / yz)
+    };
+    let (sin_pitch, cos_pitch) = (-ax / g, yz / g);
+    let z = my * sin_roll + mz * cos_roll;
+    let x = mx * cos_pitch + z * sin_pitch;
+    let y = my * cos_roll - mz * sin_roll;
+    Some([x as i32, y as i32])
+}
+
+/// Bring `angle` into -180°..180°.
+fn wrap(angle: i32) -> i32 {
+    let angle = angle.rem_euclid(FULL_TURN);
+    if angle >= HALF_TURN {
+        angle - FULL_TURN
+    } else {
+        angle
+    }
+}
+
+/// Move `filtered` `alpha` percent of the way to `raw`, along the short
+/// way round so that going past ±180° doesn't swing through zero.
+fn smooth(filtered: i32, raw: i32, alpha: u8) -> i32 {
+    let step = wrap(raw - filtered) * alpha as i32 / 100;
+    wrap(filtered + step)
+}
+
+pub struct TiltFilter {
+    alpha: u8,
+    /// `None` until the first sample, which is taken as is.
+    state: Option<(i32, i32)>,
+}
+
+impl TiltFilter {
+    pub fn new(alpha: u8) -> TiltFilter {
+        TiltFilter { alpha, state: None }
+    }
+
+    pub fn update(&mut self, (roll, pitch): (i32, i32)) -> (i32, i32) {
+        let next = match self.state {
+            None => (roll, pitch),
+            Some((r, p)) => (smooth(r, roll, self.alpha), smooth(p, pitch, self.alpha)),
+        };
+        self.state = Some(next);
+        next
+    }
+}
+
+/// Hundredths of a degree, printed as degrees with two decimals.
+pub struct Centi(pub i32);
+
+impl fmt::Display for Centi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{}{}.{:02}", sign, abs / 100, abs % 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_angles_of_the_board_on_each_side() {
+        assert_eq!(raw([0, 0, 1000]), (0, 0));
+        assert_eq!(raw([0, 1000, 0]), (9000, 0));
+        assert_eq!(raw([0, -1000, 0]), (-9000, 0));
+        assert_eq!(raw([-1000, 0, 0]), (0, 9000));
+        assert_eq!(raw([1000, 0, 0]), (0, -9000));
+        assert_eq!(raw([0, 707, 707]), (4500, 0));
+        assert_eq!(raw([0, 0, -1000]).0.abs(), HALF_TURN);
+    }
+
+    #[test]
+    fn wrap_keeps_angles_within_a_half_turn_either_way() {
+        assert_eq!(wrap(0), 0);
+        assert_eq!(wrap(HALF_TURN - 1), HALF_TURN - 1);
+        assert_eq!(wrap(HALF_TURN), -HALF_TURN);
+        assert_eq!(wrap(-HALF_TURN), -HALF_TURN);
+        assert_eq!(wrap(-HALF_TURN - 1), HALF_TURN - 1);
+        assert_eq!(wrap(FULL_TURN + 5), 5);
+        assert_eq!(wrap(-3 * FULL_TURN - 5), -5);
+    }
+
+    #[test]
+    fn alpha_is_how_far_each_step_goes() {
+        assert_eq!(smooth(0, 1000, 30), 300);
+        assert_eq!(smooth(0, -1000, 30), -300);
+        assert_eq!(smooth(500, 100, 50), 300);
+        assert_eq!(smooth(1234, 5678, 100), 5678);
+        assert_eq!(smooth(1234, 5678, 0), 1234);
+        // Steps too small to move by a whole hundredth are dropped, either
+        // way round alike
+        assert_eq!(smooth(0, 3, 30), 0);
+        assert_eq!(smooth(0, -3, 30), 0);
+    }
+
+    #[test]
+    fn the_first_sample_is_taken_as_it_is() {
+        let mut filter = TiltFilter::new(DEFAULT_ALPHA);
+        assert_eq!(filter.update((4500, -1200)), (4500, -1200));
+        assert_eq!(filter.update((5500, -1200)), (4800, -1200));
+        // A new filter primes itself over again
+        let mut filter = TiltFilter::new(DEFAULT_ALPHA);
+        assert_eq!(filter.update((-17_000, 300)), (-17_000, 300));
+    }
+
+    #[test]
+    fn the_filter_settles_on_a_steady_angle() {
+        let mut filter = TiltFilter::new(10);
+        filter.update((0, 0));
+        let mut out = (0, 0);
+        for _ in 0..200 {
+            out = filter.update((3000, -6000));
+        }
+        // Within the last step that still rounds to something
+        assert!((2990..=3000).contains(&out.0), "{:?}", out);
+        assert!((-6000..=-5990).contains(&out.1), "{:?}", out);
+    }
+
+    #[test]
+    fn going_past_a_half_turn_takes_the_short_way_round() {
+        let mut filter = TiltFilter::new(50);
+        filter.update((17_900, -17_900));
+        let steps: Vec<_> = (0..4).map(|_| filter.update((-17_900, 17_900))).collect();
+        assert_eq!(
+            steps,
+            [
+                // Both the same angle as +180°
+                (-18_000, -18_000),
+                (-17_950, 17_950),
+                (-17_925, 17_925),
+                (-17_913, 17_913),
+            ]
+        );
+        for (roll, pitch) in steps {
+            assert!(roll.abs() > 17_000 && pitch.abs() > 17_000);
+        }
+    }
+
+    #[test]
+    fn centi_prints_two_decimals() {
+        assert_eq!(format!("{}", Centi(12_345)), "123.45");
+        assert_eq!(format!("{}", Centi(-5)), "-0.05");
+        assert_eq!(format!("{}", Centi(-18_000)), "-180.00");
+        assert_eq!(format!("{}", Centi(0)), "0.00");
+    }
+}
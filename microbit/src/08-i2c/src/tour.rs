@@ -0,0 +1,271 @@
+//! The sequencing of a guided tour, apart from what it shows, for
+//! [`crate::demo`]: a table of [`Segment`]s, each a label, a handler
+//! drawing one frame at a time and how long the segment lasts. [`play`]
+//! announces every segment, keeps calling its handler until the time is up
+//! or the skip button is pressed, and moves on to the next. What a handler
+//! is and how it gets at the hardware is up to the [`Stage`] the tour
+//! plays on.
+
+/// One part of the tour.
+pub struct Segment<H> {
+    pub label: &'static str,
+    pub handler: H,
+    pub duration_ms: u32,
+}
+
+/// What a tour with handlers of type `H` plays on.
+pub trait Stage<H> {
+    type Error;
+
+    fn now_ms(&mut self) -> u32;
+    /// Whether the skip button is down right now.
+    fn skip_held(&mut self) -> bool;
+    fn begin(&mut self, label: &'static str) -> Result<(), Self::Error>;
+    /// Draw the frame for `elapsed_ms` into the segment. Handlers that
+    /// don't wait for a sensor have to pace themselves.
+    fn frame(&mut self, handler: H, elapsed_ms: u32) -> Result<(), Self::Error>;
+}
+
+/// Play `tour` from start to end, or until `stage` fails.
+pub fn play<H: Copy, S: Stage<H>>(stage: &mut S, tour: &[Segment<H>]) -> Result<(), S::Error> {
+    // Holding the button down skips one segment, not all of them
+    let mut held = stage.skip_held();
+    for segment in tour {
+        stage.begin(segment.label)?;
+        let start = stage.now_ms();
+        loop {
+            let elapsed = stage.now_ms().wrapping_sub(start);
+            if elapsed >= segment.duration_ms {
+                break;
+            }
+            let pressed = stage.skip_held();
+            if pressed && !held {
+                held = true;
+                break;
+            }
+            held = pressed;
+            stage.frame(segment.handler, elapsed)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ops::Range;
+    use std::vec::Vec;
+
+    /// How long a frame takes to draw on the bench
+    const FRAME_MS: u32 = 10;
+
+    #[derive(Debug, PartialEq)]
+    struct Interrupted;
+
+    /// A handler, told the bench and how far into its segment it is.
+    type Handler = fn(&mut Bench, u32) -> Result<(), Interrupted>;
+
+    /// Draws nothing, and leaves the log to the bench.
+    fn idle(_: &mut Bench, _: u32) -> Result<(), Interrupted> {
+        Ok(())
+    }
+
+    /// A sensor that stops answering once the clock gets to 100 ms.
+    fn failing(bench: &mut Bench, _: u32) -> Result<(), Interrupted> {
+        match bench.now >= 100 {
+            true => Err(Interrupted),
+            false => Ok(()),
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Begin(&'static str),
+        Frame(u32),
+    }
+
+    /// A clock that moves on by a frame per frame, button A down for the
+    /// times in `pressed`, and Ctrl-C coming in at `ctrl_c`.
+    struct Bench {
+        now: u32,
+        pressed: Vec<Range<u32>>,
+        ctrl_c: Option<u32>,
+        log: Vec<Event>,
+    }
+
+    impl Bench {
+        fn new() -> Bench {
+            Bench::starting_at(0)
+        }
+
+        fn starting_at(now: u32) -> Bench {
+            Bench {
+                now,
+                pressed: Vec::new(),
+                ctrl_c: None,
+                log: Vec::new(),
+            }
+        }
+
+        fn begun(&self) -> Vec<&'static str> {
+            self.log
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Begin(label) => Some(*label),
+                    Event::Frame(_) => None,
+                })
+                .collect()
+        }
+
+        /// The frames' elapsed times, per segment.
+        fn frames(&self) -> Vec<Vec<u32>> {
+            let mut frames = Vec::new();
+            for event in &self.log {
+                match event {
+                    Event::Begin(_) => frames.push(Vec::new()),
+                    Event::Frame(elapsed) => frames.last_mut().unwrap().push(*elapsed),
+                }
+            }
+            frames
+        }
+    }
+
+    impl Stage<Handler> for Bench {
+        type Error = Interrupted;
+
+        fn now_ms(&mut self) -> u32 {
+            self.now
+        }
+
+        fn skip_held(&mut self) -> bool {
+            self.pressed.iter().any(|times| times.contains(&self.now))
+        }
+
+        fn begin(&mut self, label: &'static str) -> Result<(), Interrupted> {
+            self.log.push(Event::Begin(label));
+            Ok(())
+        }
+
+        fn frame(&mut self, handler: Handler, elapsed_ms: u32) -> Result<(), Interrupted> {
+            if self.ctrl_c.is_some_and(|at| self.now >= at) {
+                return Err(Interrupted);
+            }
+            self.log.push(Event::Frame(elapsed_ms));
+            self.now = self.now.wrapping_add(FRAME_MS);
+            handler(self, elapsed_ms)
+        }
+    }
+
+    fn segment(label: &'static str, duration_ms: u32) -> Segment<Handler> {
+        Segment {
+            label,
+            handler: idle,
+            duration_ms,
+        }
+    }
+
+    fn tour() -> [Segment<Handler>; 3] {
+        [segment("one", 30), segment("two", 50), segment("three", 20)]
+    }
+
+    #[test]
+    fn segments_play_in_order_for_as_long_as_they_last() {
+        let mut bench = Bench::new();
+        assert_eq!(play(&mut bench, &tour()), Ok(()));
+        assert_eq!(bench.begun(), ["one", "two", "three"]);
+        assert_eq!(
+            bench.frames(),
+            [vec![0, 10, 20], vec![0, 10, 20, 30, 40], vec![0, 10]]
+        );
+        assert_eq!(bench.now, 100);
+    }
+
+    #[test]
+    fn a_segment_with_no_time_is_announced_and_nothing_more() {
+        let mut bench = Bench::new();
+        let tour = [segment("one", 20), segment("none", 0), segment("two", 10)];
+        assert_eq!(play(&mut bench, &tour), Ok(()));
+        assert_eq!(bench.begun(), ["one", "none", "two"]);
+        assert_eq!(bench.frames(), [vec![0, 10], vec![], vec![0]]);
+    }
+
+    #[test]
+    fn an_empty_tour_does_nothing() {
+        let mut bench = Bench::new();
+        assert_eq!(play(&mut bench, &[]), Ok(()));
+        assert!(bench.log.is_empty());
+    }
+
+    #[test]
+    fn pressing_a_skips_to_the_next_segment() {
+        let mut bench = Bench::new();
+        // A press and release in the middle of "two"
+        bench.pressed.push(40..50);
+        assert_eq!(play(&mut bench, &tour()), Ok(()));
+        assert_eq!(bench.begun(), ["one", "two", "three"]);
+        assert_eq!(bench.frames(), [vec![0, 10, 20], vec![0], vec![0, 10]]);
+    }
+
+    #[test]
+    fn a_button_held_from_the_start_skips_nothing() {
+        let mut bench = Bench::new();
+        bench.pressed.push(0..60);
+        assert_eq!(play(&mut bench, &tour()), Ok(()));
+        assert_eq!(
+            bench.frames(),
+            [vec![0, 10, 20], vec![0, 10, 20, 30, 40], vec![0, 10]]
+        );
+    }
+
+    #[test]
+    fn holding_a_down_skips_one_segment_only() {
+        let mut bench = Bench::new();
+        // Pressed during "one", and still down all through "two"
+        bench.pressed.push(10..60);
+        assert_eq!(play(&mut bench, &tour()), Ok(()));
+        assert_eq!(bench.begun(), ["one", "two", "three"]);
+        assert_eq!(
+            bench.frames(),
+            [vec![0], vec![0, 10, 20, 30, 40], vec![0, 10]]
+        );
+    }
+
+    #[test]
+    fn pressing_a_again_skips_again() {
+        let mut bench = Bench::new();
+        bench.pressed.push(10..20);
+        bench.pressed.push(30..40);
+        assert_eq!(play(&mut bench, &tour()), Ok(()));
+        assert_eq!(bench.frames(), [vec![0], vec![0, 10], vec![0, 10]]);
+    }
+
+    #[test]
+    fn ctrl_c_ends_the_tour_there_and_then() {
+        let mut bench = Bench::new();
+        bench.ctrl_c = Some(50);
+        assert_eq!(play(&mut bench, &tour()), Err(Interrupted));
+        assert_eq!(bench.begun(), ["one", "two"]);
+        assert_eq!(bench.frames(), [vec![0, 10, 20], vec![0, 10]]);
+    }
+
+    #[test]
+    fn a_failing_handler_ends_the_tour() {
+        let mut bench = Bench::new();
+        let mut tour = tour();
+        tour[1].handler = failing;
+        tour[1].duration_ms = 1000;
+        assert_eq!(play(&mut bench, &tour), Err(Interrupted));
+        assert_eq!(bench.begun(), ["one", "two"]);
+        assert_eq!(bench.now, 100);
+    }
+
+    #[test]
+    fn segments_last_as_long_across_the_clock_wrapping() {
+        let mut bench = Bench::starting_at(u32::MAX - 44);
+        assert_eq!(play(&mut bench, &tour()), Ok(()));
+        assert_eq!(
+            bench.frames(),
+            [vec![0, 10, 20], vec![0, 10, 20, 30, 40], vec![0, 10]]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! The trace "replay" plays, to try it out with: two seconds of the board
+//! rolling 30 degrees to either side and back, at 50 Hz, made up along the
+//! lines of [`crate::sim`] with a little noise on top.
+//!
+//! Replace it with a recording of your own in the same format, one raw
+//! sample per line as the sensor reports it: mg for the accelerometer, nT
+//! for the magnetometer.
+
+pub const ACCEL: [[i16; 3]; 100] = [
+    [-5, 15, 989],
+    [11, 34, 987],
+    [14, 66, 988],
+    [-2, 84, 987],
+    [-14, 140, 994],
+    [15, 147, 989],
+    [-8, 177, 983],
+    [-11, 223, 963],
+    [11, 255, 958],
+    [5, 267, 956],
+    [3, 288, 957],
+    [-2, 336, 939],
+    [-4, 344, 928],
+    [3, 366, 929],
+    [-6, 396, 906],
+    [-10, 420, 906],
+    [-14, 442, 909],
+    [10, 455, 907],
+    [4, 456, 892],
+    [15, 460, 883],
+    [5, 480, 884],
+    [13, 491, 870],
+    [-10, 495, 858],
+    [9, 490, 857],
+    [14, 511, 866],
+    [-3, 501, 859],
+    [-7, 506, 864],
+    [-11, 483, 858],
+    [-15, 491, 881],
+    [-15, 474, 872],
+    [-5, 492, 867],
+    [13, 479, 892],
+    [-3, 453, 877],
+    [-9, 429, 887],
+    [-5, 431, 889],
+    [-11, 413, 899],
+    [12, 383, 923],
+    [15, 368, 932],
+    [-12, 362, 936],
+    [-6, 314, 933],
+    [0, 313, 960],
+    [-9, 291, 975],
+    [7, 251, 982],
+    [5, 233, 962],
+    [14, 181, 977],
+    [9, 162, 981],
+    [11, 126, 999],
+    [0, 93, 1003],
+    [0, 58, 988],
+    [15, 28, 986],
+    [0, -9, 995],
+    [0, -18, 1004],
+    [14, -68, 1007],
+    [-2, -87, 1000],
+    [-1, -132, 999],
+    [-11, -176, 975],
+    [6, -177, 977],
+    [-11, -236, 960],
+    [-2, -237, 959],
+    [-9, -282, 961],
+    [-7, -300, 951],
+    [13, -328, 950],
+    [11, -336, 949],
+    [-11, -371, 929],
+    [4, -407, 928],
+    [0, -407, 919],
+    [-5, -421, 904],
+    [10, -433, 884],
+    [-9, -463, 875],
+    [2, -482, 892],
+    [4, -476, 882],
+    [-1, -484, 876],
+    [7, -490, 883],
+    [11, -497, 857],
+    [-1, -504, 853],
+    [-9, -493, 860],
+    [-11, -506, 879],
+    [8, -481, 856],
+    [6, -480, 862],
+    [-3, -490, 872],
+    [-13, -469, 874],
+    [-1, -468, 890],
+    [1, -452, 883],
+    [14, -432, 888],
+    [-7, -441, 916],
+    [11, -413, 923],
+    [2, -378, 920],
+    [-13, -379, 914],
+    [-7, -335, 921],
+    [4, -315, 936],
+    [-1, -317, 948],
+    [4, -287, 946],
+    [-10, -256, 954],
+    [5, -227, 976],
+    [1, -185, 971],
+    [-7, -175, 971],
+    [-9, -128, 991],
+    [6, -86, 1000],
+    [11, -52, 994],
+    [-8, -37, 990],
+];
+
+pub const MAG: [[i32; 3]; 100] = [
+    [20104, -251, -44226],
+    [20074, -1150, -44217],
+    [19738, -3097, -43761],
+    [19792, -4046, -43654],
+    [19826, -5785, -43331],
+    [20299, -6982, -43675],
+    [19836, -8432, -43056],
+    [20284, -9713, -42638],
+    [19805, -10686, -42323],
+    [19799, -11923, -42515],
+    [19910, -13120, -41688],
+    [20176, -14115, -41408],
+    [19884, -15486, -41420],
+    [20206, -16338, -40674],
+    [19820, -17049, -40339],
+    [19855, -17885, -39980],
+    [19779, -18553, -39483],
+    [20021, -19439, -39391],
+    [20167, -20304, -39358],
+    [19766, -20822, -38871],
+    [20156, -21025, -38561],
+    [19723, -21198, -38398],
+    [20205, -21885, -38384],
+    [19953, -21735, -38095],
+    [19782, -22090, -37968],
+    [19840, -21859, -37842],
+    [20067, -21871, -38191],
+    [19854, -21905, -38257],
+    [19886, -21676, -38319],
+    [20247, -21292, -38182],
+    [20227, -21261, -38489],
+    [20272, -20483, -38781],
+    [20193, -19964, -39390],
+    [20151, -19621, -39637],
+    [19804, -19124, -39489],
+    [20072, -18359, -40339],
+    [20085, -17421, -40509],
+    [20072, -16204, -41008],
+    [20177, -15244, -41008],
+    [19804, -14364, -41601],
+    [19865, -13100, -42209],
+    [20240, -12113, -42429],
+    [19727, -10741, -42602],
+    [19967, -9498, -42836],
+    [19928, -8183, -42931],
+    [19928, -7189, -43480],
+    [19932, -5809, -43397],
+    [19729, -4582, -43802],
+    [20052, -2728, -43848],
+    [19925, -1642, -44044],
+    [19909, 194, -44299],
+    [20052, 1232, -44154],
+    [19904, 3074, -44023],
+    [20040, 4098, -43683],
+    [19786, 5575, -43753],
+    [20176, 6937, -43240],
+    [19859, 8689, -42924],
+    [19805, 9967, -43069],
+    [19916, 10709, -42650],
+    [19946, 12483, -42246],
+    [19834, 13090, -41870],
+    [20297, 14643, -41442],
+    [20213, 15268, -40959],
+    [19719, 16539, -40946],
+    [19853, 17149, -40623],
+    [19823, 18354, -40348],
+    [20243, 19092, -39575],
+    [20273, 19245, -39495],
+    [19800, 20293, -38990],
+    [19764, 20737, -38855],
+    [20224, 20920, -38673],
+    [20189, 21589, -38508],
+    [19965, 21917, -38400],
+    [20126, 21666, -38094],
+    [19946, 22098, -38353],
+    [19825, 21857, -38031],
+    [19840, 22138, -38203],
+    [20107, 22040, -38329],
+    [19865, 21786, -38080],
+    [19900, 21435, -38435],
+    [19719, 21062, -38389],
+    [19718, 20677, -38849],
+    [20224, 19839, -39338],
+    [19807, 19273, -39478],
+    [19885, 18800, -39937],
+    [19964, 18200, -40259],
+    [20284, 17479, -40433],
+    [19887, 16524, -41059],
+    [19790, 15401, -41418],
+    [19768, 14384, -41748],
+    [20266, 13455, -41958],
+    [20239, 12127, -42467],
+    [19885, 10887, -42588],
+    [19910, 9724, -42755],
+    [19977, 8483, -43467],
+    [19718, 7305, -43161],
+    [19951, 5870, -43819],
+    [20142, 4516, -43529],
+    [20218, 2900, -43985],
+    [19843, 1560, -43921],
+];
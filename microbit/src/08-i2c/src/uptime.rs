@@ -0,0 +1,53 @@
+//! Time since boot, to the millisecond.
+//!
+//! RTC0 counts at [`COUNTER_HZ`] from the time [`crate::heartbeat`] starts
+//! it, which is early in startup, and the heartbeat ticks off a compare
+//! register rather than off every count. The counter is only 24 bits wide
+//! and wraps after about four and a half hours, so the RTC0 interrupt
+//! counts the wraps with [`overflowed`] and [`millis`] puts the two back
+//! together: the time keeps going up for as long as the board runs.
+//!
+//! Before RTC0 starts, or without it, the time stays at zero.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use microbit::pac::RTC0;
+
+/// 32768 Hz / (31 + 1)
+pub const PRESCALER: u32 = 31;
+pub const COUNTER_HZ: u32 = 32_768 / (PRESCALER + 1);
+/// The counter's width
+pub const COUNTER_BITS: u32 = 24;
+
+static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+
+/// Called from the RTC0 interrupt on every overflow event.
+pub fn overflowed() {
+    // Only the RTC0 handler counts, and the nRF51 has no atomic increment
+    let overflows = OVERFLOWS.load(Ordering::Relaxed).wrapping_add(1);
+    OVERFLOWS.store(overflows, Ordering::Relaxed);
+}
+
+/// Milliseconds from the counter's `overflows` and where it is at since,
+/// rounded down.
+pub fn to_millis(overflows: u32, counter: u32) -> u64 {
+    let counts = (overflows as u64) << COUNTER_BITS | counter as u64;
+    counts * 1000 / COUNTER_HZ as u64
+}
+
+/// Milliseconds since RTC0 started.
+pub fn millis() -> u64 {
+    let rtc = unsafe { &*RTC0::ptr() };
+    loop {
+        let overflows = OVERFLOWS.load(Ordering::Relaxed);
+        let counter = rtc.counter.read().bits();
+        // An overflow the handler hasn't got to yet, because it wrapped
+        // this very moment or because this runs with it held off. A counter
+        // still near the top was read before the wrap
+        let pending = rtc.events_ovrflw.read().bits() != 0 && counter < 1 << (COUNTER_BITS - 1);
+        // The handler ran in between, start over
+        if OVERFLOWS.load(Ordering::Relaxed) != overflows {
+            continue;
+        }
+        return to_millis(overflows.wrapping_add(pending as u32), counter);
+    }
+}
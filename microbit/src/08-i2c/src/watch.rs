@@ -0,0 +1,195 @@
+//! "watch <source> <op> <value> <action>": sample a source at 10 Hz and do
+//! something when a threshold is crossed.
+//!
+//! Without "repeat" the watch ends after the first trigger. With it, the
+//! watch re-arms once the condition has cleared again, which takes the
+//! reading moving back past the threshold by the hysteresis.
+
+use core::fmt;
+use core::str::FromStr;
+
+use crate::display;
+use crate::source::Source;
+
+pub const SAMPLE_PERIOD_MS: u32 = 100;
+
+pub const USAGE: &str =
+    "watch <source> lt|gt <value> beep|flash|print|pulse|blinkout [repeat] [hyst <n>]";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Lt,
+    Gt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// A short tone on the speaker (v2) or edge pad 0 (v1).
+    Beep,
+    /// Light the whole display for a moment.
+    Flash,
+    /// Print the reading that triggered.
+    Print,
+    /// A 10 ms high pulse on edge pad 1.
+    Pulse,
+    /// Blink the reading out on the display, see [`crate::blinkout`].
+    /// Handled by the command loop, since it takes long enough to need
+    /// Ctrl-C.
+    Blinkout,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Condition {
+    pub op: Op,
+    pub threshold: i64,
+    pub hysteresis: i64,
+}
+
+impl Condition {
+    /// Whether the condition holds for `sample`, given whether it held for
+    /// the previous one. Once it holds, it only clears when `sample` has
+    /// moved `hysteresis` past the threshold in the other direction.
+    pub fn holds(&self, sample: i64, held: bool) -> bool {
+        let margin = if held { self.hysteresis } else { 0 };
+        match self.op {
+            Op::Lt => sample < self.threshold.saturating_add(margin),
+            Op::Gt => sample > self.threshold.saturating_sub(margin),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Watch {
+    pub source: Source,
+    pub condition: Condition,
+    pub action: Action,
+    pub repeat: bool,
+}
+
+#[derive(Debug)]
+pub enum ParseError<'a> {
+    Usage,
+    UnknownSource(&'a str),
+}
+
+/// Parse everything after "watch ".
+pub fn parse(args: &str) -> Result<Watch, ParseError<'_>> {
+    let mut words = args.split_ascii_whitespace();
+    let name = words.next().ok_or(ParseError::Usage)?;
+    let source = Source::from_name(name).ok_or(ParseError::UnknownSource(name))?;
+    let op = match words.next() {
+        Some("lt") => Op::Lt,
+        Some("gt") => Op::Gt,
+        _ => return Err(ParseError::Usage),
+    };
+    let threshold = number(words.next())?;
+    let action = match words.next() {
+        Some("beep") => Action::Beep,
+        Some("flash") => Action::Flash,
+        Some("print") => Action::Print,
+        Some("pulse") => Action::Pulse,
+        Some("blinkout") => Action::Blinkout,
+        _ => return Err(ParseError::Usage),
+    };
+    let mut watch = Watch {
+        source,
+        condition: Condition {
+            op,
+            threshold,
+            hysteresis: 0,
+        },
+        action,
+        repeat: false,
+    };
+    while let Some(word) = words.next() {
+        match word {
+            "repeat" => watch.repeat = true,
+            "hyst" => watch.condition.hysteresis = number(words.next())?,
+            _ => return Err(ParseError::Usage),
+        }
+    }
+    Ok(watch)
+}
+
+fn number(word: Option<&str>) -> Result<i64, ParseError<'static>> {
+    word.and_then(|word| i64::from_str(word).ok())
+        .ok_or(ParseError::Usage)
+}
+
+impl Action {
+    pub fn perform<W: fmt::Write>(self, w: &mut W, source: Source, value: i64) -> fmt::Result {
+        match self {
+            Action::Beep => {
+                // 2 kHz for 100 ms
+                for _ in 0..200 {
+                    chip::set(chip::BEEP, true);
+                    delay_us(250);
+                    chip::set(chip::BEEP, false);
+                    delay_us(250);
+                }
+            }
+            Action::Flash => {
+                display::set_background(&[[9; 5]; 5]);
+                delay_us(200_000);
+                display::set_background(&[[0; 5]; 5]);
+            }
+            Action::Print => writeln!(w, "watch: {} = {}", source.name(), value)?,
+            Action::Pulse => {
+                chip::set(chip::PULSE, true);
+                delay_us(10_000);
+                chip::set(chip::PULSE, false);
+            }
+            Action::Blinkout => {}
+        }
+        Ok(())
+    }
+}
+
+/// Busy wait for roughly `us` microseconds.
+pub fn delay_us(us: u32) {
+    cortex_m::asm::delay(us * chip::CYCLES_PER_US);
+}
+
+#[cfg(feature = "v1")]
+mod chip {
+    use microbit::pac;
+
+    pub const CYCLES_PER_US: u32 = 16;
+
+    /// Edge pad 0
+    pub const BEEP: usize = 3;
+    /// Edge pad 1
+    pub const PULSE: usize = 2;
+
+    pub fn set(pin: usize, high: bool) {
+        let gpio = unsafe { &*pac::GPIO::ptr() };
+        gpio.pin_cnf[pin].write(|w| w.dir().output());
+        if high {
+            gpio.outset.write(|w| unsafe { w.bits(1 << pin) });
+        } else {
+            gpio.outclr.write(|w| unsafe { w.bits(1 << pin) });
+        }
+    }
+}
+
+#[cfg(feature = "v2")]
+mod chip {
+    use microbit::pac;
+
+    pub const CYCLES_PER_US: u32 = 64;
+
+    /// The on-board speaker
+    pub const BEEP: usize = 0;
+    /// Edge pad 1
+    pub const PULSE: usize = 3;
+
+    pub fn set(pin: usize, high: bool) {
+        let gpio = unsafe { &*pac::P0::ptr() };
+        gpio.pin_cnf[pin].write(|w| w.dir().output());
+        if high {
+            gpio.outset.write(|w| unsafe { w.bits(1 << pin) });
+        } else {
+            gpio.outclr.write(|w| unsafe { w.bits(1 << pin) });
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Per-command execution deadlines.
+//!
+//! The command loop [`arm`]s the watchdog before running a command and
+//! [`disarm`]s it afterwards. If the deadline passes first, the heartbeat's
+//! RTC interrupt sets the abort flag and stops the I2C bus. Handlers that
+//! loop have to call [`check`] at the top of every iteration and bail out
+//! with the error it returns.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::{bus, heartbeat};
+
+pub const DEFAULT_TIMEOUT_MS: u32 = 10_000;
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+static ABORT: AtomicBool = AtomicBool::new(false);
+static DEADLINE: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug)]
+pub struct TimedOut;
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "command timed out")
+    }
+}
+
+/// Abort the running command `timeout_ms` from now, give or take a tick.
+pub fn arm(timeout_ms: u32) {
+    // Round up, plus one for the partial tick we are already in
+    let ticks = (timeout_ms * heartbeat::TICK_HZ).div_ceil(1000) + 1;
+    ABORT.store(false, Ordering::Relaxed);
+    DEADLINE.store(heartbeat::ticks().wrapping_add(ticks), Ordering::Relaxed);
+    ARMED.store(true, Ordering::Release);
+}
+
+/// Stop the countdown and forget about any abort it caused.
+pub fn disarm() {
+    ARMED.store(false, Ordering::Relaxed);
+    ABORT.store(false, Ordering::Relaxed);
+}
+
+pub fn check() -> Result<(), TimedOut> {
+    if ABORT.load(Ordering::Relaxed) {
+        Err(TimedOut)
+    } else {
+        Ok(())
+    }
+}
+
+/// Called from the RTC0 interrupt on every heartbeat tick.
+pub fn tick(now: u32) {
+    if ARMED.load(Ordering::Acquire) && now == DEADLINE.load(Ordering::Relaxed) {
+        ARMED.store(false, Ordering::Relaxed);
+        ABORT.store(true, Ordering::Relaxed);
+        bus::stop();
+    }
+}
@@ -0,0 +1,6 @@
+/* Fails the link unless a fifth of FLASH is still free, see build.rs, which
+   appends this to memory.x. What ends up in flash ends with the initial
+   values of .data. */
+ASSERT(__sidata + SIZEOF(.data) <= ORIGIN(FLASH) + LENGTH(FLASH) / 5 * 4, "
+ERROR(i2c): less than 20% of FLASH is left free, build with fewer of the
+optional features listed in Cargo.toml");